@@ -0,0 +1,92 @@
+//! Evolves a rule with complex dynamics: the best rule of a random search is
+//! refined by a hill climber mutating entries of its table, keeping the
+//! mutants that score at least as well.
+//!
+//! ```text
+//! cargo run --release --example evolve_interesting_rule -- [STATES] [GENERATIONS] [SEED]
+//! ```
+//!
+//! The evolved rule is written to `evolved_ID.rule` and its simulation to
+//! `evolved_ID.gif`.
+
+use std::env;
+use std::error::Error;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_ca::automaton::{Automaton, AutomatonImpl};
+use rust_ca::output;
+use rust_ca::search::{Activity, Compressibility, EntropyBand, Search};
+
+/// The number of random rules the evolution starts from.
+const SAMPLES: u64 = 50;
+
+/// The number of random grids each rule is scored on, to avoid rewarding the
+/// rules that happen to do well on a single grid.
+const GRIDS: u64 = 3;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let states: u8 = args.next().map_or(Ok(2), |a| a.parse())?;
+    let generations: u32 = args.next().map_or(Ok(200), |a| a.parse())?;
+    let seed: u64 = args.next().map_or(Ok(0), |a| a.parse())?;
+
+    let mut search = Search::new(1, states, 64, 100);
+    search.set_seed(seed);
+    search.add_fitness(
+        1.,
+        EntropyBand {
+            low: 0.2,
+            high: 0.8,
+        },
+    );
+    search.add_fitness(1., Activity { min: 0.01 });
+    search.add_fitness(2., Compressibility);
+    let score = |rule: &_| {
+        (0..GRIDS)
+            .map(|g| search.evaluate(rule, seed + g))
+            .sum::<f64>()
+    };
+
+    let start = search.run(SAMPLES, 1).remove(0);
+    let mut best = (score(&start.rule), start.rule);
+    println!("generation 0: score {:.4}", best.0 / GRIDS as f64);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for generation in 1..=generations {
+        let mut mutant = best.1.clone();
+        let table = mutant.table_mut();
+        for _ in 0..rng.gen_range(1..=4) {
+            let entry = rng.gen_range(0..table.len());
+            table[entry] = rng.gen_range(0..states);
+        }
+        let mutant_score = score(&mutant);
+        if mutant_score >= best.0 {
+            if mutant_score > best.0 {
+                println!(
+                    "generation {}: score {:.4}",
+                    generation,
+                    mutant_score / GRIDS as f64
+                );
+            }
+            best = (mutant_score, mutant);
+        }
+    }
+
+    let (_, rule) = best;
+    let name = format!("evolved_{}", rule.id());
+    rule.to_file(format!("{}.rule", name))?;
+    let mut automaton = Automaton::new(states, 128, rule);
+    automaton.random_init_seeded(seed);
+    output::write_to_gif_file(
+        Some(format!("{}.gif", name)),
+        &mut automaton,
+        2,
+        200,
+        1,
+        5,
+        0,
+    )?;
+    println!("wrote {0}.rule and {0}.gif", name);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! Collides two gliders of the game of life head on and lists the debris of
+//! the collision: two gliders travelling in opposite directions are placed
+//! on the same diagonal, the second one shifted sideways by `LANE` cells (5
+//! by default, the gliders annihilating on the lanes 0 to 4).
+//!
+//! ```text
+//! cargo run --release --example glider_collider -- [LANE] [STEPS]
+//! ```
+//!
+//! The collision is written to `collision.gif`, and the still lifes,
+//! oscillators and spaceships left after it are printed in RLE.
+
+use std::env;
+use std::error::Error;
+
+use rust_ca::analysis::Zoo;
+use rust_ca::automaton::{Automaton, AutomatonImpl};
+use rust_ca::output;
+use rust_ca::pattern::{Pattern, Rotation};
+use rust_ca::rule::Rule;
+
+/// The side of the grid.
+const SIZE: usize = 64;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let lane: usize = args.next().map_or(Ok(5), |a| a.parse())?;
+    let steps: u32 = args.next().map_or(Ok(120), |a| a.parse())?;
+
+    // The glider moves down and right; turned by a half turn, it moves up
+    // and left, towards the first one.
+    let glider = Pattern::from_file("patterns/glider.rle")?;
+    let mut automaton = Automaton::new(2, SIZE, Rule::gol());
+    automaton.place_pattern(&glider, 10, 10, Rotation::Rot0, false)?;
+    automaton.place_pattern(&glider, 40 + lane, 40, Rotation::Rot180, false)?;
+
+    output::write_to_gif_file(Some("collision.gif"), &mut automaton, 4, steps, 1, 5, 0)?;
+    println!("wrote collision.gif");
+
+    let mut zoo = Zoo::new(Rule::gol(), 30);
+    zoo.collect(&automaton.grid(), SIZE);
+    if zoo.structures().is_empty() {
+        println!("the gliders annihilated");
+    }
+    for structure in zoo.structures() {
+        println!(
+            "{:?}, {}x{} cells, {} found",
+            structure.kind, structure.rows, structure.cols, structure.count
+        );
+        print!("{}", structure.to_rle(&Rule::gol()));
+    }
+    Ok(())
+}
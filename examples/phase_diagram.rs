@@ -0,0 +1,63 @@
+//! Maps the phases of a life-like rule: the density of live cells after a
+//! long run, as a function of the initial density and of the noise level,
+//! averaged over several random grids.
+//!
+//! ```text
+//! cargo run --release --example phase_diagram -- [RULE] [RESOLUTION]
+//! ```
+//!
+//! The rule is given in B/S notation (`B3/S23`, the game of life, by
+//! default). The diagram is written to `phase_diagram.csv` and rendered to
+//! `phase_diagram.png`, the initial density increasing from left to right
+//! and the noise from bottom to top.
+
+use std::env;
+use std::error::Error;
+
+use rust_ca::analysis::{self, PhaseDiagram};
+use rust_ca::automaton::{Automaton, AutomatonImpl};
+use rust_ca::output;
+use rust_ca::rule::Rule;
+
+/// The number of random grids each point of the diagram is averaged over.
+const RUNS: u64 = 4;
+
+/// The highest noise level of the diagram.
+const MAX_NOISE: f64 = 0.05;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let rule = Rule::from_bs_notation(&args.next().unwrap_or_else(|| "B3/S23".to_string()))?;
+    let resolution: usize = args.next().map_or(Ok(16), |a| a.parse())?;
+
+    let densities: Vec<f64> = (1..=resolution)
+        .map(|i| i as f64 / resolution as f64)
+        .collect();
+    let noises: Vec<f64> = (0..resolution)
+        .map(|j| MAX_NOISE * j as f64 / resolution as f64)
+        .collect();
+    let diagram = PhaseDiagram::sweep(&densities, &noises, true, |density, noise| {
+        let runs = (0..RUNS).map(|seed| {
+            let mut automaton = Automaton::new(2, 64, rule.clone());
+            automaton.set_seed(seed);
+            automaton.random_init_with_density(density);
+            automaton.set_noise(noise);
+            analysis::run_metrics(&mut automaton, 200).density
+        });
+        runs.sum::<f64>() / RUNS as f64
+    });
+
+    diagram.write_csv("phase_diagram.csv")?;
+    output::write_phase_diagram_png("phase_diagram.png", &diagram, 16)?;
+    let (min, max) = diagram
+        .values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+    println!(
+        "final densities between {:.3} and {:.3}, wrote phase_diagram.csv and phase_diagram.png",
+        min, max
+    );
+    Ok(())
+}
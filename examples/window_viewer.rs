@@ -0,0 +1,36 @@
+//! Runs a life-like rule live in a window.
+//!
+//! ```text
+//! cargo run --release --features viewer --example window_viewer -- [RULE] [SIZE] [PALETTE]
+//! ```
+//!
+//! The rule is given in B/S notation (`B3/S23`, the game of life, by
+//! default) and starts from a random grid of `SIZE^2` cells (256 by
+//! default). The space bar plays or pauses the simulation, `n` advances it
+//! by a frame, the up and down arrows change its speed, `r` randomizes the
+//! grid again and `q` quits.
+
+use std::env;
+use std::error::Error;
+
+use rust_ca::automaton::{Automaton, AutomatonImpl};
+use rust_ca::output::{self, WindowView};
+use rust_ca::rule::Rule;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let rule = Rule::from_bs_notation(&args.next().unwrap_or_else(|| "B3/S23".to_string()))?;
+    let size: usize = args.next().map_or(Ok(256), |a| a.parse())?;
+    let palette = args.next().map_or(Ok(Default::default()), |a| a.parse())?;
+
+    let mut automaton = Automaton::new(2, size, rule);
+    automaton.random_init();
+    let view = WindowView {
+        palette,
+        scale: (768 / size.max(1)).max(1),
+        ..WindowView::default()
+    };
+    let report = output::view_in_window(&mut automaton, &view)?;
+    println!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,785 @@
+//! This module contains tools to analyze the behavior of a CA from its grids.
+//!
+//! ## Local entropy
+//! The local entropy map splits the grid in square blocks and computes the
+//! Shannon entropy of the state distribution inside each block. Regions with
+//! complex behavior have a high local entropy while quiescent or uniform
+//! regions have a low one.
+//! ```
+//! use rust_ca::analysis;
+//!
+//! // A 4x4 grid with a uniform left half and a mixed right half.
+//! let grid = vec![
+//!     0, 0, 0, 1,
+//!     0, 0, 1, 0,
+//!     0, 0, 0, 1,
+//!     0, 0, 1, 0,
+//! ];
+//! let entropy = analysis::local_entropy(&grid, 4, 2, 2);
+//! assert_eq!(entropy, vec![0., 1., 0., 1.]);
+//! ```
+//!
+//! ## Coarse-graining
+//! A grid can be coarse-grained by mapping each block of cells to a single
+//! cell, either with a majority vote or with a user supplied mapping.
+//! ```
+//! use rust_ca::analysis::{self, BlockMapping};
+//!
+//! let grid = vec![
+//!     1, 1, 0, 0,
+//!     1, 0, 0, 0,
+//!     0, 0, 2, 2,
+//!     0, 1, 2, 0,
+//! ];
+//! let coarse = analysis::coarse_grain(&grid, 4, 2, &BlockMapping::Majority);
+//! assert_eq!(coarse, vec![1, 0, 0, 2]);
+//!
+//! // Any non-zero cell in the block makes the coarse cell alive.
+//! let any = BlockMapping::Custom(Box::new(|block| block.iter().any(|&c| c > 0) as u8));
+//! assert_eq!(analysis::coarse_grain(&grid, 4, 2, &any), vec![1, 0, 1, 1]);
+//! ```
+//!
+//! ## Domains and defects
+//! Similarly to computational mechanics filtering of 1D CAs, [`defects`]
+//! separates the regular spatial domains of a grid (regions that are locally
+//! periodic, like uniform areas, stripes or checkerboards) from the defects and
+//! particles moving between them.
+//! ```
+//! use rust_ca::analysis;
+//!
+//! // A checkerboard domain with a single defect.
+//! let mut grid: Vec<u8> = (0..64).map(|x| ((x / 8 + x % 8) % 2) as u8).collect();
+//! grid[27] = 1 - grid[27];
+//! let defects = analysis::defects(&grid, 8, 2);
+//! assert!(defects[27]);
+//! assert!(!defects[0]);
+//! ```
+//!
+//! ## Clusters
+//! [`clusters`] labels the connected components of non-background cells of a
+//! grid and returns their size distribution, along with a percolation
+//! indicator.
+//! ```
+//! use rust_ca::analysis;
+//!
+//! let grid = vec![
+//!     1, 1, 0, 0,
+//!     0, 1, 0, 1,
+//!     0, 1, 0, 1,
+//!     0, 1, 0, 0,
+//! ];
+//! let stats = analysis::clusters(&grid, 4, 0);
+//! assert_eq!(stats.sizes, vec![5, 2]);
+//! assert!(stats.spanning);
+//! ```
+//!
+//! ## Fingerprints
+//! [`fingerprint`] simulates a rule and summarizes its behavior with a fixed
+//! length feature vector, which can be used to cluster rules or as a
+//! descriptor for novelty search.
+//! ```
+//! use rust_ca::analysis::{self, FingerprintConfig};
+//! use rust_ca::rule::Rule;
+//!
+//! let config = FingerprintConfig { size: 32, steps: 40, samples: 4 };
+//! let features = analysis::fingerprint(&Rule::gol(), &config);
+//! assert_eq!(features.len(), config.feature_count());
+//! ```
+//!
+//! ## Zoo
+//! A [`Zoo`] collects the still lifes, oscillators and spaceships found in
+//! the grids of many runs, deduplicates them and writes them as RLE files.
+
+mod clustering;
+pub use clustering::{cluster_exemplars, cluster_exemplars_with_rng, standardize, KMeans};
+
+mod debruijn;
+pub use debruijn::DeBruijnGraph;
+
+mod phase;
+pub use phase::PhaseDiagram;
+
+mod scaling;
+pub use scaling::{finite_size_scaling, write_scaling_csv, ScalingRow};
+
+mod zoo;
+pub use zoo::{Structure, StructureKind, Zoo};
+
+use std::collections::{BTreeMap, HashSet};
+
+use rand::Rng;
+
+use crate::automaton::{Automaton, AutomatonImpl};
+use crate::rule::Rule;
+use crate::stats;
+
+/// Returns the number of blocks along each side when splitting a grid of size
+/// `size` in blocks of size `block`. Incomplete blocks on the edges are
+/// counted.
+pub fn blocks_per_side(size: usize, block: usize) -> usize {
+    size.div_ceil(block)
+}
+
+/// Computes the Shannon entropy of each `block`x`block` square of the grid,
+/// normalized by the maximum entropy `ln(states)` so that values lie in `[0,
+/// 1]`. Blocks are returned in row-major order (see [`blocks_per_side`] for the
+/// number of blocks per row).
+pub fn local_entropy(grid: &[u8], size: usize, states: u8, block: usize) -> Vec<f64> {
+    assert!(block > 0, "The block size must be positive");
+    let n_blocks = blocks_per_side(size, block);
+    let mut counts = vec![0usize; n_blocks * n_blocks * states as usize];
+    for i in 0..size {
+        for j in 0..size {
+            let b = (i / block) * n_blocks + j / block;
+            counts[b * states as usize + grid[i * size + j] as usize] += 1;
+        }
+    }
+    counts
+        .chunks(states as usize)
+        .map(|c| stats::normalized_entropy(c, states))
+        .collect()
+}
+
+/// A user supplied function mapping a block of cells to a single cell.
+pub type BlockFn = Box<dyn Fn(&[u8]) -> u8>;
+
+/// The function used to map a block of cells to a single cell when
+/// coarse-graining a grid.
+pub enum BlockMapping {
+    /// The most frequent state in the block. Ties are resolved in favor of the
+    /// lowest state.
+    Majority,
+    /// A user supplied mapping. The function receives the cells of the block
+    /// in row-major order.
+    Custom(BlockFn),
+}
+
+impl BlockMapping {
+    /// Maps a block of cells to a single cell.
+    pub fn apply(&self, block: &[u8]) -> u8 {
+        match self {
+            BlockMapping::Majority => {
+                let mut counts = [0usize; 256];
+                for &c in block {
+                    counts[c as usize] += 1;
+                }
+                counts
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .max_by_key(|&(_, c)| *c)
+                    .map_or(0, |(s, _)| s as u8)
+            }
+            BlockMapping::Custom(f) => f(block),
+        }
+    }
+}
+
+/// Coarse-grains a grid of size `size` by mapping every `block`x`block` square
+/// to a single cell with `mapping`. The resulting grid has size
+/// [`blocks_per_side`]`(size, block)`; incomplete blocks on the edges are
+/// mapped from the cells they contain.
+pub fn coarse_grain(grid: &[u8], size: usize, block: usize, mapping: &BlockMapping) -> Vec<u8> {
+    assert!(block > 0, "The block size must be positive");
+    let n_blocks = blocks_per_side(size, block);
+    let mut out = Vec::with_capacity(n_blocks * n_blocks);
+    let mut cells = Vec::with_capacity(block * block);
+    for bi in 0..n_blocks {
+        for bj in 0..n_blocks {
+            cells.clear();
+            for i in bi * block..((bi + 1) * block).min(size) {
+                cells.extend_from_slice(
+                    &grid[i * size + bj * block..i * size + ((bj + 1) * block).min(size)],
+                );
+            }
+            out.push(mapping.apply(&cells));
+        }
+    }
+    out
+}
+
+/// Returns a map of the cells of the grid that don't belong to a regular
+/// spatial domain. A cell is part of a domain if the square patch of radius
+/// `max_period` centered on it is periodic both horizontally and vertically,
+/// with periods of at most `max_period` cells. The grid is considered
+/// periodic.
+pub fn defects(grid: &[u8], size: usize, max_period: usize) -> Vec<bool> {
+    assert!(max_period > 0, "The maximum period must be positive");
+    let r = max_period as isize;
+    let s = size as isize;
+    let cell = |i: isize, j: isize| grid[(((i + s) % s) * s + (j + s) % s) as usize];
+    // Checks that the patch is invariant under the translation (di, dj),
+    // comparing only pairs of cells that both lie inside the patch.
+    let invariant = |i: isize, j: isize, di: isize, dj: isize| {
+        (-r..=r - di)
+            .all(|a| (-r..=r - dj).all(|b| cell(i + a, j + b) == cell(i + a + di, j + b + dj)))
+    };
+    let mut out = Vec::with_capacity(size * size);
+    for i in 0..s {
+        for j in 0..s {
+            let horizontal = (1..=r).any(|p| invariant(i, j, 0, p));
+            let vertical = (1..=r).any(|p| invariant(i, j, p, 0));
+            out.push(!(horizontal && vertical));
+        }
+    }
+    out
+}
+
+/// The cluster statistics of a grid, computed by [`clusters`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterStats {
+    /// The sizes of all the clusters, in decreasing order.
+    pub sizes: Vec<usize>,
+    /// Whether a cluster spans the grid from top to bottom or from left to
+    /// right (percolation indicator).
+    pub spanning: bool,
+    /// The total number of cells in the grid.
+    pub cells: usize,
+}
+
+impl ClusterStats {
+    /// Returns the histogram of cluster sizes, mapping each size to the number
+    /// of clusters of that size.
+    pub fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for &s in &self.sizes {
+            *histogram.entry(s).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Returns the size of the largest cluster.
+    pub fn largest(&self) -> usize {
+        self.sizes.first().copied().unwrap_or(0)
+    }
+
+    /// Returns the fraction of the grid occupied by the largest cluster. This
+    /// is the usual percolation order parameter and can be used as a fitness
+    /// function when searching for rules.
+    pub fn largest_fraction(&self) -> f64 {
+        if self.cells == 0 {
+            0.
+        } else {
+            self.largest() as f64 / self.cells as f64
+        }
+    }
+}
+
+/// Finds the root of `x` in a union-find forest, compressing the path.
+fn find(parents: &mut [usize], mut x: usize) -> usize {
+    while parents[x] != x {
+        parents[x] = parents[parents[x]];
+        x = parents[x];
+    }
+    x
+}
+
+/// Labels the clusters of the grid, the 4-connected components of cells
+/// sharing the same state different from `background`. The boundaries are
+/// open (clusters don't wrap around the edges of the grid) so that spanning
+/// clusters can be detected.
+pub fn clusters(grid: &[u8], size: usize, background: u8) -> ClusterStats {
+    let mut parents: Vec<usize> = (0..grid.len()).collect();
+    for i in 0..size {
+        for j in 0..size {
+            let idx = i * size + j;
+            if grid[idx] == background {
+                continue;
+            }
+            for n in [(i > 0).then(|| idx - size), (j > 0).then(|| idx - 1)]
+                .iter()
+                .flatten()
+            {
+                if grid[*n] == grid[idx] {
+                    let (a, b) = (find(&mut parents, idx), find(&mut parents, *n));
+                    parents[a] = b;
+                }
+            }
+        }
+    }
+    let mut sizes = vec![0usize; grid.len()];
+    for (idx, &c) in grid.iter().enumerate() {
+        if c != background {
+            let root = find(&mut parents, idx);
+            sizes[root] += 1;
+        }
+    }
+    // A cluster spans the grid if it has cells on two opposite edges.
+    let mut roots = |cells: &[usize]| -> HashSet<usize> {
+        cells
+            .iter()
+            .filter(|&&idx| grid[idx] != background)
+            .map(|&idx| find(&mut parents, idx))
+            .collect()
+    };
+    let top = roots(&(0..size).collect::<Vec<_>>());
+    let bottom = roots(&((size - 1) * size..size * size).collect::<Vec<_>>());
+    let left = roots(&(0..size).map(|i| i * size).collect::<Vec<_>>());
+    let right = roots(&(0..size).map(|i| i * size + size - 1).collect::<Vec<_>>());
+    let spanning =
+        top.intersection(&bottom).next().is_some() || left.intersection(&right).next().is_some();
+
+    let mut sizes: Vec<usize> = sizes.into_iter().filter(|&s| s > 0).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    ClusterStats {
+        sizes,
+        spanning,
+        cells: grid.len(),
+    }
+}
+
+/// Simulates the automaton for `steps` steps and computes the cluster
+/// statistics of every `skip`-th frame.
+pub fn cluster_series<T: AutomatonImpl>(
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    background: u8,
+) -> Vec<ClusterStats> {
+    let size = autom.size();
+    autom
+        .skipped_iter(steps, skip, 1)
+        .map(|grid| clusters(&grid, size, background))
+        .collect()
+}
+
+/// Summary metrics of the final state of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RunMetrics {
+    /// The entropy of the state distribution (see [`stats::entropy`]).
+    pub entropy: f64,
+    /// The density of non-zero cells.
+    pub density: f64,
+    /// The fraction of cells that changed during the last step (see
+    /// [`stats::activity`]).
+    pub activity: f64,
+    /// The fraction of the grid occupied by the largest cluster (see
+    /// [`ClusterStats::largest_fraction`]).
+    pub largest_cluster: f64,
+}
+
+impl RunMetrics {
+    fn to_array(self) -> [f64; 4] {
+        [
+            self.entropy,
+            self.density,
+            self.activity,
+            self.largest_cluster,
+        ]
+    }
+
+    fn from_array(a: [f64; 4]) -> RunMetrics {
+        RunMetrics {
+            entropy: a[0],
+            density: a[1],
+            activity: a[2],
+            largest_cluster: a[3],
+        }
+    }
+}
+
+/// Simulates an initialized automaton for `steps` steps and returns the
+/// metrics of its final state. The automaton is updated one more time to
+/// measure the final activity.
+pub fn run_metrics<T: AutomatonImpl>(autom: &mut T, steps: u32) -> RunMetrics {
+    let size = autom.size();
+    let states = autom.states();
+    for _ in 0..steps {
+        autom.update();
+    }
+    let grid = autom.grid();
+    autom.update();
+    RunMetrics {
+        entropy: stats::entropy(&grid, states),
+        density: 1. - stats::densities(&grid, states)[0],
+        activity: stats::activity(&grid, &autom.grid()),
+        largest_cluster: clusters(&grid, size, 0).largest_fraction(),
+    }
+}
+
+/// The mean and variance of the metrics of several runs, computed by
+/// [`aggregate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AggregateStats {
+    /// The number of runs.
+    pub runs: usize,
+    /// The mean of each metric.
+    pub mean: RunMetrics,
+    /// The variance of each metric.
+    pub variance: RunMetrics,
+}
+
+impl AggregateStats {
+    /// Computes the mean and variance of a set of run metrics.
+    pub fn from_runs(runs: &[RunMetrics]) -> AggregateStats {
+        let n = runs.len().max(1) as f64;
+        let mut mean = [0.; 4];
+        let mut variance = [0.; 4];
+        for r in runs {
+            for (m, v) in mean.iter_mut().zip(r.to_array()) {
+                *m += v / n;
+            }
+        }
+        for r in runs {
+            for ((s, m), v) in variance.iter_mut().zip(mean).zip(r.to_array()) {
+                *s += (v - m).powi(2) / n;
+            }
+        }
+        AggregateStats {
+            runs: runs.len(),
+            mean: RunMetrics::from_array(mean),
+            variance: RunMetrics::from_array(variance),
+        }
+    }
+}
+
+/// Runs the same rule from `n_seeds` random initial conditions on a grid of
+/// size `size` for `steps` steps, and returns the mean and variance of the
+/// final metrics (see [`run_metrics`]). Single runs are noisy, aggregating
+/// several seeds gives a more reliable judgement of a rule. If `parallel` is
+/// true, the runs are distributed over the available cores.
+/// ```
+/// use rust_ca::analysis;
+/// use rust_ca::rule::Rule;
+///
+/// let stats = analysis::aggregate(&Rule::gol(), 4, 20, 32, true);
+/// assert_eq!(stats.runs, 4);
+/// assert!(stats.mean.density < 0.5);
+/// ```
+pub fn aggregate(
+    rule: &Rule,
+    n_seeds: usize,
+    steps: u32,
+    size: usize,
+    parallel: bool,
+) -> AggregateStats {
+    let run = || {
+        let mut autom = Automaton::from_rule(rule.clone(), size);
+        autom.random_init();
+        run_metrics(&mut autom, steps)
+    };
+    let threads = if parallel {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        1
+    };
+    let runs: Vec<RunMetrics> = if threads > 1 && n_seeds > 1 {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let n = n_seeds / threads + usize::from(t < n_seeds % threads);
+                    scope.spawn(move || (0..n).map(|_| run()).collect::<Vec<_>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("Aggregation thread panicked"))
+                .collect()
+        })
+    } else {
+        (0..n_seeds).map(|_| run()).collect()
+    };
+    AggregateStats::from_runs(&runs)
+}
+
+/// An online detector of spikes and regime changes in a metric time series.
+/// A value is flagged when it deviates from the mean of the previous `window`
+/// values by more than `threshold` standard deviations.
+/// ```
+/// use rust_ca::analysis::MomentDetector;
+///
+/// let mut detector = MomentDetector::new(4, 3.);
+/// let series = [0.5, 0.52, 0.49, 0.51, 0.5, 0.9, 0.5];
+/// let flagged: Vec<bool> = series.iter().map(|&v| detector.push(v)).collect();
+/// assert_eq!(flagged, vec![false, false, false, false, false, true, false]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MomentDetector {
+    window: usize,
+    threshold: f64,
+    history: std::collections::VecDeque<f64>,
+}
+
+impl MomentDetector {
+    /// Makes a new detector comparing each value with the `window` previous
+    /// ones.
+    pub fn new(window: usize, threshold: f64) -> MomentDetector {
+        assert!(
+            window > 1,
+            "The detection window must contain at least 2 values"
+        );
+        MomentDetector {
+            window,
+            threshold,
+            history: std::collections::VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Adds a value to the series and returns whether it is a spike or the
+    /// start of a new regime. No value is flagged before the window is full.
+    pub fn push(&mut self, value: f64) -> bool {
+        let flagged = if self.history.len() == self.window {
+            let n = self.window as f64;
+            let mean = self.history.iter().sum::<f64>() / n;
+            let std = (self.history.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+            // Small absolute tolerance so that constant series don't flag
+            // floating point noise.
+            (value - mean).abs() > self.threshold * std + 1e-9
+        } else {
+            false
+        };
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+        flagged
+    }
+}
+
+/// The simulation parameters used to compute a rule [`fingerprint`].
+#[derive(Debug, Clone)]
+pub struct FingerprintConfig {
+    /// The size of the simulated grid.
+    pub size: usize,
+    /// The number of simulated steps.
+    pub steps: u32,
+    /// The number of samples of the entropy curve in the fingerprint.
+    pub samples: usize,
+}
+
+impl FingerprintConfig {
+    /// Returns the number of features of the fingerprints computed with this
+    /// configuration.
+    pub fn feature_count(&self) -> usize {
+        self.samples + 5
+    }
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        FingerprintConfig {
+            size: 64,
+            steps: 200,
+            samples: 8,
+        }
+    }
+}
+
+/// Computes a fixed length feature vector describing the behavior of a rule,
+/// simulated from a random initial configuration. The features are, in order:
+/// - `config.samples` samples of the entropy curve (see [`stats::entropy`]),
+///   evenly spaced over the simulation,
+/// - the equilibrium density of non-zero cells, averaged over the last
+///   quarter of the simulation,
+/// - the equilibrium activity (see [`stats::activity`]), averaged over the
+///   last quarter of the simulation,
+/// - the decay rate of the activity between the first and the last quarter of
+///   the simulation,
+/// - the number of clusters per cell in the final grid (see [`clusters`]),
+/// - the fraction of the final grid occupied by the largest cluster.
+///
+/// At least 4 steps are simulated, so that every quarter of the simulation
+/// has a step.
+pub fn fingerprint(rule: &Rule, config: &FingerprintConfig) -> Vec<f64> {
+    fingerprint_with_rng(rule, config, &mut rand::thread_rng())
+}
+
+/// Computes the fingerprint of a rule (see [`fingerprint`]) from a random
+/// initial configuration drawn from the given random number generator. A
+/// seeded generator always gives the same fingerprint.
+///
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rust_ca::analysis::{self, FingerprintConfig};
+/// use rust_ca::rule::Rule;
+///
+/// let config = FingerprintConfig { size: 16, steps: 20, samples: 2 };
+/// let a = analysis::fingerprint_with_rng(&Rule::gol(), &config, &mut StdRng::seed_from_u64(1));
+/// let b = analysis::fingerprint_with_rng(&Rule::gol(), &config, &mut StdRng::seed_from_u64(1));
+/// assert_eq!(a, b);
+/// ```
+pub fn fingerprint_with_rng<R: Rng + ?Sized>(
+    rule: &Rule,
+    config: &FingerprintConfig,
+    rng: &mut R,
+) -> Vec<f64> {
+    let states = rule.states;
+    let mut autom = Automaton::from_rule(rule.clone(), config.size);
+    autom.random_init_seeded(rng.gen());
+    fingerprint_from(&mut autom, states, config)
+}
+
+/// Computes the fingerprint of an already initialized automaton.
+pub(crate) fn fingerprint_from<T: AutomatonImpl>(
+    autom: &mut T,
+    states: u8,
+    config: &FingerprintConfig,
+) -> Vec<f64> {
+    let size = autom.size();
+    let steps = config.steps.max(4) as usize;
+    let quarter = steps / 4;
+    let mut entropies = Vec::with_capacity(steps);
+    let mut densities = Vec::with_capacity(steps);
+    let mut activities = Vec::with_capacity(steps);
+    let mut previous: Option<Vec<u8>> = None;
+    for grid in autom.skipped_iter(steps as u32, 1, 1) {
+        entropies.push(stats::entropy(&grid, states));
+        densities.push(1. - stats::densities(&grid, states)[0]);
+        activities.push(previous.as_ref().map_or(0., |p| stats::activity(p, &grid)));
+        previous = Some(grid);
+    }
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len().max(1) as f64;
+
+    let mut features: Vec<f64> = (0..config.samples)
+        .map(|i| entropies[i * (steps - 1) / (config.samples.max(2) - 1)])
+        .collect();
+    features.push(mean(&densities[steps - quarter..]));
+    let early_activity = mean(&activities[1..=quarter]);
+    let late_activity = mean(&activities[steps - quarter..]);
+    features.push(late_activity);
+    features.push(
+        ((early_activity + f64::EPSILON) / (late_activity + f64::EPSILON)).ln()
+            / (steps - quarter) as f64,
+    );
+    let final_clusters = clusters(previous.as_deref().unwrap_or(&[]), size, 0);
+    features.push(final_clusters.sizes.len() as f64 / (size * size) as f64);
+    features.push(final_clusters.largest_fraction());
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blocks_per_side, clusters, coarse_grain, defects, fingerprint, local_entropy,
+        AggregateStats, BlockMapping, FingerprintConfig, RunMetrics,
+    };
+    use crate::rule::Rule;
+
+    #[test]
+    fn uniform_grid_has_zero_entropy() {
+        let grid = vec![1; 64];
+        assert!(local_entropy(&grid, 8, 3, 4).iter().all(|&e| e == 0.));
+    }
+
+    #[test]
+    fn incomplete_blocks_are_counted() {
+        assert_eq!(blocks_per_side(10, 4), 3);
+        let grid = vec![0; 100];
+        assert_eq!(local_entropy(&grid, 10, 2, 4).len(), 9);
+    }
+
+    #[test]
+    fn maximal_entropy_is_one() {
+        let grid = vec![0, 1, 2, 0, 1, 2, 0, 1, 2];
+        let entropy = local_entropy(&grid, 3, 3, 3);
+        assert!((entropy[0] - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn majority_ties_favor_lowest_state() {
+        assert_eq!(BlockMapping::Majority.apply(&[2, 1, 1, 2]), 1);
+        assert_eq!(BlockMapping::Majority.apply(&[0, 1, 2, 3]), 0);
+    }
+
+    #[test]
+    fn coarse_grain_with_unit_block_is_identity() {
+        let grid: Vec<u8> = (0..25).map(|x| x % 3).collect();
+        assert_eq!(coarse_grain(&grid, 5, 1, &BlockMapping::Majority), grid);
+    }
+
+    #[test]
+    fn coarse_grain_handles_incomplete_blocks() {
+        let mut grid = vec![0; 25];
+        grid[24] = 1;
+        let coarse = coarse_grain(&grid, 5, 2, &BlockMapping::Majority);
+        assert_eq!(coarse.len(), 9);
+        assert_eq!(coarse[8], 1);
+    }
+
+    #[test]
+    fn periodic_domains_have_no_defects() {
+        let stripes: Vec<u8> = (0..100).map(|x| (x % 10 % 2) as u8).collect();
+        assert!(defects(&stripes, 10, 2).iter().all(|d| !d));
+        let uniform = vec![2; 100];
+        assert!(defects(&uniform, 10, 1).iter().all(|d| !d));
+    }
+
+    #[test]
+    fn defects_are_local() {
+        let mut grid = vec![0; 144];
+        grid[6 * 12 + 6] = 1;
+        let d = defects(&grid, 12, 1);
+        // Only the neighborhood of the perturbed cell is a defect.
+        assert_eq!(d.iter().filter(|&&x| x).count(), 9);
+        assert!(d[5 * 12 + 5] && d[7 * 12 + 7]);
+    }
+
+    #[test]
+    fn clusters_separate_states() {
+        let grid = vec![1, 2, 2, 1];
+        let stats = clusters(&grid, 2, 0);
+        assert_eq!(stats.sizes, vec![1, 1, 1, 1]);
+        assert_eq!(stats.histogram().get(&1), Some(&4));
+        assert!(!stats.spanning);
+    }
+
+    #[test]
+    fn empty_grid_has_no_clusters() {
+        let stats = clusters(&[0; 16], 4, 0);
+        assert!(stats.sizes.is_empty());
+        assert_eq!(stats.largest_fraction(), 0.);
+    }
+
+    #[test]
+    fn horizontal_line_spans() {
+        let mut grid = vec![0; 25];
+        for j in 0..5 {
+            grid[2 * 5 + j] = 1;
+        }
+        let stats = clusters(&grid, 5, 0);
+        assert!(stats.spanning);
+        assert_eq!(stats.largest_fraction(), 0.2);
+    }
+
+    #[test]
+    fn constant_rule_fingerprint() {
+        let config = FingerprintConfig {
+            size: 16,
+            steps: 20,
+            samples: 3,
+        };
+        let features = fingerprint(&Rule::new(1, 2, vec![0; 512]), &config);
+        assert_eq!(features.len(), config.feature_count());
+        // Everything dies after the first step.
+        assert_eq!(&features[1..5], &[0., 0., 0., 0.]);
+        assert!(features[5] > 0.);
+        assert_eq!(&features[6..], &[0., 0.]);
+        // Short simulations are extended to 4 steps.
+        for steps in 0..4 {
+            let short = FingerprintConfig { steps, ..config };
+            let features = fingerprint(&Rule::new(1, 2, vec![0; 512]), &short);
+            assert_eq!(&features[3..5], &[0., 0.]);
+        }
+    }
+
+    #[test]
+    fn aggregate_mean_and_variance() {
+        let runs = [
+            RunMetrics {
+                entropy: 1.,
+                ..Default::default()
+            },
+            RunMetrics {
+                entropy: 0.,
+                density: 0.5,
+                ..Default::default()
+            },
+        ];
+        let stats = AggregateStats::from_runs(&runs);
+        assert_eq!(stats.mean.entropy, 0.5);
+        assert_eq!(stats.variance.entropy, 0.25);
+        assert_eq!(stats.mean.density, 0.25);
+        assert_eq!(stats.variance.activity, 0.);
+    }
+}
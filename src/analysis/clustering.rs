@@ -0,0 +1,245 @@
+//! K-means clustering of feature vectors, used to group rules by their
+//! fingerprints and pick representative exemplars.
+
+use rand::Rng;
+
+use super::{fingerprint_with_rng, FingerprintConfig};
+use crate::rule::Rule;
+
+/// The result of a k-means clustering.
+#[derive(Debug, Clone)]
+pub struct KMeans {
+    /// The centroids of the clusters.
+    pub centroids: Vec<Vec<f64>>,
+    /// The cluster index of each point.
+    pub assignments: Vec<usize>,
+}
+
+impl KMeans {
+    /// Clusters `points` in `k` clusters with Lloyd's algorithm, initialized
+    /// with k-means++ seeding. Stops after `iterations` iterations or when the
+    /// assignments don't change anymore.
+    pub fn fit(points: &[Vec<f64>], k: usize, iterations: usize) -> KMeans {
+        KMeans::fit_with_rng(points, k, iterations, &mut rand::thread_rng())
+    }
+
+    /// Clusters `points` like [`KMeans::fit`], drawing the k-means++ seeding
+    /// from the given random number generator.
+    pub fn fit_with_rng<R: Rng + ?Sized>(
+        points: &[Vec<f64>],
+        k: usize,
+        iterations: usize,
+        rng: &mut R,
+    ) -> KMeans {
+        assert!(k > 0, "The number of clusters must be positive");
+        let k = k.min(points.len());
+        let mut centroids = kmeans_plus_plus(points, k, rng);
+        let mut assignments = vec![usize::MAX; points.len()];
+        for _ in 0..iterations {
+            let new_assignments: Vec<usize> =
+                points.iter().map(|p| nearest(&centroids, p)).collect();
+            if new_assignments == assignments {
+                break;
+            }
+            assignments = new_assignments;
+            for (c, centroid) in centroids.iter_mut().enumerate() {
+                let members: Vec<&Vec<f64>> = points
+                    .iter()
+                    .zip(assignments.iter())
+                    .filter(|(_, &a)| a == c)
+                    .map(|(p, _)| p)
+                    .collect();
+                if members.is_empty() {
+                    continue;
+                }
+                for (d, v) in centroid.iter_mut().enumerate() {
+                    *v = members.iter().map(|m| m[d]).sum::<f64>() / members.len() as f64;
+                }
+            }
+        }
+        KMeans {
+            centroids,
+            assignments,
+        }
+    }
+
+    /// Returns, for each non-empty cluster, the index of the point closest to
+    /// its centroid.
+    pub fn exemplars(&self, points: &[Vec<f64>]) -> Vec<usize> {
+        (0..self.centroids.len())
+            .filter_map(|c| {
+                points
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| self.assignments[*i] == c)
+                    .min_by(|(_, a), (_, b)| {
+                        distance(a, &self.centroids[c]).total_cmp(&distance(b, &self.centroids[c]))
+                    })
+                    .map(|(i, _)| i)
+            })
+            .collect()
+    }
+}
+
+/// Squared euclidean distance between two points.
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+/// Index of the centroid nearest to `point`.
+fn nearest(centroids: &[Vec<f64>], point: &[f64]) -> usize {
+    (0..centroids.len())
+        .min_by(|&a, &b| distance(&centroids[a], point).total_cmp(&distance(&centroids[b], point)))
+        .unwrap_or(0)
+}
+
+/// k-means++ seeding: centroids are sampled among the points with a
+/// probability proportional to their squared distance to the nearest already
+/// chosen centroid.
+fn kmeans_plus_plus<R: Rng + ?Sized>(points: &[Vec<f64>], k: usize, rng: &mut R) -> Vec<Vec<f64>> {
+    let mut centroids = vec![points[rng.gen_range(0..points.len())].clone()];
+    while centroids.len() < k {
+        let weights: Vec<f64> = points
+            .iter()
+            .map(|p| distance(&centroids[nearest(&centroids, p)], p))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0. {
+            // All the remaining points are duplicates of the centroids.
+            centroids.push(points[rng.gen_range(0..points.len())].clone());
+            continue;
+        }
+        let mut target = rng.gen_range(0.0..total);
+        let idx = weights
+            .iter()
+            .position(|&w| {
+                target -= w;
+                target < 0.
+            })
+            .unwrap_or(points.len() - 1);
+        centroids.push(points[idx].clone());
+    }
+    centroids
+}
+
+/// Standardizes every feature of the points to zero mean and unit variance so
+/// that all features weigh the same in the clustering.
+pub fn standardize(points: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    if points.is_empty() {
+        return vec![];
+    }
+    let n = points.len() as f64;
+    let dims = points[0].len();
+    let means: Vec<f64> = (0..dims)
+        .map(|d| points.iter().map(|p| p[d]).sum::<f64>() / n)
+        .collect();
+    let stds: Vec<f64> = (0..dims)
+        .map(|d| {
+            (points
+                .iter()
+                .map(|p| (p[d] - means[d]).powi(2))
+                .sum::<f64>()
+                / n)
+                .sqrt()
+        })
+        .collect();
+    points
+        .iter()
+        .map(|p| {
+            (0..dims)
+                .map(|d| {
+                    if stds[d] > 0. {
+                        (p[d] - means[d]) / stds[d]
+                    } else {
+                        0.
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Groups rules by their standardized fingerprints (see [`fingerprint`](super::fingerprint)) in
+/// `k` clusters and returns the indices of one representative rule per
+/// cluster. Rendering only the exemplars avoids the manual triage of
+/// thousands of similar random rules.
+///
+/// ```
+/// use rust_ca::analysis::{self, FingerprintConfig};
+/// use rust_ca::rule::Rule;
+///
+/// let rules: Vec<Rule> = (0..6).map(|_| Rule::random(1, 2)).collect();
+/// let config = FingerprintConfig { size: 16, steps: 20, samples: 2 };
+/// let exemplars = analysis::cluster_exemplars(&rules, 3, &config);
+/// assert!(!exemplars.is_empty() && exemplars.len() <= 3);
+/// ```
+pub fn cluster_exemplars(rules: &[Rule], k: usize, config: &FingerprintConfig) -> Vec<usize> {
+    cluster_exemplars_with_rng(rules, k, config, &mut rand::thread_rng())
+}
+
+/// Picks the exemplars of the rules like [`cluster_exemplars`], drawing the
+/// initial grids of the fingerprints and the seeding of the clustering from
+/// the given random number generator, so that a seeded generator always
+/// gives the same exemplars.
+pub fn cluster_exemplars_with_rng<R: Rng + ?Sized>(
+    rules: &[Rule],
+    k: usize,
+    config: &FingerprintConfig,
+    rng: &mut R,
+) -> Vec<usize> {
+    if rules.is_empty() {
+        return vec![];
+    }
+    let points = standardize(
+        &rules
+            .iter()
+            .map(|r| fingerprint_with_rng(r, config, rng))
+            .collect::<Vec<_>>(),
+    );
+    KMeans::fit_with_rng(&points, k, 100, rng).exemplars(&points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cluster_exemplars_with_rng, standardize, KMeans};
+    use crate::analysis::FingerprintConfig;
+    use crate::rule::Rule;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn separates_well_separated_groups() {
+        let points = vec![
+            vec![0., 0.],
+            vec![0.1, 0.],
+            vec![0., 0.1],
+            vec![10., 10.],
+            vec![10.1, 10.],
+        ];
+        let kmeans = KMeans::fit(&points, 2, 20);
+        assert_eq!(kmeans.assignments[0], kmeans.assignments[2]);
+        assert_eq!(kmeans.assignments[3], kmeans.assignments[4]);
+        assert_ne!(kmeans.assignments[0], kmeans.assignments[3]);
+        assert_eq!(kmeans.exemplars(&points).len(), 2);
+    }
+
+    #[test]
+    fn standardized_features_are_centered() {
+        let points = vec![vec![1., 5.], vec![3., 5.]];
+        assert_eq!(standardize(&points), vec![vec![-1., 0.], vec![1., 0.]]);
+    }
+
+    #[test]
+    fn seeded_exemplars_are_reproducible() {
+        let rules: Vec<Rule> = (0..8).map(|_| Rule::random(1, 2)).collect();
+        let config = FingerprintConfig {
+            size: 16,
+            steps: 20,
+            samples: 2,
+        };
+        let first = cluster_exemplars_with_rng(&rules, 3, &config, &mut StdRng::seed_from_u64(5));
+        let again = cluster_exemplars_with_rng(&rules, 3, &config, &mut StdRng::seed_from_u64(5));
+        assert_eq!(first, again);
+        assert!(!first.is_empty() && first.len() <= 3);
+    }
+}
@@ -0,0 +1,245 @@
+//! De Bruijn graph analysis of 1D rules.
+//!
+//! A 1D rule of radius `r` with `k` states is a table mapping each word of
+//! `2r + 1` cells to a next state. The cells of a word `c_0 ... c_2r` are
+//! indexed by `c_0 + c_1 k + ... + c_2r k^2r`, the leftmost cell having the
+//! lowest weight (as for the rows of 2D neighborhoods).
+//!
+//! The de Bruijn graph of the rule has the words of `2r` cells as nodes and
+//! the words of `2r + 1` cells as edges, labeled by the rule output. Paths in
+//! the graph spell the preimages of configurations, which gives exact
+//! preimage counts and decision procedures for surjectivity and injectivity.
+
+use std::collections::HashSet;
+
+/// The de Bruijn graph of a 1D rule.
+///
+/// ```
+/// use rust_ca::analysis::DeBruijnGraph;
+///
+/// let rule_90 = DeBruijnGraph::elementary(90);
+/// assert!(rule_90.is_surjective());
+/// assert!(!rule_90.is_injective());
+/// // Every finite word has 4 preimages under rule 90.
+/// assert_eq!(rule_90.preimages(&[0, 1, 1]), 4);
+///
+/// let rule_110 = DeBruijnGraph::elementary(110);
+/// assert!(!rule_110.is_surjective());
+/// assert!(rule_110.garden_of_eden_count(6) > 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeBruijnGraph {
+    states: u8,
+    radius: usize,
+    table: Vec<u8>,
+}
+
+impl DeBruijnGraph {
+    /// Makes the de Bruijn graph of a 1D rule with a given number of states,
+    /// radius and rule table.
+    pub fn new(states: u8, radius: usize, table: Vec<u8>) -> DeBruijnGraph {
+        assert_eq!(
+            table.len(),
+            (states as usize).pow(2 * radius as u32 + 1),
+            "Incorrect 1D rule for radius {} and number of states {}",
+            radius,
+            states
+        );
+        DeBruijnGraph {
+            states,
+            radius,
+            table,
+        }
+    }
+
+    /// Makes the de Bruijn graph of an elementary CA (2 states, radius 1) from
+    /// its Wolfram code.
+    pub fn elementary(code: u8) -> DeBruijnGraph {
+        let table = (0..8)
+            .map(|idx| {
+                // Our indexing gives the lowest weight to the leftmost cell
+                // while Wolfram codes give it the highest.
+                let wolfram = ((idx & 1) << 2) | (idx & 2) | ((idx & 4) >> 2);
+                (code >> wolfram) & 1
+            })
+            .collect();
+        DeBruijnGraph::new(2, 1, table)
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn n_nodes(&self) -> usize {
+        (self.states as usize).pow(2 * self.radius as u32)
+    }
+
+    /// Returns the edges of the graph as `(source, target, label)` triples.
+    fn edges(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        let n_nodes = self.n_nodes();
+        let k = self.states as usize;
+        (0..self.table.len()).map(move |word| (word % n_nodes, word / k, self.table[word]))
+    }
+
+    /// Advances a vector of path counts along the edges labeled `symbol`.
+    fn step_counts(&self, counts: &[u64], symbol: u8) -> Vec<u64> {
+        let mut next = vec![0; counts.len()];
+        for (src, dst, label) in self.edges() {
+            if label == symbol {
+                next[dst] += counts[src];
+            }
+        }
+        next
+    }
+
+    /// Returns the number of preimages of a finite word, i.e. the number of
+    /// words of length `word.len() + 2r` mapped to `word` by the rule.
+    pub fn preimages(&self, word: &[u8]) -> u64 {
+        word.iter()
+            .fold(vec![1; self.n_nodes()], |counts, &s| {
+                self.step_counts(&counts, s)
+            })
+            .iter()
+            .sum()
+    }
+
+    /// Returns the number of preimages of the periodic configuration of
+    /// period `word` (the number of configurations of a ring of
+    /// `word.len()` cells mapped to the ring `word`).
+    pub fn cyclic_preimages(&self, word: &[u8]) -> u64 {
+        (0..self.n_nodes())
+            .map(|start| {
+                let mut counts = vec![0; self.n_nodes()];
+                counts[start] = 1;
+                word.iter()
+                    .fold(counts, |counts, &s| self.step_counts(&counts, s))[start]
+            })
+            .sum()
+    }
+
+    /// Returns the number of Garden-of-Eden configurations of a ring of
+    /// `length` cells, configurations that have no preimage.
+    pub fn garden_of_eden_count(&self, length: usize) -> u64 {
+        let k = self.states as u64;
+        (0..k.pow(length as u32))
+            .filter(|&w| {
+                let word: Vec<u8> = (0..length)
+                    .map(|i| ((w / k.pow(i as u32)) % k) as u8)
+                    .collect();
+                self.cyclic_preimages(&word) == 0
+            })
+            .count() as u64
+    }
+
+    /// Returns whether the rule is surjective on bi-infinite configurations.
+    /// A rule is surjective if and only if every finite word has a preimage,
+    /// which is decided with a subset construction on the de Bruijn graph.
+    pub fn is_surjective(&self) -> bool {
+        let start: Vec<usize> = (0..self.n_nodes()).collect();
+        let mut seen = HashSet::new();
+        let mut stack = vec![start.clone()];
+        seen.insert(start);
+        while let Some(subset) = stack.pop() {
+            for symbol in 0..self.states {
+                let mut next: Vec<usize> = self
+                    .edges()
+                    .filter(|&(src, _, label)| {
+                        label == symbol && subset.binary_search(&src).is_ok()
+                    })
+                    .map(|(_, dst, _)| dst)
+                    .collect();
+                next.sort_unstable();
+                next.dedup();
+                if next.is_empty() {
+                    return false;
+                }
+                if seen.insert(next.clone()) {
+                    stack.push(next);
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns whether the rule is injective (and thus reversible) on
+    /// bi-infinite configurations. Two distinct configurations with the same
+    /// image correspond to a bi-infinite path visiting an off-diagonal node in
+    /// the pair graph (the product of the de Bruijn graph with itself,
+    /// restricted to pairs of edges with the same label).
+    pub fn is_injective(&self) -> bool {
+        let n = self.n_nodes();
+        let mut pair_edges = vec![];
+        for (src_a, dst_a, label_a) in self.edges() {
+            for (src_b, dst_b, label_b) in self.edges() {
+                if label_a == label_b {
+                    pair_edges.push((src_a * n + src_b, dst_a * n + dst_b));
+                }
+            }
+        }
+        let forward = infinite_path_nodes(n * n, &pair_edges, false);
+        let backward = infinite_path_nodes(n * n, &pair_edges, true);
+        (0..n * n).all(|pair| pair / n == pair % n || !(forward[pair] && backward[pair]))
+    }
+}
+
+/// Returns the nodes of a graph from which an infinite path starts (or, if
+/// `reverse` is true, at which an infinite path ends), by iteratively removing
+/// nodes without outgoing (incoming) edges.
+fn infinite_path_nodes(n_nodes: usize, edges: &[(usize, usize)], reverse: bool) -> Vec<bool> {
+    let mut alive = vec![true; n_nodes];
+    loop {
+        let mut degree = vec![0usize; n_nodes];
+        for &(src, dst) in edges {
+            let (from, to) = if reverse { (dst, src) } else { (src, dst) };
+            if alive[from] && alive[to] {
+                degree[from] += 1;
+            }
+        }
+        let mut changed = false;
+        for node in 0..n_nodes {
+            if alive[node] && degree[node] == 0 {
+                alive[node] = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            return alive;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeBruijnGraph;
+
+    #[test]
+    fn elementary_table_follows_wolfram_code() {
+        // Rule 2 maps 001 (right cell alive) to 1.
+        let g = DeBruijnGraph::elementary(2);
+        assert_eq!(g.table, vec![0, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn identity_and_shift_are_reversible() {
+        for code in [204, 170, 240, 51] {
+            let g = DeBruijnGraph::elementary(code);
+            assert!(g.is_surjective(), "rule {}", code);
+            assert!(g.is_injective(), "rule {}", code);
+        }
+    }
+
+    #[test]
+    fn constant_rule_gardens_of_eden() {
+        let g = DeBruijnGraph::elementary(0);
+        assert!(!g.is_surjective());
+        assert!(!g.is_injective());
+        assert_eq!(g.garden_of_eden_count(4), 15);
+        assert_eq!(g.cyclic_preimages(&[0, 0, 0, 0]), 16);
+    }
+
+    #[test]
+    fn preimages_of_surjective_rules_are_balanced() {
+        let g = DeBruijnGraph::elementary(30);
+        assert!(g.is_surjective());
+        for word in [[0, 0, 0], [1, 0, 1], [1, 1, 1]] {
+            assert_eq!(g.preimages(&word), 4);
+        }
+    }
+}
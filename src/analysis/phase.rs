@@ -0,0 +1,115 @@
+//! Phase diagrams: order parameters mapped over a 2D grid of parameters (e.g.
+//! the initial density and the noise level).
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::output::AtomicFile;
+
+/// The values of an order parameter over a 2D grid of parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseDiagram {
+    /// The values of the first parameter (horizontal axis).
+    pub xs: Vec<f64>,
+    /// The values of the second parameter (vertical axis).
+    pub ys: Vec<f64>,
+    /// The order parameter for every pair of parameters, in row-major order:
+    /// the value for `(xs[i], ys[j])` is at index `j * xs.len() + i`.
+    pub values: Vec<f64>,
+}
+
+impl PhaseDiagram {
+    /// Evaluates `order_parameter` on every pair of parameters of `xs` x
+    /// `ys`. Evaluations are spread over all available cores if `parallel` is
+    /// true.
+    /// ```
+    /// use rust_ca::analysis::{self, PhaseDiagram};
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // Equilibrium density of the game of life as a function of the initial
+    /// // density and the noise level.
+    /// let diagram = PhaseDiagram::sweep(&[0.1, 0.3, 0.5], &[0., 0.1], true, |density, noise| {
+    ///     let mut autom = Automaton::new(2, 32, Rule::gol());
+    ///     autom.random_init_with_density(density);
+    ///     autom.set_noise(noise);
+    ///     analysis::run_metrics(&mut autom, 20).density
+    /// });
+    /// assert_eq!(diagram.values.len(), 6);
+    /// assert!(diagram.get(0, 1) >= 0.);
+    /// ```
+    pub fn sweep<F>(xs: &[f64], ys: &[f64], parallel: bool, order_parameter: F) -> PhaseDiagram
+    where
+        F: Fn(f64, f64) -> f64 + Sync,
+    {
+        let n_points = xs.len() * ys.len();
+        let eval = |idx: usize| order_parameter(xs[idx % xs.len()], ys[idx / xs.len()]);
+        let threads = if parallel {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            1
+        };
+        let values = if threads > 1 && n_points > 1 {
+            let mut values = vec![0.; n_points];
+            let eval = &eval;
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..threads)
+                    .map(|t| {
+                        scope.spawn(move || {
+                            (t..n_points)
+                                .step_by(threads)
+                                .map(|idx| (idx, eval(idx)))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                for h in handles {
+                    for (idx, v) in h.join().expect("Sweep thread panicked") {
+                        values[idx] = v;
+                    }
+                }
+            });
+            values
+        } else {
+            (0..n_points).map(eval).collect()
+        };
+        PhaseDiagram {
+            xs: xs.to_vec(),
+            ys: ys.to_vec(),
+            values,
+        }
+    }
+
+    /// Returns the order parameter for `(xs[i], ys[j])`.
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.values[j * self.xs.len() + i]
+    }
+
+    /// Writes the phase diagram to a CSV file with one `x,y,value` row per
+    /// pair of parameters.
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut f = AtomicFile::create(path)?;
+        writeln!(f, "x,y,value")?;
+        for (j, y) in self.ys.iter().enumerate() {
+            for (i, x) in self.xs.iter().enumerate() {
+                writeln!(f, "{},{},{}", x, y, self.get(i, j))?;
+            }
+        }
+        f.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhaseDiagram;
+
+    #[test]
+    fn parallel_sweep_matches_serial_sweep() {
+        let xs = [0., 1., 2.];
+        let ys = [0., 10., 20., 30.];
+        let serial = PhaseDiagram::sweep(&xs, &ys, false, |x, y| x + y);
+        let parallel = PhaseDiagram::sweep(&xs, &ys, true, |x, y| x + y);
+        assert_eq!(serial, parallel);
+        assert_eq!(serial.get(2, 1), 12.);
+    }
+}
@@ -0,0 +1,137 @@
+//! Finite-size scaling: running the same rule on grids of several sizes to
+//! study how its behavior scales with the system size.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::automaton::{Automaton, AutomatonImpl};
+use crate::output::AtomicFile;
+use crate::rule::Rule;
+use crate::stats;
+
+/// The metrics of a rule at a given grid size, averaged over several runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalingRow {
+    /// The size of the grid.
+    pub size: usize,
+    /// The number of runs averaged.
+    pub runs: usize,
+    /// The equilibrium density of non-zero cells, averaged over the last
+    /// quarter of the steps.
+    pub density: f64,
+    /// The correlation length of the final grids (see
+    /// [`stats::correlation_length`]).
+    pub correlation_length: f64,
+    /// The transient time, the number of steps before the automaton enters a
+    /// cycle (or the number of simulated steps if no cycle was found).
+    pub transient: f64,
+}
+
+/// Runs `rule` on grids of each of the given `sizes` for `steps` steps from
+/// `seeds` random initial conditions, and reports how the equilibrium density,
+/// correlation length and transient time scale with the grid size. At least
+/// one step is simulated.
+/// ```
+/// use rust_ca::analysis;
+/// use rust_ca::rule::Rule;
+///
+/// let rows = analysis::finite_size_scaling(&Rule::gol(), &[8, 16], 30, 2);
+/// assert_eq!(rows.len(), 2);
+/// assert_eq!(rows[1].size, 16);
+/// ```
+pub fn finite_size_scaling(
+    rule: &Rule,
+    sizes: &[usize],
+    steps: u32,
+    seeds: usize,
+) -> Vec<ScalingRow> {
+    let states = rule.states;
+    let steps = steps.max(1);
+    sizes
+        .iter()
+        .map(|&size| {
+            let mut density = 0.;
+            let mut correlation_length = 0.;
+            let mut transient = 0.;
+            for _ in 0..seeds {
+                let mut autom = Automaton::from_rule(rule.clone(), size);
+                autom.random_init();
+                let mut seen = HashMap::new();
+                let mut cycle_start = None;
+                let mut densities = vec![];
+                let mut last = vec![];
+                for (step, grid) in autom.skipped_iter(steps, 1, 1).enumerate() {
+                    densities.push(1. - stats::densities(&grid, states)[0]);
+                    if cycle_start.is_none() {
+                        let mut hasher = DefaultHasher::new();
+                        grid.hash(&mut hasher);
+                        if let Some(&first) = seen.get(&hasher.finish()) {
+                            cycle_start = Some(first);
+                        } else {
+                            seen.insert(hasher.finish(), step);
+                        }
+                    }
+                    last = grid;
+                }
+                let quarter = (densities.len() / 4).max(1);
+                density +=
+                    densities[densities.len() - quarter..].iter().sum::<f64>() / quarter as f64;
+                correlation_length +=
+                    stats::correlation_length(&stats::two_point_correlation(&last, size));
+                transient += cycle_start.unwrap_or(steps as usize) as f64;
+            }
+            let n = seeds.max(1) as f64;
+            ScalingRow {
+                size,
+                runs: seeds,
+                density: density / n,
+                correlation_length: correlation_length / n,
+                transient: transient / n,
+            }
+        })
+        .collect()
+}
+
+/// Writes the result of a finite-size scaling study to a CSV file, one row per
+/// grid size.
+pub fn write_scaling_csv<P: AsRef<Path>>(path: P, rows: &[ScalingRow]) -> Result<(), io::Error> {
+    let mut f = AtomicFile::create(path)?;
+    writeln!(f, "size,runs,density,correlation_length,transient")?;
+    for r in rows {
+        writeln!(
+            f,
+            "{},{},{},{},{}",
+            r.size, r.runs, r.density, r.correlation_length, r.transient
+        )?;
+    }
+    f.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::finite_size_scaling;
+    use crate::rule::Rule;
+
+    #[test]
+    fn constant_rule_has_no_transient() {
+        let rows = finite_size_scaling(&Rule::new(1, 2, vec![0; 512]), &[4, 8], 10, 3);
+        for row in rows {
+            assert_eq!(row.density, 0.);
+            // The grid is empty from the first step on.
+            assert_eq!(row.transient, 1.);
+        }
+    }
+
+    #[test]
+    fn zero_steps_simulate_one_step() {
+        let rule = Rule::new(1, 2, vec![0; 512]);
+        let rows = finite_size_scaling(&rule, &[4], 0, 1);
+        assert_eq!(rows.len(), 1);
+        // The only frame is the random initial grid, before any cycle.
+        assert_eq!(rows[0].transient, 1.);
+        assert!((0. ..=1.).contains(&rows[0].density));
+    }
+}
@@ -0,0 +1,433 @@
+//! Collecting the still lifes, oscillators and spaceships that appear in the
+//! grids of a search campaign.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::output::AtomicFile;
+use crate::rule::Rule;
+
+/// The behavior of a structure found by a [`Zoo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StructureKind {
+    /// A structure that doesn't change.
+    StillLife,
+    /// A structure that comes back to the same place after `period` steps.
+    Oscillator {
+        /// The number of steps of a cycle.
+        period: u32,
+    },
+    /// A structure that comes back to the same shape, translated, after
+    /// `period` steps.
+    Spaceship {
+        /// The number of steps of a cycle.
+        period: u32,
+        /// The displacement per cycle, as the larger and the smaller absolute
+        /// shifts along the axes (`(1, 1)` for the glider of the game of
+        /// life).
+        displacement: (usize, usize),
+    },
+}
+
+impl StructureKind {
+    /// The name of the kind, used in the names of the RLE files.
+    fn name(&self) -> String {
+        match self {
+            StructureKind::StillLife => "still_life".to_string(),
+            StructureKind::Oscillator { period } => format!("oscillator_p{}", period),
+            StructureKind::Spaceship { period, .. } => format!("spaceship_p{}", period),
+        }
+    }
+}
+
+/// A structure found by a [`Zoo`], in its canonical phase and orientation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    /// The behavior of the structure.
+    pub kind: StructureKind,
+    /// The number of rows of the bounding box of the structure.
+    pub rows: usize,
+    /// The number of columns of the bounding box of the structure.
+    pub cols: usize,
+    /// The cells of the bounding box in row-major order, 0 being the
+    /// background.
+    pub cells: Vec<u8>,
+    /// The number of times the structure was found.
+    pub count: usize,
+}
+
+impl Structure {
+    /// Returns the number of non-background cells.
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|&&c| c != 0).count()
+    }
+
+    /// Encodes the structure in the RLE format used by Golly and the LifeWiki,
+    /// with its kind and count as comments. Two-state structures use `b` and
+    /// `o` for the cells, the others `.` and `A` to `X`.
+    ///
+    /// ```
+    /// use rust_ca::analysis::{Structure, StructureKind};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let blinker = Structure {
+    ///     kind: StructureKind::Oscillator { period: 2 },
+    ///     rows: 1,
+    ///     cols: 3,
+    ///     cells: vec![1, 1, 1],
+    ///     count: 1,
+    /// };
+    /// let rle = blinker.to_rle(&Rule::gol());
+    /// assert!(rle.ends_with("x = 3, y = 1, rule = B3/S23\n3o!\n"));
+    /// ```
+    pub fn to_rle(&self, rule: &Rule) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "#C kind: {}", self.kind.name());
+        if let StructureKind::Spaceship { displacement, .. } = self.kind {
+            let _ = writeln!(out, "#C displacement: {:?}", displacement);
+        }
+        let _ = writeln!(out, "#C population: {}", self.population());
+        let _ = writeln!(out, "#C found: {}", self.count);
+        let _ = writeln!(out, "#C rule id: {}", rule.id());
+        let _ = write!(out, "x = {}, y = {}", self.cols, self.rows);
+        match rule.to_bs_notation() {
+            Some(bs) => {
+                let _ = writeln!(out, ", rule = {}", bs);
+            }
+            None => out.push('\n'),
+        }
+
+        let symbol = |c: u8| match (rule.states, c) {
+            (2, 0) => 'b',
+            (2, _) => 'o',
+            (_, 0) => '.',
+            (_, c) => (b'A' + c - 1) as char,
+        };
+        // Runs of (count, symbol), without the trailing background of the
+        // rows, and with consecutive row ends merged.
+        let mut runs: Vec<(usize, char)> = vec![];
+        let push = |runs: &mut Vec<(usize, char)>, n: usize, s: char| match runs.last_mut() {
+            Some((m, last)) if *last == s => *m += n,
+            _ => runs.push((n, s)),
+        };
+        for (r, row) in self.cells.chunks(self.cols).enumerate() {
+            let end = row.iter().rposition(|&c| c != 0).map_or(0, |i| i + 1);
+            for &c in &row[..end] {
+                push(&mut runs, 1, symbol(c));
+            }
+            if r + 1 < self.rows {
+                push(&mut runs, 1, '$');
+            }
+        }
+        let mut line = String::new();
+        for (n, s) in runs.into_iter().chain(std::iter::once((1, '!'))) {
+            let item = if n > 1 {
+                format!("{}{}", n, s)
+            } else {
+                s.to_string()
+            };
+            if line.len() + item.len() > 70 {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            line.push_str(&item);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        out
+    }
+}
+
+/// A rectangular patch of cells.
+type Patch = (usize, usize, Vec<u8>);
+
+/// Collects the still lifes, oscillators and spaceships appearing in grids,
+/// e.g. the final grids of the runs of a search campaign, and deduplicates
+/// them.
+///
+/// The objects of a grid are the groups of non-background (non-zero) cells
+/// at most the rule horizon apart. Each new object is simulated
+/// alone on an empty grid for up to `max_period` steps, and kept if it comes
+/// back to its initial shape. Objects that die, grow or depend on their
+/// neighbors are ignored, as well as the objects touching the edges of the
+/// grid.
+///
+/// Structures are identified by a canonical form, the smallest of their
+/// phases. The rotations and reflections of a structure are also identified
+/// when the rule is symmetric (see [`Rule::symmetrize`]).
+///
+/// ```
+/// use rust_ca::analysis::{StructureKind, Zoo};
+/// use rust_ca::rule::Rule;
+///
+/// let mut zoo = Zoo::new(Rule::gol(), 4);
+/// // A block, and a blinker in both of its phases.
+/// let mut grid = vec![0; 100];
+/// for &i in &[11, 12, 21, 22, 16, 17, 18, 63, 73, 83] {
+///     grid[i] = 1;
+/// }
+/// assert_eq!(zoo.collect(&grid, 10), 2);
+/// let kinds: Vec<_> = zoo.structures().iter().map(|s| (s.kind, s.count)).collect();
+/// assert!(kinds.contains(&(StructureKind::StillLife, 1)));
+/// assert!(kinds.contains(&(StructureKind::Oscillator { period: 2 }, 2)));
+/// ```
+pub struct Zoo {
+    rule: Rule,
+    max_period: u32,
+    symmetric: bool,
+    /// The canonical forms of the structures, and their index.
+    canonical: HashMap<Patch, usize>,
+    /// The objects already simulated, and the index of their structure.
+    known: HashMap<Patch, Option<usize>>,
+    structures: Vec<Structure>,
+}
+
+impl Zoo {
+    /// Makes an empty zoo for the structures of a rule with a period up to
+    /// `max_period`.
+    pub fn new(rule: Rule, max_period: u32) -> Zoo {
+        let mut symmetrized = rule.clone();
+        symmetrized.symmetrize();
+        Zoo {
+            symmetric: symmetrized.table() == rule.table(),
+            rule,
+            max_period,
+            canonical: HashMap::new(),
+            known: HashMap::new(),
+            structures: vec![],
+        }
+    }
+
+    /// Returns the structures collected so far, in the order they were first
+    /// found.
+    pub fn structures(&self) -> &[Structure] {
+        &self.structures
+    }
+
+    /// Collects the structures of a grid of size `size`, and returns the
+    /// number of structures that weren't in the zoo yet.
+    pub fn collect(&mut self, grid: &[u8], size: usize) -> usize {
+        let before = self.structures.len();
+        for object in objects(grid, size, self.rule.horizon as usize) {
+            let index = match self.known.get(&object) {
+                Some(&index) => index,
+                None => {
+                    let index = self.classify(&object);
+                    self.known.insert(object, index);
+                    index
+                }
+            };
+            if let Some(index) = index {
+                self.structures[index].count += 1;
+            }
+        }
+        self.structures.len() - before
+    }
+
+    /// Simulates an object alone and returns the index of its structure, if
+    /// it is one, adding it to the zoo if needed (with a count of 0).
+    fn classify(&mut self, object: &Patch) -> Option<usize> {
+        let h = self.rule.horizon as usize;
+        let (rows, cols, _) = object;
+        // The object can't reach the edges of the torus in `max_period` steps.
+        let margin = (self.max_period as usize + 1) * h + 1;
+        let side = rows.max(cols) + 2 * margin;
+        let mut grid = vec![0; side * side];
+        paste(&mut grid, side, object, margin, margin);
+        let mut phases = vec![object.clone()];
+        for period in 1..=self.max_period {
+            grid = self.rule.apply_on_torus(&grid, side);
+            let (row, col, patch) = crop(&grid, side)?;
+            if patch == *object {
+                let shift = (row.abs_diff(margin), col.abs_diff(margin));
+                let kind = match shift {
+                    (0, 0) if period == 1 => StructureKind::StillLife,
+                    (0, 0) => StructureKind::Oscillator { period },
+                    (a, b) => StructureKind::Spaceship {
+                        period,
+                        displacement: (a.max(b), a.min(b)),
+                    },
+                };
+                return Some(self.insert(kind, &phases));
+            }
+            phases.push(patch);
+        }
+        None
+    }
+
+    /// Returns the index of the structure with the given phases, adding it to
+    /// the zoo if needed.
+    fn insert(&mut self, kind: StructureKind, phases: &[Patch]) -> usize {
+        let transforms = if self.symmetric { 8 } else { 1 };
+        let canonical = phases
+            .iter()
+            .flat_map(|p| (0..transforms).map(move |t| transform(p, t)))
+            .min()
+            .expect("a structure has a phase");
+        if let Some(&index) = self.canonical.get(&canonical) {
+            return index;
+        }
+        let (rows, cols, cells) = canonical.clone();
+        self.structures.push(Structure {
+            kind,
+            rows,
+            cols,
+            cells,
+            count: 0,
+        });
+        self.canonical.insert(canonical, self.structures.len() - 1);
+        self.structures.len() - 1
+    }
+
+    /// Writes each structure to an RLE file in `dir` (see
+    /// [`Structure::to_rle`]), named after its kind and index, and returns the
+    /// paths of the files.
+    pub fn write_rle<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>, io::Error> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let mut paths = vec![];
+        for (i, s) in self.structures.iter().enumerate() {
+            let path = dir.as_ref().join(format!("{}_{}.rle", s.kind.name(), i));
+            let mut f = AtomicFile::create(&path)?;
+            f.write_all(s.to_rle(&self.rule).as_bytes())?;
+            f.commit()?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Returns the objects of a grid: the bounding boxes of the groups of
+/// non-zero cells at most `horizon` cells apart, except the ones touching the
+/// edges. The cells of other objects in a bounding box are cleared.
+fn objects(grid: &[u8], size: usize, horizon: usize) -> Vec<Patch> {
+    let mut labels = vec![usize::MAX; grid.len()];
+    let mut objects = vec![];
+    for start in 0..grid.len() {
+        if grid[start] == 0 || labels[start] != usize::MAX {
+            continue;
+        }
+        let label = objects.len();
+        labels[start] = label;
+        let mut stack = vec![start];
+        let mut cells = vec![];
+        while let Some(idx) = stack.pop() {
+            cells.push(idx);
+            let (i, j) = (idx / size, idx % size);
+            for x in i.saturating_sub(horizon)..(i + horizon + 1).min(size) {
+                for y in j.saturating_sub(horizon)..(j + horizon + 1).min(size) {
+                    let n = x * size + y;
+                    if grid[n] != 0 && labels[n] == usize::MAX {
+                        labels[n] = label;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        objects.push(cells);
+    }
+    objects
+        .into_iter()
+        .filter_map(|cells| {
+            let rows = cells.iter().map(|&idx| idx / size);
+            let cols = cells.iter().map(|&idx| idx % size);
+            let (top, bottom) = (rows.clone().min()?, rows.max()?);
+            let (left, right) = (cols.clone().min()?, cols.max()?);
+            if top == 0 || left == 0 || bottom == size - 1 || right == size - 1 {
+                return None;
+            }
+            let (r, c) = (bottom - top + 1, right - left + 1);
+            let mut patch = vec![0; r * c];
+            for idx in cells {
+                patch[(idx / size - top) * c + idx % size - left] = grid[idx];
+            }
+            Some((r, c, patch))
+        })
+        .collect()
+}
+
+/// Copies a patch in a grid, with its top left corner at `(row, col)`.
+fn paste(grid: &mut [u8], size: usize, (rows, cols, cells): &Patch, row: usize, col: usize) {
+    for i in 0..*rows {
+        let start = (row + i) * size + col;
+        grid[start..start + cols].copy_from_slice(&cells[i * cols..(i + 1) * cols]);
+    }
+}
+
+/// Returns the position and contents of the bounding box of the non-zero
+/// cells of a grid, or `None` if the grid is empty.
+fn crop(grid: &[u8], size: usize) -> Option<(usize, usize, Patch)> {
+    let live = || (0..grid.len()).filter(|&idx| grid[idx] != 0);
+    let (top, bottom) = (live().next()? / size, live().next_back()? / size);
+    let left = live().map(|idx| idx % size).min()?;
+    let right = live().map(|idx| idx % size).max()?;
+    let (rows, cols) = (bottom - top + 1, right - left + 1);
+    let cells = (top..=bottom)
+        .flat_map(|i| grid[i * size + left..i * size + right + 1].iter().copied())
+        .collect();
+    Some((top, left, (rows, cols, cells)))
+}
+
+/// Applies one of the 8 symmetries of the square to a patch: a transposition
+/// if bit 2 of `t` is set, then a reversal of the rows (bit 1) and of the
+/// columns (bit 0).
+fn transform((rows, cols, cells): &Patch, t: u8) -> Patch {
+    let (r, c) = if t & 4 != 0 {
+        (*cols, *rows)
+    } else {
+        (*rows, *cols)
+    };
+    let mut out = vec![0; cells.len()];
+    for i in 0..r {
+        for j in 0..c {
+            let (i2, j2) = (
+                if t & 2 != 0 { r - 1 - i } else { i },
+                if t & 1 != 0 { c - 1 - j } else { j },
+            );
+            let (si, sj) = if t & 4 != 0 { (j2, i2) } else { (i2, j2) };
+            out[i * c + j] = cells[si * cols + sj];
+        }
+    }
+    (r, c, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StructureKind, Zoo};
+    use crate::rule::Rule;
+
+    #[test]
+    fn gliders_are_deduplicated_across_phases_and_orientations() {
+        let mut zoo = Zoo::new(Rule::gol(), 4);
+        // Two gliders in different phases and orientations, and a lone cell.
+        let mut grid = vec![0; 400];
+        for &(i, j) in &[(2, 3), (3, 4), (4, 2), (4, 3), (4, 4)] {
+            grid[i * 20 + j] = 1;
+        }
+        for &(i, j) in &[(12, 12), (12, 14), (13, 13), (13, 14), (14, 13)] {
+            grid[i * 20 + 17 - j] = 1;
+        }
+        grid[18 * 20 + 2] = 1;
+        assert_eq!(zoo.collect(&grid, 20), 1);
+        assert_eq!(zoo.collect(&grid, 20), 0);
+        let glider = &zoo.structures()[0];
+        assert_eq!(
+            glider.kind,
+            StructureKind::Spaceship {
+                period: 4,
+                displacement: (1, 1)
+            }
+        );
+        assert_eq!((glider.count, glider.population()), (4, 5));
+
+        let dir = std::env::temp_dir().join("rust_ca_zoo");
+        let paths = zoo.write_rle(&dir).unwrap();
+        assert_eq!(paths.len(), 1);
+        let rle = std::fs::read_to_string(&paths[0]).unwrap();
+        assert!(rle.contains("x = 3, y = 3, rule = B3/S23\n"));
+        assert!(rle.contains("#C found: 4\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, Rule1D, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A one-dimensional cellular automaton: a row of `size` cells updated with a
+/// [`Rule1D`].
+///
+/// Its grid is the space-time diagram of the last `size` steps, one row per
+/// step with the current row at the bottom, so the outputs of the 2D automata
+/// render the diagram unchanged. The rows before the initial one are in state
+/// 0.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton1D, AutomatonImpl};
+/// use rust_ca::rule::Rule1D;
+///
+/// let mut automaton = Automaton1D::with_rule(5, Rule1D::elementary(30));
+/// automaton.row_mut()[2] = 1;
+/// automaton.update();
+/// assert_eq!(automaton.row(), [0, 1, 1, 1, 0]);
+/// // The initial row is now above the current one.
+/// assert_eq!(automaton.grid()[15..], [0, 0, 1, 0, 0, 0, 1, 1, 1, 0]);
+/// ```
+pub struct Automaton1D {
+    size: usize,
+    states: u8,
+    rule: Rule1D,
+    /// The rows of the diagram, a ring whose current row is `current`.
+    rows: Vec<u8>,
+    current: usize,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl Automaton1D {
+    /// Makes an automaton of `size` cells with the given rule.
+    pub fn with_rule(size: usize, rule: Rule1D) -> Automaton1D {
+        Automaton1D {
+            size,
+            states: rule.states,
+            rule,
+            rows: vec![0; size * size],
+            current: size.saturating_sub(1),
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the current row.
+    pub fn row(&self) -> &[u8] {
+        let start = self.current * self.size;
+        &self.rows[start..start + self.size]
+    }
+
+    /// Returns a mutable reference to the current row.
+    pub fn row_mut(&mut self) -> &mut [u8] {
+        let start = self.current * self.size;
+        &mut self.rows[start..start + self.size]
+    }
+
+    /// Replaces the rule, which must have the same horizon and number of
+    /// states.
+    pub fn set_rule_1d(&mut self, rule: Rule1D) -> Result<(), RuleError> {
+        if (rule.horizon, rule.states) != (self.rule.horizon, self.rule.states) {
+            return Err(RuleError::RuleShapeError {
+                expected: (self.rule.horizon, self.rule.states),
+                found: (rule.horizon, rule.states),
+            });
+        }
+        self.rule = rule;
+        Ok(())
+    }
+
+    /// Sets all the rows of the diagram to the given state.
+    fn clear(&mut self, state: u8) {
+        self.rows.iter_mut().for_each(|c| *c = state);
+    }
+}
+
+impl AutomatonImpl for Automaton1D {
+    /// Makes an automaton with a random rule of the horizon and number of
+    /// states of `rule`, which can't drive a 1D automaton. Use
+    /// [`Automaton1D::with_rule`] to choose the rule.
+    fn new(states: u8, size: usize, rule: Rule) -> Automaton1D {
+        Automaton1D::with_rule(size, Rule1D::random(rule.horizon, states))
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Initializes the current row from the first row of a pattern file,
+    /// centered, and the rest of the diagram with the background.
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        if pattern_spec.states > self.states || pattern_spec.background >= self.states {
+            return Err(PatternError::PatternStateError);
+        }
+        self.clear(pattern_spec.background);
+        let first = pattern_spec
+            .rows()
+            .first()
+            .ok_or(PatternError::PatternFormatError)?;
+        let offset = (self.size / 2).saturating_sub(first.len() / 2);
+        for (cell, &state) in self.row_mut()[offset..].iter_mut().zip(first) {
+            *cell = state;
+        }
+        Ok(())
+    }
+
+    /// Places the first row of the transformed pattern in the current row,
+    /// starting at column `x`. The row `y` must be 0.
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        if y != 0 {
+            return Err(PatternError::PatternBoundsError);
+        }
+        let cells = spec.placed((x, 0), (rotation, flip), self.size, self.states)?;
+        let row = self.row_mut();
+        for (_, j, state) in cells.into_iter().filter(|&(i, _, _)| i == 0) {
+            row[j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        if self.size == 0 {
+            return;
+        }
+        let (size, h) = (self.size, self.rule.horizon as isize);
+        let next = (self.current + 1) % size;
+        let mut neighborhood = vec![0; 2 * h as usize + 1];
+        let mut row = vec![0; size];
+        for (i, cell) in row.iter_mut().enumerate() {
+            for (n, j) in neighborhood.iter_mut().zip(i as isize - h..=i as isize + h) {
+                *n = match (self.boundary.resolve(j, size), self.boundary) {
+                    (Some(j), _) => self.row()[j],
+                    (None, BoundaryCondition::Fixed(state)) => state,
+                    (None, _) => unreachable!("only fixed boundaries leave the row"),
+                };
+            }
+            *cell = self.rule.next_state(&neighborhood);
+            if self.noise > 0. && self.rng.gen_bool(self.noise) {
+                *cell = self.rng.gen_range(0..self.states);
+            }
+        }
+        self.rows[next * size..(next + 1) * size].copy_from_slice(&row);
+        self.current = next;
+    }
+
+    /// Sets random states on the current row, and state 0 on the rest of the
+    /// diagram.
+    fn random_init(&mut self) {
+        self.clear(0);
+        let states = self.states;
+        let mut rng = self.rng.clone();
+        for c in self.row_mut() {
+            *c = rng.gen_range(0..states);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The rows are short, so they are always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// 2D rules can't drive a 1D automaton, so this always fails. Use
+    /// [`Automaton1D::set_rule_1d`] instead.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError("1D automata need a 1D rule"))
+    }
+
+    /// Returns the space-time diagram of the last `size` steps, the current
+    /// row last.
+    fn grid(&self) -> Vec<u8> {
+        let split = ((self.current + 1) % self.size.max(1)) * self.size;
+        let mut grid = self.rows[split..].to_vec();
+        grid.extend_from_slice(&self.rows[..split]);
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{Automaton1D, AutomatonImpl, BoundaryCondition};
+    use crate::rule::{Rule, Rule1D};
+
+    #[test]
+    fn diagrams_scroll_one_row_per_step() {
+        let rule = Rule1D::elementary(90);
+        let mut a = Automaton1D::with_rule(8, rule.clone());
+        a.random_init_seeded(4);
+        let mut rows = vec![a.row().to_vec()];
+        for _ in 0..10 {
+            a.update();
+            rows.push(rule.apply(rows.last().unwrap()));
+            assert_eq!(a.row(), &rows.last().unwrap()[..]);
+        }
+        assert_eq!(a.grid(), rows[rows.len() - 8..].concat());
+        assert!(a.set_rule(Rule::gol()).is_err());
+        assert!(a.set_rule_1d(Rule1D::random(2, 2)).is_err());
+    }
+
+    #[test]
+    fn fixed_boundaries_fill_the_neighborhoods() {
+        // Rule 254 turns on the cells with a live neighbor.
+        let mut a = Automaton1D::with_rule(4, Rule1D::elementary(254));
+        a.set_boundary(BoundaryCondition::Fixed(1));
+        a.update();
+        assert_eq!(a.row(), [1, 0, 0, 1]);
+        a.set_boundary(BoundaryCondition::Reflective);
+        a.row_mut().copy_from_slice(&[0, 0, 0, 1]);
+        a.update();
+        assert_eq!(a.row(), [0, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,343 @@
+use std::str::FromStr;
+
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, Rule3D, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The 2D view of a 3D grid rendered as the grid of an [`Automaton3D`].
+///
+/// ```
+/// use rust_ca::automaton::View3D;
+///
+/// assert_eq!("slice:3".parse(), Ok(View3D::Slice(3)));
+/// assert_eq!("max".parse(), Ok(View3D::MaxProjection));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View3D {
+    /// The plane of the cells with the given z coordinate.
+    Slice(usize),
+    /// The maximum state of the cells along the z axis (a maximum intensity
+    /// projection).
+    MaxProjection,
+}
+
+/// Parses `slice:Z` or `max`.
+impl FromStr for View3D {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "max" => Ok(View3D::MaxProjection),
+            Some(("slice", z)) => z.parse().map(View3D::Slice).map_err(|_| "invalid slice"),
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// A three-dimensional cellular automaton: a cube of `size^3` cells updated
+/// with a [`Rule3D`] on their 3D Moore neighborhoods.
+///
+/// The cells are stored z-slice after z-slice, each slice in row-major order.
+/// The grid of the automaton is a 2D [`View3D`] of the cube, the middle
+/// z-slice by default, so the outputs of the 2D automata animate it
+/// unchanged.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton3D, AutomatonImpl, View3D};
+/// use rust_ca::rule::Rule3D;
+///
+/// // Every cell with a live neighbor becomes alive.
+/// let grow: Rule3D = "B1,2,3,4,5,6,7,8,9,10,11,12,13/S".parse()?;
+/// let mut automaton = Automaton3D::with_rule(5, grow);
+/// automaton.cells_mut()[2 * 25 + 2 * 5 + 2] = 1;
+/// automaton.update();
+/// assert_eq!(automaton.cells().iter().filter(|&&s| s == 1).count(), 26);
+/// automaton.set_view(View3D::Slice(0));
+/// assert!(automaton.grid().iter().all(|&s| s == 0));
+/// # Ok::<(), &'static str>(())
+/// ```
+pub struct Automaton3D {
+    size: usize,
+    states: u8,
+    rule: Rule3D,
+    cells: Vec<u8>,
+    view: View3D,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl Automaton3D {
+    /// Makes an automaton of `size^3` cells in state 0 with the given rule.
+    pub fn with_rule(size: usize, rule: Rule3D) -> Automaton3D {
+        Automaton3D {
+            size,
+            states: rule.states,
+            rule,
+            cells: vec![0; size * size * size],
+            view: View3D::Slice(size / 2),
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the cells of the cube.
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Returns a mutable reference to the cells of the cube.
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    /// Sets the view of the cube returned as the grid. Slices beyond the cube
+    /// are clamped to its last slice.
+    pub fn set_view(&mut self, view: View3D) {
+        self.view = view;
+    }
+
+    /// Replaces the rule, which must have the same horizon and number of
+    /// states.
+    pub fn set_rule_3d(&mut self, rule: Rule3D) -> Result<(), RuleError> {
+        if (rule.horizon, rule.states) != (self.rule.horizon, self.rule.states) {
+            return Err(RuleError::RuleShapeError {
+                expected: (self.rule.horizon, self.rule.states),
+                found: (rule.horizon, rule.states),
+            });
+        }
+        self.rule = rule;
+        Ok(())
+    }
+
+    /// Sums `values` over the `2 horizon + 1` cells centered on every cell
+    /// along the axis of the given stride. The cells outside the cube count
+    /// as `outside`.
+    fn axis_sums(&self, values: &[usize], stride: usize, outside: usize) -> Vec<usize> {
+        let (size, h) = (self.size, self.rule.horizon as isize);
+        (0..values.len())
+            .map(|i| {
+                let x = (i / stride % size) as isize;
+                let base = i - x as usize * stride;
+                (x - h..=x + h)
+                    .map(|x| match self.boundary.resolve(x, size) {
+                        Some(x) => values[base + x * stride],
+                        None => outside,
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+impl AutomatonImpl for Automaton3D {
+    /// Makes an automaton with a random rule of the horizon and number of
+    /// states of `rule`, which can't drive a 3D automaton. Use
+    /// [`Automaton3D::with_rule`] to choose the rule.
+    fn new(states: u8, size: usize, rule: Rule) -> Automaton3D {
+        Automaton3D::with_rule(size, Rule3D::random(rule.horizon, states))
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Initializes the middle z-slice from a pattern file, centered, and the
+    /// rest of the cube with the background.
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    /// Places a pattern in the middle z-slice.
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        let slice = size / 2 * size * size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, self.states)? {
+            self.cells[slice + i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let size = self.size;
+        let w = 2 * self.rule.horizon as usize + 1;
+        let fixed = match self.boundary {
+            BoundaryCondition::Fixed(state) => state as usize,
+            _ => 0,
+        };
+        // The cube sums are separable: sums along the rows, then the columns
+        // of the row sums, then the z axis of the column sums.
+        let values: Vec<usize> = self.cells.iter().map(|&c| c as usize).collect();
+        let sums = self.axis_sums(&values, 1, fixed);
+        let sums = self.axis_sums(&sums, size, w * fixed);
+        let sums = self.axis_sums(&sums, size * size, w * w * fixed);
+        for (cell, sum) in self.cells.iter_mut().zip(sums) {
+            *cell = self.rule.next_state(*cell, sum - *cell as usize);
+            if self.noise > 0. && self.rng.gen_bool(self.noise) {
+                *cell = self.rng.gen_range(0..self.states);
+            }
+        }
+    }
+
+    fn random_init(&mut self) {
+        let states = self.states;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = rng.gen_range(0..states);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The cube is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// 2D rules can't drive a 3D automaton, so this always fails. Use
+    /// [`Automaton3D::set_rule_3d`] instead.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError("3D automata need a 3D rule"))
+    }
+
+    /// Returns the view of the cube (see [`Automaton3D::set_view`]).
+    fn grid(&self) -> Vec<u8> {
+        let plane = self.size * self.size;
+        match self.view {
+            View3D::Slice(z) => {
+                let z = z.min(self.size.saturating_sub(1));
+                self.cells[z * plane..(z + 1) * plane].to_vec()
+            }
+            View3D::MaxProjection => {
+                let mut grid = vec![0; plane];
+                for slice in self.cells.chunks(plane.max(1)) {
+                    for (g, &c) in grid.iter_mut().zip(slice) {
+                        *g = (*g).max(c);
+                    }
+                }
+                grid
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{Automaton3D, AutomatonImpl, BoundaryCondition, View3D};
+    use crate::rule::{Rule, Rule3D};
+
+    /// Applies a rule to a cube by summing every neighborhood.
+    fn reference_update(rule: &Rule3D, cells: &[u8], size: usize, b: BoundaryCondition) -> Vec<u8> {
+        let h = rule.horizon as isize;
+        let cell = |z: isize, y: isize, x: isize| -> usize {
+            match (
+                b.resolve(z, size),
+                b.resolve(y, size),
+                b.resolve(x, size),
+                b,
+            ) {
+                (Some(z), Some(y), Some(x), _) => cells[(z * size + y) * size + x] as usize,
+                (_, _, _, BoundaryCondition::Fixed(state)) => state as usize,
+                _ => unreachable!(),
+            }
+        };
+        let mut next = vec![0; cells.len()];
+        for (i, n) in next.iter_mut().enumerate() {
+            let (z, y, x) = (
+                (i / size / size) as isize,
+                (i / size % size) as isize,
+                (i % size) as isize,
+            );
+            let mut sum = 0;
+            for dz in -h..=h {
+                for dy in -h..=h {
+                    for dx in -h..=h {
+                        sum += cell(z + dz, y + dy, x + dx);
+                    }
+                }
+            }
+            *n = rule.next_state(cells[i], sum - cells[i] as usize);
+        }
+        next
+    }
+
+    #[test]
+    fn updates_match_the_neighborhood_sums() {
+        let rule = Rule3D::random(1, 3);
+        for &boundary in &[
+            BoundaryCondition::Periodic,
+            BoundaryCondition::Fixed(2),
+            BoundaryCondition::Reflective,
+        ] {
+            let mut a = Automaton3D::with_rule(6, rule.clone());
+            a.set_boundary(boundary);
+            a.random_init_seeded(5);
+            for _ in 0..3 {
+                let expected = reference_update(&rule, a.cells(), 6, boundary);
+                a.update();
+                assert_eq!(a.cells(), &expected[..]);
+            }
+        }
+        let mut a = Automaton3D::new(2, 4, Rule::gol());
+        assert!(a.set_rule(Rule::gol()).is_err());
+        assert!(a.set_rule_3d(Rule3D::random(2, 2)).is_err());
+    }
+
+    #[test]
+    fn views_show_slices_and_projections() {
+        let mut a = Automaton3D::with_rule(3, Rule3D::random(1, 4));
+        a.cells_mut()[4] = 1;
+        a.cells_mut()[2 * 9 + 4] = 3;
+        a.cells_mut()[9] = 2;
+        assert_eq!(a.grid(), [2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        a.set_view(View3D::Slice(7));
+        assert_eq!(a.grid(), [0, 0, 0, 0, 3, 0, 0, 0, 0]);
+        a.set_view(View3D::MaxProjection);
+        assert_eq!(a.grid(), [2, 0, 0, 0, 3, 0, 0, 0, 0]);
+    }
+}
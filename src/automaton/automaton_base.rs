@@ -1,9 +1,16 @@
 #![deny(missing_docs)]
-use super::{AutomatonImpl, PatternError, HORIZON};
+use super::kernel::KernelLoop;
+use super::{
+    AutomatonImpl, BoundaryCondition, Dimensions, Kernel, Pattern, PatternError, Rotation,
+    StateCounts,
+};
 use crate::automaton::duplicate_array;
-use crate::{automaton::parse_pattern, rule::Rule};
-use rand::Rng;
-use std::ops::{Index, IndexMut};
+use crate::automaton::parse_pattern;
+use crate::rule::{Rule, RuleError};
+use crate::stats;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::{Index, IndexMut, Range};
 
 /// The 2D Automaton object.
 pub struct Automaton {
@@ -15,6 +22,12 @@ pub struct Automaton {
     grid1: Vec<u8>,
     grid2: Vec<u8>,
     rule: Rule,
+    kernel: Kernel,
+    noise: f64,
+    threads: usize,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+    counts: StateCounts,
 }
 
 impl Automaton {
@@ -30,6 +43,7 @@ impl Automaton {
     #[inline]
     /// Get a mutable reference to the current grid.
     pub fn grid_mut(&mut self) -> &mut Vec<u8> {
+        self.counts.invalidate();
         if self.flop {
             &mut self.grid1
         } else {
@@ -37,49 +51,243 @@ impl Automaton {
         }
     }
 
+    /// Randomly sets the cells of the grid so that a fraction `density` of
+    /// them (on average) is in a non-zero state, drawn uniformly among the
+    /// non-zero states. The rest of the cells are set to state 0.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut automaton = Automaton::new(2, 64, Rule::random(1, 2));
+    /// automaton.random_init_with_density(0.);
+    /// assert!(automaton.grid().iter().all(|&c| c == 0));
+    /// ```
+    pub fn random_init_with_density(&mut self, density: f64) {
+        let states = self.states;
+        let mut rng = self.rng.clone();
+        for i in self.grid_mut().iter_mut() {
+            *i = if states > 1 && rng.gen_bool(density.clamp(0., 1.)) {
+                rng.gen_range(1..states)
+            } else {
+                0
+            };
+        }
+        self.rng = rng;
+    }
+
+    /// Updates the cells of a rectangle of the grid whose neighborhoods fit in
+    /// the grid. Each row of the rectangle is written as a slice of the next
+    /// grid, and the state changes are counted once for the whole rectangle.
     #[inline]
-    fn single_update(&mut self, is: isize, js: isize) {
-        let size = self.size;
-        let mut ind: usize = 0;
-        let mut pw = 0;
-        let states = self.states as usize;
-        let grid = self.grid_mut();
-        for a in -HORIZON..=HORIZON {
-            for b in -HORIZON..=HORIZON {
-                let idx =
-                    ((is + isize::from(a)) * (size as isize) + (js + isize::from(b))) as usize;
-                let current_val = grid[idx] as usize;
-                let power = states.pow(pw);
-                ind += power * current_val;
-                pw += 1;
+    fn update_interior(&mut self, rows: Range<usize>, cols: Range<usize>) {
+        let (size, states, rule) = (self.size, self.states as usize, &self.rule);
+        let (current, next) = if self.flop {
+            (&self.grid1, &mut self.grid2)
+        } else {
+            (&self.grid2, &mut self.grid1)
+        };
+        let mut deltas = vec![0; states];
+        let update = RectUpdate {
+            grids: (current, next),
+            size,
+            rect: (rows, cols),
+            deltas: &mut deltas,
+        };
+        self.kernel.run(rule, size, states, update);
+        self.counts.apply(&deltas);
+    }
+
+    /// Updates the cells of a rectangle of the grid, checking the bounds only
+    /// for the cells close to the edges. The update must be completed with
+    /// [`Automaton::finish_update`] once all the cells have been updated.
+    pub(super) fn update_rect(&mut self, rows: Range<usize>, cols: Range<usize>) {
+        let h = self.rule.horizon as usize;
+        let interior = |r: &Range<usize>| r.start >= h && r.end + h <= self.size;
+        if interior(&rows) && interior(&cols) {
+            self.update_interior(rows, cols);
+        } else {
+            for i in rows {
+                for j in cols.clone() {
+                    self.single_update_bound_check(i as isize, j as isize)
+                }
+            }
+        }
+    }
+
+    /// Starts an update: recounts the states if the grid was modified since
+    /// the last update, so that the update kernels can count the changes.
+    pub(super) fn begin_update(&mut self) {
+        if !self.counts.is_valid() {
+            let current = if self.flop { &self.grid1 } else { &self.grid2 };
+            self.counts.set(stats::state_counts(current, self.states));
+        }
+    }
+
+    /// Completes an update: applies the noise to the new grid and swaps the
+    /// buffers.
+    pub(super) fn finish_update(&mut self) {
+        if self.noise > 0. {
+            let (noise, states) = (self.noise, self.states);
+            let next = if self.flop {
+                &mut self.grid2
+            } else {
+                &mut self.grid1
+            };
+            for c in next.iter_mut() {
+                if self.rng.gen_bool(noise) {
+                    let state = self.rng.gen_range(0..states);
+                    self.counts.change(*c, state);
+                    *c = state;
+                }
             }
         }
-        self.prev_grid()[is as usize * size + js as usize] = self.rule[ind];
+
+        self.flop = !self.flop;
+    }
+
+    /// Returns the kernel updating the cells whose neighborhoods fit in the
+    /// grid, chosen from the rule.
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    /// Returns the number of threads used by [`AutomatonImpl::update`].
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Performs a single step update splitting the grid in bands of rows
+    /// updated by `threads` threads. The result is the same as with
+    /// [`AutomatonImpl::update`].
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let rule = Rule::random(1, 2);
+    /// let mut a = Automaton::new(2, 100, rule.clone());
+    /// a.random_init();
+    /// let mut b = Automaton::new(2, 100, rule);
+    /// b.grid_mut().copy_from_slice(&a.grid());
+    /// a.update();
+    /// b.update_parallel(4);
+    /// assert_eq!(a.grid(), b.grid());
+    /// ```
+    pub fn update_parallel(&mut self, threads: usize) {
+        self.begin_update();
+        let (size, states) = (self.size, self.states as usize);
+        let rule = (&self.rule, self.kernel);
+        let (h, boundary) = (self.rule.horizon as usize, self.boundary);
+        let (current, next) = if self.flop {
+            (&self.grid1, &mut self.grid2)
+        } else {
+            (&self.grid2, &mut self.grid1)
+        };
+        let band = size.div_ceil(threads.max(1)).max(1) * size;
+        // Each band counts its state changes, merged once the threads are done.
+        let deltas: Vec<Vec<isize>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = next
+                .chunks_mut(band)
+                .enumerate()
+                .map(|(k, rows)| {
+                    scope.spawn(move || {
+                        let mut deltas = vec![0; states];
+                        for (c, cell) in rows.iter_mut().enumerate() {
+                            let (i, j) = ((k * band + c) / size, c % size);
+                            let edge = i < h || i + h >= size || j < h || j + h >= size;
+                            let boundary = edge.then_some(boundary);
+                            *cell = next_state(current, size, states, rule, (i, j), boundary);
+                            deltas[current[k * band + c] as usize] -= 1;
+                            deltas[*cell as usize] += 1;
+                        }
+                        deltas
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for d in &deltas {
+            self.counts.apply(d);
+        }
+        self.finish_update();
     }
 
     #[inline]
     fn single_update_bound_check(&mut self, is: isize, js: isize) {
-        let size = self.size;
-        let mut ind: usize = 0;
-        let mut pw = 0;
-        let states = self.states as usize;
-        let grid = self.grid_mut();
-        for a in -HORIZON..=HORIZON {
-            for b in -HORIZON..=HORIZON {
-                let idx = (((is + isize::from(a) + size as isize) % size as isize)
-                    * (size as isize)
-                    + (js + isize::from(b) + size as isize) % size as isize)
-                    as usize;
-                let current_val = grid[idx] as usize;
-                let power = states.pow(pw);
-                ind += power * current_val;
-                pw += 1;
+        let (size, states) = (self.size, self.states as usize);
+        let current = if self.flop { &self.grid1 } else { &self.grid2 };
+        let (i, j) = (is as usize, js as usize);
+        let state = next_state(
+            current,
+            size,
+            states,
+            (&self.rule, self.kernel),
+            (i, j),
+            Some(self.boundary),
+        );
+        self.counts.change(current[i * size + j], state);
+        self.prev_grid()[i * size + j] = state;
+    }
+}
+
+/// The update of a rectangle of cells of a grid of side `size`, whose state
+/// changes are counted in `deltas`.
+struct RectUpdate<'a> {
+    grids: (&'a [u8], &'a mut [u8]),
+    size: usize,
+    rect: (Range<usize>, Range<usize>),
+    deltas: &'a mut [isize],
+}
+
+impl KernelLoop for RectUpdate<'_> {
+    #[inline(always)]
+    fn run<F: FnMut(&[u8], usize) -> u8>(self, mut next_state: F) {
+        let ((current, next), size) = (self.grids, self.size);
+        let (rows, cols) = self.rect;
+        for i in rows {
+            let row = i * size + cols.start..i * size + cols.end;
+            for (c, (cell, &previous)) in row
+                .clone()
+                .zip(next[row.clone()].iter_mut().zip(&current[row]))
+            {
+                *cell = next_state(current, c);
+                self.deltas[previous as usize] -= 1;
+                self.deltas[*cell as usize] += 1;
             }
         }
-        self.prev_grid()[is as usize * size + js as usize] = self.rule[ind];
     }
 }
 
+/// Returns the next state of the cell `(i, j)` of a grid, reading only the
+/// cells of the neighborhood of the rule. The neighborhood is completed with
+/// the boundary condition when one is given; otherwise it must fit in the
+/// grid, and is read by the kernel.
+#[inline]
+fn next_state(
+    grid: &[u8],
+    size: usize,
+    states: usize,
+    (rule, kernel): (&Rule, Kernel),
+    (i, j): (usize, usize),
+    boundary: Option<BoundaryCondition>,
+) -> u8 {
+    let (h, neighborhood) = (rule.horizon as isize, rule.neighborhood());
+    let Some(boundary) = boundary else {
+        return rule[kernel.index(grid, size, i * size + j, states, (h, neighborhood))];
+    };
+    let mut ind: usize = 0;
+    let mut power = 1;
+    for a in -h..=h {
+        for b in neighborhood.row_span(a, h) {
+            let (x, y) = (i as isize + a, j as isize + b);
+            ind += power * boundary.cell(grid, size, (x, y)) as usize;
+            power *= states;
+        }
+    }
+    rule[ind]
+}
+
 impl Index<usize> for Automaton {
     type Output = u8;
     fn index(&self, idx: usize) -> &Self::Output {
@@ -93,6 +301,7 @@ impl Index<usize> for Automaton {
 
 impl IndexMut<usize> for Automaton {
     fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        self.counts.invalidate();
         if self.flop {
             &mut self.grid1[idx]
         } else {
@@ -108,9 +317,15 @@ impl AutomatonImpl for Automaton {
             states,
             size,
             flop: true,
+            kernel: Kernel::for_rule(&rule),
             rule,
             grid1: grid.to_vec(),
             grid2: grid.to_vec(),
+            noise: 0.,
+            threads: 1,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+            counts: StateCounts::default(),
         }
     }
 
@@ -128,7 +343,7 @@ impl AutomatonImpl for Automaton {
                 steps: Some(steps),
                 ct: 0,
             }
-            .map(move |grid| duplicate_array(&grid, size, scale)),
+            .map(move |grid| duplicate_array(&grid, Dimensions::new(size, scale))),
         )
     }
 
@@ -142,34 +357,41 @@ impl AutomatonImpl for Automaton {
 
     fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
         let pattern_spec = parse_pattern(pattern_fname)?;
-        assert!(pattern_spec.states <= self.states);
-        assert!(pattern_spec.background < self.states);
-        for i in self.grid_mut().iter_mut() {
-            *i = pattern_spec.background;
-        }
-        let lines = pattern_spec.pattern.len();
-        let cols = pattern_spec.pattern.iter().map(|x| x.len()).max().unwrap();
-        for i in 0..lines {
-            let lin = &pattern_spec.pattern[i];
-            for (j, elem) in lin.iter().enumerate() {
-                let idx =
-                    (i + (self.size / 2) - lines / 2) * self.size + (j - cols / 2 + self.size / 2);
-                self.grid_mut()[idx] = *elem;
-            }
+        let (x, y) = pattern_spec.centered(self.size);
+        self.grid_mut().fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let (size, states) = (self.size, self.states);
+        let grid = self.grid_mut();
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, states)? {
+            grid[i * size + j] = state;
         }
         Ok(())
     }
 
     #[inline]
     fn update(&mut self) {
-        let bounds_low = HORIZON as usize;
-        let bounds_high = (self.size as isize - isize::from(HORIZON)) as usize;
-        //Main update
-        for i in bounds_low..bounds_high {
-            for j in bounds_low..bounds_high {
-                self.single_update(i as isize, j as isize)
-            }
+        if self.threads > 1 {
+            self.update_parallel(self.threads);
+            return;
         }
+        // The cells closer than the horizon to the edges need wrapping around
+        // the torus.
+        self.begin_update();
+        let h = self.rule.horizon as usize;
+        let bounds_low = h.min(self.size);
+        let bounds_high = self.size.saturating_sub(h).max(bounds_low);
+        //Main update
+        self.update_interior(bounds_low..bounds_high, bounds_low..bounds_high);
 
         //Bounds update
         for j in 0..self.size {
@@ -190,15 +412,39 @@ impl AutomatonImpl for Automaton {
             }
         }
 
-        self.flop = !self.flop;
+        self.finish_update();
     }
 
     fn random_init(&mut self) {
         let states = self.states;
-        let mut rng = rand::thread_rng();
+        let mut rng = self.rng.clone();
         for i in self.grid_mut().iter_mut() {
             *i = rng.gen_range(0..states);
         }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        rule.verify_compatible(&self.rule)?;
+        self.rule = rule;
+        Ok(())
     }
 
     #[inline]
@@ -209,6 +455,19 @@ impl AutomatonImpl for Automaton {
             self.grid2.clone()
         }
     }
+
+    fn grid_into(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(if self.flop { &self.grid1 } else { &self.grid2 });
+    }
+
+    fn state_counts(&self) -> Vec<usize> {
+        match self.counts.get() {
+            Some(counts) => counts.to_vec(),
+            None if self.flop => stats::state_counts(&self.grid1, self.states),
+            None => stats::state_counts(&self.grid2, self.states),
+        }
+    }
 }
 
 pub struct AutomatonIterator<'a> {
@@ -222,19 +481,8 @@ impl Iterator for AutomatonIterator<'_> {
     type Item = Vec<u8>;
     fn next(&mut self) -> Option<Vec<u8>> {
         match self.steps {
-            Some(v) => {
-                if self.ct >= v {
-                    None
-                } else {
-                    let ret = self.autom.grid().to_vec();
-                    for _ in 0..self.skip {
-                        self.autom.update();
-                        self.ct += 1;
-                    }
-                    Some(ret)
-                }
-            }
-            None => {
+            Some(v) if self.ct >= v => None,
+            _ => {
                 let ret = self.autom.grid().to_vec();
                 for _ in 0..self.skip {
                     self.autom.update();
@@ -249,12 +497,14 @@ impl Iterator for AutomatonIterator<'_> {
 #[cfg(test)]
 mod tests {
     use crate::automaton::Automaton;
-    use crate::automaton::AutomatonImpl;
-    use crate::rule::Rule;
-    use test::Bencher;
+    use crate::automaton::{AutomatonImpl, BoundaryCondition};
+    use crate::rule::{Neighborhood, Rule, SamplingMode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::hint::black_box;
 
     fn get_random_auto(size: usize, states: u8) -> Automaton {
-        let states = test::black_box(states);
+        let states = black_box(states);
         let rule = Rule::random(1, states);
         let mut a = Automaton::new(states, size, rule);
         a.random_init();
@@ -269,51 +519,210 @@ mod tests {
         assert_ne!(b1, a.flop);
     }
 
-    #[bench]
-    fn bench_update_one_item_bd(b: &mut Bencher) {
-        let mut a = get_random_auto(64, 2);
-        b.iter(|| a.single_update_bound_check(10, 10));
+    #[test]
+    fn state_counts_follow_the_updates() {
+        use crate::automaton::RecursiveAutomaton;
+        use crate::stats::state_counts;
+        let mut a = get_random_auto(70, 3);
+        let mut b = RecursiveAutomaton::new(3, 70, Rule::random(1, 3));
+        b.random_init();
+        a.set_noise(0.05);
+        b.set_noise(0.05);
+        for step in 0..6 {
+            if step == 2 {
+                a.set_threads(4);
+                a.grid_mut()[0] = 2;
+                a[1] = 1;
+                b.inner_mut().grid_mut()[0] = 2;
+            }
+            a.update();
+            b.update();
+            assert_eq!(a.state_counts(), state_counts(&a.grid(), 3));
+            assert_eq!(b.state_counts(), state_counts(&b.grid(), 3));
+        }
     }
 
-    #[bench]
-    fn bench_update_one_item(b: &mut Bencher) {
-        let mut a = get_random_auto(64, 2);
-        b.iter(|| a.single_update(10, 10));
+    #[test]
+    fn parallel_update_matches_sequential() {
+        for &(size, threads) in &[(5, 8), (64, 3), (100, 4)] {
+            let mut a = get_random_auto(size, 3);
+            let mut b = Automaton::new(3, size, a.rule.clone());
+            b.grid_mut().copy_from_slice(&a.grid());
+            b.set_threads(threads);
+            for _ in 0..3 {
+                a.update();
+                b.update();
+            }
+            assert_eq!(a.grid(), b.grid());
+        }
     }
 
-    #[bench]
-    fn bench_single_update_32(b: &mut Bencher) {
-        let mut a = get_random_auto(32, 3);
-        b.iter(|| a.update());
+    #[test]
+    fn updates_read_the_cells_of_the_neighborhood() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for &nb in &[Neighborhood::VonNeumann, Neighborhood::Hex] {
+            // The rules copying the state of one cell of the neighborhood.
+            let n = nb.n_cells(1);
+            for (k, &(a, b)) in nb.offsets(1).iter().enumerate() {
+                let table = (0..1 << n).map(|p| (p >> k) as u8 & 1).collect();
+                let mut autom = Automaton::new(2, 6, Rule::with_neighborhood(1, 2, nb, table));
+                autom.random_init();
+                let grid = autom.grid();
+                autom.update();
+                for (idx, &state) in autom.grid().iter().enumerate() {
+                    let (i, j) = (idx as isize / 6 + a, idx as isize % 6 + b);
+                    assert_eq!(
+                        state,
+                        grid[(i.rem_euclid(6) * 6 + j.rem_euclid(6)) as usize]
+                    );
+                }
+            }
+            let rule = Rule::random_with_neighborhood(2, 2, nb, SamplingMode::Uniform, &mut rng);
+            let mut autom = Automaton::new(2, 20, rule.clone());
+            autom.random_init();
+            let expected = rule.apply_on_torus(&autom.grid(), 20);
+            autom.update();
+            assert_eq!(autom.grid(), expected);
+            assert!(autom.set_rule(Rule::random(2, 2)).is_err());
+        }
     }
 
-    #[bench]
-    fn bench_single_update_64(b: &mut Bencher) {
-        let mut a = get_random_auto(64, 3);
-        b.iter(|| a.update());
+    #[test]
+    fn larger_horizons_wrap_around_the_torus() {
+        for &size in &[3, 40] {
+            let rule = Rule::random(2, 2);
+            let mut a = Automaton::new(2, size, rule.clone());
+            a.random_init();
+            let mut expected = a.grid();
+            for _ in 0..3 {
+                a.update();
+                expected = rule.apply_on_torus(&expected, size);
+            }
+            assert_eq!(a.grid(), expected);
+        }
     }
 
-    #[bench]
-    fn bench_single_update_128(b: &mut Bencher) {
-        let mut a = get_random_auto(128, 3);
-        b.iter(|| a.update());
+    #[test]
+    fn walls_and_mirrors_match_a_padded_torus() {
+        // Surrounding the grid with a padding as wide as the horizon, made of
+        // walls or of mirrored cells, gives the same interior on a torus.
+        let (size, h) = (12, 2);
+        let padded_size = size + 2 * h;
+        for &boundary in &[BoundaryCondition::Fixed(1), BoundaryCondition::Reflective] {
+            let rule = Rule::random(h as i8, 2);
+            let mut a = Automaton::new(2, size, rule.clone());
+            a.random_init();
+            a.set_boundary(boundary);
+            let grid = a.grid();
+            let mut padded = vec![0; padded_size * padded_size];
+            for x in 0..padded_size {
+                for y in 0..padded_size {
+                    let mirror = |c: usize| {
+                        let c = c as isize - h as isize;
+                        (if c < 0 {
+                            -1 - c
+                        } else if c >= size as isize {
+                            2 * size as isize - 1 - c
+                        } else {
+                            c
+                        }) as usize
+                    };
+                    let inside = (h..size + h).contains(&x) && (h..size + h).contains(&y);
+                    padded[x * padded_size + y] = match boundary {
+                        BoundaryCondition::Fixed(state) if !inside => state,
+                        _ => grid[mirror(x) * size + mirror(y)],
+                    };
+                }
+            }
+            let next = rule.apply_on_torus(&padded, padded_size);
+            a.update();
+            let expected: Vec<u8> = (h..size + h)
+                .flat_map(|x| next[x * padded_size + h..x * padded_size + h + size].to_vec())
+                .collect();
+            assert_eq!(a.grid(), expected);
+            let mut b = Automaton::new(2, size, rule);
+            b.grid_mut().copy_from_slice(&grid);
+            b.set_boundary(boundary);
+            b.update_parallel(3);
+            assert_eq!(b.grid(), expected);
+        }
     }
 
-    #[bench]
-    fn bench_single_update_256(b: &mut Bencher) {
-        let mut a = get_random_auto(256, 3);
-        b.iter(|| a.update());
-    }
+    #[test]
+    fn cycles_report_transients_and_periods() {
+        // A lone cell dies, then the empty grid is a fixed point.
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        a.grid_mut()[10] = 1;
+        let cycle = a.detect_cycle(10).unwrap();
+        assert_eq!((cycle.transient, cycle.period), (1, 1));
+        assert!(cycle.is_fixed_point());
 
-    #[bench]
-    fn bench_single_update_512(b: &mut Bencher) {
-        let mut a = get_random_auto(512, 3);
-        b.iter(|| a.update());
+        // A glider crosses the 8x8 torus diagonally in 32 steps.
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        for &i in &[1, 10, 16, 17, 18] {
+            a.grid_mut()[i] = 1;
+        }
+        let start = a.grid();
+        assert_eq!(a.detect_cycle(31), None);
+        let mut b = Automaton::new(2, 8, Rule::gol());
+        b.grid_mut().copy_from_slice(&start);
+        let cycle = b.detect_cycle(32).unwrap();
+        assert_eq!((cycle.transient, cycle.period), (0, 32));
+        assert_eq!(b.grid(), start);
     }
 
-    #[bench]
-    fn bench_single_update_2048(b: &mut Bencher) {
-        let mut a = get_random_auto(2048, 3);
-        b.iter(|| a.update());
+    #[cfg(feature = "bench")]
+    mod benches {
+        use super::get_random_auto;
+        use crate::automaton::AutomatonImpl;
+        use test::Bencher;
+
+        #[bench]
+        fn bench_update_one_item_bd(b: &mut Bencher) {
+            let mut a = get_random_auto(64, 2);
+            b.iter(|| a.single_update_bound_check(10, 10));
+        }
+
+        #[bench]
+        fn bench_update_one_item(b: &mut Bencher) {
+            let mut a = get_random_auto(64, 2);
+            b.iter(|| a.update_interior(10..11, 10..11));
+        }
+
+        #[bench]
+        fn bench_single_update_32(b: &mut Bencher) {
+            let mut a = get_random_auto(32, 3);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_64(b: &mut Bencher) {
+            let mut a = get_random_auto(64, 3);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_128(b: &mut Bencher) {
+            let mut a = get_random_auto(128, 3);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_256(b: &mut Bencher) {
+            let mut a = get_random_auto(256, 3);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_512(b: &mut Bencher) {
+            let mut a = get_random_auto(512, 3);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_2048(b: &mut Bencher) {
+            let mut a = get_random_auto(2048, 3);
+            b.iter(|| a.update());
+        }
     }
 }
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::{
+    Automaton, AutomatonImpl, RecursiveAutomaton, TiledAutomaton, TotalisticAutomaton, TILE_SIZE,
+};
+use crate::rule::{Rule, TotalisticRule};
+
+/// The result of checking a simulation backend against the reference
+/// implementation of the rule ([`Rule::apply_on_torus`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendCheck {
+    /// The name of the backend.
+    pub name: &'static str,
+    /// Whether the backend produced the same grid as the reference.
+    pub correct: bool,
+    /// The number of cell updates performed.
+    pub cell_updates: u64,
+    /// The time spent updating the grid.
+    pub elapsed: Duration,
+}
+
+impl BackendCheck {
+    /// Returns the throughput of the backend in cell updates per second.
+    pub fn cell_updates_per_sec(&self) -> f64 {
+        self.cell_updates as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BackendCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<10} {:<8} {:.3e} cell updates/s",
+            self.name,
+            if self.correct { "ok" } else { "MISMATCH" },
+            self.cell_updates_per_sec()
+        )
+    }
+}
+
+/// The number of threads of the multi-threaded backends checked.
+const CHECK_THREADS: usize = 4;
+
+fn check_backend<A: AutomatonImpl>(
+    name: &'static str,
+    rule: &Rule,
+    size: usize,
+    steps: u32,
+) -> BackendCheck {
+    check_configured_backend(name, A::new(rule.states, size, rule.clone()), rule, steps)
+}
+
+fn check_configured_backend<A: AutomatonImpl>(
+    name: &'static str,
+    mut a: A,
+    rule: &Rule,
+    steps: u32,
+) -> BackendCheck {
+    let size = a.size();
+    a.random_init();
+    let mut expected = a.grid();
+    let start = Instant::now();
+    for _ in 0..steps {
+        a.update();
+    }
+    let elapsed = start.elapsed();
+    for _ in 0..steps {
+        expected = rule.apply_on_torus(&expected, size);
+    }
+    BackendCheck {
+        name,
+        correct: a.grid() == expected,
+        cell_updates: (size * size) as u64 * u64::from(steps),
+        elapsed,
+    }
+}
+
+/// Runs `steps` updates of a random grid of side `size` with every available
+/// backend, checking the results against [`Rule::apply_on_torus`] and timing
+/// the updates. The tiled backend is checked on one and several threads (and
+/// with pinned workers with the `numa` feature) when `size` is a multiple of
+/// [`TILE_SIZE`], and the [`TotalisticAutomaton`] when the rule is outer
+/// totalistic.
+///
+/// ```
+/// use rust_ca::automaton::check_backends;
+/// use rust_ca::rule::{Rule, TotalisticRule};
+///
+/// let checks = check_backends(&Rule::random(1, 3), 256, 2);
+/// assert!(checks.iter().any(|c| c.name == "tiled-mt"));
+/// assert!(checks.iter().all(|c| c.correct));
+/// let rule = TotalisticRule::random(1, 3, true).to_rule();
+/// let checks = check_backends(&rule, 64, 2);
+/// assert_eq!(checks.last().unwrap().name, "totalistic");
+/// assert!(checks.iter().all(|c| c.correct));
+/// ```
+pub fn check_backends(rule: &Rule, size: usize, steps: u32) -> Vec<BackendCheck> {
+    let mut checks = vec![
+        check_backend::<Automaton>("base", rule, size, steps),
+        check_backend::<RecursiveAutomaton>("recursive", rule, size, steps),
+    ];
+    if size > 0 && size.is_multiple_of(TILE_SIZE) {
+        checks.push(check_backend::<TiledAutomaton>("tiled", rule, size, steps));
+        let mut tiled = TiledAutomaton::new(rule.states, size, rule.clone());
+        tiled.set_threads(CHECK_THREADS);
+        checks.push(check_configured_backend("tiled-mt", tiled, rule, steps));
+        #[cfg(feature = "numa")]
+        {
+            let mut tiled = TiledAutomaton::new(rule.states, size, rule.clone());
+            tiled.set_threads(CHECK_THREADS);
+            if tiled.set_pinned(true) {
+                checks.push(check_configured_backend("tiled-pin", tiled, rule, steps));
+            }
+        }
+    }
+    if TotalisticRule::from_rule(rule, true).is_some() {
+        checks.push(check_backend::<TotalisticAutomaton>(
+            "totalistic",
+            rule,
+            size,
+            steps,
+        ));
+    }
+    checks
+}
@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+/// How the neighborhoods of the cells close to the edges of the grid are
+/// completed.
+///
+/// ```
+/// use rust_ca::automaton::BoundaryCondition;
+///
+/// assert_eq!(BoundaryCondition::Periodic.resolve(-1, 8), Some(7));
+/// assert_eq!(BoundaryCondition::Fixed(0).resolve(-1, 8), None);
+/// assert_eq!(BoundaryCondition::Reflective.resolve(-1, 8), Some(0));
+/// assert_eq!(BoundaryCondition::Reflective.resolve(9, 8), Some(6));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundaryCondition {
+    /// The grid wraps around its edges (the grid is a torus).
+    #[default]
+    Periodic,
+    /// The cells outside the grid are all in the given state (walls).
+    Fixed(u8),
+    /// The grid is mirrored at its edges: the cells just outside the grid
+    /// are copies of the cells on the edges.
+    Reflective,
+}
+
+impl BoundaryCondition {
+    /// Maps a coordinate along one axis of a grid of side `size` to a
+    /// coordinate in the grid. Returns `None` for the coordinates outside the
+    /// grid with fixed boundaries, whose cells are in the fixed state.
+    #[inline]
+    pub fn resolve(self, x: isize, size: usize) -> Option<usize> {
+        let s = size as isize;
+        if (0..s).contains(&x) {
+            return Some(x as usize);
+        }
+        match self {
+            BoundaryCondition::Periodic => Some(x.rem_euclid(s) as usize),
+            BoundaryCondition::Fixed(_) => None,
+            BoundaryCondition::Reflective => {
+                let x = x.rem_euclid(2 * s);
+                Some(if x < s { x } else { 2 * s - 1 - x } as usize)
+            }
+        }
+    }
+
+    /// Returns the state of the cell `(x, y)` of a square grid of side
+    /// `size` stored in row-major order, the coordinates possibly being
+    /// outside the grid.
+    #[inline]
+    pub fn cell(self, grid: &[u8], size: usize, (x, y): (isize, isize)) -> u8 {
+        match (self.resolve(x, size), self.resolve(y, size), self) {
+            (Some(i), Some(j), _) => grid[i * size + j],
+            (_, _, BoundaryCondition::Fixed(state)) => state,
+            _ => unreachable!("only fixed boundaries leave the grid"),
+        }
+    }
+}
+
+/// Parses `periodic`, `reflective`, `fixed` (walls of state 0) or
+/// `fixed:STATE`.
+impl FromStr for BoundaryCondition {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "periodic" => Ok(BoundaryCondition::Periodic),
+            None if s == "reflective" => Ok(BoundaryCondition::Reflective),
+            None if s == "fixed" => Ok(BoundaryCondition::Fixed(0)),
+            Some(("fixed", state)) => state
+                .parse()
+                .map(BoundaryCondition::Fixed)
+                .map_err(|_| "invalid fixed state"),
+            _ => Err("no match"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoundaryCondition;
+
+    #[test]
+    fn coordinates_far_outside_the_grid_are_resolved() {
+        let size = 3;
+        let periodic: Vec<_> = (-4..7)
+            .map(|x| BoundaryCondition::Periodic.resolve(x, size).unwrap())
+            .collect();
+        assert_eq!(periodic, [2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0]);
+        let reflective: Vec<_> = (-4..7)
+            .map(|x| BoundaryCondition::Reflective.resolve(x, size).unwrap())
+            .collect();
+        assert_eq!(reflective, [2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn fixed_boundaries_surround_the_grid_with_walls() {
+        let grid: Vec<u8> = (0..9).collect();
+        let wall = BoundaryCondition::Fixed(7);
+        assert_eq!(wall.cell(&grid, 3, (1, 2)), 5);
+        assert_eq!(wall.cell(&grid, 3, (1, 3)), 7);
+        assert_eq!(wall.cell(&grid, 3, (-1, 0)), 7);
+        assert_eq!(BoundaryCondition::Reflective.cell(&grid, 3, (-1, 3)), 2);
+    }
+
+    #[test]
+    fn boundaries_are_parsed() {
+        assert_eq!("periodic".parse(), Ok(BoundaryCondition::Periodic));
+        assert_eq!("fixed".parse(), Ok(BoundaryCondition::Fixed(0)));
+        assert_eq!("fixed:3".parse(), Ok(BoundaryCondition::Fixed(3)));
+        assert!("fixed:x".parse::<BoundaryCondition>().is_err());
+        assert!("wrap".parse::<BoundaryCondition>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use super::{
+    duplicate_array, AutomatonImpl, BoundaryCondition, Dimensions, Pattern, PatternError, Rotation,
+    StepIteratorBox,
+};
+use crate::analysis::{blocks_per_side, coarse_grain, BlockMapping};
+use crate::rule::{Rule, RuleError};
+
+/// An automaton wrapper simulating the inner CA at full resolution but
+/// exposing a coarse-grained view of its grid (see
+/// [`analysis::coarse_grain`](crate::analysis::coarse_grain)). This is useful
+/// for renormalization studies, where one compares the coarse-grained dynamics
+/// with the dynamics of another rule.
+///
+/// ```
+/// use rust_ca::analysis::BlockMapping;
+/// use rust_ca::automaton::{Automaton, AutomatonImpl, CoarseGrainedAutomaton};
+/// use rust_ca::rule::Rule;
+///
+/// let inner = Automaton::new(2, 64, Rule::random(1, 2));
+/// let mut automaton = CoarseGrainedAutomaton::wrap(inner, 4, BlockMapping::Majority);
+/// automaton.random_init();
+/// assert_eq!(automaton.size(), 16);
+/// assert_eq!(automaton.grid().len(), 16 * 16);
+/// ```
+pub struct CoarseGrainedAutomaton<A: AutomatonImpl> {
+    inner: A,
+    block: usize,
+    mapping: BlockMapping,
+}
+
+impl<A: AutomatonImpl> CoarseGrainedAutomaton<A> {
+    /// Wraps an automaton, coarse-graining its grid in blocks of size `block`
+    /// with the given `mapping`.
+    pub fn wrap(inner: A, block: usize, mapping: BlockMapping) -> Self {
+        assert!(block > 0, "The block size must be positive");
+        CoarseGrainedAutomaton {
+            inner,
+            block,
+            mapping,
+        }
+    }
+
+    /// Returns a reference to the full resolution automaton.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the full resolution automaton.
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+}
+
+impl<A: AutomatonImpl> AutomatonImpl for CoarseGrainedAutomaton<A> {
+    /// Makes a new wrapped automaton with blocks of size 1 (no
+    /// coarse-graining). Use [`CoarseGrainedAutomaton::wrap`] to choose the
+    /// block size and mapping.
+    fn new(states: u8, size: usize, rule: Rule) -> Self {
+        CoarseGrainedAutomaton::wrap(A::new(states, size, rule), 1, BlockMapping::Majority)
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let inner_size = self.inner.size();
+        let size = self.size();
+        let block = self.block;
+        let mapping = &self.mapping;
+        Box::new(self.inner.skipped_iter(steps, skip, 1).map(move |grid| {
+            duplicate_array(
+                &coarse_grain(&grid, inner_size, block, mapping),
+                Dimensions::new(size, scale),
+            )
+        }))
+    }
+
+    fn size(&self) -> usize {
+        blocks_per_side(self.inner.size(), self.block)
+    }
+
+    fn states(&self) -> u8 {
+        self.inner.states()
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        self.inner.init_from_pattern(pattern_fname)
+    }
+
+    /// Places a pattern in the grid of the inner CA, at full resolution.
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        self.inner.place_pattern(spec, x, y, rotation, flip)
+    }
+
+    fn update(&mut self) {
+        self.inner.update()
+    }
+
+    fn random_init(&mut self) {
+        self.inner.random_init()
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        self.inner.set_noise(p)
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.inner.set_threads(threads)
+    }
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.inner.set_boundary(boundary)
+    }
+
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        self.inner.set_rule(rule)
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        coarse_grain(
+            &self.inner.grid(),
+            self.inner.size(),
+            self.block,
+            &self.mapping,
+        )
+    }
+}
@@ -0,0 +1,222 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{CyclicRule, Rule, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A cyclic cellular automaton updated from the parameters of its
+/// [`CyclicRule`] instead of a rule table: the neighbors of every cell in the
+/// state following its own are counted until the threshold is reached. The
+/// cost of an update grows with the size of the neighborhood, but not with
+/// the number of states, so the rules with many states or large ranges,
+/// whose tables wouldn't fit in memory, can be run.
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, CyclicAutomaton};
+/// use rust_ca::rule::CyclicRule;
+///
+/// // 14 states on the Moore neighborhood of range 1: a table would have
+/// // 14^9 entries.
+/// let mut automaton = CyclicAutomaton::with_rule(64, CyclicRule::new(14, 1, 1));
+/// automaton.random_init_seeded(0);
+/// let before = automaton.grid();
+/// automaton.update();
+/// // A cell either keeps its state or advances to the next one.
+/// assert!(before
+///     .iter()
+///     .zip(automaton.grid())
+///     .all(|(&a, b)| b == a || b == (a + 1) % 14));
+/// ```
+pub struct CyclicAutomaton {
+    size: usize,
+    rule: CyclicRule,
+    cells: Vec<u8>,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl CyclicAutomaton {
+    /// Makes an automaton of `size^2` cells in state 0 with the given rule.
+    pub fn with_rule(size: usize, rule: CyclicRule) -> CyclicAutomaton {
+        CyclicAutomaton {
+            size,
+            rule,
+            cells: vec![0; size * size],
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the rule of the automaton.
+    pub fn rule(&self) -> &CyclicRule {
+        &self.rule
+    }
+
+    /// Returns a mutable reference to the cells of the grid.
+    pub fn grid_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+}
+
+impl AutomatonImpl for CyclicAutomaton {
+    /// Makes a cyclic automaton with `states` states and a threshold of 1 on
+    /// the neighborhood of `rule`, whose range is its horizon. Use
+    /// [`CyclicAutomaton::with_rule`] to choose the threshold.
+    fn new(states: u8, size: usize, rule: Rule) -> CyclicAutomaton {
+        CyclicAutomaton::with_rule(
+            size,
+            CyclicRule::with_neighborhood(states, 1, rule.horizon, rule.neighborhood()),
+        )
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        self.rule.states
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, self.rule.states)? {
+            self.cells[i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let (size, rule) = (self.size, self.rule);
+        let offsets: Vec<(isize, isize)> = rule
+            .neighborhood
+            .offsets(rule.range)
+            .into_iter()
+            .filter(|&offset| offset != (0, 0))
+            .collect();
+        let next: Vec<u8> = (0..self.cells.len())
+            .map(|k| {
+                let (i, j) = ((k / size) as isize, (k % size) as isize);
+                let successor = rule.successor(self.cells[k]);
+                let successors = offsets
+                    .iter()
+                    .filter(|&&(a, b)| {
+                        self.boundary.cell(&self.cells, size, (i + a, j + b)) == successor
+                    })
+                    .take(rule.threshold)
+                    .count();
+                rule.next_state(self.cells[k], successors)
+            })
+            .collect();
+        self.cells = next;
+        if self.noise > 0. {
+            for c in self.cells.iter_mut() {
+                if self.rng.gen_bool(self.noise) {
+                    *c = self.rng.gen_range(0..rule.states);
+                }
+            }
+        }
+    }
+
+    fn random_init(&mut self) {
+        let states = self.rule.states;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = rng.gen_range(0..states);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The grid is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// Table rules can't drive a cyclic automaton, so this always fails.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError(
+            "cyclic automata need a cyclic rule",
+        ))
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.cells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{Automaton, AutomatonImpl, BoundaryCondition, CyclicAutomaton};
+    use crate::rule::{CyclicRule, Neighborhood, Rule};
+
+    #[test]
+    fn updates_match_the_rule_tables() {
+        for &(rule, boundary) in &[
+            (CyclicRule::new(3, 3, 1), BoundaryCondition::Periodic),
+            (CyclicRule::new(4, 2, 1), BoundaryCondition::Fixed(1)),
+            (
+                CyclicRule::with_neighborhood(3, 2, 2, Neighborhood::VonNeumann),
+                BoundaryCondition::Reflective,
+            ),
+        ] {
+            let mut a = CyclicAutomaton::with_rule(16, rule);
+            let mut b = Automaton::new(rule.states, 16, rule.to_rule().unwrap());
+            a.set_boundary(boundary);
+            b.set_boundary(boundary);
+            a.random_init_seeded(7);
+            b.grid_mut().copy_from_slice(&a.grid());
+            for _ in 0..4 {
+                a.update();
+                b.update();
+                assert_eq!(a.grid(), b.grid(), "{}", rule);
+            }
+        }
+        let mut a = CyclicAutomaton::new(5, 8, Rule::gol());
+        assert_eq!(a.rule(), &CyclicRule::new(5, 1, 1));
+        assert!(a.set_rule(Rule::gol()).is_err());
+    }
+}
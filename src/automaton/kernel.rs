@@ -0,0 +1,248 @@
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::rule::{Neighborhood, Rule};
+
+/// The inner loop computing the rule table index of the neighborhoods during
+/// the updates. The Moore neighborhoods of horizon 1 and 2 have kernels
+/// specialized at compile time, whose 3x3 and 5x5 loops are fully unrolled.
+/// The binary rules on these neighborhoods build the indices with bit shifts.
+/// At horizon 1, their 512 transitions are copied to an array indexed without
+/// bounds checks, and the index of a cell is shifted from the index of the
+/// previous cell of its row. The other rules use a dynamic kernel reading the
+/// neighborhoods row by row.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl, Kernel};
+/// use rust_ca::rule::{Neighborhood, Rule};
+///
+/// assert_eq!(Kernel::for_rule(&Rule::gol()), Kernel::Binary1);
+/// assert_eq!(Kernel::for_rule(&Rule::random(1, 3)), Kernel::Horizon1);
+/// let rule = Rule::with_neighborhood(1, 2, Neighborhood::Hex, vec![0; 128]);
+/// assert_eq!(Kernel::for_rule(&rule), Kernel::Dynamic);
+/// let automaton = Automaton::new(2, 8, Rule::new(2, 2, vec![0; 1 << 25]));
+/// assert_eq!(automaton.kernel(), Kernel::Binary2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    /// The 3x3 Moore neighborhood.
+    Horizon1,
+    /// The 5x5 Moore neighborhood.
+    Horizon2,
+    /// The 3x3 Moore neighborhood with 2 states.
+    Binary1,
+    /// The 5x5 Moore neighborhood with 2 states.
+    Binary2,
+    /// Any horizon and neighborhood.
+    Dynamic,
+}
+
+/// A loop updating cells, compiled for each kernel by
+/// [`Kernel::run`].
+pub(crate) trait KernelLoop {
+    /// Runs the loop, computing the next state of the cell at offset `center`
+    /// of `grid` with `next_state(grid, center)`.
+    fn run<F: FnMut(&[u8], usize) -> u8>(self, next_state: F);
+}
+
+impl Kernel {
+    /// Returns the fastest kernel able to update the rule.
+    pub fn for_rule(rule: &Rule) -> Kernel {
+        match (rule.horizon, rule.neighborhood(), rule.states) {
+            (1, Neighborhood::Moore, 2) => Kernel::Binary1,
+            (2, Neighborhood::Moore, 2) => Kernel::Binary2,
+            (1, Neighborhood::Moore, _) => Kernel::Horizon1,
+            (2, Neighborhood::Moore, _) => Kernel::Horizon2,
+            _ => Kernel::Dynamic,
+        }
+    }
+
+    /// Runs a loop with the next state function of the kernel, for grids
+    /// with rows of `stride` cells in `states` states. The loop is compiled for each kernel, so
+    /// that the index computations are inlined in it.
+    #[inline(always)]
+    pub(crate) fn run<L: KernelLoop>(self, rule: &Rule, stride: usize, states: usize, update: L) {
+        let neighborhood = (rule.horizon as isize, rule.neighborhood());
+        match self {
+            Kernel::Horizon1 => update.run(|g, c| rule[moore_index::<3>(g, stride, c, states)]),
+            Kernel::Horizon2 => update.run(|g, c| rule[moore_index::<5>(g, stride, c, states)]),
+            Kernel::Binary1 => {
+                // The transitions indexed by the columns of the neighborhood,
+                // three bits each, so that moving to the next cell of a row
+                // shifts the previous index by a column.
+                let mut table = [0; 512];
+                for (k, state) in table.iter_mut().enumerate() {
+                    let row_major = (0..9).map(|bit| (k >> bit & 1) << (bit % 3 * 3 + bit / 3));
+                    *state = rule[row_major.sum::<usize>()];
+                }
+                let column = |g: &[u8], x: usize| {
+                    g[x - stride] as usize | (g[x] as usize) << 1 | (g[x + stride] as usize) << 2
+                };
+                let mut previous = (usize::MAX, 0);
+                update.run(move |g, c| {
+                    let ind = if c == previous.0.wrapping_add(1) {
+                        previous.1 >> 3 | column(g, c + 1) << 6
+                    } else {
+                        column(g, c - 1) | column(g, c) << 3 | column(g, c + 1) << 6
+                    };
+                    previous = (c, ind);
+                    table[ind & 511]
+                })
+            }
+            Kernel::Binary2 => update.run(|g, c| rule[binary_index::<5>(g, stride, c)]),
+            Kernel::Dynamic => {
+                update.run(|g, c| rule[dynamic_index(g, stride, c, states, neighborhood)])
+            }
+        }
+    }
+
+    /// Returns the rule table index of a neighborhood of horizon `h` around
+    /// the cell at offset `center` of a grid with rows of `stride` cells,
+    /// which must fit in the grid.
+    #[inline(always)]
+    pub(crate) fn index(
+        self,
+        grid: &[u8],
+        stride: usize,
+        center: usize,
+        states: usize,
+        neighborhood: (isize, Neighborhood),
+    ) -> usize {
+        match self {
+            Kernel::Horizon1 => moore_index::<3>(grid, stride, center, states),
+            Kernel::Horizon2 => moore_index::<5>(grid, stride, center, states),
+            Kernel::Binary1 => binary_index::<3>(grid, stride, center),
+            Kernel::Binary2 => binary_index::<5>(grid, stride, center),
+            Kernel::Dynamic => dynamic_index(grid, stride, center, states, neighborhood),
+        }
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kernel::Horizon1 => "horizon-1",
+            Kernel::Horizon2 => "horizon-2",
+            Kernel::Binary1 => "binary-horizon-1",
+            Kernel::Binary2 => "binary-horizon-2",
+            Kernel::Dynamic => "dynamic",
+        })
+    }
+}
+
+/// Returns the rule table index of the Moore neighborhood of side `W` around
+/// the cell at offset `center` of a grid of binary cells, like
+/// [`moore_index`] but shifting the cells into place.
+#[inline(always)]
+fn binary_index<const W: usize>(grid: &[u8], stride: usize, center: usize) -> usize {
+    let h = W / 2;
+    let mut ind: usize = 0;
+    for a in 0..W {
+        let start = center + a * stride - h * stride - h;
+        let row: &[u8; W] = grid[start..start + W].try_into().unwrap();
+        for (b, &cell) in row.iter().enumerate() {
+            ind += (cell as usize) << (a * W + b);
+        }
+    }
+    ind
+}
+
+/// Returns the rule table index of the Moore neighborhood of side `W` around
+/// the cell at offset `center` of a grid with rows of `stride` cells. The
+/// neighborhood must fit in the grid. Each row is read as an array of `W`
+/// cells, so the loops have constant bounds and are unrolled.
+#[inline(always)]
+fn moore_index<const W: usize>(grid: &[u8], stride: usize, center: usize, states: usize) -> usize {
+    let h = W / 2;
+    let mut ind: usize = 0;
+    let mut power = 1;
+    for a in 0..W {
+        let start = center + a * stride - h * stride - h;
+        let row: &[u8; W] = grid[start..start + W].try_into().unwrap();
+        for &cell in row {
+            ind += power * cell as usize;
+            power *= states;
+        }
+    }
+    ind
+}
+
+/// Returns the rule table index of a neighborhood of horizon `h` around the
+/// cell at offset `center` of a grid with rows of `stride` cells. The
+/// neighborhood must fit in the grid. Each row of the neighborhood is read as
+/// a single slice of the grid.
+#[inline]
+fn dynamic_index(
+    grid: &[u8],
+    stride: usize,
+    center: usize,
+    states: usize,
+    (h, neighborhood): (isize, Neighborhood),
+) -> usize {
+    let mut ind: usize = 0;
+    let mut power = 1;
+    for a in -h..=h {
+        let span = neighborhood.row_span(a, h);
+        let start = center as isize + a * stride as isize;
+        for &cell in &grid[(start + span.start()) as usize..=(start + span.end()) as usize] {
+            ind += power * cell as usize;
+            power *= states;
+        }
+    }
+    ind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{binary_index, dynamic_index, moore_index, Kernel};
+    use crate::automaton::{Automaton, AutomatonImpl, TiledAutomaton};
+    use crate::rule::{Neighborhood, Rule};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn specialized_kernels_match_the_dynamic_kernel() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (stride, states) = (9, 3);
+        let grid: Vec<u8> = (0..stride * 7).map(|_| rng.gen_range(0..3)).collect();
+        for center in [2 * stride + 2, 3 * stride + 4, 4 * stride + 6] {
+            assert_eq!(
+                moore_index::<3>(&grid, stride, center, states),
+                dynamic_index(&grid, stride, center, states, (1, Neighborhood::Moore))
+            );
+            assert_eq!(
+                moore_index::<5>(&grid, stride, center, states),
+                dynamic_index(&grid, stride, center, states, (2, Neighborhood::Moore))
+            );
+        }
+        let binary: Vec<u8> = grid.iter().map(|&c| c % 2).collect();
+        for center in [2 * stride + 2, 4 * stride + 6] {
+            assert_eq!(
+                binary_index::<3>(&binary, stride, center),
+                moore_index::<3>(&binary, stride, center, 2)
+            );
+            assert_eq!(
+                binary_index::<5>(&binary, stride, center),
+                moore_index::<5>(&binary, stride, center, 2)
+            );
+        }
+    }
+
+    #[test]
+    fn binary_kernels_match_the_reference() {
+        let rule = Rule::random(1, 2);
+        let mut a = Automaton::new(2, 256, rule.clone());
+        let mut b = TiledAutomaton::new(2, 256, rule.clone());
+        assert_eq!((a.kernel(), b.kernel()), (Kernel::Binary1, Kernel::Binary1));
+        a.random_init_seeded(7);
+        b.random_init_seeded(7);
+        let mut expected = a.grid();
+        for _ in 0..3 {
+            a.update();
+            b.update();
+            expected = rule.apply_on_torus(&expected, 256);
+        }
+        assert_eq!(a.grid(), expected);
+        assert_eq!(b.grid(), expected);
+    }
+}
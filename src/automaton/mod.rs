@@ -1,68 +1,61 @@
 //! The cellular automata related utilities.
-use std::error;
-use std::fmt;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
 
 mod automaton_base;
 pub use automaton_base::Automaton;
 
+mod automaton_1d;
+pub use automaton_1d::Automaton1D;
+
+mod automaton_3d;
+pub use automaton_3d::{Automaton3D, View3D};
+
+mod cyclic_automaton;
+pub use cyclic_automaton::CyclicAutomaton;
+
+mod totalistic_automaton;
+pub use totalistic_automaton::TotalisticAutomaton;
+
+mod sandpile;
+pub use sandpile::{Sandpile, TOPPLING_HEIGHT};
+
+mod percolation;
+pub use percolation::BootstrapPercolation;
+
+mod voter;
+pub use voter::NoisyVoter;
+
+mod traffic;
+pub use traffic::Traffic;
+
+mod boundary;
+pub use boundary::BoundaryCondition;
+
 mod tiled_automaton;
-pub use tiled_automaton::{TiledAutomaton, TILE_SIZE};
+pub use tiled_automaton::{TileCoords, TiledAutomaton, TILE_SIZE};
 
-type StepIteratorBox<'a> = Box<dyn Iterator<Item = Vec<u8>> + 'a>;
+mod coarse_grained;
+pub use coarse_grained::CoarseGrainedAutomaton;
 
-const HORIZON: i8 = 1;
+mod state_rotated;
+pub use state_rotated::{rotate_states, StateRotatedAutomaton};
 
-/// The specifications for a starting pattern.
-struct PatternSpec {
-    /// The total number of states in the pattern.
-    states: u8,
-    /// The pattern background state (for inserting in a larger CA).
-    background: u8,
-    /// The pattern itself (2D grid).
-    pattern: Vec<Vec<u8>>,
-}
+mod recursive_automaton;
+pub use recursive_automaton::RecursiveAutomaton;
 
-/// Error type for an error that happend during pattern parsing.
-#[derive(Debug)]
-pub enum PatternError {
-    /// A io error during pattern parsing.
-    PatternFileError(io::Error),
-    /// A file format error during pattern parsing.
-    PatternFormatError,
-}
+mod backends;
+pub use backends::{check_backends, BackendCheck};
 
-impl fmt::Display for PatternError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            PatternError::PatternFormatError => write!(f, "incorrect pattern format in file"),
-            // The wrapped error contains additional information and is available
-            // via the source() method.
-            PatternError::PatternFileError(..) => {
-                write!(f, "io error with the pattern file")
-            }
-        }
-    }
-}
+mod kernel;
+pub use kernel::Kernel;
 
-impl error::Error for PatternError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        match *self {
-            PatternError::PatternFormatError => None,
-            // The cause is the underlying implementation error type. Is implicitly
-            // cast to the trait object `&error::Error`. This works because the
-            // underlying type already implements the `Error` trait.
-            PatternError::PatternFileError(ref e) => Some(e),
-        }
-    }
-}
+use crate::pattern::{parse_pattern, write_pattern};
+pub use crate::pattern::{pattern_shape, Pattern, PatternError, PatternShape, Rect, Rotation};
 
-impl From<io::Error> for PatternError {
-    fn from(err: io::Error) -> PatternError {
-        PatternError::PatternFileError(err)
-    }
-}
+type StepIteratorBox<'a> = Box<dyn Iterator<Item = Vec<u8>> + 'a>;
 
 /// Any cellular automaton implementation must implement this trait. This allows
 /// users to use the CA without having to understand the underlying
@@ -94,78 +87,472 @@ pub trait AutomatonImpl {
     /// Returns an boxed iterator of CA steps, skipping every `skip` step and
     /// scaling the grid by a factor `scale`. This is useful to output an
     /// animated CA with
-    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox;
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_>;
     /// Returns the size of the automaton.
     fn size(&self) -> usize;
     /// Returns the number of states of the automaton.
     fn states(&self) -> u8;
     /// Returns a boxed iterator of CA steps.
-    fn iter(&mut self, steps: u32) -> StepIteratorBox {
+    fn iter(&mut self, steps: u32) -> StepIteratorBox<'_> {
         self.skipped_iter(steps, 0, 1)
     }
+    /// Returns an iterator over the state statistics of the next `steps`
+    /// steps (see [`stats::FrameStats`](crate::stats::FrameStats)).
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut automaton = Automaton::new(2, 32, Rule::gol());
+    /// automaton.random_init();
+    /// for stats in automaton.iter_stats(10) {
+    ///     println!("{} {} {}", stats.step, stats.entropy, stats.activity);
+    /// }
+    /// ```
+    fn iter_stats(&mut self, steps: u32) -> crate::stats::StatsIter<StepIteratorBox<'_>> {
+        let states = self.states();
+        crate::stats::StatsIter::new(self.skipped_iter(steps, 1, 1), states, 1)
+    }
     /// Initializes all the cells of the grid from a pattern file.
     fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError>;
+    /// Places a pattern with its top left corner at column `x` and row `y`
+    /// of the grid, after mirroring it left to right if `flip` is set and
+    /// rotating it. The cells of the grid outside the pattern are kept, so
+    /// several patterns can be placed in the same grid. An error is returned
+    /// if the pattern doesn't fit in the grid at this position, or if it has
+    /// more states than the CA.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, Pattern, Rotation};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let glider = Pattern::from_file("patterns/glider.rle")?;
+    /// let mut automaton = Automaton::new(2, 32, Rule::gol());
+    /// automaton.place_pattern(&glider, 2, 2, Rotation::Rot0, false)?;
+    /// automaton.place_pattern(&glider, 20, 20, Rotation::Rot180, false)?;
+    /// assert_eq!(automaton.state_counts(), vec![32 * 32 - 10, 10]);
+    /// assert!(automaton.place_pattern(&glider, 30, 0, Rotation::Rot0, false).is_err());
+    /// # Ok::<(), rust_ca::automaton::PatternError>(())
+    /// ```
+    ///
+    /// The default implementation, for the automata whose cells can't be set
+    /// one by one, always fails with [`PatternError::PatternUnsupported`].
+    fn place_pattern(
+        &mut self,
+        _spec: &Pattern,
+        _x: usize,
+        _y: usize,
+        _rotation: Rotation,
+        _flip: bool,
+    ) -> Result<(), PatternError> {
+        Err(PatternError::PatternUnsupported)
+    }
+    /// Writes the current grid, or the cells of `bounding_box`, to a pattern
+    /// file read by [`init_from_pattern`](AutomatonImpl::init_from_pattern).
+    /// The most common state of the region is recorded as the background of
+    /// the pattern.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, Rect};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut automaton = Automaton::new(2, 16, Rule::gol());
+    /// automaton.random_init();
+    /// automaton.save_pattern("test_saved.pat", Some(Rect::new(4, 4, 8, 8)))?;
+    /// let mut copy = Automaton::new(2, 16, Rule::gol());
+    /// copy.init_from_pattern("test_saved.pat")?;
+    /// for i in 4..12 {
+    ///     let row = i * 16 + 4..i * 16 + 12;
+    ///     assert_eq!(copy.grid()[row.clone()], automaton.grid()[row]);
+    /// }
+    /// # std::fs::remove_file("test_saved.pat")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn save_pattern(&self, path: &str, bounding_box: Option<Rect>) -> Result<(), PatternError> {
+        write_pattern(path, &self.grid(), self.size(), self.states(), bounding_box)
+    }
     /// Performs a single step update of the CA grid according to the rule.
     fn update(&mut self);
     /// Randomly sets all the cells of the cellular automaton grid
     fn random_init(&mut self);
+    /// Seeds the random number generator of the automaton, used for the
+    /// random initializations and the noise. Two automata with the same seed,
+    /// grid and options produce the same simulation. Automata are seeded from
+    /// the system entropy by default. The default implementation, for the
+    /// automata without a random number generator, ignores the seed.
+    fn set_seed(&mut self, _seed: u64) {}
+    /// Seeds the automaton (see [`set_seed`](AutomatonImpl::set_seed)) and
+    /// randomly sets all the cells of its grid.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, TiledAutomaton};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut a = Automaton::new(2, 256, Rule::gol());
+    /// let mut b = TiledAutomaton::new(2, 256, Rule::gol());
+    /// a.random_init_seeded(42);
+    /// b.random_init_seeded(42);
+    /// assert_eq!(a.grid(), b.grid());
+    /// ```
+    fn random_init_seeded(&mut self, seed: u64) {
+        self.set_seed(seed);
+        self.random_init();
+    }
+    /// Sets the probability `p` that each cell takes a uniformly random state
+    /// instead of the output of the rule at every update ("thermal noise").
+    /// The default probability of 0 gives the deterministic rule.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // The empty grid stays empty under the game of life, but not with noise.
+    /// let mut automaton = Automaton::new(2, 32, Rule::gol());
+    /// automaton.set_noise(0.5);
+    /// automaton.update();
+    /// assert!(automaton.grid().iter().any(|&c| c == 1));
+    /// ```
+    ///
+    /// The default implementation, for the automata without noise, ignores
+    /// it.
+    fn set_noise(&mut self, _p: f64) {}
+    /// Sets the number of threads used to update the grid (1 by default).
+    /// Backends that can't split their updates ignore it, which is the
+    /// default implementation.
+    fn set_threads(&mut self, _threads: usize) {}
+    /// Sets how the neighborhoods of the cells close to the edges of the
+    /// grid are completed (periodic by default).
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, BoundaryCondition};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // A live cell in a corner has live neighbors on the torus, but not
+    /// // between walls of dead cells.
+    /// let mut automaton = Automaton::new(2, 8, Rule::gol());
+    /// automaton.set_boundary(BoundaryCondition::Fixed(0));
+    /// automaton.grid_mut()[0] = 1;
+    /// automaton.grid_mut()[7] = 1;
+    /// automaton.grid_mut()[56] = 1;
+    /// automaton.update();
+    /// assert!(automaton.grid().iter().all(|&c| c == 0));
+    /// ```
+    ///
+    /// The default implementation, for the automata with a single boundary
+    /// condition, ignores it.
+    fn set_boundary(&mut self, _boundary: BoundaryCondition) {}
+    /// Replaces the rule of the automaton, keeping the current grid. The new
+    /// rule must have the same horizon, number of states and neighborhood as
+    /// the current one, otherwise the rule isn't changed and an error is
+    /// returned.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut automaton = Automaton::new(2, 8, Rule::gol());
+    /// automaton.random_init();
+    /// // A rule under which every cell dies.
+    /// automaton.set_rule(Rule::new(1, 2, vec![0; 512]))?;
+    /// automaton.update();
+    /// assert!(automaton.grid().iter().all(|&c| c == 0));
+    /// assert!(automaton.set_rule(Rule::random(1, 3)).is_err());
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    ///
+    /// The default implementation, for the automata that aren't driven by a
+    /// rule table, always fails.
+    fn set_rule(&mut self, _rule: crate::rule::Rule) -> Result<(), crate::rule::RuleError> {
+        Err(crate::rule::RuleError::RuleFormatError(
+            "the automaton can't change its rule",
+        ))
+    }
     /// Gets the current grid.
     fn grid(&self) -> Vec<u8>;
+    /// Copies the current grid to a buffer, replacing its contents. The
+    /// [`Automaton`] and [`TiledAutomaton`] backends reuse the allocation of
+    /// the buffer, so that the frames of long runs can be written without
+    /// allocating a grid per frame.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{AutomatonImpl, TiledAutomaton};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut automaton = TiledAutomaton::new(2, 256, Rule::gol());
+    /// automaton.random_init();
+    /// let mut buffer = vec![];
+    /// automaton.grid_into(&mut buffer);
+    /// assert_eq!(buffer, automaton.grid());
+    /// ```
+    fn grid_into(&self, buffer: &mut Vec<u8>) {
+        *buffer = self.grid();
+    }
+    /// Returns the number of cells in each state of the current grid. The
+    /// backends count the cells changing state during the updates, so that
+    /// the counts are available without scanning the grid.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl};
+    /// use rust_ca::rule::Rule;
+    /// use rust_ca::stats;
+    ///
+    /// let mut automaton = Automaton::new(3, 64, Rule::random(1, 3));
+    /// automaton.random_init();
+    /// automaton.update();
+    /// assert_eq!(automaton.state_counts(), stats::state_counts(&automaton.grid(), 3));
+    /// ```
+    fn state_counts(&self) -> Vec<usize> {
+        crate::stats::state_counts(&self.grid(), self.states())
+    }
+    /// Runs the automaton for up to `max_steps` steps until its grid repeats,
+    /// and returns the length of the transient and the period of the cycle it
+    /// entered, or `None` if no grid repeated. The automaton is left at the
+    /// first repeated grid. The grids are compared by their hashes, so the
+    /// result only makes sense without noise.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, Cycle};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // A blinker oscillates with period 2 in the game of life.
+    /// let mut automaton = Automaton::new(2, 8, Rule::gol());
+    /// automaton.grid_mut()[8 * 3 + 2..8 * 3 + 5].fill(1);
+    /// let cycle = automaton.detect_cycle(100);
+    /// assert_eq!(cycle, Some(Cycle { transient: 0, period: 2 }));
+    /// ```
+    fn detect_cycle(&mut self, max_steps: u32) -> Option<Cycle> {
+        let mut seen = HashMap::new();
+        for step in 0..=max_steps as u64 {
+            if let Some(start) = seen.insert(grid_hash(&self.grid()), step) {
+                return Some(Cycle {
+                    transient: start,
+                    period: step - start,
+                });
+            }
+            if step < max_steps as u64 {
+                self.update();
+            }
+        }
+        None
+    }
+}
+
+/// The number of cells in each state of a grid, kept up to date by the update
+/// kernels (see [`AutomatonImpl::state_counts`]). The counts are invalidated
+/// when the grid is modified outside of the updates, and recomputed at the
+/// start of the next update.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StateCounts {
+    counts: Vec<usize>,
+    valid: bool,
 }
 
-/// Parses a pattern file. This returns a PatternSpec or an error if the pattern
-/// is incorrect.
-fn parse_pattern(pattern_fname: &str) -> Result<PatternSpec, PatternError> {
-    let mut background: u8 = 0;
-    let mut states: u8 = 0;
-    let mut begin_pattern = false;
-    let mut pattern: Vec<Vec<u8>> = vec![];
-    let pat_file = File::open(pattern_fname)?;
-    for opt_line in io::BufReader::new(pat_file).lines() {
-        let line = opt_line.map_err(|_| PatternError::PatternFormatError)?;
-        if line.starts_with('#') {
-            begin_pattern = !begin_pattern;
-        } else if begin_pattern {
-            pattern.push(line.chars().into_iter().map(|x| x as u8 - b'0').collect());
-        } else if line.contains(&"=".to_string()) {
-            let content: Vec<&str> = line.split('=').take(2).collect();
-            match content[0] {
-                "N" => {
-                    states = content[1]
-                        .parse()
-                        .map_err(|_| PatternError::PatternFormatError)?
-                }
-                "BG" => {
-                    background = content[1]
-                        .parse()
-                        .map_err(|_| PatternError::PatternFormatError)?;
-                }
-                _ => {}
+impl StateCounts {
+    /// Returns the counts, if they are up to date.
+    pub(crate) fn get(&self) -> Option<&[usize]> {
+        self.valid.then_some(&self.counts[..])
+    }
+
+    /// Returns whether the counts are up to date.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Marks the counts as outdated.
+    pub(crate) fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Replaces the counts with counts computed from the grid.
+    pub(crate) fn set(&mut self, counts: Vec<usize>) {
+        self.counts = counts;
+        self.valid = true;
+    }
+
+    /// Records a cell changing from state `from` to state `to`.
+    #[inline]
+    pub(crate) fn change(&mut self, from: u8, to: u8) {
+        if self.valid && from != to {
+            self.counts[from as usize] -= 1;
+            self.counts[to as usize] += 1;
+        }
+    }
+
+    /// Records the changes accumulated by a kernel, the number of cells that
+    /// entered (or left, if negative) each state.
+    pub(crate) fn apply(&mut self, deltas: &[isize]) {
+        if self.valid {
+            for (c, &d) in self.counts.iter_mut().zip(deltas) {
+                *c = (*c as isize + d) as usize;
             }
         }
     }
-    Ok(PatternSpec {
-        states,
-        background,
-        pattern,
-    })
 }
 
-/// This will copy the CA grid of size `size` and will duplicate cells with the
-/// a `scale` factor for image generation.
+/// The steady state of a simulation, found by [`AutomatonImpl::detect_cycle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    /// The number of steps before the first grid of the cycle.
+    pub transient: u64,
+    /// The number of steps between two repetitions of a grid.
+    pub period: u64,
+}
+
+impl Cycle {
+    /// Returns whether the simulation reached a grid that doesn't change.
+    pub fn is_fixed_point(&self) -> bool {
+        self.period == 1
+    }
+}
+
+/// Hashes a grid, to find repeated grids.
+pub(crate) fn grid_hash(grid: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    grid.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The side of a square frame, in cells of the grid and in pixels once every
+/// cell is drawn as a `scale`x`scale` square. Keeping both units together
+/// avoids mixing them up when rendering scaled frames.
+///
+/// ```
+/// use rust_ca::automaton::Dimensions;
+///
+/// let dims = Dimensions::new(300, 255);
+/// assert_eq!(dims.pixels(), 76500);
+/// // Too large for the 16-bit sides of GIF images.
+/// assert_eq!(dims.pixels_u16(), None);
+/// assert_eq!(dims.fit_gif(), Some(Dimensions::new(300, 218)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+    /// The side of the grid, in cells.
+    pub cells: usize,
+    /// The side of the square of pixels drawn for each cell.
+    pub scale: u16,
+}
+
+impl Dimensions {
+    /// Makes the dimensions of a grid of side `cells` drawn at the given
+    /// scale.
+    pub fn new(cells: usize, scale: u16) -> Dimensions {
+        Dimensions { cells, scale }
+    }
+
+    /// Returns the side of the frames, in pixels.
+    pub fn pixels(&self) -> usize {
+        self.cells * self.scale.max(1) as usize
+    }
+
+    /// Returns the side of the frames in pixels as a 16-bit integer, like the
+    /// sides of GIF images, or `None` if it doesn't fit.
+    pub fn pixels_u16(&self) -> Option<u16> {
+        u16::try_from(self.pixels()).ok()
+    }
+
+    /// Returns the same grid at the largest scale, up to the current one,
+    /// whose frames fit in a GIF, or `None` if the grid is too large even at
+    /// scale 1.
+    pub fn fit_gif(&self) -> Option<Dimensions> {
+        let scale = (u16::MAX as usize / self.cells.max(1)).min(self.scale.max(1) as usize);
+        (scale >= 1).then(|| Dimensions::new(self.cells, scale as u16))
+    }
+}
+
+/// This will copy the CA grid of side `dims.cells` and will duplicate cells
+/// with the `dims.scale` factor for image generation.
 #[inline]
-fn duplicate_array(s: &[u8], size: usize, scale: u16) -> Vec<u8> {
-    if scale > 1 {
-        let scaled_size = size * scale as usize;
-        let mut out = Vec::with_capacity(scaled_size * scaled_size);
-        for i in 0..scaled_size {
-            for j in 0..scaled_size {
-                let item = s[(i / scale as usize) * size + (j / scale as usize)];
-                out.push(item);
-            }
+pub(crate) fn duplicate_array(s: &[u8], dims: Dimensions) -> Vec<u8> {
+    let scaled_size = dims.pixels();
+    let mut out = Vec::with_capacity(scaled_size * scaled_size);
+    out.extend_from_slice(s);
+    upscale_in_place(&mut out, dims);
+    out
+}
+
+/// Duplicates the cells of a grid of side `dims.cells` in squares of side
+/// `dims.scale`, in place. The buffer only grows if its capacity is too small
+/// for the scaled grid, so a buffer reused across frames is allocated once.
+pub(crate) fn upscale_in_place(cells: &mut Vec<u8>, dims: Dimensions) {
+    let (size, scale) = (dims.cells, dims.scale.max(1) as usize);
+    if scale == 1 {
+        return;
+    }
+    let side = dims.pixels();
+    cells.resize(side * side, 0);
+    // The rows are expanded from the last one and from their end, so that
+    // every cell is read before the expanded rows overwrite it.
+    for i in (0..size).rev() {
+        let start = i * scale * side;
+        for j in (0..size).rev() {
+            let cell = cells[i * size + j];
+            cells[start + j * scale..start + (j + 1) * scale].fill(cell);
         }
-        out
-    } else {
-        Vec::from(s)
+        for k in 1..scale {
+            cells.copy_within(start..start + side, start + k * side);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutomatonImpl, BoundaryCondition, Pattern, PatternError, Rotation};
+    use crate::rule::Rule;
+
+    /// An automaton implemented outside of the crate, with only the required
+    /// methods.
+    struct Frozen(Vec<u8>);
+
+    impl AutomatonImpl for Frozen {
+        fn new(_states: u8, size: usize, _rule: Rule) -> Frozen {
+            Frozen(vec![0; size * size])
+        }
+
+        fn skipped_iter(
+            &mut self,
+            steps: u32,
+            _skip: u32,
+            _scale: u16,
+        ) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
+            Box::new((0..steps).map(move |_| self.0.clone()))
+        }
+
+        fn size(&self) -> usize {
+            (self.0.len() as f64).sqrt() as usize
+        }
+
+        fn states(&self) -> u8 {
+            2
+        }
+
+        fn init_from_pattern(&mut self, _pattern_fname: &str) -> Result<(), PatternError> {
+            Ok(())
+        }
+
+        fn update(&mut self) {}
+
+        fn random_init(&mut self) {}
+
+        fn grid(&self) -> Vec<u8> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn optional_methods_have_defaults() {
+        let mut a = Frozen::new(2, 4, Rule::gol());
+        a.random_init_seeded(1);
+        assert!(a.set_rule(Rule::gol()).is_err());
+        let pattern: Pattern = "N=2\nBG=0\n#\n1\n#\n".parse().unwrap();
+        assert!(matches!(
+            a.place_pattern(&pattern, 0, 0, Rotation::Rot0, false),
+            Err(PatternError::PatternUnsupported)
+        ));
+        a.set_noise(0.5);
+        a.set_threads(4);
+        a.set_boundary(BoundaryCondition::Fixed(1));
+        a.update();
+        assert_eq!(a.state_counts(), vec![16, 0]);
     }
 }
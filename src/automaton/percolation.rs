@@ -0,0 +1,274 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Neighborhood, Rule, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Bootstrap percolation: the cells are empty (0) or occupied (1), the
+/// occupied cells stay occupied, and an empty cell becomes occupied when at
+/// least `threshold` of its neighbors are occupied. The occupied cells
+/// spread from a random sprinkling of the grid until they either fill it or
+/// get stuck in stable clusters.
+///
+/// The neighbors are the 4 von Neumann neighbors by default (see
+/// [`BootstrapPercolation::set_neighborhood`]), and the random
+/// initializations occupy each cell with the probability set by
+/// [`BootstrapPercolation::set_density`].
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, BootstrapPercolation};
+///
+/// // With a threshold of 2, a diagonal of occupied cells fills its
+/// // bounding square.
+/// let mut automaton = BootstrapPercolation::with_threshold(8, 2);
+/// for k in 0..4 {
+///     automaton.cells_mut()[k * 8 + k] = 1;
+/// }
+/// for _ in 0..4 {
+///     automaton.update();
+/// }
+/// assert_eq!(automaton.occupied(), 16. / 64.);
+/// ```
+pub struct BootstrapPercolation {
+    size: usize,
+    threshold: usize,
+    neighborhood: Neighborhood,
+    density: f64,
+    cells: Vec<u8>,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl BootstrapPercolation {
+    /// Makes an empty grid of `size^2` cells where the empty cells with at
+    /// least `threshold` occupied neighbors become occupied.
+    pub fn with_threshold(size: usize, threshold: usize) -> BootstrapPercolation {
+        BootstrapPercolation {
+            size,
+            threshold,
+            neighborhood: Neighborhood::VonNeumann,
+            density: 0.1,
+            cells: vec![0; size * size],
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the cells of the grid.
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Returns a mutable reference to the cells of the grid.
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    /// Sets the neighbors of the cells: the cells at a distance of 1 in the
+    /// given neighborhood, central cell excluded.
+    pub fn set_neighborhood(&mut self, neighborhood: Neighborhood) {
+        self.neighborhood = neighborhood;
+    }
+
+    /// Sets the probability that each cell is occupied by the random
+    /// initializations (0.1 by default).
+    pub fn set_density(&mut self, density: f64) {
+        assert!(
+            (0. ..=1.).contains(&density),
+            "The density must be a probability"
+        );
+        self.density = density;
+    }
+
+    /// Returns the fraction of the cells that are occupied.
+    pub fn occupied(&self) -> f64 {
+        let occupied = self.cells.iter().filter(|&&c| c == 1).count();
+        occupied as f64 / self.cells.len().max(1) as f64
+    }
+}
+
+impl AutomatonImpl for BootstrapPercolation {
+    /// Makes an empty grid with a threshold of 2. The number of states and
+    /// the rule are ignored: the cells are always empty or occupied. Use
+    /// [`BootstrapPercolation::with_threshold`] to choose the threshold.
+    fn new(_states: u8, size: usize, _rule: Rule) -> BootstrapPercolation {
+        BootstrapPercolation::with_threshold(size, 2)
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        2
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, 2)? {
+            self.cells[i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let size = self.size;
+        let offsets: Vec<(isize, isize)> = self
+            .neighborhood
+            .offsets(1)
+            .into_iter()
+            .filter(|&offset| offset != (0, 0))
+            .collect();
+        let next: Vec<u8> = (0..self.cells.len())
+            .map(|k| {
+                if self.cells[k] == 1 {
+                    return 1;
+                }
+                let (i, j) = ((k / size) as isize, (k % size) as isize);
+                let occupied = offsets
+                    .iter()
+                    .filter(|&&(a, b)| self.boundary.cell(&self.cells, size, (i + a, j + b)) == 1)
+                    .count();
+                (occupied >= self.threshold) as u8
+            })
+            .collect();
+        self.cells = next;
+        if self.noise > 0. {
+            for c in self.cells.iter_mut() {
+                if self.rng.gen_bool(self.noise) {
+                    *c = self.rng.gen_range(0..2);
+                }
+            }
+        }
+    }
+
+    /// Occupies each cell with the density set by
+    /// [`BootstrapPercolation::set_density`].
+    fn random_init(&mut self) {
+        let density = self.density;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = rng.gen_bool(density) as u8;
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The grid is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// Bootstrap percolation isn't driven by a rule table, so this always
+    /// fails.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError(
+            "bootstrap percolation has no rule",
+        ))
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.cells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{AutomatonImpl, BootstrapPercolation, BoundaryCondition};
+    use crate::rule::Neighborhood;
+
+    #[test]
+    fn empty_cells_with_enough_occupied_neighbors_are_occupied() {
+        let mut a = BootstrapPercolation::with_threshold(4, 2);
+        a.cells_mut()[0] = 1;
+        a.cells_mut()[5] = 1;
+        a.update();
+        // The cells (0, 1) and (1, 0) have two occupied neighbors, and the
+        // square they fill is stable.
+        assert_eq!(&a.cells()[..6], &[1, 1, 0, 0, 1, 1]);
+        a.update();
+        assert_eq!(a.occupied(), 4. / 16.);
+
+        // On the torus, the cell (0, 3) neighbors (0, 0) and (0, 2), but not
+        // between walls of empty cells.
+        let mut a = BootstrapPercolation::with_threshold(4, 2);
+        a.cells_mut()[0] = 1;
+        a.cells_mut()[2] = 1;
+        a.update();
+        assert_eq!(&a.cells()[..4], &[1, 1, 1, 1]);
+        let mut a = BootstrapPercolation::with_threshold(4, 2);
+        a.set_boundary(BoundaryCondition::Fixed(0));
+        a.cells_mut()[0] = 1;
+        a.cells_mut()[2] = 1;
+        a.update();
+        assert_eq!(&a.cells()[..4], &[1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn moore_neighbors_count_the_diagonals() {
+        let mut a = BootstrapPercolation::with_threshold(5, 2);
+        a.set_neighborhood(Neighborhood::Moore);
+        a.cells_mut()[0] = 1;
+        a.cells_mut()[2] = 1;
+        a.update();
+        // The cells (0, 1), (1, 1) and, on the torus, (4, 1) neighbor both
+        // occupied cells.
+        assert_eq!(a.cells()[1] + a.cells()[6] + a.cells()[21], 3);
+        assert_eq!(a.occupied(), 5. / 25.);
+    }
+
+    #[test]
+    fn random_initializations_have_the_density() {
+        let mut a = BootstrapPercolation::with_threshold(64, 2);
+        a.set_density(0.25);
+        a.random_init_seeded(1);
+        assert!((a.occupied() - 0.25).abs() < 0.05);
+        a.set_density(0.);
+        a.random_init();
+        assert_eq!(a.occupied(), 0.);
+    }
+}
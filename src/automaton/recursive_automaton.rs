@@ -0,0 +1,213 @@
+use super::{
+    duplicate_array, Automaton, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, RuleError};
+
+/// The largest number of cells in the blocks at the leaves of the recursion.
+const BASE_BLOCK_CELLS: usize = 1024;
+
+/// An experimental cache-oblivious version of the cellular automaton. The grid
+/// is stored like in [`Automaton`] but the update recursively splits it in
+/// halves along its longest side, updating small blocks at the leaves of the
+/// recursion. Successive blocks share most of their neighborhoods, which
+/// keeps them in cache whatever the cache sizes, without tuning a tile size
+/// as in [`TiledAutomaton`](super::TiledAutomaton).
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl, RecursiveAutomaton};
+/// use rust_ca::rule::Rule;
+///
+/// let rule = Rule::random(1, 3);
+/// let mut a = Automaton::new(3, 100, rule.clone());
+/// a.random_init();
+/// let mut b = RecursiveAutomaton::new(3, 100, rule);
+/// b.inner_mut().grid_mut().copy_from_slice(&a.grid());
+/// a.update();
+/// b.update();
+/// assert_eq!(a.grid(), b.grid());
+/// ```
+pub struct RecursiveAutomaton {
+    inner: Automaton,
+}
+
+impl RecursiveAutomaton {
+    /// Returns a mutable reference to the underlying automaton.
+    pub fn inner_mut(&mut self) -> &mut Automaton {
+        &mut self.inner
+    }
+
+    fn update_recursive(&mut self, rows: (usize, usize), cols: (usize, usize)) {
+        let (height, width) = (rows.1 - rows.0, cols.1 - cols.0);
+        if height * width <= BASE_BLOCK_CELLS {
+            self.inner.update_rect(rows.0..rows.1, cols.0..cols.1);
+        } else if height >= width {
+            let mid = rows.0 + height / 2;
+            self.update_recursive((rows.0, mid), cols);
+            self.update_recursive((mid, rows.1), cols);
+        } else {
+            let mid = cols.0 + width / 2;
+            self.update_recursive(rows, (cols.0, mid));
+            self.update_recursive(rows, (mid, cols.1));
+        }
+    }
+}
+
+impl AutomatonImpl for RecursiveAutomaton {
+    fn new(states: u8, size: usize, rule: Rule) -> RecursiveAutomaton {
+        RecursiveAutomaton {
+            inner: Automaton::new(states, size, rule),
+        }
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size();
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    fn states(&self) -> u8 {
+        self.inner.states
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        self.inner.init_from_pattern(pattern_fname)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        self.inner.place_pattern(spec, x, y, rotation, flip)
+    }
+
+    fn update(&mut self) {
+        if self.inner.threads() > 1 {
+            // The recursive blocking is sequential; use the band-parallel
+            // update of the inner automaton instead.
+            return self.inner.update();
+        }
+        let size = self.size();
+        self.inner.begin_update();
+        self.update_recursive((0, size), (0, size));
+        self.inner.finish_update();
+    }
+
+    fn random_init(&mut self) {
+        self.inner.random_init()
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        self.inner.set_noise(p)
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.inner.set_threads(threads)
+    }
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.inner.set_boundary(boundary)
+    }
+
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        self.inner.set_rule(rule)
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.inner.grid()
+    }
+
+    fn state_counts(&self) -> Vec<usize> {
+        self.inner.state_counts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{Automaton, AutomatonImpl, RecursiveAutomaton};
+    use crate::rule::Rule;
+
+    #[test]
+    fn matches_the_base_automaton() {
+        let rule = Rule::random(1, 2);
+        let mut a = Automaton::new(2, 77, rule.clone());
+        a.random_init();
+        let mut b = RecursiveAutomaton::new(2, 77, rule.clone());
+        b.inner_mut().grid_mut().copy_from_slice(&a.grid());
+        for _ in 0..5 {
+            a.update();
+            b.update();
+        }
+        assert_eq!(a.grid(), b.grid());
+    }
+
+    #[test]
+    fn honors_the_rule_horizon() {
+        let rule = Rule::random(2, 2);
+        let mut a = RecursiveAutomaton::new(2, 100, rule.clone());
+        a.random_init();
+        let expected = rule.apply_on_torus(&a.grid(), 100);
+        a.update();
+        assert_eq!(a.grid(), expected);
+    }
+
+    #[cfg(feature = "bench")]
+    mod benches {
+        use crate::automaton::{Automaton, AutomatonImpl, RecursiveAutomaton, TiledAutomaton};
+        use crate::rule::Rule;
+        use test::Bencher;
+
+        fn bench_update<T: AutomatonImpl>(b: &mut Bencher, size: usize) {
+            let mut a = T::new(3, size, Rule::random(1, 3));
+            a.random_init();
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_512_recursive(b: &mut Bencher) {
+            bench_update::<RecursiveAutomaton>(b, 512);
+        }
+
+        #[bench]
+        fn bench_single_update_1024_recursive(b: &mut Bencher) {
+            bench_update::<RecursiveAutomaton>(b, 1024);
+        }
+
+        #[bench]
+        fn bench_single_update_2048_recursive(b: &mut Bencher) {
+            bench_update::<RecursiveAutomaton>(b, 2048);
+        }
+
+        #[bench]
+        fn bench_single_update_2048_base(b: &mut Bencher) {
+            bench_update::<Automaton>(b, 2048);
+        }
+
+        #[bench]
+        fn bench_single_update_2048_tiled_reference(b: &mut Bencher) {
+            bench_update::<TiledAutomaton>(b, 2048);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The height at which a cell of a [`Sandpile`] topples.
+pub const TOPPLING_HEIGHT: u32 = 4;
+
+/// The Abelian sandpile model of Bak, Tang and Wiesenfeld: every cell holds
+/// a number of grains, and the cells holding at least [`TOPPLING_HEIGHT`]
+/// grains topple, giving one grain to each of their four von Neumann
+/// neighbors. The grains toppling off the edges of the grid are lost, so
+/// that every pile eventually stabilizes.
+///
+/// All the unstable cells topple at once at every update. The grid of the
+/// automaton shows the heights capped at 3, so that the stable piles are
+/// rendered with 4 states; the heights themselves are returned by
+/// [`Sandpile::heights`].
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, Sandpile};
+///
+/// // Grains dropped at the center of the grid spread in a fractal pattern.
+/// let mut pile = Sandpile::with_size(33);
+/// pile.add_grains(16, 16, 1000);
+/// let steps = pile.stabilize(10_000);
+/// assert!(pile.is_stable() && steps > 0);
+/// assert!(pile.grid().iter().all(|&h| h < 4));
+/// // Grains can only be lost at the edges of the grid.
+/// assert!(pile.heights().iter().sum::<u32>() <= 1000);
+/// ```
+pub struct Sandpile {
+    size: usize,
+    heights: Vec<u32>,
+    noise: f64,
+    rng: StdRng,
+    topplings: u64,
+}
+
+impl Sandpile {
+    /// Makes an empty pile of `size^2` cells.
+    pub fn with_size(size: usize) -> Sandpile {
+        Sandpile {
+            size,
+            heights: vec![0; size * size],
+            noise: 0.,
+            rng: StdRng::from_entropy(),
+            topplings: 0,
+        }
+    }
+
+    /// Returns the number of grains of every cell, in row-major order.
+    pub fn heights(&self) -> &[u32] {
+        &self.heights
+    }
+
+    /// Returns a mutable reference to the number of grains of every cell.
+    pub fn heights_mut(&mut self) -> &mut [u32] {
+        &mut self.heights
+    }
+
+    /// Drops `grains` grains on the cell `(row, col)`.
+    pub fn add_grains(&mut self, row: usize, col: usize, grains: u32) {
+        self.heights[row * self.size + col] += grains;
+    }
+
+    /// Returns whether no cell holds enough grains to topple.
+    pub fn is_stable(&self) -> bool {
+        self.heights.iter().all(|&h| h < TOPPLING_HEIGHT)
+    }
+
+    /// Returns the number of topplings since the pile was made.
+    pub fn topplings(&self) -> u64 {
+        self.topplings
+    }
+
+    /// Updates the pile until it is stable, for at most `max_steps` updates,
+    /// and returns the number of updates.
+    pub fn stabilize(&mut self, max_steps: u32) -> u32 {
+        let mut steps = 0;
+        while steps < max_steps && !self.is_stable() {
+            self.update();
+            steps += 1;
+        }
+        steps
+    }
+}
+
+impl AutomatonImpl for Sandpile {
+    /// Makes an empty pile. The number of states and the rule are ignored:
+    /// the grid of a pile always has 4 states.
+    fn new(_states: u8, size: usize, _rule: Rule) -> Sandpile {
+        Sandpile::with_size(size)
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        TOPPLING_HEIGHT as u8
+    }
+
+    /// Initializes the pile from a pattern file, centered, whose states are
+    /// the heights of the cells.
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.heights.fill(pattern_spec.background as u32);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, self.states())? {
+            self.heights[i * size + j] = state as u32;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let size = self.size;
+        let mut next = self.heights.clone();
+        for (k, &h) in self.heights.iter().enumerate() {
+            if h < TOPPLING_HEIGHT {
+                continue;
+            }
+            self.topplings += 1;
+            next[k] -= TOPPLING_HEIGHT;
+            let (i, j) = (k / size, k % size);
+            if i > 0 {
+                next[k - size] += 1;
+            }
+            if i + 1 < size {
+                next[k + size] += 1;
+            }
+            if j > 0 {
+                next[k - 1] += 1;
+            }
+            if j + 1 < size {
+                next[k + 1] += 1;
+            }
+        }
+        if self.noise > 0. {
+            for h in next.iter_mut() {
+                if self.rng.gen_bool(self.noise) {
+                    *h += 1;
+                }
+            }
+        }
+        self.heights = next;
+    }
+
+    /// Sets random heights between 0 and 3, giving a random stable pile.
+    fn random_init(&mut self) {
+        let mut rng = self.rng.clone();
+        for h in self.heights.iter_mut() {
+            *h = rng.gen_range(0..TOPPLING_HEIGHT);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Sets the probability `p` that a grain is dropped on each cell at
+    /// every update, which drives the pile instead of randomizing its cells.
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The pile is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    /// The grains toppling off the edges are always lost, so the boundary
+    /// condition is ignored.
+    fn set_boundary(&mut self, _boundary: BoundaryCondition) {}
+
+    /// Sandpiles aren't driven by a rule table, so this always fails.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError("sandpiles have no rule"))
+    }
+
+    /// Returns the heights of the cells capped at 3.
+    fn grid(&self) -> Vec<u8> {
+        self.heights
+            .iter()
+            .map(|&h| h.min(TOPPLING_HEIGHT - 1) as u8)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TOPPLING_HEIGHT;
+    use crate::automaton::{AutomatonImpl, Sandpile};
+    use crate::rule::Rule;
+
+    #[test]
+    fn grains_topple_to_the_neighbors_and_off_the_edges() {
+        let mut pile = Sandpile::with_size(3);
+        pile.add_grains(1, 1, TOPPLING_HEIGHT);
+        pile.add_grains(0, 0, TOPPLING_HEIGHT + 1);
+        pile.update();
+        assert_eq!(pile.heights(), &[1, 2, 0, 2, 0, 1, 0, 1, 0]);
+        assert_eq!(pile.topplings(), 2);
+        assert!(pile.is_stable());
+        assert!(pile.set_rule(Rule::gol()).is_err());
+    }
+
+    #[test]
+    fn the_stable_pile_does_not_depend_on_the_toppling_order() {
+        // Toppling every cell at once must give the same pile as toppling
+        // the cells one at a time (the model is Abelian).
+        let mut pile = Sandpile::with_size(9);
+        pile.set_seed(3);
+        pile.random_init();
+        for k in 0..81 {
+            pile.heights_mut()[k] += (k % 5) as u32;
+        }
+        let mut heights = pile.heights().to_vec();
+        pile.stabilize(u32::MAX);
+        while let Some(k) = heights.iter().position(|&h| h >= TOPPLING_HEIGHT) {
+            heights[k] -= TOPPLING_HEIGHT;
+            let (i, j) = (k / 9, k % 9);
+            for (a, b) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (i, j) = (i as isize + a, j as isize + b);
+                if (0..9).contains(&i) && (0..9).contains(&j) {
+                    heights[i as usize * 9 + j as usize] += 1;
+                }
+            }
+        }
+        assert_eq!(pile.heights(), &heights[..]);
+    }
+
+    #[test]
+    fn noise_drops_grains() {
+        let mut pile = Sandpile::new(2, 16, Rule::gol());
+        pile.set_noise(1.);
+        pile.update();
+        assert_eq!(pile.heights(), &[1; 256][..]);
+        assert_eq!(pile.states(), 4);
+    }
+}
@@ -0,0 +1,162 @@
+use super::{
+    duplicate_array, AutomatonImpl, BoundaryCondition, Dimensions, Pattern, PatternError, Rotation,
+    StepIteratorBox,
+};
+use crate::rule::{Rule, RuleError};
+
+/// Rotates the states of a grid in place, replacing each state `s` by
+/// `(s + k) % states`. Unlike the palette rotation of the output functions,
+/// which only changes the colors assigned to the states, this changes the
+/// grid itself, so that two runs can be compared state by state after
+/// relabeling.
+///
+/// ```
+/// use rust_ca::automaton::rotate_states;
+///
+/// let mut grid = vec![0, 1, 2, 2];
+/// rotate_states(&mut grid, 3, 1);
+/// assert_eq!(grid, vec![1, 2, 0, 0]);
+/// ```
+pub fn rotate_states(grid: &mut [u8], states: u8, k: u8) {
+    let k = k % states;
+    if k > 0 {
+        for s in grid.iter_mut() {
+            *s = ((*s as u16 + k as u16) % states as u16) as u8;
+        }
+    }
+}
+
+/// An automaton wrapper exposing the grid of the inner CA with its states
+/// rotated by `k` (see [`rotate_states`]). The simulation itself is not
+/// affected.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl, StateRotatedAutomaton};
+/// use rust_ca::rule::Rule;
+///
+/// let inner = Automaton::new(3, 16, Rule::new(1, 3, vec![0; 19683]));
+/// let mut automaton = StateRotatedAutomaton::wrap(inner, 2);
+/// automaton.update();
+/// assert!(automaton.grid().iter().all(|&s| s == 2));
+/// ```
+pub struct StateRotatedAutomaton<A: AutomatonImpl> {
+    inner: A,
+    k: u8,
+}
+
+impl<A: AutomatonImpl> StateRotatedAutomaton<A> {
+    /// Wraps an automaton, rotating the states of its grid by `k`.
+    pub fn wrap(inner: A, k: u8) -> Self {
+        StateRotatedAutomaton { inner, k }
+    }
+
+    /// Returns a reference to the wrapped automaton.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped automaton.
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+}
+
+impl<A: AutomatonImpl> AutomatonImpl for StateRotatedAutomaton<A> {
+    /// Makes a new wrapped automaton without rotation. Use
+    /// [`StateRotatedAutomaton::wrap`] to choose the rotation.
+    fn new(states: u8, size: usize, rule: Rule) -> Self {
+        StateRotatedAutomaton::wrap(A::new(states, size, rule), 0)
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let (size, states, k) = (self.inner.size(), self.inner.states(), self.k);
+        Box::new(
+            self.inner
+                .skipped_iter(steps, skip, 1)
+                .map(move |mut grid| {
+                    rotate_states(&mut grid, states, k);
+                    duplicate_array(&grid, Dimensions::new(size, scale))
+                }),
+        )
+    }
+
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn states(&self) -> u8 {
+        self.inner.states()
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        self.inner.init_from_pattern(pattern_fname)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        self.inner.place_pattern(spec, x, y, rotation, flip)
+    }
+
+    fn update(&mut self) {
+        self.inner.update()
+    }
+
+    fn random_init(&mut self) {
+        self.inner.random_init()
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        self.inner.set_noise(p)
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.inner.set_threads(threads)
+    }
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.inner.set_boundary(boundary)
+    }
+
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        self.inner.set_rule(rule)
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        let mut grid = self.inner.grid();
+        rotate_states(&mut grid, self.inner.states(), self.k);
+        grid
+    }
+
+    fn state_counts(&self) -> Vec<usize> {
+        // The cells in state `s` of the inner grid are in state `s + k`.
+        let mut counts = self.inner.state_counts();
+        counts.rotate_right((self.k % self.inner.states()) as usize);
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rotate_states;
+
+    #[test]
+    fn rotations_compose_and_wrap() {
+        let mut grid: Vec<u8> = (0..4).collect();
+        rotate_states(&mut grid, 4, 3);
+        assert_eq!(grid, vec![3, 0, 1, 2]);
+        rotate_states(&mut grid, 4, 1);
+        assert_eq!(grid, vec![0, 1, 2, 3]);
+        rotate_states(&mut grid, 4, 255);
+        assert_eq!(grid, vec![3, 0, 1, 2]);
+    }
+}
@@ -1,28 +1,70 @@
-use super::{parse_pattern, AutomatonImpl, PatternError, HORIZON};
+use super::kernel::KernelLoop;
+use super::{
+    parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Kernel, Pattern, PatternError,
+    Rotation, StateCounts,
+};
 use crate::automaton::duplicate_array;
-use crate::rule::Rule;
-use rand::Rng;
+use crate::rule::{Rule, RuleError};
+use crate::stats::{self, TileStats};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
 
-/// The size of tiles in the tiled cellular automaton.
-pub const TILE_SIZE: usize = 257;
+/// The number of cells on the side of the tiles in the tiled cellular
+/// automaton (excluding the halo).
+pub const TILE_SIZE: usize = 256;
 
-pub type TiledGrid = Vec<[u8; TILE_SIZE * TILE_SIZE]>;
+/// The tiles of a grid, each stored with its halo.
+pub type TiledGrid = Vec<Vec<u8>>;
 
 /// A tiled version of the cellular automaton for more cache-friendly simulation
 /// on large grids.
+///
+/// Each tile stores its cells surrounded by a halo of ghost cells, copies of
+/// the neighboring tiles' cells. The halo is as wide as the horizon of the
+/// rule. The halos are exchanged before each update, after which every tile
+/// can be updated independently of the others. The halos on the edges of the
+/// grid are filled according to the boundary condition.
+///
+/// The tiles that didn't change in the last update, and whose neighboring
+/// tiles didn't either, can't change in the next one and are skipped. With
+/// several threads (see [`AutomatonImpl::set_threads`]), the workers take
+/// the active tiles from a shared queue, so that they stay busy when the
+/// activity is concentrated on a few regions of the grid, unless they are
+/// pinned to cores (see `TiledAutomaton::set_pinned`).
 pub struct TiledAutomaton {
     size: usize,
     n_tiles: usize,
+    coords: TileCoords,
     states: u8,
     flop: bool,
     grid1: TiledGrid,
     grid2: TiledGrid,
     rule: Rule,
+    kernel: Kernel,
+    noise: f64,
+    threads: usize,
+    /// The cores the workers of the parallel updates are pinned to, empty if
+    /// they aren't pinned.
+    cores: Vec<usize>,
+    /// The number of workers the tiles were last allocated by, 0 if they
+    /// weren't.
+    placed: usize,
+    /// Whether each tile changed in the last update.
+    active: Vec<bool>,
+    /// Whether the grid, the rule or the boundary changed since the last
+    /// update, so that all the tiles must be updated.
+    stale: bool,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+    counts: StateCounts,
 }
 
 impl TiledAutomaton {
     #[inline]
     fn grid_mut(&mut self) -> &mut TiledGrid {
+        self.counts.invalidate();
+        self.stale = true;
         if self.flop {
             &mut self.grid1
         } else {
@@ -40,146 +82,370 @@ impl TiledAutomaton {
     }
 
     #[inline]
-    fn update_tile(&mut self, tx: usize, ty: usize) {
-        let n_tiles = self.n_tiles;
-        let states = self.states as usize;
-        let grid = self.grid_mut()[tx * n_tiles + ty];
-        for i in HORIZON as usize..TILE_SIZE - HORIZON as usize {
-            for j in HORIZON as usize..TILE_SIZE - HORIZON as usize {
-                let is = i as isize;
-                let js = j as isize;
-                let mut ind: usize = 0;
-                let mut pw = 0;
-                for a in -HORIZON..=HORIZON {
-                    for b in -HORIZON..=HORIZON {
-                        let idx =
-                            ((is + a as isize) * (TILE_SIZE as isize) + (js + b as isize)) as usize;
-                        let current_val = grid[idx] as usize;
-                        let power = states.pow(pw);
-                        ind += power * current_val;
-                        pw += 1;
-                    }
+    fn current_grid(&self) -> &TiledGrid {
+        if self.flop {
+            &self.grid1
+        } else {
+            &self.grid2
+        }
+    }
+
+    /// Returns the tiles to update: the tiles next to a tile that changed in
+    /// the last update (or that changed themselves), or all the tiles if the
+    /// grid is stale.
+    fn pending_tiles(&self) -> Vec<bool> {
+        let n = self.n_tiles;
+        if self.stale {
+            return vec![true; n * n];
+        }
+        let mut pending = vec![false; n * n];
+        for tile in (0..n * n).filter(|&t| self.active[t]) {
+            let (tx, ty) = (tile / n, tile % n);
+            // The halos are at most a tile wide, and the neighbors are taken
+            // around the torus, which covers the other boundaries.
+            for dx in [n - 1, 0, 1] {
+                for dy in [n - 1, 0, 1] {
+                    pending[(tx + dx) % n * n + (ty + dy) % n] = true;
                 }
-                self.prev_grid()[tx * n_tiles + ty][i * TILE_SIZE + j] = self.rule[ind];
             }
         }
+        pending
     }
 
-    #[inline]
-    fn update_tile_boundaries(&mut self, tx: usize, ty: usize) {
-        let states = self.states as usize;
-        let n_tiles = self.n_tiles;
-        let prev_x = (tx + self.n_tiles - 1) % self.n_tiles;
-        let prev_y = (ty + self.n_tiles - 1) % self.n_tiles;
-        let lmain_tile = self.grid_mut()[tx * n_tiles + ty];
-        let lnorth_tile = self.grid_mut()[prev_x * n_tiles + ty];
-        let lwest_tile = self.grid_mut()[tx * n_tiles + prev_y];
-        let lnorthwest_tile = self.grid_mut()[prev_x * n_tiles + prev_y];
-
-        for i in 1..TILE_SIZE - 1 {
-            let is = i as isize;
-            let mut ind: usize = 0;
-            let mut pw = 0;
-            for a in -HORIZON..=HORIZON {
-                for b in -HORIZON..=HORIZON {
-                    let current_val = if b < 0 {
-                        let idx = ((is + a as isize) * (TILE_SIZE as isize)
-                            + (TILE_SIZE as isize - 1 + b as isize))
-                            as usize;
-                        lwest_tile[idx] as usize
-                    } else {
-                        let idx = ((is + a as isize) * (TILE_SIZE as isize) + b as isize) as usize;
-                        lmain_tile[idx] as usize
+    /// Copies the cells of the neighboring tiles into the halo of the
+    /// pending tiles of the current grid.
+    fn exchange_halos(&mut self, pending: &[bool]) {
+        let (coords, boundary) = (self.coords, self.boundary);
+        let size = coords.size();
+        let (halo, side) = (coords.halo, coords.side());
+        let n_cells = self.n_tiles * self.n_tiles;
+        let grid = if self.flop {
+            &mut self.grid1
+        } else {
+            &mut self.grid2
+        };
+        for tile in (0..n_cells).filter(|&t| pending[t]) {
+            for r in 0..side {
+                let in_halo = r < halo || r >= TILE_SIZE + halo;
+                for c in (0..side).filter(|&c| in_halo || c < halo || c >= TILE_SIZE + halo) {
+                    let offset = r * side + c;
+                    let (x, y) = coords.position(tile, offset);
+                    grid[tile][offset] = match (
+                        boundary.resolve(x, size),
+                        boundary.resolve(y, size),
+                        boundary,
+                    ) {
+                        (Some(i), Some(j), _) => {
+                            let (src, src_offset) = coords.owner(i, j);
+                            grid[src][src_offset]
+                        }
+                        (_, _, BoundaryCondition::Fixed(state)) => state,
+                        _ => unreachable!("only fixed boundaries leave the grid"),
                     };
-                    let power = states.pow(pw);
-                    ind += power * current_val;
-                    pw += 1;
                 }
             }
-            self.prev_grid()[tx * n_tiles + ty][i * TILE_SIZE] = self.rule[ind];
-            self.prev_grid()[tx * n_tiles + prev_y][i * TILE_SIZE + (TILE_SIZE - 1)] =
-                self.rule[ind];
-        }
-        for j in 1..TILE_SIZE - 1 {
-            let js = j as isize;
-            let mut ind: usize = 0;
-            let mut pw = 0;
-            for a in -HORIZON..=HORIZON {
-                for b in -HORIZON..=HORIZON {
-                    let current_val = if a < 0 {
-                        let idx = ((TILE_SIZE as isize - 1 + a as isize) * (TILE_SIZE as isize)
-                            + (js + b as isize)) as usize;
-                        lnorth_tile[idx] as usize
-                    } else {
-                        let idx = (a as isize * (TILE_SIZE as isize) + js + b as isize) as usize;
-                        lmain_tile[idx] as usize
-                    };
-                    let power = states.pow(pw);
-                    ind += power * current_val;
-                    pw += 1;
+        }
+    }
+
+    /// Sets random states on the cells of the freshly updated grid with
+    /// probability `noise`.
+    fn apply_noise(&mut self) {
+        let (noise, states, coords) = (self.noise, self.states, self.coords);
+        let mut rng = self.rng.clone();
+        for i in 0..coords.size() {
+            for j in 0..coords.size() {
+                if rng.gen_bool(noise) {
+                    let (tile, offset) = coords.owner(i, j);
+                    let state = rng.gen_range(0..states);
+                    let cell = &mut self.prev_grid()[tile][offset];
+                    let previous = std::mem::replace(cell, state);
+                    self.counts.change(previous, state);
+                }
+            }
+        }
+        self.rng = rng;
+    }
+
+    /// Pins the workers of the parallel updates (see
+    /// [`AutomatonImpl::set_threads`]) to cores, for multi-socket machines
+    /// (requires the `numa` feature). Each worker always updates the same
+    /// band of tiles from the same core, and the tiles are reallocated by
+    /// their workers before the first update, so that their memory is local
+    /// to the node of the core. Returns whether the workers are pinned, which
+    /// they can't be on some platforms.
+    #[cfg(feature = "numa")]
+    pub fn set_pinned(&mut self, pinned: bool) -> bool {
+        self.cores = match core_affinity::get_core_ids() {
+            Some(ids) if pinned => ids.into_iter().map(|core| core.id).collect(),
+            _ => vec![],
+        };
+        self.placed = 0;
+        !self.cores.is_empty()
+    }
+
+    /// Reallocates the tiles of both grids from the pinned workers, each
+    /// allocating the band of tiles it updates.
+    fn place_tiles(&mut self) {
+        let per_thread = self.grid1.len().div_ceil(self.threads);
+        let cores = &self.cores;
+        let bands = self
+            .grid1
+            .chunks_mut(per_thread)
+            .zip(self.grid2.chunks_mut(per_thread));
+        std::thread::scope(|scope| {
+            for (k, (band1, band2)) in bands.enumerate() {
+                scope.spawn(move || {
+                    pin_worker(cores, k);
+                    for tile in band1.iter_mut().chain(band2) {
+                        *tile = tile.to_vec();
+                    }
+                });
+            }
+        });
+        self.placed = self.threads;
+    }
+
+    /// Returns the kernel updating the tiles, chosen from the rule.
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    /// Returns the state of the logical cell `(i, j)`.
+    pub fn get(&self, i: usize, j: usize) -> u8 {
+        let (tile, offset) = self.coords.owner(i, j);
+        self.current_grid()[tile][offset]
+    }
+
+    /// Sets the state of the logical cell `(i, j)`.
+    pub fn set(&mut self, i: usize, j: usize, state: u8) {
+        let (tile, offset) = self.coords.owner(i, j);
+        self.grid_mut()[tile][offset] = state;
+    }
+
+    /// Returns the density and the activity of every tile, the fraction of
+    /// its cells changed by the last update (before the first update, the
+    /// previous grid is empty). The statistics are computed on the tiles in
+    /// place, without flattening the grid.
+    pub fn tile_stats(&self) -> TileStats {
+        let (current, previous) = if self.flop {
+            (&self.grid1, &self.grid2)
+        } else {
+            (&self.grid2, &self.grid1)
+        };
+        let (halo, side) = (self.coords.halo, self.coords.side());
+        let cells = (TILE_SIZE * TILE_SIZE) as f64;
+        let (mut densities, mut activities) = (vec![], vec![]);
+        for (cur, prev) in current.iter().zip(previous) {
+            let (mut alive, mut changed) = (0, 0);
+            for x in halo..TILE_SIZE + halo {
+                let row = x * side + halo..x * side + halo + TILE_SIZE;
+                for (&c, &p) in cur[row.clone()].iter().zip(&prev[row]) {
+                    alive += (c != 0) as usize;
+                    changed += (c != p) as usize;
                 }
             }
-            self.prev_grid()[tx * n_tiles + ty][j] = self.rule[ind];
-            self.prev_grid()[prev_x * n_tiles + ty][(TILE_SIZE - 1) * TILE_SIZE + j] =
-                self.rule[ind];
-        }
-
-        let mut ind: usize = 0;
-        let mut pw = 0;
-        for a in -HORIZON..=HORIZON {
-            for b in -HORIZON..=HORIZON {
-                let current_val = if (a < 0) & (b < 0) {
-                    let idx = ((TILE_SIZE as isize - 1 + a as isize) * (TILE_SIZE as isize)
-                        + (TILE_SIZE as isize - 1 + b as isize))
-                        as usize;
-                    lnorthwest_tile[idx] as usize
-                } else if a < 0 {
-                    let idx = ((TILE_SIZE as isize - 1 + a as isize) * (TILE_SIZE as isize)
-                        + b as isize) as usize;
-                    lnorth_tile[idx] as usize
-                } else if b < 0 {
-                    let idx = (a as isize * (TILE_SIZE as isize)
-                        + (TILE_SIZE as isize - 1 + b as isize))
-                        as usize;
-                    lwest_tile[idx] as usize
-                } else {
-                    let idx = (a as isize * (TILE_SIZE as isize) + b as isize) as usize;
-                    lmain_tile[idx] as usize
-                };
-                let power = states.pow(pw);
-                ind += power * current_val;
-                pw += 1;
+            densities.push(alive as f64 / cells);
+            activities.push(changed as f64 / cells);
+        }
+        TileStats {
+            tiles: self.n_tiles,
+            densities,
+            activities,
+        }
+    }
+
+    /// Flattens the tiles of the current grid into `out`, reusing its
+    /// allocation.
+    fn flatten_into(&self, out: &mut Vec<u8>) {
+        let grid = self.current_grid();
+        out.clear();
+        for i in 0..self.coords.size() {
+            for ty in 0..self.n_tiles {
+                let (tile, start) = self.coords.owner(i, ty * TILE_SIZE);
+                out.extend_from_slice(&grid[tile][start..start + TILE_SIZE]);
+            }
+        }
+    }
+}
+
+/// Pins the worker `k` of a parallel update to its core, if the workers are
+/// pinned.
+#[cfg(feature = "numa")]
+fn pin_worker(cores: &[usize], k: usize) {
+    if !cores.is_empty() {
+        core_affinity::set_for_current(core_affinity::CoreId {
+            id: cores[k % cores.len()],
+        });
+    }
+}
+
+#[cfg(not(feature = "numa"))]
+fn pin_worker(_cores: &[usize], _k: usize) {}
+
+/// Updates the cells of a tile, reading the current tile (including the halo)
+/// and writing the next one. The state changes are added to `deltas`, and
+/// returns whether a cell changed.
+#[inline]
+fn update_tile(
+    current: &[u8],
+    next: &mut [u8],
+    halo: usize,
+    states: usize,
+    (rule, kernel): (&Rule, Kernel),
+    deltas: &mut [isize],
+) -> bool {
+    let mut changed = false;
+    let update = TileUpdate {
+        tiles: (current, next),
+        halo,
+        deltas,
+        changed: &mut changed,
+    };
+    kernel.run(rule, TILE_SIZE + 2 * halo, states, update);
+    changed
+}
+
+/// The update of the cells of a tile with a halo of width `halo`, whose state
+/// changes are counted in `deltas`.
+struct TileUpdate<'a> {
+    tiles: (&'a [u8], &'a mut [u8]),
+    halo: usize,
+    deltas: &'a mut [isize],
+    changed: &'a mut bool,
+}
+
+impl KernelLoop for TileUpdate<'_> {
+    #[inline(always)]
+    fn run<F: FnMut(&[u8], usize) -> u8>(self, mut next_state: F) {
+        let ((current, next), halo) = (self.tiles, self.halo);
+        let side = TILE_SIZE + 2 * halo;
+        let mut changed = false;
+        for x in halo..TILE_SIZE + halo {
+            for y in halo..TILE_SIZE + halo {
+                let center = x * side + y;
+                let state = next_state(current, center);
+                next[center] = state;
+                changed |= state != current[center];
+                self.deltas[current[center] as usize] -= 1;
+                self.deltas[state as usize] += 1;
             }
         }
-        self.prev_grid()[tx * n_tiles + ty][0] = self.rule[ind];
-        self.prev_grid()[prev_x * n_tiles + ty][(TILE_SIZE - 1) * TILE_SIZE] = self.rule[ind];
-        self.prev_grid()[tx * n_tiles + prev_y][TILE_SIZE - 1] = self.rule[ind];
-        self.prev_grid()[prev_x * n_tiles + prev_y][(TILE_SIZE - 1) * TILE_SIZE + TILE_SIZE - 1] =
-            self.rule[ind];
+        *self.changed = changed;
+    }
+}
+
+/// Maps the logical coordinates of a tiled grid to the tiles storing them.
+///
+/// Tiles are stored with a halo of `halo` ghost cells on each side, so a
+/// tile is a square of side `TILE_SIZE + 2 * halo`. Each logical cell is
+/// owned by one tile, and appears in the halos of the neighboring tiles.
+///
+/// ```
+/// use rust_ca::automaton::{TileCoords, TILE_SIZE};
+///
+/// let coords = TileCoords::new(2, 1);
+/// let side = TILE_SIZE + 2;
+/// assert_eq!(coords.size(), 2 * TILE_SIZE);
+/// assert_eq!(coords.owner(3, TILE_SIZE + 1), (1, 4 * side + 2));
+/// // The halo on the left of the second tile is the last column of the first.
+/// assert_eq!(coords.logical(1, 4 * side), (3, TILE_SIZE - 1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileCoords {
+    n_tiles: usize,
+    halo: usize,
+}
+
+impl TileCoords {
+    /// Creates the mapping for a grid of `n_tiles` by `n_tiles` tiles with
+    /// halos of width `halo`.
+    pub fn new(n_tiles: usize, halo: usize) -> TileCoords {
+        TileCoords { n_tiles, halo }
+    }
+
+    /// Returns the side of the logical grid.
+    pub fn size(&self) -> usize {
+        self.n_tiles * TILE_SIZE
+    }
+
+    /// Returns the side of a stored tile, including the halo.
+    pub fn side(&self) -> usize {
+        TILE_SIZE + 2 * self.halo
+    }
+
+    /// Returns the tile owning the logical cell `(i, j)` and the offset of
+    /// the cell in that tile.
+    #[inline]
+    pub fn owner(&self, i: usize, j: usize) -> (usize, usize) {
+        let (tx, x) = (i / TILE_SIZE, i % TILE_SIZE);
+        let (ty, y) = (j / TILE_SIZE, j % TILE_SIZE);
+        (
+            tx * self.n_tiles + ty,
+            (x + self.halo) * self.side() + y + self.halo,
+        )
+    }
+
+    /// Returns the logical coordinates of a cell of a tile, including the
+    /// ghost cells of the halo, which wrap around the torus on the tiles at
+    /// the edges of the grid.
+    pub fn logical(&self, tile: usize, offset: usize) -> (usize, usize) {
+        let (x, y) = self.position(tile, offset);
+        let size = self.size() as isize;
+        (x.rem_euclid(size) as usize, y.rem_euclid(size) as usize)
+    }
+
+    /// Returns the position of a cell of a tile in the logical grid. Unlike
+    /// [`TileCoords::logical`], the ghost cells of the halos at the edges of
+    /// the grid are outside the grid.
+    pub fn position(&self, tile: usize, offset: usize) -> (isize, isize) {
+        let (tx, ty) = (tile / self.n_tiles, tile % self.n_tiles);
+        let (x, y) = (offset / self.side(), offset % self.side());
+        let halo = self.halo as isize;
+        (
+            (tx * TILE_SIZE + x) as isize - halo,
+            (ty * TILE_SIZE + y) as isize - halo,
+        )
     }
 }
 
 impl AutomatonImpl for TiledAutomaton {
     fn new(states: u8, size: usize, rule: Rule) -> TiledAutomaton {
-        let s = size / (TILE_SIZE - 1);
+        let s = size / TILE_SIZE;
+        let halo = rule.horizon as usize;
+        assert!(halo <= TILE_SIZE, "The horizon must fit in a tile");
+        let coords = TileCoords::new(s, halo);
+        let tile = vec![0; coords.side() * coords.side()];
         TiledAutomaton {
             states,
             n_tiles: s,
+            coords,
             size,
             flop: true,
+            kernel: Kernel::for_rule(&rule),
             rule,
-            grid1: vec![[0; TILE_SIZE * TILE_SIZE]; s * s],
-            grid2: vec![[0; TILE_SIZE * TILE_SIZE]; s * s],
+            grid1: vec![tile.clone(); s * s],
+            grid2: vec![tile; s * s],
+            noise: 0.,
+            threads: 1,
+            cores: vec![],
+            placed: 0,
+            active: vec![true; s * s],
+            stale: true,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+            counts: StateCounts::default(),
         }
     }
 
     #[inline]
     fn grid(&self) -> Vec<u8> {
-        duplicate_array_tiled(
-            if self.flop { &self.grid1 } else { &self.grid2 },
-            self.size,
-            1,
-        )
+        let mut out = Vec::with_capacity(self.size * self.size);
+        self.flatten_into(&mut out);
+        out
+    }
+
+    fn grid_into(&self, buffer: &mut Vec<u8>) {
+        self.flatten_into(buffer);
     }
 
     fn skipped_iter(
@@ -188,16 +454,14 @@ impl AutomatonImpl for TiledAutomaton {
         skip: u32,
         scale: u16,
     ) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
-        let size = self.size;
-        Box::new(
-            TiledAutomatonIterator {
-                autom: self,
-                skip,
-                steps: Some(steps),
-                ct: 0,
-            }
-            .map(move |grid| duplicate_array(&grid, size, scale)),
-        )
+        Box::new(TiledAutomatonIterator {
+            buffer: Vec::with_capacity(self.size * self.size),
+            autom: self,
+            skip,
+            steps: Some(steps),
+            ct: 0,
+            scale,
+        })
     }
 
     fn size(&self) -> usize {
@@ -210,45 +474,125 @@ impl AutomatonImpl for TiledAutomaton {
 
     fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
         let pattern_spec = parse_pattern(pattern_fname)?;
-        assert!(pattern_spec.states <= self.states);
-        assert!(pattern_spec.background < self.states);
-        for i in self.grid_mut().iter_mut() {
-            for j in i.iter_mut() {
-                *j = pattern_spec.background;
-            }
+        let (x, y) = pattern_spec.centered(self.size());
+        for tile in self.grid_mut().iter_mut() {
+            tile.fill(pattern_spec.background);
         }
-        let lines = pattern_spec.pattern.len();
-        let cols = pattern_spec.pattern.iter().map(|x| x.len()).max().unwrap();
-        let n_tiles = self.n_tiles;
-        for i in 0..lines {
-            let lin = &pattern_spec.pattern[i];
-            for (j, elem) in lin.iter().enumerate() {
-                let idx_x = i + (self.size / 2) - lines / 2;
-                let idx_y = j - cols / 2 + self.size / 2;
-                let tx = idx_x / TILE_SIZE;
-                let ty = idx_y / TILE_SIZE;
-                let x = idx_x % TILE_SIZE;
-                let y = idx_y % TILE_SIZE;
-                self.grid_mut()[tx * n_tiles + ty][x * TILE_SIZE + y] = *elem;
-            }
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), self.size(), self.states)? {
+            self.set(i, j, state);
         }
         Ok(())
     }
 
     #[inline]
     fn update(&mut self) {
-        let bounds_high = self.n_tiles;
-        //Main update
-        for tx in 0..bounds_high {
-            for ty in 0..bounds_high {
-                self.update_tile(tx, ty);
-            }
+        if !self.counts.is_valid() {
+            self.counts
+                .set(stats::state_counts(&self.grid(), self.states));
         }
-        //Bounds update
-        for tx in 0..bounds_high {
-            for ty in 0..bounds_high {
-                self.update_tile_boundaries(tx, ty);
+        let pending = self.pending_tiles();
+        self.exchange_halos(&pending);
+        let parallel = self.threads > 1 && self.grid1.len() > 1;
+        if parallel && !self.cores.is_empty() && self.placed != self.threads {
+            self.place_tiles();
+        }
+        let (halo, states, threads) = (self.coords.halo, self.states as usize, self.threads);
+        let (cores, active) = (&self.cores, &mut self.active);
+        let rule = (&self.rule, self.kernel);
+        let (current, next) = if self.flop {
+            (&self.grid1, &mut self.grid2)
+        } else {
+            (&self.grid2, &mut self.grid1)
+        };
+        // The tiles skipped by an update didn't change in the previous one,
+        // so both grids already hold the same cells for them.
+        let deltas: Vec<Vec<isize>> = if parallel && !cores.is_empty() {
+            // The tiles only read their own halo, so they can be updated
+            // concurrently once the halos have been exchanged. The pinned
+            // workers keep the bands of tiles they allocated.
+            let per_thread = current.len().div_ceil(threads);
+            let bands = current
+                .chunks(per_thread)
+                .zip(next.chunks_mut(per_thread))
+                .zip(active.chunks_mut(per_thread))
+                .zip(pending.chunks(per_thread));
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = bands
+                    .enumerate()
+                    .map(|(k, (((cur, nxt), act), pend))| {
+                        scope.spawn(move || {
+                            pin_worker(cores, k);
+                            let mut deltas = vec![0; states];
+                            let tiles = cur.iter().zip(nxt.iter_mut()).zip(act.iter_mut());
+                            for (((c, n), a), _) in tiles.zip(pend).filter(|(_, &p)| p) {
+                                *a = update_tile(c, n, halo, states, rule, &mut deltas);
+                            }
+                            deltas
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            })
+        } else {
+            let tiles = current
+                .iter()
+                .zip(next.iter_mut())
+                .zip(active.iter_mut())
+                .zip(&pending)
+                .filter(|(_, &p)| p)
+                .map(|(tile, _)| tile);
+            if parallel {
+                // The other workers take the active tiles one at a time from a
+                // shared queue, so that the tiles left by a slow worker are
+                // updated by the ones done early.
+                let queue = Mutex::new(tiles);
+                std::thread::scope(|scope| {
+                    let handles: Vec<_> = (0..threads.min(current.len()))
+                        .map(|_| {
+                            let queue = &queue;
+                            scope.spawn(move || {
+                                let mut deltas = vec![0; states];
+                                loop {
+                                    // The lock is released before the update.
+                                    let tile = queue.lock().unwrap().next();
+                                    match tile {
+                                        Some(((c, n), a)) => {
+                                            *a = update_tile(c, n, halo, states, rule, &mut deltas)
+                                        }
+                                        None => break deltas,
+                                    }
+                                }
+                            })
+                        })
+                        .collect();
+                    handles.into_iter().map(|h| h.join().unwrap()).collect()
+                })
+            } else {
+                let mut deltas = vec![0; states];
+                for ((c, n), a) in tiles {
+                    *a = update_tile(c, n, halo, states, rule, &mut deltas);
+                }
+                vec![deltas]
             }
+        };
+        for d in &deltas {
+            self.counts.apply(d);
+        }
+        // The noise changes the cells outside of the update of their tiles.
+        self.stale = self.noise > 0.;
+        if self.noise > 0. {
+            self.apply_noise();
         }
         // Flip buffer choice
         self.flop = !self.flop;
@@ -256,12 +600,46 @@ impl AutomatonImpl for TiledAutomaton {
 
     fn random_init(&mut self) {
         let states = self.states;
-        let mut rng = rand::thread_rng();
-        for i in self.grid_mut().iter_mut() {
-            for j in i.iter_mut() {
-                *j = rng.gen_range(0..states);
+        let mut rng = self.rng.clone();
+        for i in 0..self.coords.size() {
+            for j in 0..self.coords.size() {
+                let s = rng.gen_range(0..states);
+                self.set(i, j, s);
             }
         }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+        self.stale = true;
+    }
+
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        rule.verify_compatible(&self.rule)?;
+        self.rule = rule;
+        self.stale = true;
+        Ok(())
+    }
+
+    fn state_counts(&self) -> Vec<usize> {
+        match self.counts.get() {
+            Some(counts) => counts.to_vec(),
+            None => stats::state_counts(&self.grid(), self.states),
+        }
     }
 }
 
@@ -270,65 +648,40 @@ pub struct TiledAutomatonIterator<'a> {
     skip: u32,
     steps: Option<u32>,
     ct: u32,
+    scale: u16,
+    /// The flattened grid, reused across frames.
+    buffer: Vec<u8>,
 }
 
 impl Iterator for TiledAutomatonIterator<'_> {
     type Item = Vec<u8>;
     fn next(&mut self) -> Option<Vec<u8>> {
         match self.steps {
-            Some(v) => {
-                if self.ct >= v {
-                    None
-                } else {
-                    let ret = self.autom.grid().to_vec();
-                    for _ in 0..self.skip {
-                        self.autom.update();
-                        self.ct += 1;
-                    }
-                    Some(ret)
-                }
-            }
-            None => {
-                let ret = self.autom.grid().to_vec();
+            Some(v) if self.ct >= v => None,
+            _ => {
+                self.autom.flatten_into(&mut self.buffer);
                 for _ in 0..self.skip {
                     self.autom.update();
                     self.ct += 1;
                 }
-                Some(ret)
+                Some(duplicate_array(
+                    &self.buffer,
+                    Dimensions::new(self.autom.size, self.scale),
+                ))
             }
         }
     }
 }
 
-#[inline]
-fn duplicate_array_tiled(s: &[[u8; TILE_SIZE * TILE_SIZE]], size: usize, scale: u16) -> Vec<u8> {
-    let scaled_size = size * scale as usize;
-    let n_tiles = size / TILE_SIZE;
-    let mut out = Vec::with_capacity(scaled_size * scaled_size);
-    for a in 0..scaled_size {
-        for b in 0..scaled_size {
-            let idx_i = a / scale as usize;
-            let idx_j = b / scale as usize;
-            let tx = idx_i / (TILE_SIZE - 1);
-            let ty = idx_j / (TILE_SIZE - 1);
-            let idx_x = idx_i % (TILE_SIZE - 1);
-            let idx_y = idx_j % (TILE_SIZE - 1);
-            let item = s[tx * n_tiles + ty][idx_x * TILE_SIZE + idx_y];
-            out.push(item);
-        }
-    }
-    out
-}
-
 #[cfg(test)]
 mod tests {
     use crate::automaton::AutomatonImpl;
-    use crate::automaton::TiledAutomaton;
-    use crate::rule::Rule;
-    use test::Bencher;
+    use crate::automaton::{Automaton, BoundaryCondition, TileCoords, TiledAutomaton, TILE_SIZE};
+    use crate::rule::{Neighborhood, Rule, SamplingMode::Dirichlet};
+    use std::hint::black_box;
 
     fn get_random_tiled_auto(size: usize, states: u8) -> TiledAutomaton {
-        let states = test::black_box(states);
+        let states = black_box(states);
         let rule = Rule::random(1, states);
         let mut a = TiledAutomaton::new(states, size, rule);
         a.random_init();
@@ -343,21 +696,291 @@ mod tests {
         assert_ne!(b1, a.flop);
     }
 
-    #[bench]
-    fn bench_single_update_512_tiled(b: &mut Bencher) {
-        let mut a = test::black_box(get_random_tiled_auto(512, 3));
-        b.iter(|| a.update());
+    #[test]
+    fn frames_are_flattened_row_major() {
+        let mut a = TiledAutomaton::new(2, 512, Rule::new(1, 2, vec![0; 512]));
+        // Cell (300, 10) is in the second row of tiles.
+        a.set(300, 10, 1);
+        let grid = a.grid();
+        assert_eq!(grid.len(), 512 * 512);
+        assert_eq!(grid.iter().position(|&c| c == 1), Some(300 * 512 + 10));
+        let frames: Vec<Vec<u8>> = a.skipped_iter(2, 1, 2).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0][600 * 1024 + 20], 1);
+        assert_eq!(frames[1], vec![0; 1024 * 1024]);
+    }
+
+    #[test]
+    fn patterns_are_centered_on_multi_tile_grids() {
+        std::fs::write("test_tiled_pattern.ca", "N=2\nBG=0\n#\n010\n111\n#\n").unwrap();
+        for &size in &[512, 1024] {
+            let mut a = TiledAutomaton::new(2, size, Rule::new(1, 2, vec![0; 512]));
+            a.init_from_pattern("test_tiled_pattern.ca").unwrap();
+            let grid = a.grid();
+            let live: Vec<usize> = (0..size * size).filter(|&k| grid[k] == 1).collect();
+            let (c, r) = (size / 2, size / 2 - 1);
+            assert_eq!(
+                live,
+                vec![
+                    r * size + c,
+                    (r + 1) * size + c - 1,
+                    (r + 1) * size + c,
+                    (r + 1) * size + c + 1
+                ]
+            );
+        }
+        std::fs::remove_file("test_tiled_pattern.ca").unwrap();
+    }
+
+    #[test]
+    fn halo_cells_map_to_neighboring_tiles() {
+        let coords = TileCoords::new(2, 1);
+        let side = coords.side();
+        // The top-left corner of the halo of the first tile wraps around.
+        assert_eq!(coords.logical(0, 0), (511, 511));
+        // The bottom halo of the last tile is the first row of the grid.
+        assert_eq!(coords.logical(3, (side - 1) * side + 1), (0, TILE_SIZE));
+        for (i, j) in [(0, 0), (255, 256), (300, 511)] {
+            let (tile, offset) = coords.owner(i, j);
+            assert_eq!(coords.logical(tile, offset), (i, j));
+        }
+    }
+
+    #[test]
+    fn get_and_set_use_logical_coordinates() {
+        let mut a = TiledAutomaton::new(2, 512, Rule::new(1, 2, vec![0; 512]));
+        a.set(256, 511, 1);
+        assert_eq!(a.get(256, 511), 1);
+        assert_eq!(a.grid()[256 * 512 + 511], 1);
+        assert_eq!(a.grid().iter().filter(|&&c| c == 1).count(), 1);
     }
 
-    #[bench]
-    fn bench_single_update_1024_tiled(b: &mut Bencher) {
-        let mut a = test::black_box(get_random_tiled_auto(1024, 3));
-        b.iter(|| a.update());
+    #[test]
+    fn matches_the_base_automaton() {
+        let rule = Rule::random(1, 3);
+        let mut a = TiledAutomaton::new(3, 512, rule.clone());
+        a.set_noise(0.1);
+        a.random_init();
+        a.update();
+        a.set_noise(0.);
+        let mut b = Automaton::new(3, 512, rule);
+        b.grid_mut().copy_from_slice(&a.grid());
+        for _ in 0..3 {
+            a.update();
+            b.update();
+        }
+        assert_eq!(a.grid(), b.grid());
     }
 
-    #[bench]
-    fn bench_single_update_2048_tiled(b: &mut Bencher) {
-        let mut a = test::black_box(get_random_tiled_auto(2048, 4));
-        b.iter(|| a.update());
+    #[test]
+    fn hexagonal_rules_match_the_base_automaton() {
+        let mut rng = rand::thread_rng();
+        let rule = Rule::random_with_neighborhood(1, 3, Neighborhood::Hex, Dirichlet, &mut rng);
+        let mut a = TiledAutomaton::new(3, 512, rule.clone());
+        a.random_init();
+        let mut b = Automaton::new(3, 512, rule);
+        b.grid_mut().copy_from_slice(&a.grid());
+        for _ in 0..3 {
+            a.update();
+            b.update();
+        }
+        assert_eq!(a.grid(), b.grid());
+    }
+
+    #[test]
+    fn parallel_update_matches_sequential() {
+        let mut a = get_random_tiled_auto(1024, 3);
+        let mut b = TiledAutomaton::new(3, 1024, a.rule.clone());
+        b.grid_mut().clone_from(a.grid_mut());
+        b.set_threads(3);
+        for step in 0..4 {
+            if step == 2 {
+                // More workers than tiles.
+                b.set_threads(32);
+            }
+            a.update();
+            b.update();
+        }
+        assert_eq!(a.grid(), b.grid());
+        assert_eq!(a.state_counts(), b.state_counts());
+    }
+
+    #[cfg(feature = "numa")]
+    #[test]
+    fn pinned_update_matches_sequential() {
+        let mut a = get_random_tiled_auto(1024, 3);
+        let mut b = TiledAutomaton::new(3, 1024, a.rule.clone());
+        b.grid_mut().clone_from(a.grid_mut());
+        b.set_threads(3);
+        if !b.set_pinned(true) {
+            return;
+        }
+        for step in 0..4 {
+            if step == 2 {
+                b.set_threads(5);
+            }
+            a.update();
+            b.update();
+        }
+        assert_eq!(b.placed, 5);
+        assert_eq!(a.grid(), b.grid());
+        assert!(!b.set_pinned(false));
+    }
+
+    #[test]
+    fn quiescent_tiles_are_skipped() {
+        let mut a = TiledAutomaton::new(2, 1024, Rule::gol());
+        let mut b = Automaton::new(2, 1024, Rule::gol());
+        let mut set = |cells: &[(usize, usize)]| {
+            for &(i, j) in cells {
+                a.set(i, j, 1);
+                b.grid_mut()[i * 1024 + j] = 1;
+            }
+        };
+        // A glider crossing the corner of four tiles and a blinker.
+        set(&[(250, 251), (251, 252), (252, 250), (252, 251), (252, 252)]);
+        set(&[(600, 600), (600, 601), (600, 602)]);
+        a.set_threads(3);
+        for step in 0..30 {
+            if step == 20 {
+                // The cells set between the updates wake their tile up.
+                for j in 100..103 {
+                    a.set(900, j, 1);
+                    b.grid_mut()[900 * 1024 + j] = 1;
+                }
+            }
+            a.update();
+            b.update();
+            assert!(a.active.iter().filter(|&&t| t).count() <= 6);
+            assert_eq!(a.grid(), b.grid());
+        }
+        // The noise changes the cells of the quiescent tiles too.
+        a.set_noise(0.01);
+        a.update();
+        a.set_noise(0.);
+        b.grid_mut().copy_from_slice(&a.grid());
+        for _ in 0..3 {
+            a.update();
+            b.update();
+        }
+        assert_eq!(a.grid(), b.grid());
+        assert_eq!(a.state_counts(), crate::stats::state_counts(&a.grid(), 2));
+    }
+
+    #[test]
+    fn state_counts_follow_the_updates() {
+        let mut a = get_random_tiled_auto(512, 3);
+        a.set_noise(0.05);
+        for step in 0..4 {
+            if step == 2 {
+                a.set_threads(3);
+                a.set(0, 0, 2);
+            }
+            a.update();
+            assert_eq!(a.state_counts(), crate::stats::state_counts(&a.grid(), 3));
+        }
+    }
+
+    #[test]
+    fn tile_stats_match_the_flattened_grid() {
+        let mut a = get_random_tiled_auto(512, 3);
+        a.update();
+        let previous = a.grid();
+        a.update();
+        let (grid, tiles) = (a.grid(), a.tile_stats());
+        assert_eq!(tiles.tiles, 2);
+        for (t, (&density, &activity)) in tiles.densities.iter().zip(&tiles.activities).enumerate()
+        {
+            let (tx, ty) = (t / 2, t % 2);
+            let cells: Vec<usize> = (0..TILE_SIZE * TILE_SIZE)
+                .map(|c| (tx * TILE_SIZE + c / TILE_SIZE) * 512 + ty * TILE_SIZE + c % TILE_SIZE)
+                .collect();
+            let alive = cells.iter().filter(|&&c| grid[c] != 0).count();
+            let changed = cells.iter().filter(|&&c| grid[c] != previous[c]).count();
+            assert_eq!(density, alive as f64 / cells.len() as f64);
+            assert_eq!(activity, changed as f64 / cells.len() as f64);
+        }
+    }
+
+    #[test]
+    fn boundaries_match_the_base_automaton() {
+        for &boundary in &[BoundaryCondition::Fixed(1), BoundaryCondition::Reflective] {
+            let rule = Rule::random(2, 2);
+            let mut a = TiledAutomaton::new(2, 512, rule.clone());
+            a.random_init();
+            a.set_boundary(boundary);
+            let mut b = Automaton::new(2, 512, rule);
+            b.grid_mut().copy_from_slice(&a.grid());
+            b.set_boundary(boundary);
+            for _ in 0..2 {
+                a.update();
+                b.update();
+            }
+            assert_eq!(a.grid(), b.grid());
+        }
+    }
+
+    #[test]
+    fn seeded_noisy_runs_are_reproducible() {
+        fn run<T: AutomatonImpl>(mut a: T) -> T {
+            a.random_init_seeded(5);
+            a.set_noise(0.1);
+            for _ in 0..3 {
+                a.update();
+            }
+            a
+        }
+        let rule = Rule::random(1, 3);
+        let a = run(TiledAutomaton::new(3, 256, rule.clone()));
+        let mut b = run(Automaton::new(3, 256, rule));
+        assert_eq!(a.grid(), b.grid());
+        b.random_init_seeded(6);
+        assert_ne!(a.grid(), b.grid());
+    }
+
+    #[test]
+    fn wide_halos_follow_the_rule_horizon() {
+        let rule = Rule::random(2, 2);
+        let mut a = TiledAutomaton::new(2, 512, rule.clone());
+        a.random_init();
+        let mut expected = a.grid();
+        for _ in 0..3 {
+            a.update();
+            expected = rule.apply_on_torus(&expected, 512);
+        }
+        assert_eq!(a.grid(), expected);
+    }
+
+    #[cfg(feature = "bench")]
+    mod benches {
+        use super::get_random_tiled_auto;
+        use crate::automaton::AutomatonImpl;
+        use std::hint::black_box;
+        use test::Bencher;
+
+        #[bench]
+        fn bench_single_update_512_tiled(b: &mut Bencher) {
+            let mut a = black_box(get_random_tiled_auto(512, 3));
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_1024_tiled(b: &mut Bencher) {
+            let mut a = black_box(get_random_tiled_auto(1024, 3));
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_2048_tiled_parallel(b: &mut Bencher) {
+            let mut a = black_box(get_random_tiled_auto(2048, 3));
+            a.set_threads(4);
+            b.iter(|| a.update());
+        }
+
+        #[bench]
+        fn bench_single_update_2048_tiled(b: &mut Bencher) {
+            let mut a = black_box(get_random_tiled_auto(2048, 4));
+            b.iter(|| a.update());
+        }
     }
 }
@@ -0,0 +1,251 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, RuleError, TotalisticRule};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A cellular automaton updated from the sum table of its
+/// [`TotalisticRule`] instead of a full rule table. The sums of the
+/// neighborhoods are computed with sliding windows along the rows and then
+/// the columns of the grid, so the cost of an update doesn't depend on the
+/// horizon, and the rules whose full tables wouldn't fit in memory can be
+/// run.
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, TotalisticAutomaton};
+/// use rust_ca::rule::TotalisticRule;
+///
+/// // 4 states on the Moore neighborhood of horizon 5: a full table would
+/// // have 4^121 entries.
+/// let rule = TotalisticRule::random(5, 4, true);
+/// let mut automaton = TotalisticAutomaton::with_rule(64, rule);
+/// automaton.random_init_seeded(0);
+/// automaton.update();
+/// assert!(automaton.grid().iter().all(|&s| s < 4));
+/// ```
+pub struct TotalisticAutomaton {
+    size: usize,
+    rule: TotalisticRule,
+    cells: Vec<u8>,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl TotalisticAutomaton {
+    /// Makes an automaton of `size^2` cells in state 0 with the given rule.
+    pub fn with_rule(size: usize, rule: TotalisticRule) -> TotalisticAutomaton {
+        TotalisticAutomaton {
+            size,
+            rule,
+            cells: vec![0; size * size],
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the rule of the automaton.
+    pub fn rule(&self) -> &TotalisticRule {
+        &self.rule
+    }
+
+    /// Returns a mutable reference to the cells of the grid.
+    pub fn grid_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+}
+
+impl AutomatonImpl for TotalisticAutomaton {
+    /// Makes an automaton with the outer totalistic version of `rule` (see
+    /// [`TotalisticRule::from_rule`]). Use [`TotalisticAutomaton::with_rule`]
+    /// for the rules whose full tables wouldn't fit in memory.
+    ///
+    /// # Panics
+    /// Panics if `rule` isn't an outer totalistic rule of the Moore
+    /// neighborhood with `states` states.
+    fn new(states: u8, size: usize, rule: Rule) -> TotalisticAutomaton {
+        match TotalisticRule::from_rule(&rule, true) {
+            Some(rule) if rule.states == states => TotalisticAutomaton::with_rule(size, rule),
+            _ => panic!("Totalistic automata need an outer totalistic rule"),
+        }
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        self.rule.states
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, self.rule.states)? {
+            self.cells[i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let (size, h) = (self.size, self.rule.horizon as usize);
+        let w = 2 * h + 1;
+        // Sums of the `w` cells of the rows centered on every column, for the
+        // rows of the grid and the `h` rows beyond each of its edges.
+        let mut rows = vec![0; (size + 2 * h) * size];
+        for (i, sums) in rows.chunks_exact_mut(size).enumerate() {
+            let cell = |j: usize| {
+                let at = (i as isize - h as isize, j as isize - h as isize);
+                self.boundary.cell(&self.cells, size, at) as usize
+            };
+            let mut sum: usize = (0..w).map(cell).sum();
+            for (j, row_sum) in sums.iter_mut().enumerate() {
+                *row_sum = sum;
+                if j + 1 < size {
+                    sum = sum + cell(j + w) - cell(j);
+                }
+            }
+        }
+        let mut next = vec![0; size * size];
+        for j in 0..size {
+            let mut sum: usize = (0..w).map(|i| rows[i * size + j]).sum();
+            for i in 0..size {
+                let center = self.cells[i * size + j];
+                next[i * size + j] = self.rule.next_state(center, sum - center as usize);
+                if i + 1 < size {
+                    sum = sum + rows[(i + w) * size + j] - rows[i * size + j];
+                }
+            }
+        }
+        self.cells = next;
+        if self.noise > 0. {
+            let states = self.rule.states;
+            for c in self.cells.iter_mut() {
+                if self.rng.gen_bool(self.noise) {
+                    *c = self.rng.gen_range(0..states);
+                }
+            }
+        }
+    }
+
+    fn random_init(&mut self) {
+        let states = self.rule.states;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = rng.gen_range(0..states);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The grid is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// Replaces the rule with the sum table of a table rule, of the same kind
+    /// (totalistic or outer totalistic) as the current rule. Fails if the
+    /// rule isn't of this kind or if its number of states differs.
+    fn set_rule(&mut self, rule: Rule) -> Result<(), RuleError> {
+        match TotalisticRule::from_rule(&rule, self.rule.is_outer()) {
+            Some(rule) if rule.states == self.rule.states => {
+                self.rule = rule;
+                Ok(())
+            }
+            Some(_) => Err(RuleError::RuleFormatError(
+                "the rule has a different number of states",
+            )),
+            None => Err(RuleError::RuleFormatError(
+                "totalistic automata need a totalistic rule",
+            )),
+        }
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.cells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{Automaton, AutomatonImpl, BoundaryCondition, TotalisticAutomaton};
+    use crate::rule::{Rule, TotalisticRule};
+
+    #[test]
+    fn updates_match_the_rule_tables() {
+        for &(outer, boundary) in &[
+            (true, BoundaryCondition::Periodic),
+            (false, BoundaryCondition::Fixed(2)),
+            (true, BoundaryCondition::Reflective),
+        ] {
+            let rule = TotalisticRule::random(1, 3, outer);
+            let mut a = TotalisticAutomaton::with_rule(12, rule.clone());
+            let mut b = Automaton::new(3, 12, rule.to_rule());
+            a.set_boundary(boundary);
+            b.set_boundary(boundary);
+            a.random_init_seeded(3);
+            b.grid_mut().copy_from_slice(&a.grid());
+            for _ in 0..4 {
+                a.update();
+                b.update();
+                assert_eq!(a.grid(), b.grid(), "{}", rule);
+            }
+        }
+        // The full table of this rule would have 3^25 entries.
+        let rule = TotalisticRule::random(2, 3, true);
+        let mut a = TotalisticAutomaton::with_rule(12, rule.clone());
+        a.random_init_seeded(5);
+        let next = rule.apply_on_torus(&a.grid(), 12);
+        a.update();
+        assert_eq!(a.grid(), next);
+        let mut a = TotalisticAutomaton::new(2, 8, Rule::gol());
+        assert_eq!(a.rule().to_string(), "B3/S23");
+        assert!(a
+            .set_rule(Rule::from_bs_notation("B36/S23").unwrap())
+            .is_ok());
+        assert_eq!(a.rule().to_string(), "B36/S23");
+        assert!(a.set_rule(Rule::random(1, 2)).is_err());
+    }
+}
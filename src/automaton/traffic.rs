@@ -0,0 +1,277 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Rule, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The traffic model of Biham, Middleton and Levine: the cells are empty or
+/// hold a car moving east or south, and the two kinds of cars move in turn.
+/// Every update is a time step of two half steps: the cars moving east all
+/// advance by one cell if it is empty, and then the cars moving south do.
+/// The grid is a torus, the cars leaving an edge coming back on the opposite
+/// one.
+///
+/// Below a critical density of cars the traffic flows freely, and above it
+/// the cars get stuck in a global jam. The fraction of the cars that moved
+/// at the last update, returned by [`Traffic::mobility`], tells the two
+/// phases apart. The grids are best rendered with
+/// [`Palette::traffic`](crate::output::Palette::traffic).
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, Traffic};
+///
+/// let mut traffic = Traffic::with_density(64, 0.2);
+/// traffic.random_init_seeded(0);
+/// for _ in 0..500 {
+///     traffic.update();
+/// }
+/// assert!(traffic.mobility() > 0.9 && !traffic.is_jammed());
+/// ```
+pub struct Traffic {
+    size: usize,
+    density: f64,
+    cells: Vec<u8>,
+    noise: f64,
+    rng: StdRng,
+    moved: Option<usize>,
+}
+
+impl Traffic {
+    /// The state of the empty cells.
+    pub const EMPTY: u8 = 0;
+    /// The state of the cars moving east.
+    pub const EAST: u8 = 1;
+    /// The state of the cars moving south.
+    pub const SOUTH: u8 = 2;
+
+    /// Makes an empty grid of `size^2` cells whose random initializations
+    /// place a car on every cell with probability `density`, moving east or
+    /// south with equal probabilities.
+    pub fn with_density(size: usize, density: f64) -> Traffic {
+        let mut traffic = Traffic {
+            size,
+            density: 0.,
+            cells: vec![Traffic::EMPTY; size * size],
+            noise: 0.,
+            rng: StdRng::from_entropy(),
+            moved: None,
+        };
+        traffic.set_density(density);
+        traffic
+    }
+
+    /// Returns the cells of the grid.
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Returns a mutable reference to the cells of the grid.
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    /// Sets the probability that each cell holds a car after a random
+    /// initialization.
+    pub fn set_density(&mut self, density: f64) {
+        assert!(
+            (0. ..=1.).contains(&density),
+            "The density must be a probability"
+        );
+        self.density = density;
+    }
+
+    /// Returns the number of cars on the grid.
+    pub fn cars(&self) -> usize {
+        self.cells.iter().filter(|&&c| c != Traffic::EMPTY).count()
+    }
+
+    /// Returns the fraction of the cars that moved at the last update, 1
+    /// for an empty grid or before the first update.
+    pub fn mobility(&self) -> f64 {
+        match (self.moved, self.cars()) {
+            (Some(moved), cars) if cars > 0 => moved as f64 / cars as f64,
+            _ => 1.,
+        }
+    }
+
+    /// Returns whether no car moved at the last update. A jammed grid stays
+    /// jammed unless the noise stalls some of its cars.
+    pub fn is_jammed(&self) -> bool {
+        self.mobility() == 0.
+    }
+
+    /// Moves the cars of `kind` by one cell if it is empty, and returns the
+    /// number of cars that moved.
+    fn half_step(&mut self, kind: u8, rng: &mut StdRng) -> usize {
+        let size = self.size;
+        let mut next = self.cells.clone();
+        let mut moved = 0;
+        for (k, &c) in self.cells.iter().enumerate() {
+            if c != kind {
+                continue;
+            }
+            let (i, j) = (k / size, k % size);
+            let ahead = if kind == Traffic::EAST {
+                i * size + (j + 1) % size
+            } else {
+                (i + 1) % size * size + j
+            };
+            let stalled = self.noise > 0. && rng.gen_bool(self.noise);
+            if self.cells[ahead] == Traffic::EMPTY && !stalled {
+                next[k] = Traffic::EMPTY;
+                next[ahead] = kind;
+                moved += 1;
+            }
+        }
+        self.cells = next;
+        moved
+    }
+}
+
+impl AutomatonImpl for Traffic {
+    /// Makes an empty grid with a density of cars of 0.3. The number of
+    /// states and the rule are ignored: the grid always has 3 states.
+    fn new(_states: u8, size: usize, _rule: Rule) -> Traffic {
+        Traffic::with_density(size, 0.3)
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        3
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, 3)? {
+            self.cells[i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let mut rng = self.rng.clone();
+        let east = self.half_step(Traffic::EAST, &mut rng);
+        self.moved = Some(east + self.half_step(Traffic::SOUTH, &mut rng));
+        self.rng = rng;
+    }
+
+    /// Places cars with the density set by [`Traffic::set_density`].
+    fn random_init(&mut self) {
+        let density = self.density;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = if rng.gen_bool(density) {
+                rng.gen_range(Traffic::EAST..=Traffic::SOUTH)
+            } else {
+                Traffic::EMPTY
+            };
+        }
+        self.rng = rng;
+        self.moved = None;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Sets the probability `p` that a car stalls for a half step even
+    /// though the cell ahead of it is empty. The number of cars never
+    /// changes.
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The grid is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    /// The grid is always a torus, so the boundary condition is ignored.
+    fn set_boundary(&mut self, _boundary: BoundaryCondition) {}
+
+    /// The traffic model isn't driven by a rule table, so this always fails.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError("the traffic model has no rule"))
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.cells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{AutomatonImpl, Traffic};
+    use crate::rule::Rule;
+
+    #[test]
+    fn cars_move_east_then_south() {
+        let (east, south) = (Traffic::EAST, Traffic::SOUTH);
+        let mut traffic = Traffic::with_density(3, 0.);
+        // The first east car is blocked by the south car, which only moves
+        // away in the second half step.
+        traffic.cells_mut()[..2].copy_from_slice(&[east, south]);
+        traffic.cells_mut()[8] = east;
+        traffic.update();
+        // The east car of the last column came back on the first one.
+        assert_eq!(traffic.cells(), &[east, 0, 0, 0, south, 0, east, 0, 0]);
+        assert_eq!(traffic.mobility(), 2. / 3.);
+        traffic.update();
+        // The south car is now blocked by the east car that moved in front
+        // of it in the first half step.
+        assert_eq!(traffic.cells(), &[0, east, 0, 0, south, 0, 0, east, 0]);
+        assert_eq!(traffic.mobility(), 2. / 3.);
+        assert_eq!(traffic.cars(), 3);
+        assert!(traffic.set_rule(Rule::gol()).is_err());
+    }
+
+    #[test]
+    fn dense_grids_jam() {
+        let mut traffic = Traffic::with_density(32, 0.7);
+        traffic.random_init_seeded(1);
+        let cars = traffic.cars();
+        for _ in 0..2000 {
+            traffic.update();
+        }
+        assert!(traffic.is_jammed());
+        assert_eq!(traffic.cars(), cars);
+        traffic.set_noise(1.);
+        traffic.update();
+        assert_eq!(traffic.mobility(), 0.);
+        assert_eq!(Traffic::new(5, 4, Rule::gol()).states(), 3);
+    }
+}
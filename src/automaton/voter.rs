@@ -0,0 +1,238 @@
+use super::{
+    duplicate_array, parse_pattern, AutomatonImpl, BoundaryCondition, Dimensions, Pattern,
+    PatternError, Rotation, StepIteratorBox,
+};
+use crate::rule::{Neighborhood, Rule, RuleError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The noisy voter model, the stochastic counterpart of the majority vote
+/// rules (see [`Rule::majority`]): at every update, each cell copies the
+/// state of one of its neighbors drawn at random, and then takes a random
+/// state with the probability set by [`AutomatonImpl::set_noise`]. Without
+/// noise, the grid slowly coarsens until a single state is left; the noise
+/// keeps the states mixed.
+///
+/// The neighbors are the 8 Moore neighbors by default (see
+/// [`NoisyVoter::set_neighborhood`]).
+///
+/// ```
+/// use rust_ca::automaton::{AutomatonImpl, NoisyVoter};
+///
+/// let mut voter = NoisyVoter::with_states(32, 3);
+/// voter.random_init_seeded(0);
+/// for _ in 0..100 {
+///     voter.update();
+/// }
+/// // The largest opinion has grown from about a third of the grid.
+/// assert!(voter.consensus() > 0.4);
+/// ```
+pub struct NoisyVoter {
+    size: usize,
+    states: u8,
+    neighborhood: Neighborhood,
+    cells: Vec<u8>,
+    noise: f64,
+    boundary: BoundaryCondition,
+    rng: StdRng,
+}
+
+impl NoisyVoter {
+    /// Makes a grid of `size^2` cells in state 0 with `states` states.
+    pub fn with_states(size: usize, states: u8) -> NoisyVoter {
+        NoisyVoter {
+            size,
+            states,
+            neighborhood: Neighborhood::Moore,
+            cells: vec![0; size * size],
+            noise: 0.,
+            boundary: BoundaryCondition::default(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns the cells of the grid.
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Returns a mutable reference to the cells of the grid.
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    /// Sets the neighbors of the cells: the cells at a distance of 1 in the
+    /// given neighborhood, central cell excluded.
+    pub fn set_neighborhood(&mut self, neighborhood: Neighborhood) {
+        self.neighborhood = neighborhood;
+    }
+
+    /// Returns the fraction of the cells in the most common state.
+    pub fn consensus(&self) -> f64 {
+        let mut counts = vec![0; self.states.max(1) as usize];
+        for &c in &self.cells {
+            counts[c as usize] += 1;
+        }
+        let most = counts.into_iter().max().unwrap_or(0);
+        most as f64 / self.cells.len().max(1) as f64
+    }
+}
+
+impl AutomatonImpl for NoisyVoter {
+    /// Makes a grid in state 0 on the neighborhood of `rule`, whose table is
+    /// ignored.
+    fn new(states: u8, size: usize, rule: Rule) -> NoisyVoter {
+        let mut voter = NoisyVoter::with_states(size, states);
+        voter.set_neighborhood(rule.neighborhood());
+        voter
+    }
+
+    fn skipped_iter(&mut self, steps: u32, skip: u32, scale: u16) -> StepIteratorBox<'_> {
+        let size = self.size;
+        let mut ct = 0;
+        Box::new(std::iter::from_fn(move || {
+            if ct >= steps {
+                return None;
+            }
+            let ret = self.grid();
+            for _ in 0..skip {
+                self.update();
+                ct += 1;
+            }
+            Some(duplicate_array(&ret, Dimensions::new(size, scale)))
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn states(&self) -> u8 {
+        self.states
+    }
+
+    fn init_from_pattern(&mut self, pattern_fname: &str) -> Result<(), PatternError> {
+        let pattern_spec = parse_pattern(pattern_fname)?;
+        let (x, y) = pattern_spec.centered(self.size);
+        self.cells.fill(pattern_spec.background);
+        self.place_pattern(&pattern_spec, x, y, Rotation::Rot0, false)
+    }
+
+    fn place_pattern(
+        &mut self,
+        spec: &Pattern,
+        x: usize,
+        y: usize,
+        rotation: Rotation,
+        flip: bool,
+    ) -> Result<(), PatternError> {
+        let size = self.size;
+        for (i, j, state) in spec.placed((x, y), (rotation, flip), size, self.states)? {
+            self.cells[i * size + j] = state;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self) {
+        let size = self.size;
+        let offsets: Vec<(isize, isize)> = self
+            .neighborhood
+            .offsets(1)
+            .into_iter()
+            .filter(|&offset| offset != (0, 0))
+            .collect();
+        let mut rng = self.rng.clone();
+        let next: Vec<u8> = (0..self.cells.len())
+            .map(|k| {
+                let (i, j) = ((k / size) as isize, (k % size) as isize);
+                let (a, b) = offsets[rng.gen_range(0..offsets.len())];
+                self.boundary.cell(&self.cells, size, (i + a, j + b))
+            })
+            .collect();
+        self.cells = next;
+        if self.noise > 0. {
+            for c in self.cells.iter_mut() {
+                if rng.gen_bool(self.noise) {
+                    *c = rng.gen_range(0..self.states);
+                }
+            }
+        }
+        self.rng = rng;
+    }
+
+    fn random_init(&mut self) {
+        let states = self.states;
+        let mut rng = self.rng.clone();
+        for c in self.cells.iter_mut() {
+            *c = rng.gen_range(0..states);
+        }
+        self.rng = rng;
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_noise(&mut self, p: f64) {
+        assert!((0. ..=1.).contains(&p), "The noise must be a probability");
+        self.noise = p;
+    }
+
+    /// The grid is always updated by a single thread.
+    fn set_threads(&mut self, _threads: usize) {}
+
+    fn set_boundary(&mut self, boundary: BoundaryCondition) {
+        self.boundary = boundary;
+    }
+
+    /// The voter model isn't driven by a rule table, so this always fails.
+    fn set_rule(&mut self, _rule: Rule) -> Result<(), RuleError> {
+        Err(RuleError::RuleFormatError("the voter model has no rule"))
+    }
+
+    fn grid(&self) -> Vec<u8> {
+        self.cells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::{AutomatonImpl, BoundaryCondition, NoisyVoter};
+    use crate::rule::{Neighborhood, Rule};
+
+    #[test]
+    fn cells_copy_one_of_their_neighbors() {
+        let mut voter = NoisyVoter::with_states(8, 4);
+        voter.set_neighborhood(Neighborhood::VonNeumann);
+        voter.set_boundary(BoundaryCondition::Fixed(3));
+        voter.random_init_seeded(5);
+        let before = voter.grid();
+        voter.update();
+        for (k, &c) in voter.cells().iter().enumerate() {
+            let (i, j) = ((k / 8) as isize, (k % 8) as isize);
+            let neighbors = [(i - 1, j), (i + 1, j), (i, j - 1), (i, j + 1)];
+            assert!(neighbors.iter().any(|&(a, b)| {
+                let inside = (0..8).contains(&a) && (0..8).contains(&b);
+                let state = if inside {
+                    before[(a * 8 + b) as usize]
+                } else {
+                    3
+                };
+                state == c
+            }));
+        }
+        assert!(voter.set_rule(Rule::gol()).is_err());
+    }
+
+    #[test]
+    fn consensus_is_absorbing_without_noise() {
+        let mut voter = NoisyVoter::with_states(16, 3);
+        voter.cells_mut().fill(2);
+        voter.update();
+        assert_eq!(voter.consensus(), 1.);
+        voter.set_seed(1);
+        voter.set_noise(1.);
+        voter.update();
+        assert!(voter.consensus() < 0.5);
+    }
+}
@@ -42,22 +42,28 @@
 //! # automaton.random_init();
 //! output::write_to_gif_file(Some("test_bis.gif"), &mut automaton, 4, 100, 10, 1, 0);
 //! ```
-#![feature(test)]
+#![cfg_attr(feature = "bench", feature(test))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "bench")]
 extern crate test;
 
+pub mod analysis;
 pub mod automaton;
 pub mod output;
+pub mod pattern;
+pub mod pipeline;
 pub mod rule;
+pub mod search;
+pub mod stats;
 
-#[cfg(test)]
-mod tests {
+#[cfg(all(test, feature = "bench", feature = "output"))]
+mod benches {
     use crate::automaton::AutomatonImpl;
     use crate::automaton::{Automaton, TiledAutomaton};
     use crate::output;
     use crate::rule::Rule;
-    use crate::test::Bencher;
+    use test::Bencher;
 
     #[bench]
     fn write_autom(b: &mut Bencher) {
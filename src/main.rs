@@ -2,19 +2,36 @@
 #![deny(missing_docs)]
 //! The main crate for rust_ca.
 
-use core::panic;
-use std::path::Path;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
 
-use clap::{ArgGroup, Parser};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 
 use rust_ca::automaton::AutomatonImpl;
-use rust_ca::automaton::{Automaton, TiledAutomaton, TILE_SIZE};
+use rust_ca::automaton::{
+    self, Automaton, Automaton1D, Automaton3D, BootstrapPercolation, BoundaryCondition,
+    CyclicAutomaton, Dimensions, Kernel, NoisyVoter, Pattern, PatternError, RecursiveAutomaton,
+    Rotation, Sandpile, StateRotatedAutomaton, TiledAutomaton, Traffic, View3D, TILE_SIZE,
+};
 use rust_ca::output;
+use rust_ca::pipeline::{Pipeline, PipelineError};
 use rust_ca::rule::Rule;
-use rust_ca::rule::{self, SamplingMode};
+use rust_ca::rule::{
+    self, CyclicRule, Neighborhood, Rule1D, Rule3D, RuleCatalog, RuleError, SamplingMode, TieBreak,
+    TotalisticRule,
+};
+use rust_ca::search::{self, Activity, Compressibility, EntropyBand, Search};
+use rust_ca::stats::{self, StatsCsv};
 
 /// A CLI CA simulator. With no options, this runs a randomly sampled CA rule
 /// with 2 states for 50 steps and outputs it as a gif file `test.gif`.
+///
+/// Exit codes: 1 for failed backend checks, 2 for invalid options, 3 for rule
+/// errors, 4 for pattern errors and 5 for output errors.
 #[derive(Parser, Debug)]
 #[clap(
     name = "Rust CA",
@@ -24,12 +41,17 @@ use rust_ca::rule::{self, SamplingMode};
 #[clap(group(
             ArgGroup::new("write_rule")
                 .required(false)
-                .args(&["write_rule", "write_to_id"]),
+                .args(&["write-rule", "write-to-id"]),
         ))]
 struct CLIOpts {
     /// The size of the 2D CA grid
     #[clap(short, long, default_value = "128")]
     size: u16,
+    /// Side of the square of pixels drawn for each cell. Defaults to 4, 3 or
+    /// 2 depending on the size, lowered when needed to fit the 65535 pixels
+    /// sides of GIF images
+    #[clap(long)]
+    scale: Option<u16>,
     /// Number of states of the CA
     #[clap(short = 'n', long, default_value = "2")]
     states: u8,
@@ -39,6 +61,8 @@ struct CLIOpts {
     /// Steps to skip at every time step for the output
     #[clap(short = 'k', long, default_value = "1")]
     skip: u32,
+    /// The radius of the neighborhood of the sampled rules (rules read from
+    /// files or built in have their own).
     #[clap(long, default_value = "1")]
     horizon: i8,
     #[clap(long, default_value = "10")]
@@ -50,130 +74,1436 @@ struct CLIOpts {
     /// File to write the rule to.
     #[clap(short, long)]
     write_rule: Option<String>,
-    /// Write the rule to a file $ID.rule
+    /// Write the rule to a file $CANONICAL_ID.rule, named after the id
+    /// shared by the rules equivalent up to a symmetry or a relabeling of the
+    /// states. A file written for an equivalent rule is kept as is
     #[clap(long)]
     write_to_id: bool,
-    /// Specify one of the implemented CA rule.
-    #[clap(short, long, possible_values = &["GOL"])]
+    /// Specify one of the implemented CA rule (GOL), a Life-like rule
+    /// string such as B36/S23, or `majority[:TIE]` for the majority vote of
+    /// the Moore neighborhood, the ties being broken by keeping the state
+    /// of the cell (`keep`, the default), by the lowest state (`lowest`) or
+    /// at random (`random`), or `greenberg-hastings[:T]` for the excitable
+    /// media where the resting cells with at least T (1 by default) excited
+    /// von Neumann neighbors get excited.
+    #[clap(short, long)]
     rule: Option<String>,
+    /// A rule encoded as a base64 string (see `Rule::to_base64`).
+    #[clap(long, conflicts_with_all = &["rule", "file"])]
+    rule_b64: Option<String>,
+    /// Run the elementary 1D CA with the given Wolfram code (0 to 255). The
+    /// frames are the space-time diagrams of the last SIZE steps
+    #[clap(
+        long,
+        conflicts_with_all = &["rule", "file", "rule-b64", "catalog", "symmetric", "write_rule"]
+    )]
+    wolfram: Option<u8>,
+    /// Run a 3D CA of SIZE^3 cells with the given 3D Life-like rule, such as
+    /// B5/S4,5 (counts from 0 to 26, separated by commas when needed)
+    #[clap(
+        long,
+        conflicts_with_all = &["rule", "file", "rule-b64", "catalog", "symmetric", "write_rule", "wolfram"]
+    )]
+    rule_3d: Option<Rule3D>,
+    /// The view of the 3D CA rendered as frames: `slice:Z` for the cells
+    /// with z coordinate Z, or `max` for their maximum state along the z
+    /// axis. Defaults to the middle slice
+    #[clap(long, requires = "rule-3d")]
+    view_3d: Option<View3D>,
+    /// Run a built-in model instead of a rule: `sandpile` for the Abelian
+    /// sandpile (4 states, the noise dropping grains), or `bootstrap:T` for
+    /// bootstrap percolation where the empty cells with at least T occupied
+    /// von Neumann neighbors are occupied (2 states, the random grids
+    /// occupying a tenth of the cells), `cyclic:T[,R]` for the cyclic CA
+    /// where the cells advance to the next state when at least T of their
+    /// Moore neighbors of range R (1 by default) are in it, `voter` for the
+    /// noisy voter model where the cells copy a random Moore neighbor (the
+    /// noise randomizing them), or `traffic[:D]` for the BML traffic model
+    /// where the cars moving east and south take turns (3 states, the random
+    /// grids holding cars with density D, 0.3 by default, and the noise
+    /// stalling cars)
+    #[clap(
+        long,
+        conflicts_with_all = &["rule", "file", "rule-b64", "catalog", "symmetric", "write_rule", "wolfram", "rule-3d"]
+    )]
+    model: Option<Model>,
+    /// Initialize the grid with a pattern file, centered on a background of
+    /// its background state: a pattern of the crate, a run length encoded
+    /// pattern of Golly (.rle) or a plaintext pattern (.cells). With
+    /// `FILE@X,Y`, the top left corner of the pattern is placed at column X
+    /// and row Y on an empty grid instead, optionally rotated (`,rot90`,
+    /// `,rot180` or `,rot270`) and mirrored (`,flip`). Repeat to place
+    /// several patterns
     #[clap(short, long)]
-    pattern: Option<String>,
+    pattern: Vec<PatternArg>,
     #[clap(long, possible_values = &["uniform", "dirichlet"], default_value = "dirichlet")]
     rule_sampling: rule::SamplingMode,
+    /// The cells read by the random rules: the square around each cell
+    /// (moore), the cells at a Manhattan distance of at most the horizon
+    /// (von-neumann), or a hexagonal grid drawn with shifted rows (hex)
+    #[clap(
+        long,
+        possible_values = &["moore", "von-neumann", "hex"],
+        default_value = "moore",
+        conflicts_with_all = &["rule", "file", "rule-b64", "wolfram", "rule-3d", "model"]
+    )]
+    neighborhood: Neighborhood,
+    /// Compression of the rule files written.
+    #[clap(long, possible_values = &["gzip", "zstd"], default_value = "gzip")]
+    compression: rule::FileCompression,
+    /// Rotate the colors of the palette (the states are unchanged).
+    #[clap(long, alias = "rotate", default_value = "0")]
+    palette_rotate: u8,
+    /// Rotate the states of the grids before rendering them, replacing each
+    /// state s by (s + k) mod the number of states.
     #[clap(long, default_value = "0")]
-    rotate: u8,
-    /// Use a tiled CA (defaults to true when the size is a multiple of TILE_SIZE).
-    #[clap(long)]
+    state_rotate: u8,
+    /// Probability for each cell to take a random state instead of the rule
+    /// output at every step (thermal noise).
+    #[clap(long, default_value = "0")]
+    noise: f64,
+    /// Number of threads used to update the grid (0 to use all the available
+    /// cores).
+    #[clap(long, default_value = "1")]
+    threads: usize,
+    /// How the neighborhoods of the cells on the edges of the grid are
+    /// completed: periodic (torus), reflective (mirrors) or fixed:STATE
+    /// (walls of the given state).
+    #[clap(long, default_value = "periodic")]
+    boundary: BoundaryCondition,
+    /// Use a tiled CA (same as --backend tiled).
+    #[clap(long, conflicts_with = "backend")]
     use_tiled: bool,
+    /// The simulation backend. The default picks the tiled backend when the
+    /// size is a multiple of TILE_SIZE and the base one otherwise.
+    #[clap(
+        long,
+        possible_values = &["auto", "base", "tiled", "recursive"],
+        default_value = "auto"
+    )]
+    backend: Backend,
     /// Make the rule symmetric (this will also apply to rules passed as files).
     #[clap(long)]
     symmetric: bool,
     /// A file to write the GIF to. Defaults to standard output.
     #[clap(short, long)]
     output: Option<String>,
+    /// Also write the last grid of the simulation to a PNG file.
+    #[clap(long)]
+    snapshot: Option<String>,
+    /// Also write the last grid of the simulation to a pattern file, to be
+    /// used as the initial state of other simulations with --pattern.
+    #[clap(long)]
+    save_pattern: Option<String>,
+    /// Write the GIF to the standard output even if it is a terminal.
+    #[clap(long)]
+    force_stdout: bool,
+    /// Render a heatmap of the local entropy computed over blocks of the given
+    /// size instead of the CA states.
+    #[clap(long)]
+    entropy_block: Option<usize>,
+    /// Draw every cell in the colors of the states mixed in proportion to
+    /// the time it spent in them over the given number of steps before each
+    /// frame (useful to reduce flicker with stochastic rules).
+    #[clap(long, conflicts_with = "entropy-block")]
+    smooth: Option<u32>,
+    /// Render the defects between regular spatial domains, with trails fading
+    /// over the given number of frames.
+    #[clap(long, conflicts_with_all = &["entropy-block", "smooth"])]
+    defects: Option<u32>,
+    /// Run a second CA from the same initial grid under the rule of the given
+    /// file, in lockstep with the first one, and render the cells in
+    /// different states in the two grids, fading over a few frames
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "entropy-block", "smooth", "defects", "wolfram", "rule-3d", "model"
+        ]
+    )]
+    diff_rule: Option<String>,
+    /// Instead of writing the whole run, detect spikes in the activity and
+    /// write short clips around them to files $PREFIX_$STEP.gif
+    #[clap(long, conflicts_with_all = &["entropy-block", "smooth", "defects", "diff-rule"])]
+    moment_clips: Option<String>,
+    /// The colors of the states: a colormap (default, viridis, grayscale,
+    /// rainbow, hue-cycle, excitable or traffic), a comma separated list of
+    /// #rrggbb colors, or a file with one color per line or a JSON array of
+    /// colors. Defaults to traffic for the traffic model and to excitable for
+    /// the greenberg-hastings rules
+    #[clap(
+        long,
+        default_value = "default",
+        conflicts_with_all = &["entropy-block", "defects", "moment-clips"]
+    )]
+    palette: String,
+    /// Rotate the palette by one state every given number of frames.
+    #[clap(
+        long,
+        conflicts_with_all = &["entropy-block", "smooth", "defects", "diff-rule", "moment-clips"]
+    )]
+    palette_cycle: Option<u32>,
+    /// Render tiny grids (at most 32 cells wide) for teaching, as large cells
+    /// of the given side in pixels between gridlines, labeled with their
+    /// states.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "entropy-block", "smooth", "defects", "diff-rule", "moment-clips", "palette-cycle"
+        ]
+    )]
+    teaching: Option<u16>,
+    /// With --teaching, also label every cell with the rule table position of
+    /// its neighborhood (on a periodic grid).
+    #[clap(long, requires = "teaching")]
+    teaching_indices: bool,
+    /// Watch the simulation live in the terminal instead of writing it, the
+    /// grids being drawn every SKIP steps and DELAY hundredths of a second
+    /// apart: space plays or pauses, n advances by a frame and q quits.
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "output", "entropy-block", "smooth", "defects", "diff-rule", "moment-clips",
+            "palette-cycle", "teaching", "format", "max-fps", "stats"
+        ]
+    )]
+    watch: bool,
+    /// The output format: a GIF animation, an animated PNG, a sequence of PNG
+    /// files $OUTPUT_$INDEX.png, the raw cells (one byte per cell), a NumPy
+    /// array of shape [frames, size, size] or nothing.
+    #[clap(
+        long,
+        possible_values = &["gif", "apng", "png", "raw", "npy", "null"],
+        default_value = "gif",
+        conflicts_with_all = &[
+            "entropy-block", "smooth", "defects", "diff-rule", "moment-clips", "palette-cycle",
+            "teaching"
+        ]
+    )]
+    format: OutputFormat,
+    /// Print errors to the standard error as JSON objects with the fields
+    /// `category`, `code` and `message`.
+    #[clap(long)]
+    json_errors: bool,
+    /// Print the resolved options and the estimated output size and memory
+    /// use without running the simulation (rule files aren't written).
+    #[clap(long)]
+    dry_run: bool,
+    /// Refuse to start simulations estimated to use more memory than the
+    /// given size, in bytes or with a unit (e.g. 512M, 4GiB).
+    #[clap(long)]
+    max_memory: Option<ByteSize>,
+    /// When writing raw frames to the standard output (e.g. piped to a video
+    /// player), drop the frames coming faster than the given rate instead of
+    /// slowing the simulation down.
+    #[clap(long)]
+    max_fps: Option<u32>,
+    /// Seed the random number generators to make the simulation
+    /// reproducible: the random rule, the initial state and the noise.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// A file recording the rules already simulated. Random rules equivalent
+    /// to one of them (up to a rotation or a reflection) are sampled again,
+    /// and the simulated rule is added to the file.
+    #[clap(long)]
+    catalog: Option<String>,
+    /// Write the entropy, the activity and the density of each state of every
+    /// frame to a CSV file, or to the standard output with `-` (use --format
+    /// null to only compute the statistics).
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "entropy-block", "smooth", "defects", "diff-rule", "moment-clips", "palette-cycle",
+            "teaching"
+        ]
+    )]
+    stats: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check all the simulation backends against the reference implementation
+    /// of a random rule and of a random outer totalistic rule, and compare
+    /// their speed.
+    Doctor {
+        /// The size of the grid used for the checks.
+        #[clap(long, default_value = "512")]
+        size: usize,
+        /// The number of steps simulated with each backend.
+        #[clap(long, default_value = "10")]
+        steps: u32,
+    },
+    /// Sample random rules, score short simulations of them by the entropy of
+    /// their states, their activity and the compressibility of their grids,
+    /// and write the best ones to rule files.
+    Search(SearchArgs),
+    /// Draw a family of rules indexed by Langton's lambda parameter from a
+    /// seed, and render the rules of evenly spaced parameters to GIF files to
+    /// show the transition from frozen to chaotic dynamics.
+    Sweep(SweepArgs),
+    /// Run the stages of a pipeline file, each one starting from the final
+    /// grid of the previous one, and render every stage to a GIF file.
+    Pipeline {
+        /// The pipeline file.
+        config: String,
+        /// The scale of the GIF frames.
+        #[clap(long, default_value = "2")]
+        scale: u16,
+        /// The directory the GIF files are written to, as INDEX_ID.gif.
+        #[clap(short, long, default_value = "pipeline")]
+        output: String,
+    },
+    /// Inspect a rule file.
+    #[clap(subcommand)]
+    Rule(RuleCommand),
+}
+
+/// The subcommands of the rule subcommand.
+#[derive(Subcommand, Debug)]
+enum RuleCommand {
+    /// Print the next state of the central cell of a neighborhood and, for
+    /// totalistic and outer totalistic rules, the sums it depends on.
+    Explain {
+        /// The rule file.
+        rule: String,
+        /// The neighborhood, with rows separated by `/` (e.g. 010/111/000).
+        #[clap(long)]
+        neighborhood: String,
+    },
+}
+
+/// The options of the search subcommand.
+#[derive(Args, Debug)]
+struct SearchArgs {
+    /// The number of rules sampled.
+    #[clap(long, default_value = "1000")]
+    samples: u64,
+    /// The number of rules written.
+    #[clap(long, default_value = "10")]
+    top: usize,
+    /// The number of states of the rules.
+    #[clap(short = 'n', long, default_value = "2")]
+    states: u8,
+    #[clap(long, default_value = "1")]
+    horizon: i8,
+    /// The size of the grids of the simulations.
+    #[clap(short, long, default_value = "64")]
+    size: usize,
+    /// The number of steps of the simulations.
+    #[clap(short = 't', long, default_value = "200")]
+    steps: u32,
+    #[clap(long, default_value = "dirichlet", possible_values = &["uniform", "dirichlet"])]
+    rule_sampling: rule::SamplingMode,
+    /// Seed the sampling of the rules and the initial grids.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Cluster the best rules by their fingerprints and only write the best
+    /// rule of each of at most this number of clusters.
+    #[clap(long)]
+    exemplars: Option<usize>,
+    /// A file recording the rules already found. The rules equivalent to one
+    /// of them are left out, and the written rules are added to the file.
+    #[clap(long)]
+    catalog: Option<String>,
+    /// The directory the rule files are written to, as RANK_ID.rule.
+    #[clap(short, long, default_value = "search")]
+    output: String,
+}
+
+/// The options of the sweep subcommand.
+#[derive(Args, Debug)]
+struct SweepArgs {
+    /// The number of rules of the sweep.
+    #[clap(long, default_value = "11")]
+    points: usize,
+    /// The number of states of the rules.
+    #[clap(short = 'n', long, default_value = "2")]
+    states: u8,
+    #[clap(long, default_value = "1")]
+    horizon: i8,
+    /// The size of the grids of the simulations.
+    #[clap(short, long, default_value = "64")]
+    size: usize,
+    /// The number of steps of the simulations.
+    #[clap(short = 't', long, default_value = "100")]
+    steps: u32,
+    /// The scale of the GIF frames.
+    #[clap(long, default_value = "2")]
+    scale: u16,
+    /// Seed the family of rules and the initial grid.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// The directory the GIF files are written to, as INDEX_lambda_LAMBDA.gif.
+    #[clap(short, long, default_value = "sweep")]
+    output: String,
+}
+
+/// The formats the simulation can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Gif,
+    Apng,
+    Png,
+    Raw,
+    Npy,
+    Null,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(OutputFormat::Gif),
+            "apng" => Ok(OutputFormat::Apng),
+            "png" => Ok(OutputFormat::Png),
+            "raw" => Ok(OutputFormat::Raw),
+            "npy" => Ok(OutputFormat::Npy),
+            "null" => Ok(OutputFormat::Null),
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// The simulation backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Auto,
+    Base,
+    Tiled,
+    Recursive,
+}
+
+impl FromStr for Backend {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Backend::Auto),
+            "base" => Ok(Backend::Base),
+            "tiled" => Ok(Backend::Tiled),
+            "recursive" => Ok(Backend::Recursive),
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// The built-in models run with --model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Model {
+    Sandpile,
+    Bootstrap(usize),
+    Cyclic(usize, i8),
+    Voter,
+    Traffic(f64),
+}
+
+impl Model {
+    /// The number of states of the model, if it is fixed.
+    fn states(self) -> Option<u8> {
+        match self {
+            Model::Sandpile => Some(4),
+            Model::Bootstrap(_) => Some(2),
+            Model::Traffic(_) => Some(3),
+            Model::Cyclic(..) | Model::Voter => None,
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Model::Sandpile => write!(f, "Abelian sandpile"),
+            Model::Bootstrap(threshold) => {
+                write!(f, "bootstrap percolation with threshold {}", threshold)
+            }
+            Model::Cyclic(threshold, range) => write!(
+                f,
+                "cyclic CA with threshold {} and range {}",
+                threshold, range
+            ),
+            Model::Voter => write!(f, "noisy voter model"),
+            Model::Traffic(density) => write!(f, "BML traffic with density {}", density),
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "sandpile" => Ok(Model::Sandpile),
+            None if s == "voter" => Ok(Model::Voter),
+            None if s == "traffic" => Ok(Model::Traffic(0.3)),
+            Some(("traffic", density)) => match density.parse() {
+                Ok(density) if (0. ..=1.).contains(&density) => Ok(Model::Traffic(density)),
+                _ => Err("invalid density"),
+            },
+            Some(("bootstrap", threshold)) => threshold
+                .parse()
+                .map(Model::Bootstrap)
+                .map_err(|_| "invalid threshold"),
+            Some(("cyclic", params)) => {
+                let (threshold, range) = params.split_once(',').unwrap_or((params, "1"));
+                match (threshold.parse(), range.parse()) {
+                    (Ok(threshold), Ok(range)) if range >= 1 => Ok(Model::Cyclic(threshold, range)),
+                    _ => Err("invalid threshold or range"),
+                }
+            }
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// A pattern file given with --pattern, and where to place it: centered if
+/// no position is given, or at `FILE@X,Y[,ROTATION][,flip]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PatternArg {
+    path: String,
+    placement: Option<(usize, usize, Rotation, bool)>,
+}
+
+impl FromStr for PatternArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, placement) = match s.rsplit_once('@') {
+            Some((path, placement)) => (path, placement),
+            None => {
+                return Ok(PatternArg {
+                    path: s.to_string(),
+                    placement: None,
+                })
+            }
+        };
+        let mut items = placement.split(',').map(str::trim);
+        let mut coordinate = || -> Result<usize, String> {
+            items
+                .next()
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(|| format!("expected FILE@X,Y, got {:?}", s))
+        };
+        let (x, y) = (coordinate()?, coordinate()?);
+        let (mut rotation, mut flip) = (Rotation::Rot0, false);
+        for item in items {
+            match item {
+                "flip" => flip = true,
+                _ => rotation = item.parse()?,
+            }
+        }
+        Ok(PatternArg {
+            path: path.to_string(),
+            placement: Some((x, y, rotation, flip)),
+        })
+    }
+}
+
+impl fmt::Display for PatternArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some((x, y, rotation, flip)) = self.placement {
+            write!(f, " at ({}, {})", x, y)?;
+            if rotation != Rotation::Rot0 {
+                write!(f, " {}", format!("{:?}", rotation).to_lowercase())?;
+            }
+            if flip {
+                write!(f, " flipped")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The errors of the CLI, grouped by category. Each category exits with its
+/// own code.
+#[derive(Debug)]
+enum CliError {
+    /// The options are invalid.
+    Usage(String),
+    /// The rule couldn't be read, decoded or written.
+    Rule(RuleError),
+    /// The pattern couldn't be read.
+    Pattern(PatternError),
+    /// The output couldn't be written.
+    Output(io::Error),
+}
+
+impl CliError {
+    /// The name of the category of the error.
+    fn category(&self) -> &'static str {
+        match self {
+            CliError::Usage(..) => "usage",
+            CliError::Rule(..) => "rule",
+            CliError::Pattern(..) => "pattern",
+            CliError::Output(..) => "output",
+        }
+    }
+
+    /// The exit code of the category of the error.
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(..) => 2,
+            CliError::Rule(..) => 3,
+            CliError::Pattern(..) => 4,
+            CliError::Output(..) => 5,
+        }
+    }
+
+    /// Prints the error to the standard error, as JSON if `json` is true, and
+    /// exits with the code of its category.
+    fn exit(&self, json: bool) -> ! {
+        if json {
+            eprintln!(
+                "{{\"category\":{},\"code\":{},\"message\":{}}}",
+                json_string(self.category()),
+                self.exit_code(),
+                json_string(&self.to_string())
+            );
+        } else {
+            eprintln!("Error: {}", self);
+        }
+        std::process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The messages of the library errors don't include their source.
+        match self {
+            CliError::Usage(msg) => write!(f, "{}", msg),
+            CliError::Rule(RuleError::RuleFileError(e)) => write!(f, "rule file: {}", e),
+            CliError::Rule(e) => write!(f, "{}", e),
+            CliError::Pattern(PatternError::PatternFileError(e)) => {
+                write!(f, "pattern file: {}", e)
+            }
+            CliError::Pattern(e) => write!(f, "{}", e),
+            CliError::Output(e) => write!(f, "output: {}", e),
+        }
+    }
+}
+
+impl From<RuleError> for CliError {
+    fn from(err: RuleError) -> CliError {
+        CliError::Rule(err)
+    }
+}
+
+impl From<PatternError> for CliError {
+    fn from(err: PatternError) -> CliError {
+        CliError::Pattern(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> CliError {
+        CliError::Output(err)
+    }
+}
+
+/// Formats a number of bytes with a binary unit.
+fn human_bytes(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < units.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+/// Quotes and escapes a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
 
 struct SimulationOpts {
     size: u16,
     scale: u16,
     states: u8,
-    _horizon: i8, // Hardcoded for now to 1
     steps: u32,
     skip: u32,
     delay: u16,
     rule: Rule,
-    pattern: Option<String>,
-    rotate: u8,
+    /// The horizon and neighborhood of the random rule to sample, until it
+    /// is sampled.
+    pending_rule: Option<(i8, Neighborhood)>,
+    rule_source: String,
+    patterns: Vec<PatternArg>,
+    palette: output::Palette,
+    palette_rotate: u8,
+    state_rotate: u8,
+    noise: f64,
+    threads: usize,
+    boundary: BoundaryCondition,
+    backend: Backend,
     output: Option<String>,
+    snapshot: Option<String>,
+    save_pattern: Option<String>,
+    force_stdout: bool,
+    entropy_block: Option<usize>,
+    smooth: Option<u32>,
+    defects: Option<u32>,
+    diff_rule: Option<Rule>,
+    moment_clips: Option<String>,
+    palette_cycle: Option<u32>,
+    teaching: Option<u16>,
+    teaching_indices: bool,
+    watch: bool,
+    format: OutputFormat,
+    dry_run: bool,
+    max_memory: Option<ByteSize>,
+    max_fps: Option<u32>,
+    seed: Option<u64>,
+    catalog: Option<String>,
+    catalog_skipped: u64,
+    stats: Option<String>,
+    wolfram: Option<u8>,
+    rule_3d: Option<Rule3D>,
+    view_3d: Option<View3D>,
+    model: Option<Model>,
+}
+
+/// A number of bytes, parsed from an integer with an optional binary unit
+/// (`K`, `M`, `G` or `T`, optionally followed by `iB` or `B`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteSize(u64);
+
+impl FromStr for ByteSize {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: u64 = number.parse().map_err(|_| "expected a number of bytes")?;
+        let unit = unit.trim_start();
+        let unit = unit
+            .strip_suffix("iB")
+            .or_else(|| unit.strip_suffix('B'))
+            .unwrap_or(unit);
+        let shift = match unit.to_ascii_uppercase().as_str() {
+            "" => 0,
+            "K" => 10,
+            "M" => 20,
+            "G" => 30,
+            "T" => 40,
+            _ => return Err("unknown unit, expected K, M, G or T"),
+        };
+        number
+            .checked_mul(1 << shift)
+            .map(ByteSize)
+            .ok_or("the size is too large")
+    }
+}
+
+/// The estimated memory use of a simulation, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MemoryEstimate {
+    /// The grids of the automaton, including the buffers for the next state.
+    grids: u64,
+    /// The transition table of the rule.
+    rule: u64,
+    /// The frames kept while rendering (grid copies, upscaled frames and the
+    /// histories of the smoothing, defect and moment modes).
+    frames: u64,
+    /// The buffers of the image encoder.
+    encoder: u64,
+}
+
+impl MemoryEstimate {
+    fn total(&self) -> u64 {
+        self.grids + self.rule + self.frames + self.encoder
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (grids {}, rule table {}, frame buffers {}, encoder {})",
+            human_bytes(self.total()),
+            human_bytes(self.grids),
+            human_bytes(self.rule),
+            human_bytes(self.frames),
+            human_bytes(self.encoder)
+        )
+    }
 }
 
 impl SimulationOpts {
     /// Parse options from clap and construct a SimulationOpts object.
-    fn from_clap_opts(opts: CLIOpts) -> Result<SimulationOpts, std::io::Error> {
-        let scale = if opts.size > 512 {
-            2
-        } else if opts.size > 256 {
-            3
+    fn from_clap_opts(opts: CLIOpts) -> Result<SimulationOpts, CliError> {
+        let scale = opts.scale.unwrap_or_else(|| {
+            let scale = if opts.size > 512 {
+                2
+            } else if opts.size > 256 {
+                3
+            } else {
+                4
+            };
+            let dims = Dimensions::new(opts.size.into(), scale);
+            dims.fit_gif().unwrap_or(dims).scale
+        });
+        // Some models and rules have their own default palette.
+        let palette = match (opts.model, opts.rule.as_deref()) {
+            _ if opts.palette != "default" => load_palette(&opts.palette)?,
+            (Some(Model::Traffic(_)), _) => output::Palette::traffic(),
+            (_, Some(name)) if name.starts_with("greenberg-hastings") => {
+                output::Palette::excitable()
+            }
+            _ => output::Palette::default(),
+        };
+        let rule_source = match (&opts.rule, &opts.file, &opts.rule_b64) {
+            _ if opts.wolfram.is_some() => "elementary 1D".to_string(),
+            _ if opts.rule_3d.is_some() => "3D Life-like".to_string(),
+            _ if opts.model.is_some() => "built-in model".to_string(),
+            (Some(name), _, _) => format!("built-in {}", name),
+            (None, Some(file), _) => format!("file {}", file),
+            (None, None, Some(_)) => "base64 string".to_string(),
+            (None, None, None) => {
+                format!("random ({:?} sampling)", opts.rule_sampling).to_lowercase()
+            }
+        };
+        let mut catalog = match &opts.catalog {
+            Some(path) => Some(RuleCatalog::from_file(path)?),
+            None => None,
+        };
+        let named = opts.model.is_some() || opts.rule.is_some();
+        let sampled = !named && opts.file.is_none() && opts.rule_b64.is_none();
+        let write_rule = if opts.dry_run || named {
+            RuleWrite::None
+        } else if opts.write_to_id {
+            RuleWrite::WriteToID
         } else {
-            4
+            opts.write_rule
+                .as_ref()
+                .map_or(RuleWrite::None, |s| RuleWrite::WriteToFile(s.to_string()))
+        };
+        let backend = match (opts.backend, opts.use_tiled) {
+            (Backend::Auto, true) => Backend::Tiled,
+            (Backend::Auto, false) if (opts.size as usize).is_multiple_of(TILE_SIZE) => {
+                Backend::Tiled
+            }
+            (Backend::Auto, false) => Backend::Base,
+            (backend, _) => backend,
         };
-        let mut rule = if let Some(rule_name) = opts.rule {
+        let (rule_name, file, rule_b64) = (opts.rule, opts.file, opts.rule_b64);
+        let (sampling, symmetric) = (opts.rule_sampling, opts.symmetric);
+        let mut sim_opts = SimulationOpts {
+            size: opts.size,
+            scale,
+            states: opts.states,
+            steps: opts.steps,
+            skip: opts.skip,
+            // The rule is resolved once the options are validated.
+            rule: Rule::new(0, opts.states, (0..opts.states).collect()),
+            pending_rule: Some((opts.horizon, opts.neighborhood)).filter(|_| sampled),
+            rule_source,
+            patterns: opts.pattern,
+            delay: opts.delay,
+            palette,
+            palette_rotate: opts.palette_rotate,
+            state_rotate: opts.state_rotate,
+            noise: opts.noise,
+            threads: if opts.threads == 0 {
+                std::thread::available_parallelism().map_or(1, |n| n.get())
+            } else {
+                opts.threads
+            },
+            boundary: opts.boundary,
+            backend,
+            output: opts.output,
+            snapshot: opts.snapshot,
+            save_pattern: opts.save_pattern,
+            force_stdout: opts.force_stdout,
+            entropy_block: opts.entropy_block,
+            smooth: opts.smooth,
+            defects: opts.defects,
+            diff_rule: opts.diff_rule.as_deref().map(Rule::from_file).transpose()?,
+            palette_cycle: opts.palette_cycle,
+            teaching: opts.teaching,
+            teaching_indices: opts.teaching_indices,
+            watch: opts.watch,
+            moment_clips: opts.moment_clips,
+            format: opts.format,
+            dry_run: opts.dry_run,
+            max_memory: opts.max_memory,
+            max_fps: opts.max_fps,
+            seed: opts.seed,
+            catalog: opts.catalog,
+            catalog_skipped: 0,
+            stats: opts.stats,
+            wolfram: opts.wolfram,
+            rule_3d: opts.rule_3d,
+            view_3d: opts.view_3d,
+            model: opts.model,
+        };
+        // Nothing is sampled, loaded or written before the options are
+        // checked, the tables of the rules to sample can be huge.
+        sim_opts.validate()?;
+        let rule = if opts.model.is_some() {
+            // The models have no table, the identity of horizon 0 stands for
+            // them (their tables could be far too large to sample).
+            Rule::new(0, opts.states, (0..opts.states).collect())
+        } else if let Some(rule_name) = rule_name {
             match rule_name.as_str() {
                 "GOL" => Rule::gol(),
-                _ => panic!("Unknown rule name"),
+                name if name.split(':').next() == Some("majority") => {
+                    let tie_break: TieBreak = name
+                        .strip_prefix("majority:")
+                        .unwrap_or("keep")
+                        .parse()
+                        .map_err(|e| CliError::Usage(format!("invalid rule {}: {}", name, e)))?;
+                    Rule::majority(opts.states, tie_break)?
+                }
+                name if name.split(':').next() == Some("greenberg-hastings") => {
+                    let threshold = name
+                        .strip_prefix("greenberg-hastings:")
+                        .unwrap_or("1")
+                        .parse()
+                        .map_err(|_| CliError::Usage(format!("invalid threshold in {}", name)))?;
+                    Rule::greenberg_hastings(opts.states, threshold)?
+                }
+                name => Rule::from_bs_notation(name)
+                    .map_err(|_| CliError::Usage(format!("unknown rule name {}", rule_name)))?,
             }
         } else {
-            let write_rule = if opts.write_to_id {
-                RuleWrite::WriteToID
-            } else {
-                opts.write_rule
-                    .as_ref()
-                    .map_or(RuleWrite::None, |s| RuleWrite::WriteToFile(s.to_string()))
+            let loaded = match (file, rule_b64) {
+                (Some(file), _) => Some(Rule::from_file(file.as_str())?),
+                (None, Some(encoded)) => Some(Rule::from_base64(&encoded)?),
+                (None, None) => None,
             };
-            match (opts.file, write_rule) {
-                (Some(file), RuleWrite::WriteToID) => {
-                    let r = Rule::from_file(&file).unwrap();
-                    r.to_file(format!("{}.rule", r.id()))?;
-                    r
-                }
-                (Some(file), RuleWrite::WriteToFile(s)) => {
-                    let r = Rule::from_file(&file).unwrap();
-                    r.to_file(s)?;
-                    r
-                }
-                (Some(file), RuleWrite::None) => Rule::from_file(&file).unwrap(),
-                (None, RuleWrite::WriteToFile(write)) => {
-                    make_new_rule(opts.rule_sampling, opts.horizon, opts.states, Some(write))?
-                }
-                (None, RuleWrite::None) => {
-                    make_new_rule::<String>(opts.rule_sampling, opts.horizon, opts.states, None)?
-                }
-                (None, RuleWrite::WriteToID) => {
-                    let rule = make_new_rule::<String>(
-                        opts.rule_sampling,
+            match loaded {
+                Some(r) => r,
+                None => {
+                    let (rule, skipped) = sample_new_rule(
+                        sampling,
+                        opts.neighborhood,
                         opts.horizon,
                         opts.states,
-                        None,
+                        symmetric,
+                        opts.seed,
+                        catalog.as_ref(),
                     )?;
-                    rule.to_file(format!("{}.rule", rule.id()))?;
+                    sim_opts.catalog_skipped = skipped;
                     rule
                 }
             }
         };
-        if opts.symmetric {
+        sim_opts.rule = rule;
+        sim_opts.pending_rule = None;
+        sim_opts.validate()?;
+        let rule = &mut sim_opts.rule;
+        match write_rule {
+            RuleWrite::WriteToID => {
+                let path = format!("{}.rule", rule.canonical_id());
+                if std::path::Path::new(&path).exists() {
+                    eprintln!("An equivalent rule was already written to {}", path);
+                } else {
+                    rule.to_file_with_compression(path, opts.compression)
+                        .map_err(RuleError::from)?
+                }
+            }
+            RuleWrite::WriteToFile(s) => rule
+                .to_file_with_compression(s, opts.compression)
+                .map_err(RuleError::from)?,
+            RuleWrite::None => (),
+        }
+        if symmetric {
             rule.symmetrize();
         }
-        Ok(SimulationOpts {
-            size: opts.size,
-            scale,
-            states: opts.states,
-            _horizon: opts.horizon,
-            steps: opts.steps,
-            skip: opts.skip,
+        if let (Some(catalog), Some(path)) = (&mut catalog, &sim_opts.catalog) {
+            if let Some(representative) = catalog.record(rule) {
+                eprintln!(
+                    "Rule {} is equivalent to the rule {} of the catalog",
+                    rule.id(),
+                    representative
+                );
+            }
+            if !sim_opts.dry_run {
+                catalog.to_file(path).map_err(RuleError::from)?;
+            }
+        }
+        Ok(sim_opts)
+    }
+
+    /// Describes the resolved options with the estimated output size and
+    /// memory use of the simulation.
+    fn summary(&self) -> String {
+        let (size, scale) = (self.size as u64, self.scale as u64);
+        let scaled = size * scale;
+        let frames = (self.steps / self.skip) as u64;
+        let mode = if let Some(block) = self.entropy_block {
+            format!("local entropy over {}x{} blocks", block, block)
+        } else if let Some(window) = self.smooth {
+            format!("state colors mixed over {} steps", window)
+        } else if let Some(trail) = self.defects {
+            format!("defects with trails of {} frames", trail)
+        } else if let Some(rule) = &self.diff_rule {
+            format!("disagreement with rule {}", rule.id())
+        } else if let Some(prefix) = &self.moment_clips {
+            format!("clips around activity spikes to {}_$STEP.gif", prefix)
+        } else if let Some(every) = self.palette_cycle {
+            format!("states, palette cycling every {} frames", every)
+        } else if let Some(cell) = self.teaching {
+            format!("states on a teaching grid with cells of {} pixels", cell)
+        } else {
+            "states".to_string()
+        };
+        let destination = self.output.as_deref().unwrap_or("standard output");
+        let output_size = match self.format {
+            OutputFormat::Null => "none".to_string(),
+            OutputFormat::Raw | OutputFormat::Npy => human_bytes(frames * size * size),
+            OutputFormat::Gif | OutputFormat::Apng | OutputFormat::Png => {
+                format!(
+                    "at most {} (uncompressed)",
+                    human_bytes(frames * scaled * scaled)
+                )
+            }
+        };
+        let mut lines = vec![
+            format!(
+                "rule: {}, id {}, {} states, horizon {} ({} kernel)",
+                self.rule_source,
+                self.rule.id(),
+                self.rule.states,
+                self.rule.horizon,
+                Kernel::for_rule(&self.rule)
+            ),
+            format!(
+                "grid: {}x{} cells, {} backend, {} thread(s), {} boundary, {}",
+                size,
+                size,
+                format!("{:?}", self.backend).to_lowercase(),
+                self.threads,
+                format!("{:?}", self.boundary).to_lowercase(),
+                match (&self.patterns[..], self.seed) {
+                    ([], Some(seed)) => format!("random initial state (seed {})", seed),
+                    ([], None) => "random initial state".to_string(),
+                    (patterns, _) => format!(
+                        "pattern {}",
+                        patterns
+                            .iter()
+                            .map(PatternArg::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            ),
+            format!(
+                "output: {} as {} to {}, {} frames of {}x{} pixels (scale {}) every {} steps",
+                mode,
+                format!("{:?}", self.format).to_lowercase(),
+                destination,
+                frames,
+                scaled,
+                scaled,
+                scale,
+                self.skip
+            ),
+            format!("estimated output size: {}", output_size),
+            format!("estimated memory: {}", self.memory_estimate()),
+        ];
+        if self.watch {
+            lines[2] = format!(
+                "output: {} live in the terminal every {} steps, {} ms apart",
+                mode,
+                self.skip,
+                10 * self.delay as u32
+            );
+            lines[3] = "estimated output size: none".to_string();
+        }
+        if let Some(code) = self.wolfram {
+            lines[0] = format!(
+                "rule: {} rule {}, space-time diagrams of the last {} steps",
+                self.rule_source, code, size
+            );
+        }
+        if self.rule.neighborhood() != Neighborhood::Moore {
+            lines[0] += &format!(", {} neighborhood", self.rule.neighborhood());
+        }
+        if let Some(rule) = &self.rule_3d {
+            lines[0] = format!("rule: {} rule {}", self.rule_source, rule);
+            lines[1] = lines[1].replacen(
+                &format!("{}x{}", size, size),
+                &format!("{}x{}x{}", size, size, size),
+                1,
+            );
+            let view = match self.view_3d {
+                Some(View3D::MaxProjection) => "maximum along z".to_string(),
+                Some(View3D::Slice(z)) => format!("slice z={}", z),
+                None => format!("slice z={}", size / 2),
+            };
+            lines.insert(2, format!("view: {}", view));
+        }
+        if let Some(model) = self.model {
+            lines[0] = format!("rule: {} {}", self.rule_source, model);
+        }
+        if let Some(path) = &self.stats {
+            let destination = if path == "-" { "standard output" } else { path };
+            lines.insert(3, format!("statistics: CSV to {}", destination));
+        }
+        if let Some(path) = &self.catalog {
+            lines.insert(
+                1,
+                format!(
+                    "catalog: {}, {} equivalent rule(s) skipped",
+                    path, self.catalog_skipped
+                ),
+            );
+        }
+        lines.join("\n")
+    }
+
+    /// Estimates the peak memory use of the simulation. This ignores the small
+    /// allocations (palettes, statistics) and the memory of the process
+    /// itself.
+    fn memory_estimate(&self) -> MemoryEstimate {
+        let (size, scale) = (self.size as u64, self.scale as u64);
+        let (cells, pixels) = (size * size, size * size * scale * scale);
+        let grids = match self.backend {
+            // The cube and the neighborhood sums of its update.
+            _ if self.rule_3d.is_some() => 17 * cells * size,
+            // The heights of the pile and of its next step.
+            _ if self.model == Some(Model::Sandpile) => 8 * cells,
+            Backend::Tiled => {
+                let (n_tiles, side) = (
+                    size / TILE_SIZE as u64,
+                    (TILE_SIZE as u64) + 2 * self.rule.horizon as u64,
+                );
+                2 * n_tiles * n_tiles * side * side
+            }
+            _ => 2 * cells,
+        };
+        // The history of the rendering mode.
+        let history = if let Some(block) = self.entropy_block {
+            // The entropies and state counts of the blocks.
+            let blocks = cells / (block * block).max(1) as u64;
+            blocks * 8 * (1 + self.states as u64)
+        } else if let Some(window) = self.smooth {
+            // The grids of the window.
+            window as u64 * cells
+        } else if self.defects.is_some() {
+            // The defects of the frame and the fading intensities of the
+            // past defects.
+            2 * cells
+        } else if self.diff_rule.is_some() {
+            // The grids of the second CA and the fading intensities of the
+            // disagreements.
+            3 * cells
+        } else if self.moment_clips.is_some() {
+            // The frames before a moment and the clip being recorded.
+            let clips = output::MomentClips::default();
+            (2 * clips.before + clips.after + 1) as u64 * cells
+        } else {
+            0
+        };
+        // The plain states are copied to a frame reused across steps and
+        // upscaled in place.
+        let streamed = history == 0 && self.palette_cycle.is_none() && self.teaching.is_none();
+        let frames = if streamed {
+            pixels
+        } else {
+            cells + pixels + history
+        };
+        // The encoders keep a compressed buffer of at most the size of the
+        // frame, the PNG encoders also copying the frame.
+        let encoder = match self.format {
+            OutputFormat::Raw | OutputFormat::Npy | OutputFormat::Null => 0,
+            OutputFormat::Gif => pixels,
+            OutputFormat::Apng | OutputFormat::Png => 2 * pixels,
+        };
+        // The table of the rule to sample, which validate checked.
+        let rule = match self.pending_rule {
+            Some((horizon, neighborhood)) => {
+                Rule::checked_table_size(horizon, self.states, neighborhood).unwrap_or(0)
+            }
+            None => self.rule.table().len() as u64,
+        };
+        MemoryEstimate {
+            grids,
             rule,
-            pattern: opts.pattern,
-            delay: opts.delay,
-            rotate: opts.rotate,
-            output: opts.output,
-        })
+            frames,
+            encoder,
+        }
     }
+
+    /// Checks that the options are consistent before starting a simulation,
+    /// returning a usage error describing how to fix them otherwise.
+    fn validate(&self) -> Result<(), CliError> {
+        let usage = |msg: String| Err(CliError::Usage(msg));
+        let size = self.size as usize;
+        if size == 0 {
+            return usage("the size of the grid must be positive".to_string());
+        }
+        if self.states < 2 {
+            return usage("the CA needs at least 2 states (see --states)".to_string());
+        }
+        if let Some((horizon, neighborhood)) = self.pending_rule {
+            if horizon < 0 {
+                return usage("the horizon can't be negative (see --horizon)".to_string());
+            }
+            if Rule::checked_table_size(horizon, self.states, neighborhood).is_err() {
+                return usage(format!(
+                    "the table of a rule of horizon {} with {} states would be larger than 64 \
+                     MiB (see --horizon)",
+                    horizon, self.states
+                ));
+            }
+        }
+        if self.rule.states != self.states {
+            return usage(format!(
+                "the rule has {} states but the CA has {} (see --states)",
+                self.rule.states, self.states
+            ));
+        }
+        if let Some(rule) = self.diff_rule.as_ref().filter(|r| r.states != self.states) {
+            return usage(format!(
+                "the second rule has {} states but the CA has {} (see --diff-rule)",
+                rule.states, self.states
+            ));
+        }
+        if self.wolfram.is_some() && self.states != 2 {
+            return usage("elementary rules have 2 states (see --wolfram)".to_string());
+        }
+        if self.rule_3d.is_some() && self.states != 2 {
+            return usage("3D Life-like rules have 2 states (see --rule-3d)".to_string());
+        }
+        if let Some(states) = self.model.and_then(Model::states) {
+            if self.states != states {
+                return usage(format!(
+                    "the {} model has {} states (see --model)",
+                    self.model.unwrap_or(Model::Sandpile),
+                    states
+                ));
+            }
+        }
+        if let Some(View3D::Slice(z)) = self.view_3d {
+            if z >= size {
+                return usage(format!(
+                    "slice {} is outside the 3D grid of side {} (see --view-3d)",
+                    z, size
+                ));
+            }
+        }
+        if self.backend == Backend::Tiled && !size.is_multiple_of(TILE_SIZE) {
+            return usage(format!(
+                "the tiled backend needs a size multiple of {}, got {} (see --backend)",
+                TILE_SIZE, size
+            ));
+        }
+        if self.backend == Backend::Tiled && self.rule.horizon as usize > TILE_SIZE {
+            return usage(format!(
+                "the tiled backend needs a horizon of at most {} (see --backend)",
+                TILE_SIZE
+            ));
+        }
+        if self.teaching.is_some() && size > output::MAX_TEACHING_SIZE {
+            return usage(format!(
+                "teaching renders grids of at most {} cells, got {} (see --teaching)",
+                output::MAX_TEACHING_SIZE,
+                size
+            ));
+        }
+        if self.skip == 0 || self.skip > self.steps {
+            return usage(format!(
+                "the number of steps skipped between frames must be between 1 and the number \
+                 of steps {} (see --skip)",
+                self.steps
+            ));
+        }
+        if self.scale == 0 {
+            return usage("the scale must be at least 1 (see --scale)".to_string());
+        }
+        let dims = Dimensions::new(size, self.scale);
+        if dims.pixels_u16().is_none() {
+            return usage(format!(
+                "the scaled frames ({} pixels) are too large for a GIF, the largest scale for a \
+                 size of {} is {} (see --scale)",
+                dims.pixels(),
+                size,
+                dims.fit_gif().map_or(0, |fit| fit.scale)
+            ));
+        }
+        if !(0. ..=1.).contains(&self.noise) {
+            return usage(format!(
+                "the noise must be a probability, got {} (see --noise)",
+                self.noise
+            ));
+        }
+        if let BoundaryCondition::Fixed(state) = self.boundary {
+            if state >= self.states {
+                return usage(format!(
+                    "the fixed boundary state {} isn't a state of the CA",
+                    state
+                ));
+            }
+        }
+        if let Some(block) = self.entropy_block {
+            if block == 0 || block > size {
+                return usage(format!(
+                    "the entropy blocks must have between 1 and {} cells per side",
+                    size
+                ));
+            }
+        }
+        if self.smooth == Some(0) {
+            return usage("the smoothing window must be at least 1 step".to_string());
+        }
+        if self.format == OutputFormat::Png && self.output.is_none() {
+            return usage("PNG sequences need an output prefix (see --output)".to_string());
+        }
+        if self
+            .patterns
+            .iter()
+            .filter(|p| p.placement.is_none())
+            .count()
+            > 1
+        {
+            return usage(
+                "only one pattern can be centered, place the others with FILE@X,Y (see --pattern)"
+                    .to_string(),
+            );
+        }
+        for pattern in &self.patterns {
+            let shape = automaton::pattern_shape(&pattern.path)?;
+            let (rows, cols) = match pattern.placement {
+                Some((_, _, Rotation::Rot90 | Rotation::Rot270, _)) => (shape.cols, shape.rows),
+                _ => (shape.rows, shape.cols),
+            };
+            let (x, y) = pattern.placement.map_or((0, 0), |(x, y, ..)| (x, y));
+            if y + rows > size || x + cols > size {
+                return usage(format!(
+                    "the pattern {} ({}x{}) doesn't fit in the grid ({}x{})",
+                    pattern, rows, cols, size, size
+                ));
+            }
+            if shape.states > self.states || shape.background >= self.states {
+                return usage(format!(
+                    "the pattern uses {} states but the CA has {} (see --states)",
+                    shape.states.max(shape.background + 1),
+                    self.states
+                ));
+            }
+        }
+        if self.stats.as_deref() == Some("-")
+            && self.output.is_none()
+            && self.format != OutputFormat::Null
+        {
+            return usage(
+                "the frames and the statistics can't both be written to the standard output \
+                 (see --stats)"
+                    .to_string(),
+            );
+        }
+        if self.max_fps.is_some() && (self.format != OutputFormat::Raw || self.output.is_some()) {
+            return usage(
+                "the frame rate can only be limited for raw frames written to the standard \
+                 output (see --max-fps)"
+                    .to_string(),
+            );
+        }
+        if let Some(ByteSize(ceiling)) = self.max_memory {
+            let estimate = self.memory_estimate();
+            if estimate.total() > ceiling {
+                return usage(format!(
+                    "the simulation would use about {}, more than the limit of {} (see \
+                     --max-memory)",
+                    estimate,
+                    human_bytes(ceiling)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the `--palette` option: a palette name or list of colors, or
+/// else the path of a palette file.
+fn load_palette(spec: &str) -> Result<output::Palette, CliError> {
+    let palette = match spec.parse() {
+        Err(_) if !spec.starts_with('#') => output::Palette::from_file(spec),
+        palette => palette,
+    };
+    palette.map_err(|e| {
+        let source = match &e {
+            output::PaletteError::PaletteFileError(err) => format!(": {}", err),
+            output::PaletteError::PaletteFormatError => String::new(),
+        };
+        CliError::Usage(format!("{}{} (see --palette)", e, source))
+    })
 }
 
-fn make_new_rule<P: AsRef<Path>>(
+fn make_new_rule(
     sampling_mode: SamplingMode,
+    neighborhood: Neighborhood,
     horizon: i8,
     states: u8,
-    path: Option<P>,
-) -> Result<Rule, std::io::Error> {
-    let rule = match sampling_mode {
-        rule::SamplingMode::Dirichlet => Rule::random_dirichlet(horizon, states, None),
-        rule::SamplingMode::Uniform => Rule::random(horizon, states),
+    seed: Option<u64>,
+) -> Rule {
+    let mut rng: Box<dyn RngCore> = match seed {
+        Some(seed) => Box::new(StdRng::seed_from_u64(seed)),
+        None => Box::new(rand::thread_rng()),
     };
+    Rule::random_with_neighborhood(horizon, states, neighborhood, sampling_mode, &mut rng)
+}
+
+/// The number of random rules sampled to find one that isn't in the catalog.
+const CATALOG_ATTEMPTS: u64 = 1000;
 
-    if let Some(path) = path {
-        rule.to_file(path)?;
+/// Samples a random rule. The samples equivalent to a rule of the catalog
+/// (once symmetrized if `symmetric` is set) are skipped, and a rule is
+/// sampled again with the next seed. Returns the rule and the number of
+/// skipped samples.
+fn sample_new_rule(
+    sampling_mode: SamplingMode,
+    neighborhood: Neighborhood,
+    horizon: i8,
+    states: u8,
+    symmetric: bool,
+    seed: Option<u64>,
+    catalog: Option<&RuleCatalog>,
+) -> Result<(Rule, u64), CliError> {
+    for attempt in 0..CATALOG_ATTEMPTS {
+        let rule = make_new_rule(
+            sampling_mode,
+            neighborhood,
+            horizon,
+            states,
+            seed.map(|s| s.wrapping_add(attempt)),
+        );
+        let mut simulated = rule.clone();
+        if symmetric {
+            simulated.symmetrize();
+        }
+        match catalog {
+            Some(catalog) if catalog.lookup(&simulated).is_some() => continue,
+            _ => return Ok((rule, attempt)),
+        }
     }
-    Ok(rule)
+    Err(CliError::Usage(format!(
+        "all the {} sampled rules are equivalent to rules of the catalog",
+        CATALOG_ATTEMPTS
+    )))
 }
 
 enum RuleWrite {
@@ -184,40 +1514,1043 @@ enum RuleWrite {
 
 /// Generate a gif file from a automaton implementing AutomatonImpl. Will use
 /// the options defined in `opts`.
-fn generate_gif_from_init<T: AutomatonImpl>(a: &mut T, opts: &SimulationOpts) {
-    if let Some(fname) = &opts.pattern {
-        a.init_from_pattern(fname).unwrap();
-    } else {
+fn generate_gif_from_init<T: AutomatonImpl>(
+    a: &mut T,
+    opts: &SimulationOpts,
+) -> Result<(), CliError> {
+    if let Some(seed) = opts.seed {
+        a.set_seed(seed);
+    }
+    if opts.patterns.is_empty() {
         a.random_init();
     }
-    output::write_to_gif_file(
-        opts.output.as_ref(),
-        a,
-        opts.scale,
-        opts.steps,
-        opts.skip,
-        opts.delay,
-        opts.rotate,
-    )
-    .expect("Error writing output");
+    // The centered pattern clears the grid, so it goes before the others.
+    for pattern in opts.patterns.iter().filter(|p| p.placement.is_none()) {
+        a.init_from_pattern(&pattern.path)?;
+    }
+    for pattern in &opts.patterns {
+        if let Some((x, y, rotation, flip)) = pattern.placement {
+            a.place_pattern(&Pattern::from_file(&pattern.path)?, x, y, rotation, flip)?;
+        }
+    }
+    a.set_noise(opts.noise);
+    a.set_threads(opts.threads);
+    a.set_boundary(opts.boundary);
+    if opts.watch {
+        let view = output::TerminalView {
+            palette: opts.palette.clone(),
+            palette_rotate: opts.palette_rotate,
+            skip: opts.skip,
+            delay: Duration::from_millis(10 * opts.delay as u64),
+        };
+        eprintln!("{}", output::watch(a, &view)?);
+        return write_snapshot(a, opts);
+    }
+    if let Some(prefix) = &opts.moment_clips {
+        let moments = output::write_moment_clips(
+            prefix,
+            a,
+            opts.scale,
+            opts.steps,
+            opts.delay,
+            &Default::default(),
+        )?;
+        eprintln!("Wrote clips for moments at steps {:?}", moments);
+        return write_snapshot(a, opts);
+    }
+    let mut twin = match &opts.diff_rule {
+        Some(rule) => Some(twin(a, rule.clone(), opts)?),
+        None => None,
+    };
+    let report = if let Some(block) = opts.entropy_block {
+        output::write_entropy_to_gif_file(
+            opts.output.as_ref(),
+            a,
+            opts.scale,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            block,
+        )
+    } else if let Some(window) = opts.smooth {
+        output::write_smoothed_to_gif_file(
+            opts.output.as_ref(),
+            a,
+            opts.scale,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            &output::Smoothing {
+                window,
+                palette: opts.palette.clone(),
+                rotate: opts.palette_rotate,
+            },
+        )
+    } else if let Some(trail) = opts.defects {
+        output::write_defects_to_gif_file(
+            opts.output.as_ref(),
+            a,
+            opts.scale,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            &output::DefectRendering {
+                trail,
+                ..Default::default()
+            },
+        )
+    } else if let Some(b) = twin.as_mut() {
+        output::write_difference_to_gif_file(
+            opts.output.as_ref(),
+            (&mut *a, b),
+            opts.scale,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            DIFFERENCE_TRAIL,
+        )
+    } else if let Some(cell) = opts.teaching {
+        output::write_teaching_gif_file(
+            opts.output.as_ref(),
+            a,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            &output::Teaching {
+                cell,
+                labels: true,
+                rule: opts.teaching_indices.then(|| opts.rule.clone()),
+                palette: opts.palette.clone(),
+                palette_rotate: opts.palette_rotate,
+            },
+        )
+    } else if let Some(every) = opts.palette_cycle {
+        output::write_palette_cycle_to_gif_file(
+            opts.output.as_ref(),
+            a,
+            opts.scale,
+            opts.steps,
+            opts.skip,
+            opts.delay,
+            &output::PaletteCycle {
+                palette: opts.palette.clone(),
+                rotate: opts.palette_rotate,
+                every,
+            },
+        )
+    } else {
+        write_to_format(a, opts)
+    }?;
+    eprintln!("{}", report);
+    if let Some(b) = &twin {
+        eprintln!(
+            "{:.1}% of the cells disagree at the last step",
+            100. * stats::activity(&a.grid(), &b.grid())
+        );
+    }
+    write_snapshot(a, opts)
+}
+
+/// The number of frames over which the past disagreements of --diff-rule
+/// fade out.
+const DIFFERENCE_TRAIL: u32 = 4;
+
+/// Makes a CA of the backend of `a` under another rule, starting from the
+/// grid of `a` with the same settings, to run in lockstep with it.
+fn twin<T: AutomatonImpl>(a: &T, rule: Rule, opts: &SimulationOpts) -> Result<T, CliError> {
+    let (size, states) = (a.size(), a.states());
+    let mut b = T::new(states, size, rule);
+    let rows = a.grid().chunks(size).map(<[u8]>::to_vec).collect();
+    b.place_pattern(&Pattern::new(states, 0, rows)?, 0, 0, Rotation::Rot0, false)?;
+    if let Some(seed) = opts.seed {
+        b.set_seed(seed);
+    }
+    b.set_noise(opts.noise);
+    b.set_threads(opts.threads);
+    b.set_boundary(opts.boundary);
+    Ok(b)
+}
+
+/// Writes the current grid to the snapshot and pattern files if they were
+/// requested.
+fn write_snapshot<T: AutomatonImpl>(a: &T, opts: &SimulationOpts) -> Result<(), CliError> {
+    if let Some(path) = &opts.save_pattern {
+        a.save_pattern(path, None)?;
+    }
+    if let Some(path) = &opts.snapshot {
+        let palette = opts
+            .palette
+            .to_rgb(opts.states as usize, opts.palette_rotate);
+        let mut filter = output::FilterChain::new();
+        filter.push(output::Upscale(opts.scale));
+        if opts.rule.neighborhood() == Neighborhood::Hex {
+            filter.push(output::HexLayout(opts.scale));
+        }
+        output::write_filtered_to_png_file(path, a, &mut filter, &palette)?;
+    }
+    Ok(())
+}
+
+/// Writes the states of the simulation in the chosen format.
+fn write_to_format<T: AutomatonImpl>(
+    a: &mut T,
+    opts: &SimulationOpts,
+) -> Result<output::RunReport, io::Error> {
+    let (steps, skip) = (opts.steps, opts.skip);
+    let mut stats = match opts.stats {
+        Some(_) => Some(StatsCsv::new(vec![], opts.states)?),
+        None => None,
+    };
+    // The statistics are computed on the frames before they are upscaled.
+    let mut filter = output::FilterChain::new();
+    if let Some(csv) = stats.as_mut() {
+        filter.push(csv);
+    }
+    if matches!(
+        opts.format,
+        OutputFormat::Gif | OutputFormat::Apng | OutputFormat::Png
+    ) {
+        filter.push(output::Upscale(opts.scale));
+        if opts.rule.neighborhood() == Neighborhood::Hex {
+            filter.push(output::HexLayout(opts.scale));
+        }
+    }
+    let report = match (opts.format, &opts.output) {
+        (OutputFormat::Gif | OutputFormat::Apng, path) => {
+            let animation = output::Animation {
+                format: if opts.format == OutputFormat::Apng {
+                    output::AnimationFormat::Apng
+                } else {
+                    output::AnimationFormat::Gif
+                },
+                delay: opts.delay,
+                palette: opts.palette.clone(),
+                palette_rotate: opts.palette_rotate,
+            };
+            output::write_animation_file(path.as_ref(), a, steps, skip, &mut filter, &animation)
+        }
+        (OutputFormat::Png, Some(prefix)) => {
+            let mut sink = output::PngSequenceSink::new(
+                prefix,
+                opts.palette
+                    .to_rgb(opts.states as usize, opts.palette_rotate),
+            );
+            output::write_to_sink(a, steps, skip, &mut filter, &mut sink)
+        }
+        (OutputFormat::Png, None) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PNG sequences need an output prefix",
+        )),
+        (OutputFormat::Raw, Some(path)) => {
+            let mut f = output::AtomicFile::create(path)?;
+            let report =
+                output::write_to_sink(a, steps, skip, &mut filter, &mut output::RawSink(&mut f))?;
+            f.commit()?;
+            Ok(report)
+        }
+        (OutputFormat::Raw, None) => {
+            let control = output::ThrottleControl::new();
+            control.set_max_fps(opts.max_fps.unwrap_or(0));
+            output::write_to_sink(
+                a,
+                steps,
+                skip,
+                &mut filter,
+                &mut output::ThrottledSink::new(output::RawSink(io::stdout().lock()), control),
+            )
+        }
+        (OutputFormat::Npy, Some(path)) => {
+            let mut f = output::AtomicFile::create(path)?;
+            let report = output::write_to_sink(
+                a,
+                steps,
+                skip,
+                &mut filter,
+                &mut output::NpySink::new(&mut f, steps / skip),
+            )?;
+            f.commit()?;
+            Ok(report)
+        }
+        (OutputFormat::Npy, None) => output::write_to_sink(
+            a,
+            steps,
+            skip,
+            &mut filter,
+            &mut output::NpySink::new(io::stdout().lock(), steps / skip),
+        ),
+        _ => output::write_to_sink(a, steps, skip, &mut filter, &mut output::NullSink),
+    }?;
+    drop(filter);
+    if let (Some(csv), Some(path)) = (stats, &opts.stats) {
+        let csv = csv.into_inner()?;
+        if path == "-" {
+            io::stdout().lock().write_all(&csv)?;
+        } else {
+            let mut f = output::AtomicFile::create(path)?;
+            f.write_all(&csv)?;
+            f.commit()?;
+        }
+    }
+    Ok(report)
+}
+
+/// Checks the backends on a random rule and a random outer totalistic rule
+/// (which the totalistic backend can run), and prints which ones are correct
+/// and fastest.
+fn doctor(size: usize, steps: u32) -> Result<(), CliError> {
+    if size == 0 || steps == 0 {
+        return Err(CliError::Usage(
+            "the checks need a non-empty grid and one step (see --size and --steps)".to_string(),
+        ));
+    }
+    let rules = [
+        ("random rule", Rule::random(1, 3)),
+        (
+            "totalistic rule",
+            TotalisticRule::random(1, 3, true).to_rule(),
+        ),
+    ];
+    let mut correct = true;
+    for (kind, rule) in &rules {
+        println!("{}:", kind);
+        let checks = automaton::check_backends(rule, size, steps);
+        for check in &checks {
+            println!("{}", check);
+        }
+        if let Some(fastest) = checks.iter().filter(|c| c.correct).max_by(|a, b| {
+            a.cell_updates_per_sec()
+                .total_cmp(&b.cell_updates_per_sec())
+        }) {
+            println!("fastest: {}", fastest.name);
+        }
+        correct &= checks.iter().all(|c| c.correct);
+    }
+    if !correct {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs a rule search and writes the best rules.
+fn search(args: SearchArgs) -> Result<(), CliError> {
+    let SearchArgs {
+        samples,
+        top,
+        states,
+        horizon,
+        size,
+        steps,
+        rule_sampling,
+        seed,
+        exemplars,
+        catalog,
+        output,
+    } = args;
+    if states < 2 || size == 0 || steps == 0 {
+        return Err(CliError::Usage(
+            "searches need at least 2 states, a non-empty grid and one step".to_string(),
+        ));
+    }
+    if exemplars == Some(0) {
+        return Err(CliError::Usage(
+            "searches need at least one cluster of exemplars (see --exemplars)".to_string(),
+        ));
+    }
+    check_sampled_rules(horizon, states)?;
+    let mut search = Search::new(horizon, states, size, steps);
+    search.set_sampling(rule_sampling);
+    if let Some(seed) = seed {
+        search.set_seed(seed);
+    }
+    if let Some(path) = &catalog {
+        search.set_catalog(RuleCatalog::from_file(path)?);
+    }
+    add_fitness(&mut search);
+    let mut best = search.run(samples, top);
+    if let Some(k) = exemplars {
+        best = search.exemplars(&best, k);
+    }
+    let paths = search::write_rules(&output, &best).map_err(CliError::Output)?;
+    if let (Some(recorded), Some(path)) = (search.catalog_mut(), &catalog) {
+        for candidate in &best {
+            recorded.record(&candidate.rule);
+        }
+        recorded.to_file(path).map_err(RuleError::from)?;
+    }
+    for (candidate, path) in best.iter().zip(&paths) {
+        println!("{:.4} {}", candidate.score, path.display());
+    }
+    Ok(())
+}
+
+/// Checks the horizon and the table size of the rules sampled by the search
+/// and sweep subcommands.
+fn check_sampled_rules(horizon: i8, states: u8) -> Result<(), CliError> {
+    if horizon < 1 {
+        return Err(CliError::Usage(
+            "the horizon must be at least 1 (see --horizon)".to_string(),
+        ));
+    }
+    if Rule::checked_table_size(horizon, states, Neighborhood::Moore).is_err() {
+        return Err(CliError::Usage(format!(
+            "the table of a rule of horizon {} with {} states would be larger than 64 MiB \
+             (see --horizon)",
+            horizon, states
+        )));
+    }
+    Ok(())
+}
+
+/// Adds the fitness functions of the search and sweep subcommands.
+fn add_fitness(search: &mut Search) {
+    search.add_fitness(
+        1.,
+        EntropyBand {
+            low: 0.2,
+            high: 0.8,
+        },
+    );
+    search.add_fitness(1., Activity { min: 0.01 });
+    search.add_fitness(1., Compressibility);
+}
+
+/// Runs a lambda sweep and renders its rules.
+fn sweep(args: SweepArgs) -> Result<(), CliError> {
+    let SweepArgs {
+        points,
+        states,
+        horizon,
+        size,
+        steps,
+        scale,
+        seed,
+        output,
+    } = args;
+    if states < 2 || size == 0 || steps == 0 || points == 0 {
+        return Err(CliError::Usage(
+            "sweeps need at least 2 states, a non-empty grid, one step and one point".to_string(),
+        ));
+    }
+    check_sampled_rules(horizon, states)?;
+    let mut search = Search::new(horizon, states, size, steps);
+    search.set_seed(seed.unwrap_or_else(|| rand::thread_rng().next_u64()));
+    add_fitness(&mut search);
+    std::fs::create_dir_all(&output).map_err(CliError::Output)?;
+    for (i, candidate) in search.sweep(points).into_iter().enumerate() {
+        let lambda = candidate.rule.lambda();
+        let path = std::path::Path::new(&output).join(format!("{}_lambda_{:.3}.gif", i, lambda));
+        let mut autom = Automaton::new(states, size, candidate.rule);
+        autom.random_init_seeded(candidate.seed);
+        output::write_to_gif_file(Some(&path), &mut autom, scale, steps, 1, 3, 0)
+            .map_err(CliError::Output)?;
+        println!("{:.3} {:.4} {}", lambda, candidate.score, path.display());
+    }
+    Ok(())
+}
+
+/// Runs a pipeline and renders its stages.
+fn pipeline(config: &str, scale: u16, output: &str) -> Result<(), CliError> {
+    let cli_error = |e| match e {
+        PipelineError::Io(e) => CliError::Output(e),
+        PipelineError::Rule(e) => CliError::Rule(e),
+        PipelineError::Pattern(e) => CliError::Pattern(e),
+        e @ PipelineError::Config { .. } => CliError::Usage(e.to_string()),
+    };
+    let pipeline = Pipeline::from_file(config).map_err(cli_error)?;
+    std::fs::create_dir_all(output).map_err(CliError::Output)?;
+    let mut index = 0;
+    pipeline
+        .run_with(|stage, autom| {
+            let name = format!("{}_{}.gif", index, stage.rule.id());
+            let path = std::path::Path::new(output).join(name);
+            output::write_to_gif_file(Some(&path), autom, scale, stage.steps, 1, 3, 0)?;
+            println!("{}", path.display());
+            index += 1;
+            Ok(())
+        })
+        .map_err(cli_error)?;
+    Ok(())
+}
+
+/// Runs a rule subcommand.
+fn rule_command(command: RuleCommand) -> Result<(), CliError> {
+    match command {
+        RuleCommand::Explain { rule, neighborhood } => {
+            let rule = Rule::from_file(rule.as_str())?;
+            let explanation = rule
+                .explain(&neighborhood)
+                .map_err(|e| CliError::Usage(format!("{} (see --neighborhood)", e)))?;
+            print!("{}", explanation);
+        }
+    }
+    Ok(())
 }
 
 /// Main CLI entrypoint.
 fn main() {
-    let opts: SimulationOpts = SimulationOpts::from_clap_opts(CLIOpts::parse()).unwrap();
-    // If the size of the CA is a multiple of the TILE_SIZE, use the tiled
-    // implementation.
-    if opts.size as usize % (TILE_SIZE - 1) == 0 {
-        generate_gif_from_init(
-            &mut TiledAutomaton::new(opts.states, opts.size.into(), opts.rule.clone()),
+    let mut cli_opts = match CLIOpts::try_parse() {
+        Ok(cli_opts) => cli_opts,
+        // Help and version requests are reported as errors by clap.
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) if std::env::args().any(|arg| arg == "--json-errors") => {
+            let msg = e.to_string();
+            let msg = msg.lines().next().unwrap_or_default();
+            CliError::Usage(msg.trim_start_matches("error: ").to_string()).exit(true)
+        }
+        Err(e) => e.exit(),
+    };
+    let json = cli_opts.json_errors;
+    match cli_opts.command.take() {
+        Some(Command::Doctor { size, steps }) => {
+            return doctor(size, steps).unwrap_or_else(|e| e.exit(json))
+        }
+        Some(Command::Search(args)) => return search(args).unwrap_or_else(|e| e.exit(json)),
+        Some(Command::Sweep(args)) => return sweep(args).unwrap_or_else(|e| e.exit(json)),
+        Some(Command::Pipeline {
+            config,
+            scale,
+            output,
+        }) => return pipeline(&config, scale, &output).unwrap_or_else(|e| e.exit(json)),
+        Some(Command::Rule(command)) => {
+            return rule_command(command).unwrap_or_else(|e| e.exit(json))
+        }
+        None => {}
+    }
+    let opts = SimulationOpts::from_clap_opts(cli_opts).unwrap_or_else(|e| e.exit(json));
+    if opts.dry_run {
+        println!("{}", opts.summary());
+        return;
+    }
+    if opts.output.is_none()
+        && opts.moment_clips.is_none()
+        && !opts.watch
+        && opts.format != OutputFormat::Null
+    {
+        if let Err(e) = output::check_stdout(opts.force_stdout) {
+            CliError::Output(e).exit(json);
+        }
+    }
+    let (states, size) = (opts.states, opts.size.into());
+    let result = match opts.backend {
+        _ if opts.wolfram.is_some() => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(
+                Automaton1D::with_rule(size, Rule1D::elementary(opts.wolfram.unwrap_or(0))),
+                opts.state_rotate,
+            ),
+            &opts,
+        ),
+        _ if opts.rule_3d.is_some() => {
+            let mut autom = Automaton3D::with_rule(
+                size,
+                opts.rule_3d
+                    .clone()
+                    .unwrap_or_else(|| Rule3D::random(1, states)),
+            );
+            if let Some(view) = opts.view_3d {
+                autom.set_view(view);
+            }
+            generate_gif_from_init(
+                &mut StateRotatedAutomaton::wrap(autom, opts.state_rotate),
+                &opts,
+            )
+        }
+        _ if opts.model == Some(Model::Sandpile) => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(Sandpile::with_size(size), opts.state_rotate),
             &opts,
+        ),
+        _ if matches!(opts.model, Some(Model::Cyclic(..))) => {
+            let (threshold, range) = match opts.model {
+                Some(Model::Cyclic(threshold, range)) => (threshold, range),
+                _ => (1, 1),
+            };
+            generate_gif_from_init(
+                &mut StateRotatedAutomaton::wrap(
+                    CyclicAutomaton::with_rule(size, CyclicRule::new(states, threshold, range)),
+                    opts.state_rotate,
+                ),
+                &opts,
+            )
+        }
+        _ if matches!(opts.model, Some(Model::Traffic(_))) => {
+            let density = match opts.model {
+                Some(Model::Traffic(density)) => density,
+                _ => 0.3,
+            };
+            let mut traffic = StateRotatedAutomaton::wrap(
+                Traffic::with_density(size, density),
+                opts.state_rotate,
+            );
+            generate_gif_from_init(&mut traffic, &opts).map(|()| {
+                let traffic = traffic.inner();
+                eprintln!(
+                    "{:.1}% of the cars moved at the last step{}",
+                    100. * traffic.mobility(),
+                    if traffic.is_jammed() { ": jammed" } else { "" }
+                );
+            })
+        }
+        _ if opts.model == Some(Model::Voter) => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(
+                NoisyVoter::with_states(size, states),
+                opts.state_rotate,
+            ),
+            &opts,
+        ),
+        _ if opts.model.is_some() => {
+            let threshold = match opts.model {
+                Some(Model::Bootstrap(threshold)) => threshold,
+                _ => 2,
+            };
+            generate_gif_from_init(
+                &mut StateRotatedAutomaton::wrap(
+                    BootstrapPercolation::with_threshold(size, threshold),
+                    opts.state_rotate,
+                ),
+                &opts,
+            )
+        }
+        Backend::Tiled => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(
+                TiledAutomaton::new(states, size, opts.rule.clone()),
+                opts.state_rotate,
+            ),
+            &opts,
+        ),
+        Backend::Recursive => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(
+                RecursiveAutomaton::new(states, size, opts.rule.clone()),
+                opts.state_rotate,
+            ),
+            &opts,
+        ),
+        // The auto backend is resolved when parsing the options.
+        Backend::Base | Backend::Auto => generate_gif_from_init(
+            &mut StateRotatedAutomaton::wrap(
+                Automaton::new(states, size, opts.rule.clone()),
+                opts.state_rotate,
+            ),
+            &opts,
+        ),
+    };
+    if let Err(e) = result {
+        e.exit(json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        doctor, human_bytes, pipeline, rule_command, search, sweep, ByteSize, CLIOpts, CliError,
+        Command, Model, PatternArg, SimulationOpts,
+    };
+    use clap::Parser;
+    use rust_ca::automaton::Rotation;
+    use rust_ca::rule::{Neighborhood, Rule, RuleCatalog};
+
+    fn parse(args: &[&str]) -> Result<SimulationOpts, CliError> {
+        let args = ["rust_ca"].iter().chain(args).copied();
+        SimulationOpts::from_clap_opts(CLIOpts::try_parse_from(args).unwrap())
+    }
+
+    fn usage_message(args: &[&str]) -> String {
+        match parse(args) {
+            Err(CliError::Usage(msg)) => msg,
+            Err(e) => panic!("expected a usage error, got {}", e),
+            Ok(_) => panic!("expected a usage error for {:?}", args),
+        }
+    }
+
+    #[test]
+    fn default_options_are_valid() {
+        assert!(parse(&[]).is_ok());
+        assert!(parse(&["-r", "GOL", "-s", "512", "-t", "10", "-k", "10"]).is_ok());
+        assert!(parse(&["--backend", "recursive", "-s", "100"]).is_ok());
+        assert!(parse(&["--palette", "viridis"]).is_ok());
+        assert!(parse(&["--palette", "#000000,#ffffff"]).is_ok());
+    }
+
+    #[test]
+    fn inconsistent_options_are_rejected() {
+        assert!(usage_message(&["-r", "GOL", "-n", "3"]).contains("--states"));
+        assert!(usage_message(&["-t", "10", "-k", "20"]).contains("--skip"));
+        assert!(usage_message(&["-k", "0"]).contains("--skip"));
+        assert!(usage_message(&["--noise", "1.5"]).contains("--noise"));
+        assert!(usage_message(&["--boundary", "fixed:2"]).contains("boundary"));
+        assert!(usage_message(&["--entropy-block", "200"]).contains("entropy"));
+        assert!(usage_message(&["--format", "png"]).contains("--output"));
+        assert!(usage_message(&["-s", "4096", "--scale", "16"]).contains("largest scale"));
+        assert!(usage_message(&["--scale", "0"]).contains("--scale"));
+        assert!(usage_message(&["--teaching", "24", "-s", "64"]).contains("--teaching"));
+        let teaching = parse(&[
+            "--teaching",
+            "24",
+            "--teaching-indices",
+            "-s",
+            "8",
+            "--dry-run",
+        ]);
+        assert!(teaching.unwrap().summary().contains("teaching grid"));
+        // Without --scale, large grids are drawn with fewer pixels per cell.
+        let summary = parse(&["-s", "40000", "--dry-run"]).unwrap().summary();
+        assert!(summary.contains("40000x40000 pixels (scale 1)"));
+        assert!(usage_message(&["--use-tiled", "-s", "100"]).contains("--backend"));
+        assert!(usage_message(&["--backend", "tiled", "-s", "100"]).contains("--backend"));
+        assert!(usage_message(&["--palette", "#00"]).contains("--palette"));
+        assert!(usage_message(&["--max-fps", "30"]).contains("--max-fps"));
+        assert!(usage_message(&["--wolfram", "30", "-n", "3"]).contains("--wolfram"));
+        assert!(usage_message(&["--rule-3d", "B5/S45", "-n", "3"]).contains("--rule-3d"));
+        let hex = parse(&["--neighborhood", "hex", "-n", "3", "--dry-run"]).unwrap();
+        assert_eq!(hex.rule.table().len(), 3usize.pow(7));
+        assert!(hex
+            .summary()
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("hex neighborhood"));
+        assert!(usage_message(&["--model", "sandpile"]).contains("4 states"));
+        let sandpile = parse(&["--model", "sandpile", "-n", "4", "--dry-run"]).unwrap();
+        assert!(sandpile
+            .summary()
+            .starts_with("rule: built-in model Abelian sandpile"));
+        let watch = parse(&["--watch", "-k", "2", "--delay", "5", "--dry-run"]).unwrap();
+        assert!(watch
+            .summary()
+            .contains("output: states live in the terminal every 2 steps, 50 ms apart"));
+        assert!(CLIOpts::try_parse_from(["rust_ca", "--watch", "-o", "run.gif"]).is_err());
+        let cyclic = parse(&["--model", "cyclic:3", "-n", "14", "--dry-run"]).unwrap();
+        assert_eq!(cyclic.model, Some(Model::Cyclic(3, 1)));
+        assert!(cyclic
+            .summary()
+            .contains("cyclic CA with threshold 3 and range 1"));
+        let bootstrap = parse(&["--model", "bootstrap:3", "--dry-run"]).unwrap();
+        assert_eq!(bootstrap.model, Some(Model::Bootstrap(3)));
+        assert!(CLIOpts::try_parse_from(["rust_ca", "--model", "bootstrap:x"]).is_err());
+        let traffic = parse(&["--model", "traffic:0.4", "-n", "3", "--dry-run"]).unwrap();
+        assert_eq!(traffic.model, Some(Model::Traffic(0.4)));
+        assert_eq!(traffic.palette, rust_ca::output::Palette::traffic());
+        assert!(CLIOpts::try_parse_from(["rust_ca", "--model", "traffic:2"]).is_err());
+        let voter = parse(&["--model", "voter", "-n", "5", "--dry-run"]).unwrap();
+        assert_eq!(voter.model, Some(Model::Voter));
+        let majority = parse(&["-r", "majority:lowest", "-n", "3", "--dry-run"]).unwrap();
+        assert_eq!(majority.rule.table().len(), 3usize.pow(9));
+        assert!(usage_message(&["-r", "majority:first"]).contains("keep"));
+        let excitable = ["-r", "greenberg-hastings:2", "-n", "8", "--dry-run"];
+        let excitable = parse(&excitable).unwrap();
+        assert_eq!(excitable.rule.neighborhood(), Neighborhood::VonNeumann);
+        assert_eq!(excitable.palette, rust_ca::output::Palette::excitable());
+        let cube = ["--rule-3d", "B5/S45", "-s", "32", "--view-3d", "slice:32"];
+        assert!(usage_message(&cube).contains("--view-3d"));
+        let summary = parse(&cube[..4]).unwrap().summary();
+        assert!(summary.contains("32x32x32 cells") && summary.contains("slice z=16"));
+        assert!(parse(&["--format", "raw", "--max-fps", "30"]).is_ok());
+        assert!(usage_message(&["--stats", "-"]).contains("--stats"));
+        let stats_only = ["--stats", "-", "--format", "null", "--dry-run"];
+        let summary = parse(&stats_only).unwrap().summary();
+        assert!(summary.contains("statistics: CSV to standard output"));
+        assert!(usage_message(&["--palette", "missing.txt"]).contains("palette file"));
+        let smooth = [
+            "--smooth",
+            "8",
+            "--palette",
+            "viridis",
+            "-k",
+            "2",
+            "-t",
+            "100",
+            "-s",
+            "64",
+            "--dry-run",
+        ];
+        let summary = parse(&smooth).unwrap().summary();
+        assert!(summary.contains("state colors mixed over 8 steps"));
+        assert!(summary.contains("50 frames of 256x256 pixels (scale 4) every 2 steps"));
+    }
+
+    #[test]
+    fn dry_runs_describe_the_simulation() {
+        let opts = parse(&[
+            "-r",
+            "B36/S23",
+            "-s",
+            "512",
+            "-t",
+            "100",
+            "-k",
+            "10",
+            "--dry-run",
+        ]);
+        let summary = opts.unwrap().summary();
+        assert!(summary.contains("built-in B36/S23"));
+        assert!(summary.contains("tiled backend"));
+        assert!(summary.contains("horizon 1 (binary-horizon-1 kernel)"));
+        assert!(summary.contains("10 frames of 1536x1536 pixels (scale 3)"));
+        assert!(summary.contains("at most 22.5 MiB"));
+        assert!(summary.contains("estimated memory: 5.0 MiB (grids 520.0 KiB"));
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(3 << 30), "3.0 GiB");
+    }
+
+    #[test]
+    fn seeds_make_rules_reproducible() {
+        let rule = |args: &[&str]| parse(args).unwrap().rule.id();
+        assert_eq!(rule(&["--seed", "3"]), rule(&["--seed", "3"]));
+        assert_ne!(rule(&["--seed", "3"]), rule(&["--seed", "4"]));
+        let uniform = ["--rule-sampling", "uniform", "--seed", "3"];
+        assert_eq!(rule(&uniform), rule(&uniform));
+        assert!(parse(&["--seed", "3", "--dry-run"])
+            .unwrap()
+            .summary()
+            .contains("(seed 3)"));
+    }
+
+    #[test]
+    fn catalogs_skip_equivalent_rules() {
+        let path = std::env::temp_dir().join("rust_ca_cli_catalog.txt");
+        let _ = std::fs::remove_file(&path);
+        let catalog = path.to_str().unwrap();
+        let first = parse(&["--seed", "3", "--catalog", catalog]).unwrap();
+        assert_eq!(first.rule.id(), parse(&["--seed", "3"]).unwrap().rule.id());
+        // The rule of seed 3 is in the catalog, the one of seed 4 is sampled.
+        let dry_run = parse(&["--seed", "3", "--catalog", catalog, "--dry-run"]).unwrap();
+        assert_eq!(
+            dry_run.rule.id(),
+            parse(&["--seed", "4"]).unwrap().rule.id()
         );
+        assert!(dry_run.summary().contains("1 equivalent rule(s) skipped"));
+        assert_eq!(RuleCatalog::from_file(&path).unwrap().len(), 1);
+        parse(&["--rule", "GOL", "--catalog", catalog]).unwrap();
+        parse(&["--rule", "GOL", "--catalog", catalog]).unwrap();
+        assert_eq!(RuleCatalog::from_file(&path).unwrap().len(), 2);
+        std::fs::remove_file(&path).unwrap();
     }
-    // Otherwise use the default implementation.
-    else {
-        generate_gif_from_init(
-            &mut Automaton::new(opts.states, opts.size.into(), opts.rule.clone()),
-            &opts,
+
+    #[test]
+    fn searches_write_their_best_rules() {
+        let dir = std::env::temp_dir().join("rust_ca_cli_search");
+        let catalog = std::env::temp_dir().join("rust_ca_cli_search_catalog.txt");
+        let args = [
+            "rust_ca",
+            "search",
+            "--samples",
+            "5",
+            "--top",
+            "2",
+            "-s",
+            "16",
+        ];
+        let args = args.iter().copied().chain([
+            "-o",
+            dir.to_str().unwrap(),
+            "--catalog",
+            catalog.to_str().unwrap(),
+        ]);
+        match CLIOpts::try_parse_from(args).unwrap().command {
+            Some(Command::Search(args)) => search(args).unwrap(),
+            command => panic!("expected a search, got {:?}", command),
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(RuleCatalog::from_file(&catalog).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&catalog).unwrap();
+    }
+
+    /// Runs a search, a sweep or the checks of the backends, expecting a
+    /// usage error.
+    fn subcommand_usage(args: &[&str]) -> String {
+        let args = ["rust_ca"].iter().chain(args).copied();
+        let result = match CLIOpts::try_parse_from(args).unwrap().command {
+            Some(Command::Search(args)) => search(args),
+            Some(Command::Sweep(args)) => sweep(args),
+            Some(Command::Doctor { size, steps }) => doctor(size, steps),
+            command => panic!("expected a search, a sweep or a doctor, got {:?}", command),
+        };
+        match result {
+            Err(CliError::Usage(msg)) => msg,
+            Err(e) => panic!("expected a usage error, got {}", e),
+            Ok(()) => panic!("expected a usage error"),
+        }
+    }
+
+    #[test]
+    fn searches_reject_invalid_horizons() {
+        let args = [
+            "search",
+            "--samples",
+            "2",
+            "--horizon",
+            "4",
+            "-s",
+            "8",
+            "-t",
+            "5",
+        ];
+        let msg = subcommand_usage(&args);
+        assert!(msg.contains("larger than 64 MiB (see --horizon)"));
+        assert!(subcommand_usage(&["search", "--horizon=-1"]).contains("--horizon"));
+        assert!(subcommand_usage(&["search", "--horizon=0"]).contains("--horizon"));
+    }
+
+    #[test]
+    fn sweeps_reject_invalid_horizons() {
+        let msg = subcommand_usage(&["sweep", "--points", "2", "--horizon", "5"]);
+        assert!(msg.contains("larger than 64 MiB (see --horizon)"));
+        assert!(subcommand_usage(&["sweep", "--horizon=-1"]).contains("--horizon"));
+    }
+
+    #[test]
+    fn empty_backend_checks_are_rejected() {
+        assert!(subcommand_usage(&["doctor", "--size", "0"]).contains("--size"));
+        assert!(subcommand_usage(&["doctor", "--steps", "0"]).contains("--steps"));
+    }
+
+    #[test]
+    fn sweeps_render_every_rule() {
+        let dir = std::env::temp_dir().join("rust_ca_cli_sweep");
+        let args = ["rust_ca", "sweep", "--points", "3", "-s", "8", "-t", "4"];
+        let args = args.iter().copied().chain(["-o", dir.to_str().unwrap()]);
+        match CLIOpts::try_parse_from(args).unwrap().command {
+            Some(Command::Sweep(args)) => sweep(args).unwrap(),
+            command => panic!("expected a sweep, got {:?}", command),
+        }
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names[0], "0_lambda_0.000.gif");
+        assert_eq!(names.len(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn second_rules_match_the_states() {
+        let path = std::env::temp_dir().join("rust_ca_cli_diff.rule");
+        Rule::from_bs_notation("B36/S23")
+            .unwrap()
+            .to_file(&path)
+            .unwrap();
+        let second = path.to_str().unwrap();
+        let opts = parse(&["-r", "GOL", "--diff-rule", second]).unwrap();
+        assert!(opts.summary().contains("disagreement with rule"));
+        let msg = usage_message(&["-n", "3", "-r", "majority", "--diff-rule", second]);
+        assert!(msg.contains("--diff-rule"));
+        let args = ["rust_ca", "--diff-rule", second, "--defects", "4"];
+        assert!(CLIOpts::try_parse_from(args).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pipelines_render_every_stage() {
+        let dir = std::env::temp_dir().join("rust_ca_cli_pipeline");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("pipeline.txt");
+        std::fs::write(&config, "size 8\nstage gol 3\nblock 2\nstage gol 3\n").unwrap();
+        let out = dir.join("out");
+        let args = ["rust_ca", "pipeline", config.to_str().unwrap()];
+        let args = args.iter().copied().chain(["-o", out.to_str().unwrap()]);
+        match CLIOpts::try_parse_from(args).unwrap().command {
+            Some(Command::Pipeline {
+                config,
+                scale,
+                output,
+            }) => pipeline(&config, scale, &output).unwrap(),
+            command => panic!("expected a pipeline, got {:?}", command),
+        }
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 2);
+        std::fs::write(&config, "stage life 3\n").unwrap();
+        let err = pipeline(config.to_str().unwrap(), 1, out.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, CliError::Rule(_)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rules_are_explained() {
+        let path = std::env::temp_dir().join("rust_ca_cli_explain.rule");
+        Rule::gol().to_file(&path).unwrap();
+        let explain = |neighborhood: &str| {
+            let args = ["rust_ca", "rule", "explain", path.to_str().unwrap()];
+            let args = args.iter().copied().chain(["--neighborhood", neighborhood]);
+            match CLIOpts::try_parse_from(args).unwrap().command {
+                Some(Command::Rule(command)) => rule_command(command),
+                command => panic!("expected a rule command, got {:?}", command),
+            }
+        };
+        explain("010/111/000").unwrap();
+        assert!(matches!(explain("010/111"), Err(CliError::Usage(..))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn memory_ceilings_are_enforced() {
+        assert_eq!("1024".parse(), Ok(ByteSize(1024)));
+        assert_eq!("512M".parse(), Ok(ByteSize(512 << 20)));
+        assert_eq!("4GiB".parse(), Ok(ByteSize(4 << 30)));
+        assert_eq!("2 KB".parse(), Ok(ByteSize(2 << 10)));
+        assert!("4X".parse::<ByteSize>().is_err());
+        assert!("G".parse::<ByteSize>().is_err());
+        let message = usage_message(&["-s", "4096", "--max-memory", "64M"]);
+        assert!(message.contains("--max-memory"), "{}", message);
+        assert!(message.contains("limit of 64.0 MiB"), "{}", message);
+        assert!(parse(&["-s", "4096", "--max-memory", "1G"]).is_ok());
+        // Huge rules are rejected before they are sampled.
+        let args = [
+            "--horizon",
+            "3",
+            "-n",
+            "2",
+            "--max-memory",
+            "1G",
+            "--dry-run",
+        ];
+        assert!(usage_message(&args).contains("--horizon"));
+        let args = [
+            "--horizon",
+            "2",
+            "-n",
+            "2",
+            "--max-memory",
+            "16M",
+            "--dry-run",
+        ];
+        assert!(usage_message(&args).contains("--max-memory"));
+        assert!(parse(&["--horizon", "2", "-n", "2", "--dry-run"]).is_ok());
+    }
+
+    #[test]
+    fn patterns_must_fit_in_the_grid() {
+        let pattern = ["-p", "patterns/exploding.pat"];
+        assert!(usage_message(&[&pattern[..], &["-n", "3", "-s", "3"]].concat()).contains("fit"));
+        assert!(usage_message(&[&pattern[..], &["-n", "2"]].concat()).contains("--states"));
+        assert!(parse(&[&pattern[..], &["-n", "3"]].concat()).is_ok());
+        assert!(matches!(
+            parse(&["-p", "missing.pat"]),
+            Err(CliError::Pattern(..))
+        ));
+    }
+
+    #[test]
+    fn patterns_are_placed() {
+        assert_eq!(
+            "glider.pat@10,20,rot90".parse(),
+            Ok(PatternArg {
+                path: "glider.pat".to_string(),
+                placement: Some((10, 20, Rotation::Rot90, false)),
+            })
         );
-    };
+        let arg: PatternArg = "a@b.rle@0, 3, flip".parse().unwrap();
+        assert_eq!(arg.path, "a@b.rle");
+        assert_eq!(arg.placement, Some((0, 3, Rotation::Rot0, true)));
+        for bad in ["glider.pat@10", "glider.pat@1,2,rot45", "glider.pat@x,2"] {
+            assert!(bad.parse::<PatternArg>().is_err(), "{}", bad);
+        }
+
+        let glider = "patterns/glider.rle";
+        let placed = format!("{}@13,0,rot90", glider);
+        let opts = parse(&["-p", glider, "-p", &placed, "-s", "16"]).unwrap();
+        assert_eq!(opts.patterns.len(), 2);
+        assert!(opts.summary().contains("glider.rle at (13, 0) rot90"));
+        let outside = format!("{}@14,0", glider);
+        assert!(usage_message(&["-p", &outside, "-s", "16"]).contains("fit"));
+        assert!(usage_message(&["-p", glider, "-p", glider]).contains("centered"));
+    }
 }
@@ -1,12 +1,168 @@
 //! The output utilities. Use to save the CA state to an output GIF.
+//!
+//! The GIF and PNG writers require the `output` feature (enabled by default),
+//! the live terminal viewer the `terminal` feature (enabled by default), the
+//! window viewer the `viewer` feature, the QR code export of rules the `qr`
+//! feature, and the scripted step hooks the `scripting` feature.
 
-use crate::automaton::AutomatonImpl;
-use gif::{Encoder, Frame};
-use std::fs::File;
-use std::io::{self, Write};
+mod atomic;
+pub use atomic::AtomicFile;
+
+mod filter;
+pub use filter::{
+    Crop, Downscale, FilterChain, Frame, FrameFilter, FrameMeta, HexLayout, StateRotation, Upscale,
+};
+
+#[cfg(feature = "output")]
+mod palette;
+#[cfg(feature = "output")]
+pub use palette::{Palette, PaletteError};
+
+mod events;
+pub use events::{Event, EventBus, EventKind};
+
+mod hook;
+pub use hook::{write_to_sink_with_hook, StepHook, StepInfo};
+
+#[cfg(feature = "scripting")]
+mod script;
+#[cfg(feature = "scripting")]
+pub use script::{ScriptError, ScriptHook};
+
+mod sink;
+pub use sink::{write_to_sink, NpySink, NullSink, OutputSink, RawSink};
+#[cfg(feature = "output")]
+pub use sink::{ApngSink, GifSink, PngSequenceSink};
+
+#[cfg(feature = "output")]
+mod teaching;
+#[cfg(feature = "output")]
+pub use teaching::{write_teaching_gif_file, Teaching, MAX_TEACHING_SIZE};
+
+#[cfg(feature = "terminal")]
+mod terminal;
+#[cfg(feature = "terminal")]
+pub use terminal::{render_half_blocks, watch, TerminalView};
+
+#[cfg(feature = "viewer")]
+mod viewer;
+#[cfg(feature = "viewer")]
+pub use viewer::{view_in_window, WindowView};
+
+mod throttle;
+pub use throttle::{ThrottleControl, ThrottledSink};
+
+#[cfg(feature = "output")]
+use crate::analysis;
+#[cfg(feature = "output")]
+use crate::automaton::{duplicate_array, AutomatonImpl, Dimensions};
+#[cfg(feature = "output")]
+use crate::stats;
+#[cfg(feature = "output")]
+use gif::Encoder;
+#[cfg(feature = "output")]
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+#[cfg(feature = "output")]
+use std::io::Write;
+use std::io::{self, IsTerminal};
+#[cfg(feature = "output")]
 use std::path::Path;
+use std::time::Duration;
+#[cfg(feature = "output")]
+use std::time::Instant;
+
+/// A summary of the work done while writing a simulation to a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunReport {
+    /// The number of simulated steps.
+    pub steps: u64,
+    /// The number of cell updates (the number of steps times the number of
+    /// cells).
+    pub cell_updates: u64,
+    /// The number of frames encoded.
+    pub frames: u32,
+    /// The size of the output in bytes.
+    pub bytes: u64,
+    /// The duration of the simulation and encoding.
+    pub elapsed: Duration,
+}
+
+impl RunReport {
+    /// Returns the number of simulated steps per second.
+    pub fn steps_per_sec(&self) -> f64 {
+        self.steps as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Returns the number of cell updates per second.
+    pub fn cell_updates_per_sec(&self) -> f64 {
+        self.cell_updates as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Completes a report with the simulation work, `steps_per_frame` steps of
+    /// a grid of `cells` cells for every frame.
+    fn with_work(mut self, steps_per_frame: u32, cells: usize) -> RunReport {
+        self.steps = self.frames as u64 * steps_per_frame as u64;
+        self.cell_updates = self.steps * cells as u64;
+        self
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} steps in {:.2}s ({:.1} steps/s, {:.3e} cell updates/s), {} frames, {} bytes",
+            self.steps,
+            self.elapsed.as_secs_f64(),
+            self.steps_per_sec(),
+            self.cell_updates_per_sec(),
+            self.frames,
+            self.bytes
+        )
+    }
+}
+
+/// A writer counting the bytes written through it.
+#[cfg(feature = "output")]
+struct CountingWriter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+#[cfg(feature = "output")]
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Checks that binary output can be written to the standard output. Writing a
+/// GIF to a terminal only prints garbage, so this returns an error when the
+/// standard output is a terminal, unless `force` is true.
+pub fn check_stdout(force: bool) -> Result<(), io::Error> {
+    if !force && io::stdout().is_terminal() {
+        Err(io::Error::other(
+            "refusing to write binary output to a terminal, redirect it or write to a file",
+        ))
+    } else {
+        Ok(())
+    }
+}
 
-/// Write the CA state to a GIF file.
+/// Write the CA state to a GIF file. The colors assigned to the states are
+/// rotated by `palette_rotate`; the states themselves are unchanged (see
+/// [`StateRotatedAutomaton`](crate::automaton::StateRotatedAutomaton) to
+/// rotate the states of the grids). The grids are copied to a single frame
+/// buffer and scaled in place, so that large exports don't allocate a scaled
+/// frame per step.
+#[cfg(feature = "output")]
 pub fn write_to_gif_file<P: AsRef<Path>, T>(
     path: Option<P>,
     autom: &mut T,
@@ -14,51 +170,1285 @@ pub fn write_to_gif_file<P: AsRef<Path>, T>(
     steps: u32,
     skip: u32,
     delay: u16,
-    rotate: u8,
+    palette_rotate: u8,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    // Fail before rendering the first frame, which may be huge.
+    gif_side(Dimensions::new(autom.size(), scale))?;
+    let mut chain = FilterChain::new();
+    chain.push(Upscale(scale));
+    write_filtered_to_gif_file(path, autom, steps, skip, delay, palette_rotate, &mut chain)
+}
+
+/// Write the CA state to a GIF file, passing every frame through a chain of
+/// filters (see [`FrameFilter`]) before encoding it. The size of the GIF is
+/// the size of the filtered frames. See [`write_to_sink`] to write the frames
+/// in other formats.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, Crop, FilterChain, Upscale};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 64, Rule::random(1, 2));
+/// automaton.random_init();
+/// // Zoom on the top left corner.
+/// let mut chain = FilterChain::new();
+/// chain.push(Crop { row: 0, col: 0, size: 16 }).push(Upscale(4));
+/// output::write_filtered_to_gif_file(Some("test_filtered.gif"), &mut automaton, 10, 1, 1, 0, &mut chain)?;
+/// # std::fs::remove_file("test_filtered.gif")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "output")]
+pub fn write_filtered_to_gif_file<P: AsRef<Path>, T, F>(
+    path: Option<P>,
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    palette_rotate: u8,
+    filter: &mut F,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+    F: FrameFilter,
+{
+    let animation = Animation {
+        format: AnimationFormat::Gif,
+        delay,
+        palette_rotate,
+        ..Default::default()
+    };
+    write_animation_file(path, autom, steps, skip, filter, &animation)
+}
+
+/// The formats of the animations written by [`write_animation_file`].
+#[cfg(feature = "output")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimationFormat {
+    /// An animated GIF.
+    #[default]
+    Gif,
+    /// An animated PNG, compressed with deflate (see [`ApngSink`]).
+    Apng,
+}
+
+/// Options for encoding animations with [`write_animation_file`].
+#[cfg(feature = "output")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    /// The format of the animation.
+    pub format: AnimationFormat,
+    /// The delay between frames, in hundredths of a second.
+    pub delay: u16,
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The rotation of the palette (the colors, not the states).
+    pub palette_rotate: u8,
+}
+
+#[cfg(feature = "output")]
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            format: AnimationFormat::Gif,
+            delay: 10,
+            palette: Palette::default(),
+            palette_rotate: 0,
+        }
+    }
+}
+
+/// Write the CA state to an animation file (or standard output if `path` is
+/// `None`) in the given format, passing every frame through a chain of
+/// filters like [`write_filtered_to_gif_file`].
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, Animation, AnimationFormat, Upscale};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 64, Rule::gol());
+/// automaton.random_init();
+/// let animation = Animation {
+///     format: AnimationFormat::Apng,
+///     ..Default::default()
+/// };
+/// let path = Some("test_animation.png");
+/// let report = output::write_animation_file(path, &mut automaton, 10, 1, &mut Upscale(2), &animation)?;
+/// assert_eq!(report.frames, 10);
+/// # std::fs::remove_file("test_animation.png")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "output")]
+pub fn write_animation_file<P: AsRef<Path>, T, F>(
+    path: Option<P>,
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    filter: &mut F,
+    animation: &Animation,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+    F: FrameFilter,
+{
+    let palette = animation
+        .palette
+        .to_rgb(autom.states() as usize, animation.palette_rotate);
+    let delay = animation.delay;
+    let run = |w: &mut dyn Write| match animation.format {
+        AnimationFormat::Gif => write_to_sink(
+            autom,
+            steps,
+            skip,
+            filter,
+            &mut GifSink::new(w, palette, delay),
+        ),
+        AnimationFormat::Apng => {
+            let mut sink = ApngSink::new(w, palette, delay, steps / skip);
+            write_to_sink(autom, steps, skip, filter, &mut sink)
+        }
+    };
+    let start = Instant::now();
+    let mut report = if let Some(path) = path {
+        let mut f = CountingWriter {
+            inner: AtomicFile::create(path)?,
+            bytes: 0,
+        };
+        let report = run(&mut f)?;
+        f.inner.commit()?;
+        RunReport {
+            bytes: f.bytes,
+            ..report
+        }
+    } else {
+        let mut f = CountingWriter {
+            inner: io::stdout(),
+            bytes: 0,
+        };
+        let report = run(&mut f)?;
+        RunReport {
+            bytes: f.bytes,
+            ..report
+        }
+    };
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// Write the current grid of the CA to a PNG file, every cell being a
+/// `scale`x`scale` square colored with `palette` (3 bytes per state, see
+/// [`make_palette`]). Use a [`PngSequenceSink`] to write every frame of a
+/// simulation to numbered files.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output;
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(3, 64, Rule::random(1, 3));
+/// automaton.random_init();
+/// let palette = output::make_palette(3, 0);
+/// output::write_to_png_file("test_snapshot.png", &automaton, 4, &palette)?;
+/// # std::fs::remove_file("test_snapshot.png")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "output")]
+pub fn write_to_png_file<P: AsRef<Path>, T>(
+    path: P,
+    autom: &T,
+    scale: u16,
+    palette: &[u8],
 ) -> Result<(), io::Error>
 where
     T: AutomatonImpl,
 {
-    let size = autom.size() as u16;
-    let scaled_size = size * scale;
+    write_filtered_to_png_file(path, autom, &mut Upscale(scale), palette)
+}
+
+/// Write the current grid of the CA to a PNG image like
+/// [`write_to_png_file`], passing it through a filter (see [`FrameFilter`])
+/// instead of upscaling it.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, HexLayout, Upscale};
+/// use rust_ca::rule::Rule;
+///
+/// let automaton = Automaton::new(2, 16, Rule::random(1, 2));
+/// let mut chain = FilterChain::new();
+/// chain.push(Upscale(4)).push(HexLayout(4));
+/// let palette = output::make_palette(2, 0);
+/// output::write_filtered_to_png_file("test_hex.png", &automaton, &mut chain, &palette)?;
+/// # std::fs::remove_file("test_hex.png")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "output")]
+pub fn write_filtered_to_png_file<P: AsRef<Path>, T, F>(
+    path: P,
+    autom: &T,
+    filter: &mut F,
+    palette: &[u8],
+) -> Result<(), io::Error>
+where
+    T: AutomatonImpl,
+    F: FrameFilter + ?Sized,
+{
+    let mut frame = Frame {
+        cells: autom.grid(),
+        size: autom.size(),
+    };
+    let meta = FrameMeta {
+        index: 0,
+        step: 0,
+        states: autom.states(),
+    };
+    filter.apply(&mut frame, &meta);
+    let mut f = AtomicFile::create(path)?;
+    sink::encode_png(&mut f, &frame, palette)?;
+    f.commit()
+}
+
+/// Options for cycling the palette over time with
+/// [`write_palette_cycle_to_gif_file`].
+#[cfg(feature = "output")]
+pub struct PaletteCycle {
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The initial rotation of the palette (the colors, not the states).
+    pub rotate: u8,
+    /// The number of frames between two rotations of the palette.
+    pub every: u32,
+}
+
+#[cfg(feature = "output")]
+impl Default for PaletteCycle {
+    fn default() -> Self {
+        PaletteCycle {
+            palette: Palette::default(),
+            rotate: 0,
+            every: 1,
+        }
+    }
+}
+
+/// Write the CA state to a GIF file, rotating the palette by one state every
+/// `cycle.every` frames. The grids are not recomputed: only the palette of
+/// each frame changes, which animates the state cycles of generations-style
+/// rules.
+#[cfg(feature = "output")]
+pub fn write_palette_cycle_to_gif_file<P: AsRef<Path>, T>(
+    path: Option<P>,
+    autom: &mut T,
+    scale: u16,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    cycle: &PaletteCycle,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    let dims = Dimensions::new(autom.size(), scale);
     let states = autom.states();
+    let palettes: Vec<Vec<u8>> = (0..states)
+        .map(|r| {
+            cycle.palette.to_rgb(
+                states as usize,
+                ((cycle.rotate as u16 + r as u16) % states as u16) as u8,
+            )
+        })
+        .collect();
+    let frames = autom.skipped_iter(steps, skip, scale);
+    write_frames(
+        path,
+        dims,
+        &palettes,
+        cycle.every,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, dims.cells * dims.cells))
+}
+
+/// Write a heatmap of the local entropy of the CA to a GIF file. The grid is
+/// split into `block`x`block` squares and each square is colored according to
+/// the entropy of the states it contains (see [`analysis::local_entropy`]).
+/// This highlights where complex behavior happens on large grids.
+#[cfg(feature = "output")]
+pub fn write_entropy_to_gif_file<P: AsRef<Path>, T>(
+    path: Option<P>,
+    autom: &mut T,
+    scale: u16,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    block: usize,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    let size = autom.size();
+    let states = autom.states();
+    let n_blocks = analysis::blocks_per_side(size, block);
+    let palette = make_heat_palette();
+    let frames = autom.skipped_iter(steps, skip, 1).map(|grid| {
+        let entropy = analysis::local_entropy(&grid, size, states, block);
+        let mut pixels = Vec::with_capacity(size * size);
+        for i in 0..size {
+            for j in 0..size {
+                let e = entropy[(i / block) * n_blocks + j / block];
+                pixels.push((e * 255.).round() as u8);
+            }
+        }
+        duplicate_array(&pixels, Dimensions::new(size, scale))
+    });
+    write_frames(
+        path,
+        Dimensions::new(size, scale),
+        &[palette],
+        1,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, size * size))
+}
+
+/// Options for rendering a temporally smoothed animation with
+/// [`write_smoothed_to_gif_file`].
+#[cfg(feature = "output")]
+pub struct Smoothing {
+    /// The number of steps the states of every frame are taken over.
+    pub window: u32,
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The rotation of the palette (the colors, not the states).
+    pub rotate: u8,
+}
+
+#[cfg(feature = "output")]
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            window: 4,
+            palette: Palette::default(),
+            rotate: 0,
+        }
+    }
+}
+
+/// Write a temporally smoothed animation of the CA to a GIF file, with a
+/// frame every `skip` steps. Every cell of a frame is drawn in the colors of
+/// the states mixed in proportion to the time the cell spent in them over
+/// the last `smoothing.window` steps, which shows the occupancy of the states
+/// by stochastic CAs without their flicker. The proportions are quantized so
+/// that the mixtures fit in the 256 colors of a GIF palette: to the steps of
+/// the window, or to 255ths for 2 states, 21sts for 3 states and 9ths for 4
+/// states over longer windows.
+#[cfg(feature = "output")]
+pub fn write_smoothed_to_gif_file<P: AsRef<Path>, T>(
+    path: Option<P>,
+    autom: &mut T,
+    scale: u16,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    smoothing: &Smoothing,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    assert!(
+        smoothing.window > 0,
+        "The smoothing window must be positive"
+    );
+    let (size, states) = (autom.size(), autom.states() as usize);
+    let window = smoothing.window as usize;
+    let mut mixtures =
+        ColorMixtures::new(&smoothing.palette.to_rgb(states, smoothing.rotate), window);
+    let palette = mixtures.palette();
+    let mut history: VecDeque<Vec<u8>> = VecDeque::with_capacity(window);
+    let mut tally = vec![0u32; states];
+    let mut grids = autom.skipped_iter(steps, 1, 1);
+    let frames = std::iter::from_fn(|| {
+        // The first frame is the initial grid, and the next ones add the
+        // steps simulated since the previous frame to the window.
+        let new_steps = if history.is_empty() { 1 } else { skip };
+        for _ in 0..new_steps {
+            if history.len() == window {
+                history.pop_front();
+            }
+            history.push_back(grids.next()?);
+        }
+        let pixels: Vec<u8> = (0..size * size)
+            .map(|k| {
+                tally.fill(0);
+                for grid in &history {
+                    tally[grid[k] as usize] += 1;
+                }
+                mixtures.index(&tally)
+            })
+            .collect();
+        Some(duplicate_array(&pixels, Dimensions::new(size, scale)))
+    });
+    write_frames(
+        path,
+        Dimensions::new(size, scale),
+        &[palette],
+        1,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, size * size))
+}
+
+/// The mixtures of the colors of the states drawn by the smoothed
+/// animations. The proportions of the states are quantized to `levels`
+/// units, and the palette has a color for every way of spreading the units
+/// over the states.
+#[cfg(feature = "output")]
+struct ColorMixtures {
+    colors: Vec<[f64; 3]>,
+    levels: u32,
+    /// The units of the states of every color of the palette.
+    units: Vec<Vec<u32>>,
+    /// The palette indices of the tallies of states already seen.
+    indices: HashMap<Vec<u32>, u8>,
+}
+
+#[cfg(feature = "output")]
+impl ColorMixtures {
+    /// Makes the mixtures of the RGB `colors` of the states over `window`
+    /// steps, with as many levels as fit in a GIF palette (at most one per
+    /// step of the window).
+    fn new(colors: &[u8], window: usize) -> ColorMixtures {
+        let colors: Vec<[f64; 3]> = colors
+            .chunks_exact(3)
+            .map(|c| [c[0] as f64, c[1] as f64, c[2] as f64])
+            .collect();
+        let states = colors.len();
+        let levels = (1..=window.min(255) as u32)
+            .rev()
+            .find(|&q| mixture_count(q as usize, states) <= 256)
+            .unwrap_or(1);
+        let mut units = vec![];
+        spread_units(levels, states, &mut vec![], &mut units);
+        ColorMixtures {
+            colors,
+            levels,
+            units,
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Returns the RGB palette of the mixtures.
+    fn palette(&self) -> Vec<u8> {
+        self.units
+            .iter()
+            .flat_map(|units| {
+                let mut rgb = [0.; 3];
+                for (color, &u) in self.colors.iter().zip(units) {
+                    for (c, &channel) in rgb.iter_mut().zip(color) {
+                        *c += channel * u as f64 / self.levels as f64;
+                    }
+                }
+                rgb.map(|c| c.round() as u8)
+            })
+            .collect()
+    }
+
+    /// Returns the palette index of the mixture of the states counted in
+    /// `tally`, the proportions being rounded to units with the largest
+    /// remainder method (ties going to the lowest states).
+    fn index(&mut self, tally: &[u32]) -> u8 {
+        if let Some(&index) = self.indices.get(tally) {
+            return index;
+        }
+        let (levels, total) = (self.levels, tally.iter().sum::<u32>().max(1));
+        let mut units: Vec<u32> = tally.iter().map(|&t| t * levels / total).collect();
+        let mut order: Vec<usize> = (0..tally.len()).collect();
+        order.sort_by_key(|&s| std::cmp::Reverse(tally[s] * levels % total));
+        let missing = levels - units.iter().sum::<u32>();
+        for &s in order.iter().take(missing as usize) {
+            units[s] += 1;
+        }
+        let index = self
+            .units
+            .iter()
+            .position(|u| *u == units)
+            .expect("every spread of the units is in the palette") as u8;
+        self.indices.insert(tally.to_vec(), index);
+        index
+    }
+}
+
+/// Returns the number of ways of spreading `levels` units over `states`
+/// states, or 257 if there are more than 256.
+#[cfg(feature = "output")]
+fn mixture_count(levels: usize, states: usize) -> usize {
+    // The binomial coefficient (levels + states - 1, states - 1), computed
+    // with the smallest of the two choices so that it only grows.
+    let n = levels + states - 1;
+    let k = (states - 1).min(levels);
+    let mut count = 1;
+    for i in 0..k {
+        count = count * (n - i) / (i + 1);
+        if count > 256 {
+            return 257;
+        }
+    }
+    count
+}
+
+/// Appends all the ways of spreading `levels` units over `states` states,
+/// after the units of the first states given in `prefix`, to `out`.
+#[cfg(feature = "output")]
+fn spread_units(levels: u32, states: usize, prefix: &mut Vec<u32>, out: &mut Vec<Vec<u32>>) {
+    if prefix.len() + 1 == states {
+        let mut units = prefix.clone();
+        units.push(levels);
+        out.push(units);
+        return;
+    }
+    for u in 0..=levels {
+        prefix.push(u);
+        spread_units(levels - u, states, prefix, out);
+        prefix.pop();
+    }
+}
+
+/// Options for rendering the defects of a CA with
+/// [`write_defects_to_gif_file`].
+#[cfg(feature = "output")]
+pub struct DefectRendering {
+    /// The maximum spatial period of the regular domains (see
+    /// [`analysis::defects`]).
+    pub max_period: usize,
+    /// The number of frames over which past defects fade out. This draws the
+    /// trajectories of the particles moving between domains.
+    pub trail: u32,
+}
+
+#[cfg(feature = "output")]
+impl Default for DefectRendering {
+    fn default() -> Self {
+        DefectRendering {
+            max_period: 2,
+            trail: 8,
+        }
+    }
+}
+
+/// Write the defects of the CA (cells between regular spatial domains, see
+/// [`analysis::defects`]) to a GIF file. Domains are rendered in black and
+/// defects are bright, leaving a fading trail behind them.
+#[cfg(feature = "output")]
+pub fn write_defects_to_gif_file<P: AsRef<Path>, T>(
+    path: Option<P>,
+    autom: &mut T,
+    scale: u16,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    rendering: &DefectRendering,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    let size = autom.size();
+    let palette = make_heat_palette();
+    let fade = (255 / (rendering.trail + 1)).max(1) as u8;
+    let mut intensity = vec![0u8; size * size];
+    let frames = autom.skipped_iter(steps, skip, 1).map(|grid| {
+        let defects = analysis::defects(&grid, size, rendering.max_period);
+        for (v, &d) in intensity.iter_mut().zip(defects.iter()) {
+            *v = if d { 255 } else { v.saturating_sub(fade) };
+        }
+        duplicate_array(&intensity, Dimensions::new(size, scale))
+    });
+    write_frames(
+        path,
+        Dimensions::new(size, scale),
+        &[palette],
+        1,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, size * size))
+}
+
+/// Write the disagreement map of two CAs run in lockstep to a GIF file. The
+/// CAs usually start from the same grid under two different rules, and the
+/// cells in different states in the two grids are bright, leaving a trail
+/// fading over `trail` frames. This shows how far and how fast an edit of a
+/// rule changes its dynamics. Panics if the grids have different sizes.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output;
+/// use rust_ca::rule::Rule;
+///
+/// let mut a = Automaton::new(2, 64, Rule::gol());
+/// let mut b = Automaton::new(2, 64, Rule::from_bs_notation("B36/S23")?);
+/// a.random_init_seeded(3);
+/// b.random_init_seeded(3);
+/// let path = Some("test_difference.gif");
+/// let report = output::write_difference_to_gif_file(path, (&mut a, &mut b), 2, 20, 1, 5, 4)?;
+/// assert_eq!(report.frames, 20);
+/// # std::fs::remove_file("test_difference.gif")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "output")]
+pub fn write_difference_to_gif_file<P: AsRef<Path>, A, B>(
+    path: Option<P>,
+    (a, b): (&mut A, &mut B),
+    scale: u16,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    trail: u32,
+) -> Result<RunReport, io::Error>
+where
+    A: AutomatonImpl,
+    B: AutomatonImpl,
+{
+    let size = a.size();
+    assert_eq!(size, b.size(), "The grids must have the same size");
+    let palette = make_heat_palette();
+    let fade = (255 / (trail + 1)).max(1) as u8;
+    let mut intensity = vec![0u8; size * size];
+    let frames = a
+        .skipped_iter(steps, skip, 1)
+        .zip(b.skipped_iter(steps, skip, 1))
+        .map(|(grid_a, grid_b)| {
+            for ((v, &x), &y) in intensity.iter_mut().zip(&grid_a).zip(&grid_b) {
+                *v = if x != y { 255 } else { v.saturating_sub(fade) };
+            }
+            duplicate_array(&intensity, Dimensions::new(size, scale))
+        });
+    write_frames(
+        path,
+        Dimensions::new(size, scale),
+        &[palette],
+        1,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, 2 * size * size))
+}
+
+/// Options for extracting clips around interesting moments of a long run with
+/// [`write_moment_clips`].
+#[cfg(feature = "output")]
+pub struct MomentClips {
+    /// The number of frames kept before each detected moment.
+    pub before: usize,
+    /// The number of frames recorded after each detected moment.
+    pub after: usize,
+    /// The size of the window of the spike detector (see
+    /// [`analysis::MomentDetector`]).
+    pub window: usize,
+    /// The threshold of the spike detector, in standard deviations.
+    pub threshold: f64,
+    /// The maximum number of clips to write.
+    pub max_clips: usize,
+}
+
+#[cfg(feature = "output")]
+impl Default for MomentClips {
+    fn default() -> Self {
+        MomentClips {
+            before: 20,
+            after: 40,
+            window: 50,
+            threshold: 4.,
+            max_clips: 10,
+        }
+    }
+}
+
+/// Simulates the automaton for `steps` steps, watching the activity (the
+/// fraction of cells changing at each step) for spikes and regime changes.
+/// A short GIF clip `{prefix}_{step}.gif` is written around each detected
+/// moment, so that long runs don't need to be watched entirely. Returns the
+/// steps at which moments were detected.
+#[cfg(feature = "output")]
+pub fn write_moment_clips<T>(
+    prefix: &str,
+    autom: &mut T,
+    scale: u16,
+    steps: u32,
+    delay: u16,
+    clips: &MomentClips,
+) -> Result<Vec<u32>, io::Error>
+where
+    T: AutomatonImpl,
+{
+    let dims = Dimensions::new(autom.size(), scale);
+    let palette = make_palette(autom.states(), 0);
+    let mut detector = analysis::MomentDetector::new(clips.window, clips.threshold);
+    let mut history: VecDeque<Vec<u8>> = VecDeque::with_capacity(clips.before + 1);
+    let mut recording: Option<(u32, Vec<Vec<u8>>)> = None;
+    let mut moments = vec![];
+    let mut previous: Option<Vec<u8>> = None;
+
+    let write_clip = |step: u32, clip: Vec<Vec<u8>>| {
+        let n_frames = clip.len() as u32;
+        write_frames(
+            Some(format!("{}_{}.gif", prefix, step)),
+            dims,
+            std::slice::from_ref(&palette),
+            1,
+            delay,
+            n_frames,
+            clip.into_iter().map(|g| duplicate_array(&g, dims)),
+        )
+    };
 
-    let mut im_file = if let Some(path) = path {
-        Box::new(File::create(path)?) as Box<dyn Write>
+    for (step, grid) in autom.skipped_iter(steps, 1, 1).enumerate() {
+        let step = step as u32;
+        let flagged = previous
+            .as_ref()
+            .is_some_and(|p| detector.push(stats::activity(p, &grid)));
+        if let Some((_, clip)) = recording.as_mut() {
+            clip.push(grid.clone());
+            if clip.len() > clips.before + clips.after {
+                let (start, clip) = recording.take().unwrap();
+                write_clip(start, clip)?;
+            }
+        } else if flagged && moments.len() < clips.max_clips {
+            moments.push(step);
+            let mut clip: Vec<Vec<u8>> = history.iter().cloned().collect();
+            clip.push(grid.clone());
+            recording = Some((step, clip));
+        }
+        if clips.before > 0 {
+            if history.len() == clips.before {
+                history.pop_front();
+            }
+            history.push_back(grid.clone());
+        }
+        previous = Some(grid);
+    }
+    if let Some((start, clip)) = recording {
+        write_clip(start, clip)?;
+    }
+    Ok(moments)
+}
+
+/// Returns the side of the GIF images of frames of the given dimensions, or an
+/// [`io::ErrorKind::InvalidInput`] error if it is larger than the 65535
+/// pixels allowed by the format. The error suggests the largest scale that
+/// fits (see [`Dimensions::fit_gif`]).
+#[cfg(feature = "output")]
+pub(crate) fn gif_side(dims: Dimensions) -> Result<u16, io::Error> {
+    dims.pixels_u16().ok_or_else(|| {
+        let hint = match dims.fit_gif() {
+            Some(fit) => format!("use a scale of at most {}", fit.scale),
+            None => "crop or coarse-grain the grid".to_string(),
+        };
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "frames of {} pixels ({} cells at scale {}) are too large for a GIF, {}",
+                dims.pixels(),
+                dims.cells,
+                dims.scale,
+                hint
+            ),
+        )
+    })
+}
+
+/// Encode a sequence of square frames of palette indices to a GIF file (or
+/// standard output if `path` is `None`). The frames use the palettes in turn,
+/// moving to the next one every `every` frames. The returned report contains
+/// the number of frames, the output size and the elapsed time.
+#[cfg(feature = "output")]
+fn write_frames<P: AsRef<Path>, I>(
+    path: Option<P>,
+    dims: Dimensions,
+    palettes: &[Vec<u8>],
+    every: u32,
+    delay: u16,
+    n_frames: u32,
+    frames: I,
+) -> Result<RunReport, io::Error>
+where
+    I: Iterator<Item = Vec<u8>>,
+{
+    let scaled_size = gif_side(dims)?;
+    let start = Instant::now();
+    let mut report = if let Some(path) = path {
+        let mut f = CountingWriter {
+            inner: AtomicFile::create(path)?,
+            bytes: 0,
+        };
+        let frames = encode_frames(
+            &mut f,
+            scaled_size,
+            (palettes, every),
+            delay,
+            n_frames,
+            frames,
+        )?;
+        f.inner.commit()?;
+        RunReport {
+            frames,
+            bytes: f.bytes,
+            ..Default::default()
+        }
     } else {
-        Box::new(io::stdout()) as Box<dyn Write>
+        let mut f = CountingWriter {
+            inner: io::stdout(),
+            bytes: 0,
+        };
+        let frames = encode_frames(
+            &mut f,
+            scaled_size,
+            (palettes, every),
+            delay,
+            n_frames,
+            frames,
+        )?;
+        RunReport {
+            frames,
+            bytes: f.bytes,
+            ..Default::default()
+        }
     };
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
 
-    let mut g = Encoder::new(&mut im_file, scaled_size, scaled_size, &[]).unwrap();
+/// Encodes frames as a GIF animation to a writer, returning the number of
+/// frames encoded. The palettes are used in turn (see [`write_frames`]).
+#[cfg(feature = "output")]
+fn encode_frames<W: Write, I>(
+    w: &mut W,
+    scaled_size: u16,
+    (palettes, every): (&[Vec<u8>], u32),
+    delay: u16,
+    n_frames: u32,
+    frames: I,
+) -> Result<u32, io::Error>
+where
+    I: Iterator<Item = Vec<u8>>,
+{
+    let mut g = Encoder::new(w, scaled_size, scaled_size, &[]).unwrap();
     g.set_repeat(gif::Repeat::Infinite).unwrap();
 
-    let autom_iterator = autom.skipped_iter(steps, skip, scale);
-    let mut c = 0;
-    let palette = make_palette(states, rotate);
-    let frames = autom_iterator.map(|grid| {
-        let mut frame = Frame::from_palette_pixels(scaled_size, scaled_size, &grid, &palette, None);
-        frame.delay = delay;
-        eprint!("\rProcessing image {}/{}", c + 1, steps / skip);
-        c += 1;
-        frame
-    });
-    for frame in frames {
+    let mut count = 0;
+    for (c, grid) in frames.enumerate() {
+        let palette = &palettes[(c / every.max(1) as usize) % palettes.len()];
+        let mut frame = sink::borrowed_gif_frame(scaled_size, &grid, delay);
+        frame.palette = Some(palette.clone());
+        eprint!("\rProcessing image {}/{}", c + 1, n_frames);
         g.write_frame(&frame).expect("Error writing frame");
+        count += 1;
     }
     eprintln!();
-    Ok(())
+    Ok(count)
+}
+
+/// Builds the default palette of the states (see [`Palette`]), interpolating
+/// between two colors. The palette is rotated by `palette_rotate`: state `x`
+/// gets the color of state `x + palette_rotate` in the unrotated palette.
+#[cfg(feature = "output")]
+pub fn make_palette(states: u8, palette_rotate: u8) -> Vec<u8> {
+    Palette::default().to_rgb(states as usize, palette_rotate)
 }
 
-fn make_palette(states: u8, rotate: u8) -> Vec<u8> {
-    let col_1 = [255., 255., 255.];
-    let col_2 = [0., 0., 255.];
+/// Renders a phase diagram as a heatmap PNG, each pair of parameters being a
+/// `scale`x`scale` square. The first parameter increases from left to right
+/// and the second from bottom to top. The values are mapped linearly from the
+/// minimum (black) to the maximum (white) of the diagram.
+#[cfg(feature = "output")]
+pub fn write_phase_diagram_png<P: AsRef<Path>>(
+    path: P,
+    diagram: &analysis::PhaseDiagram,
+    scale: u32,
+) -> Result<(), io::Error> {
+    let (width, height) = (diagram.xs.len(), diagram.ys.len());
+    let min = diagram.values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = diagram
+        .values
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1. };
+    let palette = make_heat_palette();
+    let scale = scale as usize;
+    let mut data = Vec::with_capacity(3 * width * height * scale * scale);
+    for row in 0..height * scale {
+        let j = height - 1 - row / scale;
+        for col in 0..width * scale {
+            let level = ((diagram.get(col / scale, j) - min) / range * 255.).round() as usize;
+            data.extend_from_slice(&palette[3 * level..3 * level + 3]);
+        }
+    }
+    let mut f = AtomicFile::create(path)?;
+    let mut encoder = png::Encoder::new(&mut f, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    f.commit()
+}
+
+/// The width of the blank margin around QR codes, in modules.
+#[cfg(feature = "qr")]
+const QR_QUIET_ZONE: usize = 4;
+
+/// Renders the base64 encoding of a rule (see
+/// [`Rule::to_base64`](crate::rule::Rule::to_base64)) as a black and white QR
+/// code PNG, each module being a `scale`x`scale` square. Returns an error of
+/// kind `InvalidInput` if the rule is too large to fit in a QR code.
+///
+/// ```
+/// use rust_ca::output;
+/// use rust_ca::rule::Rule;
+///
+/// output::write_rule_qr_png("test_gol_qr.png", &Rule::gol(), 4)?;
+/// # std::fs::remove_file("test_gol_qr.png")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "qr")]
+pub fn write_rule_qr_png<P: AsRef<Path>>(
+    path: P,
+    rule: &crate::rule::Rule,
+    scale: u32,
+) -> Result<(), io::Error> {
+    let code = qrcode::QrCode::new(rule.to_base64())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let colors = code.to_colors();
+    let (width, scale) = (code.width(), scale as usize);
+    let side = (width + 2 * QR_QUIET_ZONE) * scale;
+    let mut data = vec![255; side * side];
+    for (k, color) in colors.iter().enumerate() {
+        if *color == qrcode::Color::Dark {
+            let (i, j) = (k / width + QR_QUIET_ZONE, k % width + QR_QUIET_ZONE);
+            for row in i * scale..(i + 1) * scale {
+                data[row * side + j * scale..row * side + (j + 1) * scale].fill(0);
+            }
+        }
+    }
+    let mut f = AtomicFile::create(path)?;
+    let mut encoder = png::Encoder::new(&mut f, side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    f.commit()
+}
 
-    let mut palette = vec![];
-    for x in 0..states {
-        let t = (((x + rotate) % states) as f64) / ((states - 1) as f64);
-        palette.push((col_1[0] * t + col_2[0] * (1. - t)) as u8);
-        palette.push((col_1[1] * t + col_2[1] * (1. - t)) as u8);
-        palette.push((col_1[2] * t + col_2[2] * (1. - t)) as u8);
+/// A 256 colors black-red-yellow-white palette used for heatmaps.
+#[cfg(feature = "output")]
+fn make_heat_palette() -> Vec<u8> {
+    let mut palette = Vec::with_capacity(3 * 256);
+    for x in 0..256 {
+        let t = x as f64 / 255.;
+        palette.push(((3. * t).min(1.) * 255.) as u8);
+        palette.push(((3. * t - 1.).clamp(0., 1.) * 255.) as u8);
+        palette.push(((3. * t - 2.).clamp(0., 1.) * 255.) as u8);
     }
     palette
 }
+
+#[cfg(all(test, feature = "output"))]
+mod tests {
+    use super::{
+        make_palette, write_animation_file, write_difference_to_gif_file, write_moment_clips,
+        write_palette_cycle_to_gif_file, write_smoothed_to_gif_file, write_to_gif_file,
+        write_to_png_file, Animation, AnimationFormat, MomentClips, PaletteCycle, Smoothing,
+        Upscale,
+    };
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::rule::{Rule, TotalisticRule};
+
+    #[test]
+    fn oversized_gifs_are_rejected() {
+        let path = std::env::temp_dir().join("rust_ca_oversized.gif");
+        let mut a = Automaton::new(2, 300, Rule::gol());
+        // 300 cells at scale 255 is 76500 pixels, more than a GIF can hold.
+        let err = write_to_gif_file(Some(&path), &mut a, 255, 2, 1, 1, 0).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(err.to_string().ends_with("use a scale of at most 218"));
+        let cycle = PaletteCycle::default();
+        assert!(
+            write_palette_cycle_to_gif_file(Some(&path), &mut a, 255, 2, 1, 1, &cycle).is_err()
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn report_counts_work_and_output_size() {
+        let path = std::env::temp_dir().join("rust_ca_report.gif");
+        let mut a = Automaton::new(2, 16, Rule::gol());
+        a.random_init();
+        let report = write_to_gif_file(Some(&path), &mut a, 1, 10, 2, 1, 0).unwrap();
+        assert_eq!(report.frames, 5);
+        assert_eq!(report.steps, 10);
+        assert_eq!(report.cell_updates, 10 * 256);
+        assert_eq!(report.bytes, std::fs::metadata(&path).unwrap().len());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn png_snapshots_contain_the_scaled_grid() {
+        let path = std::env::temp_dir().join("rust_ca_snapshot.png");
+        let mut a = Automaton::new(3, 8, Rule::random(1, 3));
+        a.random_init();
+        write_to_png_file(&path, &a, 2, &make_palette(3, 0)).unwrap();
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut cells = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut cells).unwrap();
+        assert_eq!((info.width, info.height), (16, 16));
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        let grid = a.grid();
+        assert_eq!(cells[..4], [grid[0], grid[0], grid[1], grid[1]]);
+        assert_eq!(cells[16..18], [grid[0], grid[0]]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn apng_frames_match_the_gif_frames() {
+        let (gif_path, apng_path) = (
+            std::env::temp_dir().join("rust_ca_frames.gif"),
+            std::env::temp_dir().join("rust_ca_frames.png"),
+        );
+        let rule = Rule::random(1, 3);
+        let mut a = Automaton::new(3, 16, rule.clone());
+        a.random_init();
+        let mut b = Automaton::new(3, 16, rule);
+        b.grid_mut().copy_from_slice(&a.grid());
+        write_to_gif_file(Some(&gif_path), &mut a, 1, 6, 2, 1, 0).unwrap();
+        let animation = Animation {
+            format: AnimationFormat::Apng,
+            ..Default::default()
+        };
+        let report =
+            write_animation_file(Some(&apng_path), &mut b, 6, 2, &mut Upscale(1), &animation)
+                .unwrap();
+        assert_eq!(report.frames, 3);
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut gif = options
+            .read_info(std::fs::File::open(&gif_path).unwrap())
+            .unwrap();
+        let mut apng = png::Decoder::new(std::fs::File::open(&apng_path).unwrap())
+            .read_info()
+            .unwrap();
+        assert_eq!(apng.info().animation_control().unwrap().num_frames, 3);
+        let mut buffer = vec![0; apng.output_buffer_size()];
+        while let Some(frame) = gif.read_next_frame().unwrap() {
+            let info = apng.next_frame(&mut buffer).unwrap();
+            assert_eq!(frame.buffer[..], buffer[..info.buffer_size()]);
+        }
+        std::fs::remove_file(&gif_path).unwrap();
+        std::fs::remove_file(&apng_path).unwrap();
+    }
+
+    #[test]
+    fn identical_runs_never_disagree() {
+        let path = std::env::temp_dir().join("rust_ca_difference.gif");
+        let lit_pixels = |rule_b: Rule| {
+            let mut a = Automaton::new(2, 16, Rule::gol());
+            let mut b = Automaton::new(2, 16, rule_b);
+            a.random_init_seeded(2);
+            b.random_init_seeded(2);
+            write_difference_to_gif_file(Some(&path), (&mut a, &mut b), 1, 6, 1, 1, 2).unwrap();
+            let mut options = gif::DecodeOptions::new();
+            options.set_color_output(gif::ColorOutput::Indexed);
+            let mut decoder = options
+                .read_info(std::fs::File::open(&path).unwrap())
+                .unwrap();
+            let mut lit = vec![];
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                lit.push(frame.buffer.iter().filter(|&&p| p > 0).count());
+            }
+            lit
+        };
+        assert_eq!(lit_pixels(Rule::gol()), [0; 6]);
+        let lit = lit_pixels(Rule::from_bs_notation("B36/S23").unwrap());
+        // The runs start from the same grid and drift apart.
+        assert_eq!(lit[0], 0);
+        assert!(lit[5] > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Decodes the RGB pixels of the frames of a GIF file.
+    fn decode_rgb(path: &std::path::Path) -> Vec<Vec<[u8; 3]>> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options
+            .read_info(std::fs::File::open(path).unwrap())
+            .unwrap();
+        let mut frames = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            let pixels = frame.buffer.chunks_exact(4);
+            frames.push(pixels.map(|p| [p[0], p[1], p[2]]).collect());
+        }
+        frames
+    }
+
+    /// Mixes the RGB colors of a palette with the given weights.
+    fn mix(colors: &[u8], weights: &[f64]) -> [u8; 3] {
+        let mut rgb = [0.; 3];
+        for (color, &w) in colors.chunks_exact(3).zip(weights) {
+            for (c, &channel) in rgb.iter_mut().zip(color) {
+                *c += channel as f64 * w;
+            }
+        }
+        rgb.map(|c| c.round() as u8)
+    }
+
+    #[test]
+    fn smoothed_frames_mix_the_state_colors() {
+        let path = std::env::temp_dir().join("rust_ca_smoothed.gif");
+        // Every cell goes to state 2 and stays there.
+        let mut a = Automaton::new(3, 8, Rule::new(1, 3, vec![2; 19683]));
+        let smoothing = Smoothing {
+            window: 3,
+            ..Default::default()
+        };
+        let report =
+            write_smoothed_to_gif_file(Some(&path), &mut a, 1, 6, 2, 1, &smoothing).unwrap();
+        assert_eq!((report.frames, report.steps), (3, 6));
+        // The frames are taken every 2 steps, over the states 0 of the
+        // initial grid then 0, 2, 2 and 2, 2, 2.
+        let colors = smoothing.palette.to_rgb(3, 0);
+        let expected: Vec<Vec<[u8; 3]>> = [[1., 0., 0.], [1. / 3., 0., 2. / 3.], [0., 0., 1.]]
+            .iter()
+            .map(|weights| vec![mix(&colors, weights); 64])
+            .collect();
+        assert_eq!(decode_rgb(&path), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn alternating_cells_are_drawn_at_the_midpoint_color() {
+        let path = std::env::temp_dir().join("rust_ca_smoothed_midpoint.gif");
+        // Every cell alternates between states 0 and 1.
+        let mut a = Automaton::new(2, 8, Rule::new(0, 2, vec![1, 0]));
+        for window in [2, 4] {
+            let smoothing = Smoothing {
+                window,
+                ..Default::default()
+            };
+            write_smoothed_to_gif_file(Some(&path), &mut a, 1, 8, 2, 1, &smoothing).unwrap();
+            let colors = smoothing.palette.to_rgb(2, 0);
+            let frames = decode_rgb(&path);
+            let midpoint = mix(&colors, &[0.5, 0.5]);
+            // Once the window is full, every cell spent half of it in each
+            // state.
+            assert!(frames[2..].iter().all(|f| f == &vec![midpoint; 64]));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn moment_clips_surround_the_detected_events() {
+        // A fuse: a cell counts down from state 1 to state 3, then state 3
+        // spreads to its neighbors. The activity is constant until the
+        // explosion at step 3.
+        let mut table = vec![0; 4 * 25];
+        table[3..25].fill(3);
+        table[25..50].fill(2);
+        table[50..].fill(3);
+        let rule = TotalisticRule::new(1, 4, true, table).to_rule();
+        let run = || {
+            let mut a = Automaton::new(4, 16, rule.clone());
+            a.grid_mut()[8 * 16 + 8] = 1;
+            a
+        };
+        let grids: Vec<Vec<u8>> = run().skipped_iter(8, 1, 1).collect();
+        assert_eq!(grids[3].iter().filter(|&&c| c == 3).count(), 9);
+        let prefix = std::env::temp_dir().join("rust_ca_moment");
+        let prefix = prefix.to_str().unwrap();
+        let clips = MomentClips {
+            before: 1,
+            after: 2,
+            window: 2,
+            threshold: 100.,
+            max_clips: 10,
+        };
+        let moments = write_moment_clips(prefix, &mut run(), 1, 8, 1, &clips).unwrap();
+        assert_eq!(moments, [3]);
+        let path = format!("{}_3.gif", prefix);
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options
+            .read_info(std::fs::File::open(&path).unwrap())
+            .unwrap();
+        let mut frames = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.buffer.to_vec());
+        }
+        // The frame before the event, the event and the 2 frames after it.
+        assert_eq!(frames, grids[2..6]);
+        std::fs::remove_file(&path).unwrap();
+        let none = MomentClips {
+            max_clips: 0,
+            ..clips
+        };
+        assert!(write_moment_clips(prefix, &mut run(), 1, 8, 1, &none)
+            .unwrap()
+            .is_empty());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn palette_rotation_permutes_colors() {
+        let palette = make_palette(3, 0);
+        let rotated = make_palette(3, 1);
+        assert_eq!(rotated[..6], palette[3..]);
+        assert_eq!(rotated[6..], palette[..3]);
+        assert_eq!(make_palette(3, 255), make_palette(3, 0));
+    }
+
+    #[test]
+    fn palette_cycle_changes_frame_palettes() {
+        let (static_path, cycled_path) = (
+            std::env::temp_dir().join("rust_ca_static.gif"),
+            std::env::temp_dir().join("rust_ca_cycled.gif"),
+        );
+        let rule = Rule::new(1, 3, vec![0; 19683]);
+        let mut a = Automaton::new(3, 16, rule.clone());
+        write_to_gif_file(Some(&static_path), &mut a, 1, 4, 1, 1, 0).unwrap();
+        let mut b = Automaton::new(3, 16, rule);
+        let cycle = PaletteCycle {
+            every: 2,
+            ..Default::default()
+        };
+        write_palette_cycle_to_gif_file(Some(&cycled_path), &mut b, 1, 4, 1, 1, &cycle).unwrap();
+        let palettes = |path| {
+            let mut options = gif::DecodeOptions::new();
+            options.set_color_output(gif::ColorOutput::Indexed);
+            let mut decoder = options
+                .read_info(std::fs::File::open(path).unwrap())
+                .unwrap();
+            // The frames without a local palette use the global one.
+            let global = decoder.global_palette().map(<[u8]>::to_vec);
+            let mut palettes = vec![];
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                palettes.push(frame.palette.clone().or_else(|| global.clone()).unwrap());
+            }
+            palettes
+        };
+        let (p, q) = (palettes(&static_path), palettes(&cycled_path));
+        assert_eq!(q.len(), 4);
+        assert_eq!((q[0].clone(), q[1].clone()), (p[0].clone(), p[1].clone()));
+        assert_ne!(q[2], p[2]);
+        assert_eq!(q[2], q[3]);
+        std::fs::remove_file(&static_path).unwrap();
+        std::fs::remove_file(&cycled_path).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A buffered file writer that writes to a temporary file next to the
+/// destination and atomically renames it to the destination on
+/// [`AtomicFile::commit`]. If the writer is dropped before being committed
+/// (e.g. after an error), the temporary file is removed and the destination
+/// is left untouched, so a crash never leaves a partially written file.
+///
+/// Destinations that exist but aren't regular files (named pipes, devices like
+/// `/dev/stdout`) can't be renamed over and are written directly.
+///
+/// ```
+/// use std::io::Write;
+/// use rust_ca::output::AtomicFile;
+///
+/// let mut f = AtomicFile::create("test_atomic.txt")?;
+/// f.write_all(b"hello")?;
+/// assert!(!std::path::Path::new("test_atomic.txt").exists());
+/// f.commit()?;
+/// assert_eq!(std::fs::read("test_atomic.txt")?, b"hello");
+/// # std::fs::remove_file("test_atomic.txt")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct AtomicFile {
+    inner: BufWriter<File>,
+    path: PathBuf,
+    /// The temporary file, `None` when writing directly to the destination.
+    temp: Option<PathBuf>,
+}
+
+impl AtomicFile {
+    /// Creates a writer for the file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<AtomicFile, io::Error> {
+        let path = path.as_ref().to_path_buf();
+        let direct = fs::metadata(&path).is_ok_and(|m| !m.is_file());
+        let temp = if direct {
+            None
+        } else {
+            let mut name = std::ffi::OsString::from(".");
+            name.push(path.file_name().unwrap_or_default());
+            name.push(format!(".{}.tmp", std::process::id()));
+            Some(path.with_file_name(name))
+        };
+        let inner = BufWriter::new(File::create(temp.as_ref().unwrap_or(&path))?);
+        Ok(AtomicFile { inner, path, temp })
+    }
+
+    /// Flushes the written data to disk and moves the file to its
+    /// destination.
+    pub fn commit(mut self) -> Result<(), io::Error> {
+        self.inner.flush()?;
+        if let Some(temp) = self.temp.take() {
+            if let Err(e) = self
+                .inner
+                .get_ref()
+                .sync_all()
+                .and_then(|_| fs::rename(&temp, &self.path))
+            {
+                let _ = fs::remove_file(&temp);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicFile;
+    use std::io::Write;
+    use std::path::Path;
+
+    #[test]
+    fn uncommitted_files_are_discarded() {
+        let path = std::env::temp_dir().join("rust_ca_uncommitted.txt");
+        std::fs::write(&path, b"previous").unwrap();
+        {
+            let mut f = AtomicFile::create(&path).unwrap();
+            f.write_all(b"partial").unwrap();
+            assert!(f.temp.as_deref().is_some_and(Path::exists));
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"previous");
+        let leftovers = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".rust_ca_uncommitted.txt")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn devices_are_written_directly() {
+        let f = AtomicFile::create("/dev/null").unwrap();
+        assert!(f.temp.is_none());
+        f.commit().unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use super::{StepHook, StepInfo};
+use crate::automaton::{grid_hash, AutomatonImpl};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+/// A structural event detected during a run by an [`EventBus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// All the cells are in state 0 at the given step.
+    Extinction {
+        /// The step of the first empty frame.
+        step: u64,
+    },
+    /// The grid at the given step is the same as `period` steps earlier: the
+    /// whole simulation has entered a cycle (or a fixed point, for a period
+    /// of one frame).
+    Cycle {
+        /// The step at which the repeated grid is seen again.
+        step: u64,
+        /// The number of steps between the repetitions.
+        period: u64,
+    },
+}
+
+/// The kinds of events, to subscribe to one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// [`Event::Extinction`].
+    Extinction,
+    /// [`Event::Cycle`].
+    Cycle,
+}
+
+impl Event {
+    /// Returns the kind of the event.
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Extinction { .. } => EventKind::Extinction,
+            Event::Cycle { .. } => EventKind::Cycle,
+        }
+    }
+}
+
+type Subscriber<'a, T> = Box<dyn FnMut(&mut T, &Event) -> ControlFlow<()> + 'a>;
+
+/// Detects structural events on the frames of a run and dispatches them to
+/// subscribers. The bus is a [`StepHook`], to be used with
+/// [`write_to_sink_with_hook`](super::write_to_sink_with_hook). Each event is
+/// dispatched once per run. Subscribers get the automaton, e.g. to write a
+/// snapshot, and stop the run by returning [`ControlFlow::Break`].
+///
+/// The grids are only compared on frames, so the detected cycle periods are
+/// multiples of the number of steps between frames.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, Event, EventBus, EventKind, FilterChain, NullSink};
+/// use rust_ca::rule::Rule;
+/// use std::ops::ControlFlow;
+///
+/// // A blinker oscillates with period 2 in the game of life.
+/// let mut automaton = Automaton::new(2, 8, Rule::gol());
+/// automaton.grid_mut()[8 * 3 + 2..8 * 3 + 5].fill(1);
+/// let mut cycles = vec![];
+/// let mut bus = EventBus::new();
+/// bus.subscribe(EventKind::Cycle, |_: &mut Automaton, event: &Event| {
+///     cycles.push(*event);
+///     ControlFlow::Break(())
+/// });
+/// let (filter, sink) = (&mut FilterChain::new(), &mut NullSink);
+/// output::write_to_sink_with_hook(&mut automaton, 100, 1, filter, sink, &mut bus)?;
+/// drop(bus);
+/// assert_eq!(cycles, [Event::Cycle { step: 2, period: 2 }]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct EventBus<'a, T: AutomatonImpl> {
+    subscribers: Vec<(EventKind, Subscriber<'a, T>)>,
+    seen: HashMap<u64, u64>,
+    fired: Vec<EventKind>,
+}
+
+impl<'a, T: AutomatonImpl> EventBus<'a, T> {
+    /// Makes a bus with no subscribers.
+    pub fn new() -> EventBus<'a, T> {
+        EventBus {
+            subscribers: vec![],
+            seen: HashMap::new(),
+            fired: vec![],
+        }
+    }
+
+    /// Calls `f` on the events of the given kind.
+    pub fn subscribe<F>(&mut self, kind: EventKind, f: F)
+    where
+        F: FnMut(&mut T, &Event) -> ControlFlow<()> + 'a,
+    {
+        self.subscribers.push((kind, Box::new(f)));
+    }
+
+    /// Dispatches an event to its subscribers, unless an event of the same
+    /// kind was already dispatched.
+    fn dispatch(&mut self, autom: &mut T, event: Event) -> ControlFlow<()> {
+        if self.fired.contains(&event.kind()) {
+            return ControlFlow::Continue(());
+        }
+        self.fired.push(event.kind());
+        let mut flow = ControlFlow::Continue(());
+        for (kind, f) in self.subscribers.iter_mut() {
+            if *kind == event.kind() && f(autom, &event).is_break() {
+                flow = ControlFlow::Break(());
+            }
+        }
+        flow
+    }
+}
+
+impl<T: AutomatonImpl> Default for EventBus<'_, T> {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl<T: AutomatonImpl> StepHook<T> for EventBus<'_, T> {
+    fn on_frame(&mut self, autom: &mut T, info: &StepInfo) -> ControlFlow<()> {
+        let mut flow = ControlFlow::Continue(());
+        if info.density == 0. {
+            let event = Event::Extinction { step: info.step };
+            if self.dispatch(autom, event).is_break() {
+                flow = ControlFlow::Break(());
+            }
+        }
+        if let Some(previous) = self.seen.insert(grid_hash(&autom.grid()), info.step) {
+            let event = Event::Cycle {
+                step: info.step,
+                period: info.step - previous,
+            };
+            if self.dispatch(autom, event).is_break() {
+                flow = ControlFlow::Break(());
+            }
+        }
+        flow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventBus, EventKind};
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::output::{write_to_sink_with_hook, FilterChain, NullSink};
+    use crate::rule::Rule;
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn extinctions_are_dispatched_once() {
+        // A lone cell dies, and the empty grid is a fixed point.
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        a.grid_mut()[10] = 1;
+        let mut events = vec![];
+        let mut bus = EventBus::new();
+        bus.subscribe(EventKind::Extinction, |_: &mut Automaton, e: &Event| {
+            events.push(*e);
+            ControlFlow::Continue(())
+        });
+        let mut cycles = 0;
+        bus.subscribe(EventKind::Cycle, |_: &mut Automaton, _: &Event| {
+            cycles += 1;
+            ControlFlow::Continue(())
+        });
+        let report = write_to_sink_with_hook(
+            &mut a,
+            10,
+            1,
+            &mut FilterChain::new(),
+            &mut NullSink,
+            &mut bus,
+        )
+        .unwrap();
+        drop(bus);
+        assert_eq!(report.steps, 10);
+        assert_eq!(events, [Event::Extinction { step: 1 }]);
+        assert_eq!(cycles, 1);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::analysis::{blocks_per_side, coarse_grain, BlockMapping};
+use crate::automaton::{rotate_states, upscale_in_place, Dimensions};
+
+/// A square frame of palette indices going through the output pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The cells of the frame in row-major order.
+    pub cells: Vec<u8>,
+    /// The side of the frame.
+    pub size: usize,
+}
+
+/// Information about the frame being processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameMeta {
+    /// The index of the frame in the output.
+    pub index: u32,
+    /// The simulation step of the frame.
+    pub step: u64,
+    /// The number of states of the CA.
+    pub states: u8,
+}
+
+/// A post-processing effect applied to every frame before it is encoded.
+/// Filters may change the size of the frames, as long as all the frames of a
+/// run end up with the same size.
+pub trait FrameFilter {
+    /// Applies the effect to a frame.
+    fn apply(&mut self, frame: &mut Frame, meta: &FrameMeta);
+}
+
+impl<F: FrameFilter + ?Sized> FrameFilter for &mut F {
+    fn apply(&mut self, frame: &mut Frame, meta: &FrameMeta) {
+        (**self).apply(frame, meta)
+    }
+}
+
+/// A sequence of filters applied in order. The chain can borrow filters, e.g.
+/// to read their state after a run.
+///
+/// ```
+/// use rust_ca::output::{Crop, FilterChain, Frame, FrameFilter, FrameMeta, Upscale};
+///
+/// let mut chain = FilterChain::new();
+/// chain.push(Crop { row: 1, col: 1, size: 2 }).push(Upscale(2));
+/// let mut frame = Frame { cells: (0..16).collect(), size: 4 };
+/// chain.apply(&mut frame, &FrameMeta { index: 0, step: 0, states: 16 });
+/// assert_eq!(frame.size, 4);
+/// assert_eq!(frame.cells[..4], [5, 5, 6, 6]);
+/// ```
+#[derive(Default)]
+pub struct FilterChain<'a> {
+    filters: Vec<Box<dyn FrameFilter + 'a>>,
+}
+
+impl<'a> FilterChain<'a> {
+    /// Makes an empty chain.
+    pub fn new() -> FilterChain<'a> {
+        FilterChain::default()
+    }
+
+    /// Appends a filter to the chain.
+    pub fn push<F: FrameFilter + 'a>(&mut self, filter: F) -> &mut FilterChain<'a> {
+        self.filters.push(Box::new(filter));
+        self
+    }
+}
+
+impl FrameFilter for FilterChain<'_> {
+    fn apply(&mut self, frame: &mut Frame, meta: &FrameMeta) {
+        for filter in self.filters.iter_mut() {
+            filter.apply(frame, meta);
+        }
+    }
+}
+
+/// Keeps the `size`x`size` square starting at (`row`, `col`), wrapping around
+/// the edges of the frame.
+pub struct Crop {
+    /// The first row kept.
+    pub row: usize,
+    /// The first column kept.
+    pub col: usize,
+    /// The side of the square kept.
+    pub size: usize,
+}
+
+impl FrameFilter for Crop {
+    fn apply(&mut self, frame: &mut Frame, _: &FrameMeta) {
+        let n = frame.size;
+        let mut cells = Vec::with_capacity(self.size * self.size);
+        for i in 0..self.size {
+            for j in 0..self.size {
+                cells.push(frame.cells[((self.row + i) % n) * n + (self.col + j) % n]);
+            }
+        }
+        *frame = Frame {
+            cells,
+            size: self.size,
+        };
+    }
+}
+
+/// Makes the frame larger by duplicating every cell in a square of the given
+/// side. The cells are expanded in place, so that frames reused across steps
+/// (see [`write_to_sink`](crate::output::write_to_sink)) are only allocated
+/// once.
+pub struct Upscale(pub u16);
+
+impl FrameFilter for Upscale {
+    fn apply(&mut self, frame: &mut Frame, _: &FrameMeta) {
+        if self.0 > 1 {
+            let dims = Dimensions::new(frame.size, self.0);
+            upscale_in_place(&mut frame.cells, dims);
+            frame.size = dims.pixels();
+        }
+    }
+}
+
+/// Draws a hexagonal grid (see [`Neighborhood::Hex`]) upscaled with the
+/// given scale, shifting every row of cells half a cell to the right of the
+/// row above. The rows wrap around the frame.
+///
+/// [`Neighborhood::Hex`]: crate::rule::Neighborhood::Hex
+pub struct HexLayout(pub u16);
+
+impl FrameFilter for HexLayout {
+    fn apply(&mut self, frame: &mut Frame, _: &FrameMeta) {
+        let (n, scale) = (frame.size, self.0.max(1) as usize);
+        for (r, row) in frame.cells.chunks_mut(n.max(1)).enumerate() {
+            row.rotate_right((r / scale * scale / 2) % n);
+        }
+    }
+}
+
+/// Makes the frame smaller by mapping every square of the given side to a
+/// single cell (see [`coarse_grain`]).
+pub struct Downscale {
+    /// The side of the squares mapped to a single cell.
+    pub block: usize,
+    /// The mapping of the squares.
+    pub mapping: BlockMapping,
+}
+
+impl FrameFilter for Downscale {
+    fn apply(&mut self, frame: &mut Frame, _: &FrameMeta) {
+        frame.cells = coarse_grain(&frame.cells, frame.size, self.block, &self.mapping);
+        frame.size = blocks_per_side(frame.size, self.block);
+    }
+}
+
+/// Rotates the states of the frame (see [`rotate_states`]).
+pub struct StateRotation(pub u8);
+
+impl FrameFilter for StateRotation {
+    fn apply(&mut self, frame: &mut Frame, meta: &FrameMeta) {
+        rotate_states(&mut frame.cells, meta.states, self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Crop, Downscale, Frame, FrameFilter, FrameMeta, HexLayout, Upscale};
+    use crate::analysis::BlockMapping;
+
+    const META: FrameMeta = FrameMeta {
+        index: 0,
+        step: 0,
+        states: 2,
+    };
+
+    #[test]
+    fn crop_wraps_around() {
+        let mut frame = Frame {
+            cells: (0..9).collect(),
+            size: 3,
+        };
+        Crop {
+            row: 2,
+            col: 2,
+            size: 2,
+        }
+        .apply(&mut frame, &META);
+        assert_eq!(frame.cells, vec![8, 6, 2, 0]);
+    }
+
+    #[test]
+    fn upscaling_expands_the_cells_in_place() {
+        let cells: Vec<u8> = (0..9).collect();
+        let mut frame = Frame {
+            cells: Vec::with_capacity(81),
+            size: 3,
+        };
+        frame.cells.extend_from_slice(&cells);
+        let buffer = frame.cells.as_ptr();
+        Upscale(3).apply(&mut frame, &META);
+        assert_eq!((frame.size, frame.cells.as_ptr()), (9, buffer));
+        for (p, &cell) in frame.cells.iter().enumerate() {
+            assert_eq!(cell, cells[p / 9 / 3 * 3 + p % 9 / 3]);
+        }
+    }
+
+    #[test]
+    fn hex_rows_are_shifted_by_half_cells() {
+        let mut frame = Frame {
+            cells: vec![1, 0, 0, 2],
+            size: 2,
+        };
+        Upscale(2).apply(&mut frame, &META);
+        HexLayout(2).apply(&mut frame, &META);
+        assert_eq!(frame.cells[..8], [1, 1, 0, 0, 1, 1, 0, 0]);
+        assert_eq!(frame.cells[8..], [2, 0, 0, 2, 2, 0, 0, 2]);
+    }
+
+    #[test]
+    fn downscale_uses_the_block_mapping() {
+        let mut frame = Frame {
+            cells: vec![1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1],
+            size: 4,
+        };
+        Downscale {
+            block: 2,
+            mapping: BlockMapping::Majority,
+        }
+        .apply(&mut frame, &META);
+        assert_eq!((frame.size, frame.cells), (2, vec![1, 0, 0, 1]));
+    }
+}
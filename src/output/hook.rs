@@ -0,0 +1,176 @@
+use super::{Frame, FrameFilter, FrameMeta, OutputSink, RunReport};
+use crate::automaton::AutomatonImpl;
+use crate::stats;
+use std::io;
+use std::ops::ControlFlow;
+use std::time::Instant;
+
+/// The metrics of a frame passed to a [`StepHook`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepInfo {
+    /// The index of the frame.
+    pub index: u32,
+    /// The number of steps simulated before the frame.
+    pub step: u64,
+    /// The fraction of non-zero cells.
+    pub density: f64,
+    /// The normalized entropy of the state distribution (see
+    /// [`stats::entropy`]).
+    pub entropy: f64,
+    /// The fraction of cells that changed since the previous frame (0 for
+    /// the first frame).
+    pub activity: f64,
+}
+
+/// Custom experiment logic run on every frame of [`write_to_sink_with_hook`].
+/// The hook can inspect the metrics of the frame and act on the automaton
+/// before the next steps are simulated: perturb it (e.g. with
+/// [`AutomatonImpl::set_noise`]), change its rule, write a snapshot of the
+/// grid, or stop the run by returning [`ControlFlow::Break`].
+///
+/// This is the extension point for scripted experiments: the `scripting`
+/// feature implements it for Rhai scripts (see `ScriptHook`).
+pub trait StepHook<T: AutomatonImpl> {
+    /// Called after the frame is written, before simulating the next steps.
+    fn on_frame(&mut self, autom: &mut T, info: &StepInfo) -> ControlFlow<()>;
+}
+
+impl<T, F> StepHook<T> for F
+where
+    T: AutomatonImpl,
+    F: FnMut(&mut T, &StepInfo) -> ControlFlow<()>,
+{
+    fn on_frame(&mut self, autom: &mut T, info: &StepInfo) -> ControlFlow<()> {
+        self(autom, info)
+    }
+}
+
+/// Runs a simulation like [`write_to_sink`](super::write_to_sink), calling a
+/// hook on every frame. The run stops after `steps` steps or as soon as the
+/// hook breaks.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, NullSink, StepInfo};
+/// use rust_ca::rule::Rule;
+/// use std::ops::ControlFlow;
+///
+/// // Run the game of life until it settles, shaking it once.
+/// let mut automaton = Automaton::new(2, 64, Rule::gol());
+/// automaton.random_init_seeded(1);
+/// let mut shaken = false;
+/// let mut hook = |a: &mut Automaton, info: &StepInfo| {
+///     a.set_noise(0.);
+///     if info.index > 0 && info.activity < 0.01 {
+///         if shaken {
+///             return ControlFlow::Break(());
+///         }
+///         a.set_noise(0.1);
+///         shaken = true;
+///     }
+///     ControlFlow::Continue(())
+/// };
+/// let (filter, sink) = (&mut FilterChain::new(), &mut NullSink);
+/// let report = output::write_to_sink_with_hook(&mut automaton, 100_000, 10, filter, sink, &mut hook)?;
+/// assert!(shaken && report.steps < 100_000);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_to_sink_with_hook<T, F, S, H>(
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    filter: &mut F,
+    sink: &mut S,
+    hook: &mut H,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+    F: FrameFilter,
+    S: OutputSink,
+    H: StepHook<T>,
+{
+    let start = Instant::now();
+    let (size, states) = (autom.size(), autom.states());
+    // The grids of the current and previous frames swap their buffers, and
+    // the frame reuses the allocation left by the filters.
+    let mut previous: Option<Vec<u8>> = None;
+    let mut cells = Vec::with_capacity(size * size);
+    let mut frame = Frame {
+        cells: Vec::with_capacity(size * size),
+        size,
+    };
+    let (mut frames, mut step) = (0, 0);
+    while step < steps {
+        autom.grid_into(&mut cells);
+        let counts = autom.state_counts();
+        let info = StepInfo {
+            index: frames,
+            step: step as u64,
+            density: (cells.len() - counts[0]) as f64 / cells.len() as f64,
+            entropy: stats::normalized_entropy(&counts, states),
+            activity: previous.as_ref().map_or(0., |p| stats::activity(p, &cells)),
+        };
+        let meta = FrameMeta {
+            index: frames,
+            step: info.step,
+            states,
+        };
+        frame.cells.clear();
+        frame.cells.extend_from_slice(&cells);
+        frame.size = size;
+        filter.apply(&mut frame, &meta);
+        sink.write_frame(&frame, &meta)?;
+        frames += 1;
+        if hook.on_frame(autom, &info).is_break() {
+            break;
+        }
+        for _ in 0..skip {
+            autom.update();
+        }
+        step += skip;
+        cells = previous.replace(cells).unwrap_or_default();
+    }
+    sink.finish()?;
+    Ok(RunReport {
+        steps: step as u64,
+        cell_updates: step as u64 * (size * size) as u64,
+        frames,
+        elapsed: start.elapsed(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_to_sink_with_hook, StepInfo};
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::output::{FilterChain, RawSink};
+    use crate::rule::Rule;
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn hooks_see_every_frame_and_can_stop() {
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        a.random_init();
+        let mut seen = vec![];
+        let mut hook = |_: &mut Automaton, info: &StepInfo| {
+            seen.push(*info);
+            if info.index == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        let mut raw = RawSink(vec![]);
+        let report =
+            write_to_sink_with_hook(&mut a, 100, 2, &mut FilterChain::new(), &mut raw, &mut hook)
+                .unwrap();
+        assert_eq!((report.frames, report.steps), (4, 6));
+        assert_eq!(raw.0.len(), 4 * 64);
+        assert_eq!(seen.len(), 4);
+        assert_eq!((seen[3].step, seen[0].activity), (6, 0.));
+        let last = &raw.0[3 * 64..];
+        let density = last.iter().filter(|&&c| c != 0).count() as f64 / 64.;
+        assert_eq!(seen[3].density, density);
+    }
+}
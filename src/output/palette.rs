@@ -0,0 +1,394 @@
+use std::convert::TryInto;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Sampled colors of the viridis colormap of matplotlib, at regular intervals.
+const VIRIDIS: [[u8; 3]; 9] = [
+    [0x44, 0x01, 0x54],
+    [0x47, 0x2d, 0x7b],
+    [0x3b, 0x52, 0x8b],
+    [0x2c, 0x72, 0x8e],
+    [0x21, 0x91, 0x8c],
+    [0x28, 0xae, 0x80],
+    [0x5e, 0xc9, 0x62],
+    [0xad, 0xdc, 0x30],
+    [0xfd, 0xe7, 0x25],
+];
+
+/// The colors of the states of a CA in the output images.
+///
+/// A palette is either a colormap, sampled at regular intervals for the
+/// states, or an explicit list of colors, one per state. The default palette
+/// is a gradient from blue (state 0) to white (the last state).
+///
+/// ```
+/// use rust_ca::output::Palette;
+///
+/// let gray = Palette::grayscale();
+/// assert_eq!(gray.to_rgb(3, 0), [0, 0, 0, 127, 127, 127, 255, 255, 255]);
+/// let custom: Palette = "#000000,#ff8000".parse()?;
+/// assert_eq!(custom.to_rgb(2, 1), [255, 128, 0, 0, 0, 0]);
+/// # Ok::<(), rust_ca::output::PaletteError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    kind: PaletteKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PaletteKind {
+    Gradient([u8; 3], [u8; 3]),
+    Viridis,
+    Rainbow,
+    HueCycle,
+    Fading([u8; 3], [u8; 3]),
+    Colors(Vec<[u8; 3]>),
+}
+
+impl Palette {
+    /// A linear gradient from the color of state 0 to the color of the last
+    /// state.
+    pub fn gradient(first: [u8; 3], last: [u8; 3]) -> Palette {
+        Palette {
+            kind: PaletteKind::Gradient(first, last),
+        }
+    }
+
+    /// The perceptually uniform viridis colormap, from dark purple to yellow.
+    pub fn viridis() -> Palette {
+        Palette {
+            kind: PaletteKind::Viridis,
+        }
+    }
+
+    /// A gradient from black to white.
+    pub fn grayscale() -> Palette {
+        Palette::gradient([0, 0, 0], [255, 255, 255])
+    }
+
+    /// The hues from red to violet, at full saturation.
+    pub fn rainbow() -> Palette {
+        Palette {
+            kind: PaletteKind::Rainbow,
+        }
+    }
+
+    /// The full circle of hues at full saturation, the last state being
+    /// followed by the first one like in the cycle of the states of a
+    /// [`CyclicRule`](crate::rule::CyclicRule): the hues are spaced evenly,
+    /// so that the colors of consecutive states are as close as those of the
+    /// last and first states.
+    pub fn hue_cycle() -> Palette {
+        Palette {
+            kind: PaletteKind::HueCycle,
+        }
+    }
+
+    /// The colors of excitable media: state 0 (resting) gets the color
+    /// `rest`, state 1 (excited) the color `excited`, and the following
+    /// (refractory) states fade from the excited color back towards the
+    /// resting one, whatever the number of states.
+    pub fn fading(rest: [u8; 3], excited: [u8; 3]) -> Palette {
+        Palette {
+            kind: PaletteKind::Fading(rest, excited),
+        }
+    }
+
+    /// The fading palette of the
+    /// [`greenberg_hastings`](crate::rule::Rule::greenberg_hastings) rules,
+    /// pale yellow waves on a dark blue background.
+    ///
+    /// ```
+    /// use rust_ca::output::Palette;
+    ///
+    /// let colors = Palette::excitable().to_rgb(4, 0);
+    /// assert_eq!(colors[..6], [16, 16, 64, 255, 240, 160]);
+    /// // The last refractory state is still brighter than the resting one.
+    /// assert!(colors[9] > colors[0] && colors[9] < colors[6]);
+    /// ```
+    pub fn excitable() -> Palette {
+        Palette::fading([16, 16, 64], [255, 240, 160])
+    }
+
+    /// The colors of the [`Traffic`](crate::automaton::Traffic) model:
+    /// white roads, red cars moving east and blue cars moving south.
+    pub fn traffic() -> Palette {
+        Palette::from_colors(vec![[255, 255, 255], [220, 40, 40], [40, 80, 220]])
+    }
+
+    /// One color per state. The colors are reused in turn if there are more
+    /// states than colors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn from_colors(colors: Vec<[u8; 3]>) -> Palette {
+        assert!(!colors.is_empty(), "A palette needs at least one color");
+        Palette {
+            kind: PaletteKind::Colors(colors),
+        }
+    }
+
+    /// Reads a list of colors from a file. The file is either a text file
+    /// with one color per line, written `#rrggbb` or as three integers
+    /// `r g b` (blank lines and lines starting with `;` are ignored), or a
+    /// JSON array whose elements are `"#rrggbb"` strings or `[r, g, b]`
+    /// arrays.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Palette, PaletteError> {
+        let contents = fs::read_to_string(path)?;
+        let colors = if contents.trim_start().starts_with('[') {
+            parse_json_colors(&contents)
+        } else {
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with(';'))
+                .map(|l| parse_color(l).ok_or(PaletteError::PaletteFormatError))
+                .collect()
+        }?;
+        if colors.is_empty() {
+            return Err(PaletteError::PaletteFormatError);
+        }
+        Ok(Palette::from_colors(colors))
+    }
+
+    /// Returns the color of the `k`-th of `n` states.
+    fn color(&self, k: usize, n: usize) -> [u8; 3] {
+        let t = if n > 1 { k as f64 / (n - 1) as f64 } else { 0. };
+        match &self.kind {
+            PaletteKind::Gradient(first, last) => {
+                let mut color = [0; 3];
+                for c in 0..3 {
+                    color[c] = (last[c] as f64 * t + first[c] as f64 * (1. - t)) as u8;
+                }
+                color
+            }
+            PaletteKind::Viridis => {
+                let x = t * (VIRIDIS.len() - 1) as f64;
+                let i = (x as usize).min(VIRIDIS.len() - 2);
+                let (a, b, f) = (VIRIDIS[i], VIRIDIS[i + 1], x - i as f64);
+                let mut color = [0; 3];
+                for c in 0..3 {
+                    color[c] = (a[c] as f64 * (1. - f) + b[c] as f64 * f).round() as u8;
+                }
+                color
+            }
+            // The hue goes from 0 (red) to 270 (violet) degrees.
+            PaletteKind::Rainbow => hue(t * 4.5),
+            // The hue goes around the circle, state n being state 0.
+            PaletteKind::HueCycle => hue(6. * k as f64 / n.max(1) as f64),
+            // The excited state 1 fades towards state 0 with the refractory
+            // states, the last one stopping short of the resting color.
+            PaletteKind::Fading(rest, excited) => {
+                if k == 0 {
+                    return *rest;
+                }
+                let f = (k - 1) as f64 / (n - 1).max(1) as f64;
+                let mut color = [0; 3];
+                for c in 0..3 {
+                    color[c] = (excited[c] as f64 * (1. - f) + rest[c] as f64 * f).round() as u8;
+                }
+                color
+            }
+            PaletteKind::Colors(colors) => colors[k % colors.len()],
+        }
+    }
+
+    /// Returns the palette of `n` states as RGB triplets, as expected by the
+    /// GIF and PNG encoders. The palette is rotated by `rotate`: state `x`
+    /// gets the color of state `x + rotate` in the unrotated palette.
+    pub fn to_rgb(&self, n: usize, rotate: u8) -> Vec<u8> {
+        (0..n)
+            .flat_map(|x| self.color((x + rotate as usize) % n, n))
+            .collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::gradient([0, 0, 255], [255, 255, 255])
+    }
+}
+
+/// Parses the name of a palette (`default`, `viridis`, `grayscale`,
+/// `rainbow`, `hue-cycle`, `excitable` or `traffic`) or a comma separated
+/// list of `#rrggbb` colors.
+impl FromStr for Palette {
+    type Err = PaletteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Palette::default()),
+            "viridis" => Ok(Palette::viridis()),
+            "grayscale" => Ok(Palette::grayscale()),
+            "rainbow" => Ok(Palette::rainbow()),
+            "hue-cycle" => Ok(Palette::hue_cycle()),
+            "excitable" => Ok(Palette::excitable()),
+            "traffic" => Ok(Palette::traffic()),
+            _ => s
+                .split(',')
+                .map(|c| parse_hex_color(c.trim()).ok_or(PaletteError::PaletteFormatError))
+                .collect::<Result<_, _>>()
+                .map(Palette::from_colors),
+        }
+    }
+}
+
+/// Returns the color of full saturation of hue `h`, in sixths of a turn from
+/// red.
+fn hue(h: f64) -> [u8; 3] {
+    let x = 1. - (h % 2. - 1.).abs();
+    let (r, g, b) = match h as usize {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+    [(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8]
+}
+
+/// Parses a `#rrggbb` color.
+fn parse_hex_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let mut color = [0; 3];
+    for (c, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[2 * c..2 * c + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
+/// Parses a `#rrggbb` color or three integers separated by spaces or commas.
+fn parse_color(s: &str) -> Option<[u8; 3]> {
+    if s.starts_with('#') {
+        return parse_hex_color(s);
+    }
+    let channels: Vec<u8> = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|c| !c.is_empty())
+        .map(|c| c.parse().ok())
+        .collect::<Option<_>>()?;
+    channels.try_into().ok()
+}
+
+/// Parses a JSON array of `"#rrggbb"` strings and `[r, g, b]` arrays.
+fn parse_json_colors(s: &str) -> Result<Vec<[u8; 3]>, PaletteError> {
+    let body = s
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or(PaletteError::PaletteFormatError)?;
+    let mut colors = vec![];
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let (close, skip) = match rest.as_bytes()[0] {
+            b'"' => ('"', 1),
+            b'[' => (']', 1),
+            _ => return Err(PaletteError::PaletteFormatError),
+        };
+        let end = rest[skip..]
+            .find(close)
+            .ok_or(PaletteError::PaletteFormatError)?
+            + skip;
+        colors.push(parse_color(rest[skip..end].trim()).ok_or(PaletteError::PaletteFormatError)?);
+        rest = rest[end + 1..].trim_start();
+        match rest.strip_prefix(',') {
+            Some(r) => rest = r.trim_start(),
+            None if rest.is_empty() => {}
+            None => return Err(PaletteError::PaletteFormatError),
+        }
+    }
+    Ok(colors)
+}
+
+/// Error type for an error that happened while reading a palette.
+#[derive(Debug)]
+pub enum PaletteError {
+    /// An io error while reading a palette file.
+    PaletteFileError(io::Error),
+    /// A palette name, color or file that couldn't be parsed.
+    PaletteFormatError,
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaletteError::PaletteFormatError => write!(
+                f,
+                "incorrect palette, expected a palette name, #rrggbb colors or a palette file"
+            ),
+            // The wrapped error is available via the source() method.
+            PaletteError::PaletteFileError(..) => write!(f, "io error with the palette file"),
+        }
+    }
+}
+
+impl error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            PaletteError::PaletteFormatError => None,
+            PaletteError::PaletteFileError(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(err: io::Error) -> PaletteError {
+        PaletteError::PaletteFileError(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+
+    #[test]
+    fn colormaps_span_their_range() {
+        let viridis = Palette::viridis().to_rgb(5, 0);
+        assert_eq!(viridis[..3], [0x44, 0x01, 0x54]);
+        assert_eq!(viridis[12..], [0xfd, 0xe7, 0x25]);
+        let rainbow = Palette::rainbow().to_rgb(3, 0);
+        assert_eq!(rainbow[..3], [255, 0, 0]);
+        assert_eq!(rainbow[6..], [127, 0, 255]);
+        assert_eq!(Palette::default().to_rgb(2, 0), [0, 0, 255, 255, 255, 255]);
+        let hues = Palette::hue_cycle().to_rgb(6, 0);
+        assert_eq!(hues[..6], [255, 0, 0, 255, 255, 0]);
+        assert_eq!(hues[15..], [255, 0, 255]);
+        assert_eq!("traffic".parse::<Palette>().unwrap(), Palette::traffic());
+    }
+
+    #[test]
+    fn palette_files_are_read() {
+        let dir = std::env::temp_dir();
+        let (text, json) = (
+            dir.join("rust_ca_palette.txt"),
+            dir.join("rust_ca_palette.json"),
+        );
+        std::fs::write(&text, "; black and orange\n#000000\n\n255 128 0\n").unwrap();
+        std::fs::write(&json, r##"["#000000", [255, 128, 0]]"##).unwrap();
+        let expected = Palette::from_colors(vec![[0, 0, 0], [255, 128, 0]]);
+        assert_eq!(Palette::from_file(&text).unwrap(), expected);
+        assert_eq!(Palette::from_file(&json).unwrap(), expected);
+        std::fs::write(&json, r#"["black"]"#).unwrap();
+        assert!(Palette::from_file(&json).is_err());
+        std::fs::remove_file(&text).unwrap();
+        std::fs::remove_file(&json).unwrap();
+        assert!(Palette::from_file(&text).is_err());
+    }
+
+    #[test]
+    fn explicit_colors_are_reused() {
+        let palette: Palette = "#ff0000, #00ff00".parse().unwrap();
+        assert_eq!(palette.to_rgb(3, 0)[6..], [255, 0, 0]);
+        assert!("#ff00".parse::<Palette>().is_err());
+        assert!("magma".parse::<Palette>().is_err());
+    }
+}
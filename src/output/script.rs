@@ -0,0 +1,315 @@
+use super::sink::encode_png;
+use super::{AtomicFile, Frame, Palette, PaletteError, StepHook, StepInfo};
+use crate::automaton::AutomatonImpl;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::rc::Rc;
+use std::{error, fmt, fs, io};
+
+/// A [`StepHook`] running a [Rhai](https://rhai.rs) script on every frame,
+/// for custom experiment logic without recompiling (requires the
+/// `scripting` feature).
+///
+/// The script defines a function `on_frame(info)`, called with a map of the
+/// metrics of the frame (`index`, `step`, `density`, `entropy` and
+/// `activity`, see [`StepInfo`]). It acts on the run with the functions:
+/// - `perturb(p)`: simulates the steps until the next frame with a noise of
+///   probability `p` (see [`AutomatonImpl::set_noise`]),
+/// - `snapshot(path)`: writes the grid to a PNG file,
+/// - `palette(name)`: changes the palette of the following snapshots (see
+///   [`Palette`]),
+/// - `stop()`: stops the run after this frame.
+///
+/// The state kept across frames is stored in `this`, an object map starting
+/// empty.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, NullSink, ScriptHook};
+/// use rust_ca::rule::Rule;
+///
+/// // Run the game of life until it settles, shaking it once.
+/// let mut hook = ScriptHook::new(r#"
+///     fn on_frame(info) {
+///         if info.index > 0 && info.activity < 0.01 {
+///             if "shaken" in this {
+///                 stop();
+///             } else {
+///                 perturb(0.1);
+///                 this.shaken = true;
+///             }
+///         }
+///     }
+/// "#)?;
+/// let mut automaton = Automaton::new(2, 64, Rule::gol());
+/// automaton.random_init_seeded(1);
+/// let (filter, sink) = (&mut FilterChain::new(), &mut NullSink);
+/// let report = output::write_to_sink_with_hook(&mut automaton, 100_000, 10, filter, sink, &mut hook)?;
+/// assert!(hook.error().is_none() && report.steps < 100_000);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ScriptHook {
+    engine: Engine,
+    ast: AST,
+    state: Dynamic,
+    actions: Rc<RefCell<Actions>>,
+    noise: f64,
+    palette: Palette,
+    error: Option<ScriptError>,
+}
+
+/// The actions requested by the script during a frame.
+#[derive(Debug, Default)]
+struct Actions {
+    noise: Option<f64>,
+    snapshots: Vec<String>,
+    palette: Option<String>,
+    stop: bool,
+}
+
+impl ScriptHook {
+    /// Compiles a script. Fails if the script doesn't parse or doesn't
+    /// define an `on_frame` function of one parameter.
+    pub fn new(source: &str) -> Result<ScriptHook, ScriptError> {
+        let mut engine = Engine::new();
+        let actions = Rc::new(RefCell::new(Actions::default()));
+        let a = actions.clone();
+        engine.register_fn("perturb", move |p: f64| {
+            a.borrow_mut().noise = Some(p);
+        });
+        let a = actions.clone();
+        engine.register_fn("snapshot", move |path: &str| {
+            a.borrow_mut().snapshots.push(path.to_string());
+        });
+        let a = actions.clone();
+        engine.register_fn("palette", move |name: &str| {
+            a.borrow_mut().palette = Some(name.to_string());
+        });
+        let a = actions.clone();
+        engine.register_fn("stop", move || {
+            a.borrow_mut().stop = true;
+        });
+        let ast = engine
+            .compile(source)
+            .map_err(|e| ScriptError::Script(e.to_string()))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "on_frame" && f.params.len() == 1)
+        {
+            return Err(ScriptError::Script(
+                "the script doesn't define on_frame(info)".to_string(),
+            ));
+        }
+        Ok(ScriptHook {
+            engine,
+            ast,
+            state: Dynamic::from_map(Map::new()),
+            actions,
+            noise: 0.,
+            palette: Palette::default(),
+            error: None,
+        })
+    }
+
+    /// Reads and compiles a script file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ScriptHook, ScriptError> {
+        ScriptHook::new(&fs::read_to_string(path)?)
+    }
+
+    /// Sets the noise of the automaton between perturbations (0 by default).
+    pub fn set_noise(&mut self, p: f64) {
+        self.noise = p;
+    }
+
+    /// Sets the palette of the snapshots.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Returns the error that stopped the run, if the script failed.
+    pub fn error(&self) -> Option<&ScriptError> {
+        self.error.as_ref()
+    }
+
+    /// Calls `on_frame` and applies the actions of the script.
+    fn run_frame<T: AutomatonImpl>(
+        &mut self,
+        autom: &mut T,
+        info: &StepInfo,
+    ) -> Result<ControlFlow<()>, ScriptError> {
+        let mut metrics = Map::new();
+        metrics.insert("index".into(), Dynamic::from_int(info.index.into()));
+        metrics.insert("step".into(), Dynamic::from_int(info.step as i64));
+        metrics.insert("density".into(), Dynamic::from_float(info.density));
+        metrics.insert("entropy".into(), Dynamic::from_float(info.entropy));
+        metrics.insert("activity".into(), Dynamic::from_float(info.activity));
+        // The top-level statements only run once, when the script is
+        // compiled, and the value returned by on_frame is ignored.
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let _ = self
+            .engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                &self.ast,
+                "on_frame",
+                (metrics,),
+            )
+            .map_err(|e: Box<EvalAltResult>| ScriptError::Script(e.to_string()))?;
+        let actions = std::mem::take(&mut *self.actions.borrow_mut());
+        match actions.noise {
+            Some(p) if (0. ..=1.).contains(&p) => autom.set_noise(p),
+            Some(_) => {
+                return Err(ScriptError::Script(
+                    "perturb needs a probability".to_string(),
+                ))
+            }
+            None => autom.set_noise(self.noise),
+        }
+        if let Some(name) = actions.palette {
+            self.palette = name.parse()?;
+        }
+        if !actions.snapshots.is_empty() {
+            let palette = self.palette.to_rgb(autom.states() as usize, 0);
+            let frame = Frame {
+                cells: autom.grid(),
+                size: autom.size(),
+            };
+            for path in actions.snapshots {
+                let mut f = AtomicFile::create(path)?;
+                encode_png(&mut f, &frame, &palette)?;
+                f.commit()?;
+            }
+        }
+        Ok(if actions.stop {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        })
+    }
+}
+
+/// Stops the run on the first error of the script, which is then available
+/// with [`ScriptHook::error`].
+impl<T: AutomatonImpl> StepHook<T> for ScriptHook {
+    fn on_frame(&mut self, autom: &mut T, info: &StepInfo) -> ControlFlow<()> {
+        match self.run_frame(autom, info) {
+            Ok(flow) => flow,
+            Err(err) => {
+                self.error = Some(err);
+                ControlFlow::Break(())
+            }
+        }
+    }
+}
+
+/// Error type for an error that happened while compiling or running a
+/// script.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// An io error while reading the script or writing a snapshot.
+    ScriptFileError(io::Error),
+    /// A script that doesn't compile or fails while running, with the
+    /// message of the engine.
+    Script(String),
+    /// An unknown palette passed to `palette`.
+    Palette(PaletteError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // The wrapped errors are available via the source() method.
+            ScriptError::ScriptFileError(..) => write!(f, "io error with the script"),
+            ScriptError::Script(message) => write!(f, "script error: {}", message),
+            ScriptError::Palette(..) => write!(f, "incorrect palette in the script"),
+        }
+    }
+}
+
+impl error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ScriptError::ScriptFileError(e) => Some(e),
+            ScriptError::Script(_) => None,
+            ScriptError::Palette(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(err: io::Error) -> ScriptError {
+        ScriptError::ScriptFileError(err)
+    }
+}
+
+impl From<PaletteError> for ScriptError {
+    fn from(err: PaletteError) -> ScriptError {
+        ScriptError::Palette(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScriptError, ScriptHook};
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::output::{write_to_sink_with_hook, FilterChain, NullSink};
+    use crate::rule::Rule;
+
+    fn run(hook: &mut ScriptHook, steps: u32) -> u64 {
+        let mut a = Automaton::new(2, 16, Rule::gol());
+        a.random_init_seeded(2);
+        write_to_sink_with_hook(
+            &mut a,
+            steps,
+            1,
+            &mut FilterChain::new(),
+            &mut NullSink,
+            hook,
+        )
+        .unwrap()
+        .steps
+    }
+
+    #[test]
+    fn scripts_stop_and_snapshot() {
+        let path = std::env::temp_dir().join("rust_ca_script_snapshot.png");
+        let script = format!(
+            r#"
+            fn on_frame(info) {{
+                this.frames = if "frames" in this {{ this.frames + 1 }} else {{ 1 }};
+                if info.step == 3 {{
+                    palette("viridis");
+                    snapshot("{}");
+                    stop();
+                }}
+            }}
+            "#,
+            path.display()
+        );
+        let mut hook = ScriptHook::new(&script).unwrap();
+        assert_eq!(run(&mut hook, 100), 3);
+        assert!(hook.error().is_none());
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn script_errors_stop_the_run() {
+        assert!(matches!(
+            ScriptHook::new("fn other(x) { x }"),
+            Err(ScriptError::Script(_))
+        ));
+        assert!(ScriptHook::new("fn on_frame(info) {").is_err());
+        let mut hook = ScriptHook::new("fn on_frame(info) { perturb(2.0); }").unwrap();
+        assert_eq!(run(&mut hook, 100), 0);
+        assert!(matches!(hook.error(), Some(ScriptError::Script(_))));
+        let mut hook = ScriptHook::new(r#"fn on_frame(info) { palette("nope"); }"#).unwrap();
+        assert_eq!(run(&mut hook, 100), 0);
+        assert!(matches!(hook.error(), Some(ScriptError::Palette(_))));
+    }
+}
@@ -0,0 +1,495 @@
+#[cfg(feature = "output")]
+use super::{gif_side, AtomicFile};
+use super::{Frame, FrameFilter, FrameMeta, RunReport};
+use crate::automaton::AutomatonImpl;
+#[cfg(feature = "output")]
+use crate::automaton::Dimensions;
+#[cfg(feature = "output")]
+use gif::Encoder;
+#[cfg(feature = "output")]
+use std::borrow::Cow;
+use std::io::{self, Write};
+#[cfg(feature = "output")]
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// A destination for the frames of a simulation.
+pub trait OutputSink {
+    /// Writes a frame. All the frames of a run have the same size.
+    fn write_frame(&mut self, frame: &Frame, meta: &FrameMeta) -> Result<(), io::Error>;
+
+    /// Completes the output after the last frame.
+    fn finish(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+impl<S: OutputSink + ?Sized> OutputSink for &mut S {
+    fn write_frame(&mut self, frame: &Frame, meta: &FrameMeta) -> Result<(), io::Error> {
+        (**self).write_frame(frame, meta)
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        (**self).finish()
+    }
+}
+
+/// Runs a simulation for `steps` steps and writes a frame every `skip` steps
+/// to a sink, after passing it through a filter. The returned report doesn't
+/// include the output size, which only the caller knows.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, NullSink};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 32, Rule::gol());
+/// automaton.random_init();
+/// let report = output::write_to_sink(&mut automaton, 20, 2, &mut FilterChain::new(), &mut NullSink)?;
+/// assert_eq!(report.frames, 10);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_to_sink<T, F, S>(
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    filter: &mut F,
+    sink: &mut S,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+    F: FrameFilter,
+    S: OutputSink,
+{
+    let start = Instant::now();
+    let (size, states) = (autom.size(), autom.states());
+    let n_frames = steps / skip;
+    // The frame is reused across steps, the grid being copied to the
+    // allocation left by the filters of the previous frame.
+    let mut frame = Frame {
+        cells: Vec::with_capacity(size * size),
+        size,
+    };
+    let (mut frames, mut step) = (0, 0);
+    while step < steps {
+        autom.grid_into(&mut frame.cells);
+        frame.size = size;
+        let meta = FrameMeta {
+            index: frames,
+            step: step as u64,
+            states,
+        };
+        filter.apply(&mut frame, &meta);
+        eprint!("\rProcessing image {}/{}", frames + 1, n_frames);
+        sink.write_frame(&frame, &meta)?;
+        frames += 1;
+        for _ in 0..skip {
+            autom.update();
+        }
+        step += skip;
+    }
+    eprintln!();
+    sink.finish()?;
+    let mut report = RunReport {
+        frames,
+        ..Default::default()
+    }
+    .with_work(skip, size * size);
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// Encodes the frames as a looping GIF animation. The palette is written
+/// once as the global palette and the frames are encoded from borrowed
+/// pixels, without copying them. The GIF is complete once the sink is
+/// finished or dropped.
+#[cfg(feature = "output")]
+pub struct GifSink<W: Write> {
+    writer: Option<W>,
+    encoder: Option<Encoder<W>>,
+    palette: Vec<u8>,
+    delay: u16,
+}
+
+#[cfg(feature = "output")]
+impl<W: Write> GifSink<W> {
+    /// Makes a sink encoding frames to a writer with the given palette (3
+    /// bytes per state) and delay between frames (in hundredths of a second).
+    pub fn new(writer: W, palette: Vec<u8>, delay: u16) -> GifSink<W> {
+        GifSink {
+            writer: Some(writer),
+            encoder: None,
+            palette,
+            delay,
+        }
+    }
+}
+
+#[cfg(feature = "output")]
+impl<W: Write> OutputSink for GifSink<W> {
+    fn write_frame(&mut self, frame: &Frame, _: &FrameMeta) -> Result<(), io::Error> {
+        let size = gif_side(Dimensions::new(frame.size, 1))?;
+        if let Some(writer) = self.writer.take() {
+            let mut encoder =
+                Encoder::new(writer, size, size, &self.palette).map_err(io::Error::other)?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(io::Error::other)?;
+            self.encoder = Some(encoder);
+        }
+        let encoder = self
+            .encoder
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the GIF sink is already finished"))?;
+        let gif_frame = borrowed_gif_frame(size, &frame.cells, self.delay);
+        encoder.write_frame(&gif_frame).map_err(io::Error::other)
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        // Dropping the encoder writes the trailer of the GIF.
+        self.encoder.take();
+        Ok(())
+    }
+}
+
+/// Returns a square GIF frame of indexed pixels in the global palette,
+/// borrowing the pixels.
+#[cfg(feature = "output")]
+pub(crate) fn borrowed_gif_frame(size: u16, pixels: &[u8], delay: u16) -> gif::Frame<'_> {
+    assert_eq!(pixels.len(), size as usize * size as usize);
+    gif::Frame {
+        width: size,
+        height: size,
+        buffer: Cow::Borrowed(pixels),
+        delay,
+        ..gif::Frame::default()
+    }
+}
+
+/// Encodes the frames as a looping animated PNG (APNG). APNG frames are
+/// compressed with deflate, which usually gives much smaller files than GIF
+/// on large grids. The number of frames is written in the header, so it must
+/// be known in advance. The animation is complete once the sink is finished
+/// or dropped.
+#[cfg(feature = "output")]
+pub struct ApngSink<W: Write> {
+    writer: Option<W>,
+    encoder: Option<png::Writer<W>>,
+    palette: Vec<u8>,
+    delay: u16,
+    n_frames: u32,
+}
+
+#[cfg(feature = "output")]
+impl<W: Write> ApngSink<W> {
+    /// Makes a sink encoding `n_frames` frames to a writer with the given
+    /// palette (3 bytes per state) and delay between frames (in hundredths of
+    /// a second).
+    pub fn new(writer: W, palette: Vec<u8>, delay: u16, n_frames: u32) -> ApngSink<W> {
+        ApngSink {
+            writer: Some(writer),
+            encoder: None,
+            palette,
+            delay,
+            n_frames,
+        }
+    }
+}
+
+#[cfg(feature = "output")]
+impl<W: Write> OutputSink for ApngSink<W> {
+    fn write_frame(&mut self, frame: &Frame, _: &FrameMeta) -> Result<(), io::Error> {
+        if let Some(writer) = self.writer.take() {
+            let size = frame.size as u32;
+            let mut encoder = png::Encoder::new(writer, size, size);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(self.palette.as_slice());
+            encoder.set_animated(self.n_frames.max(1), 0)?;
+            encoder.set_frame_delay(self.delay, 100)?;
+            self.encoder = Some(encoder.write_header()?);
+        }
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the APNG sink is already finished"))?
+            .write_image_data(&frame.cells)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish().map_err(io::Error::from),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes every frame to its own indexed PNG file `$PREFIX_$INDEX.png`, the
+/// index being padded to 5 digits.
+#[cfg(feature = "output")]
+pub struct PngSequenceSink {
+    prefix: PathBuf,
+    palette: Vec<u8>,
+}
+
+#[cfg(feature = "output")]
+impl PngSequenceSink {
+    /// Makes a sink writing files starting with `prefix` with the given
+    /// palette (3 bytes per state).
+    pub fn new<P: Into<PathBuf>>(prefix: P, palette: Vec<u8>) -> PngSequenceSink {
+        PngSequenceSink {
+            prefix: prefix.into(),
+            palette,
+        }
+    }
+
+    /// Returns the path of the file of a frame.
+    pub fn frame_path(&self, index: u32) -> PathBuf {
+        let mut name = self.prefix.clone().into_os_string();
+        name.push(format!("_{:05}.png", index));
+        name.into()
+    }
+}
+
+#[cfg(feature = "output")]
+impl OutputSink for PngSequenceSink {
+    fn write_frame(&mut self, frame: &Frame, meta: &FrameMeta) -> Result<(), io::Error> {
+        let mut f = AtomicFile::create(self.frame_path(meta.index))?;
+        encode_png(&mut f, frame, &self.palette)?;
+        f.commit()
+    }
+}
+
+/// Encodes a frame as an indexed PNG image with the given palette (3 bytes
+/// per state).
+#[cfg(feature = "output")]
+pub(super) fn encode_png<W: Write>(w: W, frame: &Frame, palette: &[u8]) -> Result<(), io::Error> {
+    let mut encoder = png::Encoder::new(w, frame.size as u32, frame.size as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    encoder.write_header()?.write_image_data(&frame.cells)?;
+    Ok(())
+}
+
+/// Writes the cells of every frame as raw bytes, one byte per cell in
+/// row-major order, with no header. This is meant to be piped to other tools,
+/// e.g. `ffmpeg -f rawvideo -pix_fmt gray -s $SIZEx$SIZE -i -`.
+pub struct RawSink<W: Write>(pub W);
+
+impl<W: Write> OutputSink for RawSink<W> {
+    fn write_frame(&mut self, frame: &Frame, _: &FrameMeta) -> Result<(), io::Error> {
+        self.0.write_all(&frame.cells)
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        self.0.flush()
+    }
+}
+
+/// Writes the cells of the frames as a NumPy `.npy` array of shape
+/// `[frames, size, size]` and dtype `u8`, to be loaded with `numpy.load`.
+/// The shape is written in the header, so the number of frames must be known
+/// in advance; finishing the sink after another number of frames is an error.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, NpySink};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 16, Rule::gol());
+/// let mut sink = NpySink::new(vec![], 10);
+/// output::write_to_sink(&mut automaton, 10, 1, &mut FilterChain::new(), &mut sink)?;
+/// let npy = sink.into_inner();
+/// assert!(npy.starts_with(b"\x93NUMPY"));
+/// assert_eq!(npy.len(), 128 + 10 * 16 * 16);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct NpySink<W: Write> {
+    writer: W,
+    n_frames: u32,
+    written: u32,
+}
+
+impl<W: Write> NpySink<W> {
+    /// Makes a sink writing an array of `n_frames` frames to a writer.
+    pub fn new(writer: W, n_frames: u32) -> NpySink<W> {
+        NpySink {
+            writer,
+            n_frames,
+            written: 0,
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> OutputSink for NpySink<W> {
+    fn write_frame(&mut self, frame: &Frame, _: &FrameMeta) -> Result<(), io::Error> {
+        if self.written == 0 {
+            let mut header = format!(
+                "{{'descr': '|u1', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+                self.n_frames, frame.size, frame.size
+            );
+            // The magic string, version and header length take 10 bytes, and
+            // the header ends with a newline at a multiple of 64 bytes.
+            let padding = 63 - (10 + header.len()) % 64;
+            header.extend(std::iter::repeat_n(' ', padding));
+            header.push('\n');
+            self.writer.write_all(b"\x93NUMPY\x01\x00")?;
+            self.writer
+                .write_all(&(header.len() as u16).to_le_bytes())?;
+            self.writer.write_all(header.as_bytes())?;
+        }
+        if self.written == self.n_frames {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "more frames than announced in the npy header",
+            ));
+        }
+        self.written += 1;
+        self.writer.write_all(&frame.cells)
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        if self.written != self.n_frames {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} frames written but {} announced in the npy header",
+                    self.written, self.n_frames
+                ),
+            ));
+        }
+        self.writer.flush()
+    }
+}
+
+/// Discards the frames. Useful to benchmark the simulation or to run tests
+/// without encoding anything.
+pub struct NullSink;
+
+impl OutputSink for NullSink {
+    fn write_frame(&mut self, _: &Frame, _: &FrameMeta) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_to_sink, NpySink, NullSink, OutputSink, RawSink};
+    use crate::automaton::{Automaton, AutomatonImpl, TiledAutomaton};
+    use crate::output::{Frame, FrameMeta, Upscale};
+    use crate::rule::Rule;
+    use std::io;
+
+    /// Records the frames it receives.
+    struct Recorder(Vec<(Frame, FrameMeta)>, bool);
+
+    impl OutputSink for Recorder {
+        fn write_frame(&mut self, frame: &Frame, meta: &FrameMeta) -> Result<(), io::Error> {
+            self.0.push((frame.clone(), *meta));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), io::Error> {
+            self.1 = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sinks_receive_filtered_frames() {
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        a.random_init();
+        let first = a.grid();
+        let mut recorder = Recorder(vec![], false);
+        let report = write_to_sink(&mut a, 6, 3, &mut Upscale(2), &mut recorder).unwrap();
+        assert_eq!(report.frames, 2);
+        assert_eq!(report.steps, 6);
+        assert_eq!(report.cell_updates, 6 * 64);
+        assert!(recorder.1);
+        let (frame, meta) = &recorder.0[1];
+        assert_eq!((frame.size, meta.index, meta.step), (16, 1, 3));
+        assert_eq!(recorder.0[0].0.cells[..2], [first[0], first[0]]);
+    }
+
+    #[test]
+    fn reused_frames_match_the_step_iterator() {
+        let rule = Rule::random(1, 3);
+        let (mut a, mut b) = (
+            TiledAutomaton::new(3, 256, rule.clone()),
+            TiledAutomaton::new(3, 256, rule),
+        );
+        a.random_init_seeded(3);
+        b.random_init_seeded(3);
+        let mut recorder = Recorder(vec![], false);
+        write_to_sink(&mut a, 8, 2, &mut Upscale(2), &mut recorder).unwrap();
+        let frames: Vec<Vec<u8>> = b.skipped_iter(8, 2, 2).collect();
+        assert_eq!(recorder.0.len(), frames.len());
+        for ((frame, _), expected) in recorder.0.iter().zip(&frames) {
+            assert_eq!(&frame.cells, expected);
+        }
+        assert_eq!(a.grid(), b.grid());
+    }
+
+    #[cfg(feature = "output")]
+    #[test]
+    fn gif_frames_borrow_their_pixels() {
+        use super::GifSink;
+
+        let mut a = Automaton::new(3, 16, Rule::random(1, 3));
+        a.random_init();
+        let first = a.grid();
+        let palette = crate::output::make_palette(3, 0);
+        let mut gif = vec![];
+        let mut sink = GifSink::new(&mut gif, palette.clone(), 5);
+        write_to_sink(&mut a, 4, 1, &mut Upscale(1), &mut sink).unwrap();
+        drop(sink);
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(&gif[..]).unwrap();
+        assert_eq!(decoder.global_palette().unwrap()[..9], palette[..]);
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!((frame.delay, frame.palette.is_none()), (5, true));
+        assert_eq!(frame.buffer[..], first[..]);
+        let mut frames = 1;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 4);
+    }
+
+    #[test]
+    fn raw_sink_writes_the_cells() {
+        let mut a = Automaton::new(3, 4, Rule::random(1, 3));
+        a.random_init();
+        let (first, mut raw) = (a.grid(), RawSink(vec![]));
+        write_to_sink(&mut a, 3, 1, &mut Upscale(1), &mut raw).unwrap();
+        assert_eq!(raw.0.len(), 3 * 16);
+        assert_eq!(raw.0[..16], first[..]);
+        let report = write_to_sink(&mut a, 3, 1, &mut Upscale(1), &mut NullSink).unwrap();
+        assert_eq!(report.frames, 3);
+    }
+
+    #[test]
+    fn npy_headers_describe_the_frames() {
+        let mut a = Automaton::new(3, 5, Rule::random(1, 3));
+        a.random_init();
+        let (first, mut npy) = (a.grid(), NpySink::new(vec![], 4));
+        write_to_sink(&mut a, 8, 2, &mut Upscale(1), &mut npy).unwrap();
+        let bytes = npy.into_inner();
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (4, 5, 5)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_len + 4 * 25);
+        assert_eq!(bytes[10 + header_len..10 + header_len + 25], first[..]);
+        let mut short = NpySink::new(vec![], 5);
+        assert!(write_to_sink(&mut a, 8, 2, &mut Upscale(1), &mut short).is_err());
+    }
+}
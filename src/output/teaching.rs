@@ -0,0 +1,230 @@
+use std::io;
+use std::path::Path;
+
+use super::{write_frames, Palette, RunReport};
+use crate::automaton::{AutomatonImpl, Dimensions};
+use crate::rule::Rule;
+
+/// The largest grids rendered by [`write_teaching_gif_file`].
+pub const MAX_TEACHING_SIZE: usize = 32;
+
+/// The digits of the labels, 3 pixels wide and 5 pixels high, one row per
+/// byte with the leftmost pixel in the highest bit.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Options for rendering tiny grids for the classroom with
+/// [`write_teaching_gif_file`].
+#[derive(Debug, Clone)]
+pub struct Teaching {
+    /// The side of the cells in pixels, gridlines excluded.
+    pub cell: u16,
+    /// Whether the state of every cell is written in its top left corner.
+    pub labels: bool,
+    /// A rule whose table position for the neighborhood of every cell (on a
+    /// periodic grid) is written in the bottom left corner of the cell, when
+    /// it fits. This is the transition the cell goes through at the next
+    /// step.
+    pub rule: Option<Rule>,
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The rotation of the palette (the colors, not the states).
+    pub palette_rotate: u8,
+}
+
+impl Default for Teaching {
+    fn default() -> Self {
+        Teaching {
+            cell: 24,
+            labels: true,
+            rule: None,
+            palette: Palette::default(),
+            palette_rotate: 0,
+        }
+    }
+}
+
+/// A frame of palette indices being annotated.
+struct Canvas {
+    pixels: Vec<u8>,
+    side: usize,
+    /// The side of the pixels of the digits.
+    dot: usize,
+}
+
+impl Canvas {
+    fn fill(&mut self, (row, col): (usize, usize), (height, width): (usize, usize), color: u8) {
+        for r in row..row + height {
+            self.pixels[r * self.side + col..r * self.side + col + width].fill(color);
+        }
+    }
+
+    /// Writes a number from its top left corner, if it fits in `width`
+    /// pixels.
+    fn number(&mut self, (row, col): (usize, usize), width: usize, n: usize, color: u8) {
+        let digits = n.to_string();
+        if digits.len() * 4 * self.dot > width + self.dot {
+            return;
+        }
+        for (k, d) in digits.bytes().enumerate() {
+            let glyph = &DIGITS[(d - b'0') as usize];
+            let left = col + k * 4 * self.dot;
+            for (y, bits) in glyph.iter().enumerate() {
+                for x in (0..3).filter(|x| bits >> (2 - x) & 1 == 1) {
+                    let at = (row + y * self.dot, left + x * self.dot);
+                    self.fill(at, (self.dot, self.dot), color);
+                }
+            }
+        }
+    }
+}
+
+/// Write the CA to a GIF file for teaching: every cell of a tiny grid (at
+/// most [`MAX_TEACHING_SIZE`] cells wide) is drawn as a large square between
+/// gridlines, labeled with its state and optionally with the rule table
+/// position of its neighborhood. The labels are black or white, whichever
+/// contrasts the most with the color of the cell.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, Teaching};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 8, Rule::gol());
+/// automaton.random_init();
+/// let teaching = Teaching {
+///     rule: Some(Rule::gol()),
+///     ..Default::default()
+/// };
+/// output::write_teaching_gif_file(Some("test_teaching.gif"), &mut automaton, 4, 1, 50, &teaching)?;
+/// # std::fs::remove_file("test_teaching.gif")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_teaching_gif_file<P: AsRef<Path>, T>(
+    path: Option<P>,
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+    delay: u16,
+    teaching: &Teaching,
+) -> Result<RunReport, io::Error>
+where
+    T: AutomatonImpl,
+{
+    let (size, states) = (autom.size(), autom.states());
+    if size > MAX_TEACHING_SIZE || states > 253 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "teaching renders grids of at most {} cells with at most 253 states",
+                MAX_TEACHING_SIZE
+            ),
+        ));
+    }
+    let cell = teaching.cell.max(1) as usize;
+    let side = size * (cell + 1) + 1;
+    // The colors of the states, then of the gridlines and of the labels.
+    let mut palette = teaching
+        .palette
+        .to_rgb(states as usize, teaching.palette_rotate);
+    let (grid_color, black, white) = (states, states + 1, states + 2);
+    palette.extend_from_slice(&[0x80, 0x80, 0x80, 0, 0, 0, 0xff, 0xff, 0xff]);
+    let label_colors: Vec<u8> = palette
+        .chunks(3)
+        .take(states as usize)
+        .map(|c| {
+            let luma = 299 * c[0] as u32 + 587 * c[1] as u32 + 114 * c[2] as u32;
+            if luma > 128_000 {
+                black
+            } else {
+                white
+            }
+        })
+        .collect();
+    let dot = (cell / 16).max(1);
+    let frames = autom.skipped_iter(steps, skip, 1).map(|grid| {
+        let mut canvas = Canvas {
+            pixels: vec![grid_color; side * side],
+            side,
+            dot,
+        };
+        for i in 0..size {
+            for j in 0..size {
+                let state = grid[i * size + j];
+                let (row, col) = (i * (cell + 1) + 1, j * (cell + 1) + 1);
+                canvas.fill((row, col), (cell, cell), state);
+                let label = label_colors[state as usize];
+                if teaching.labels && cell >= 6 * dot {
+                    canvas.number((row + dot, col + dot), cell - dot, state as usize, label);
+                }
+                if let Some(rule) = &teaching.rule {
+                    if cell >= 13 * dot {
+                        let position = rule.position_on_torus(&grid, size, (i, j));
+                        let bottom = (row + cell - 6 * dot, col + dot);
+                        canvas.number(bottom, cell - dot, position, label);
+                    }
+                }
+            }
+        }
+        canvas.pixels
+    });
+    write_frames(
+        path,
+        Dimensions::new(side, 1),
+        &[palette],
+        1,
+        delay,
+        steps / skip,
+        frames,
+    )
+    .map(|r| r.with_work(skip, size * size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_teaching_gif_file, Teaching};
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::rule::Rule;
+
+    #[test]
+    fn cells_are_drawn_between_gridlines_with_labels() {
+        let path = std::env::temp_dir().join("rust_ca_teaching.gif");
+        let mut a = Automaton::new(2, 4, Rule::gol());
+        a.grid_mut()[5] = 1;
+        let teaching = Teaching {
+            cell: 16,
+            rule: Some(Rule::gol()),
+            ..Default::default()
+        };
+        let report = write_teaching_gif_file(Some(&path), &mut a, 2, 1, 50, &teaching).unwrap();
+        assert_eq!(report.frames, 2);
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options
+            .read_info(std::fs::File::open(&path).unwrap())
+            .unwrap();
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        let side = 4 * 17 + 1;
+        assert_eq!((frame.width as usize, frame.height as usize), (side, side));
+        let pixel = |row: usize, col: usize| frame.buffer[row * side + col];
+        // The gridlines, the live cell (1, 1) and a dead cell.
+        assert_eq!((pixel(0, 5), pixel(17, 30), pixel(30, 17)), (2, 2, 2));
+        assert_eq!((pixel(17 + 16, 17 + 8), pixel(5, 12)), (1, 0));
+        // The top row of the label "1" of the live cell and of the label
+        // "0" of the dead cell (0, 0).
+        assert_eq!(pixel(18 + 1, 18 + 1), 1);
+        assert!(matches!(pixel(18 + 1, 18 + 2), 3 | 4));
+        assert!(matches!(pixel(2, 2), 3 | 4));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
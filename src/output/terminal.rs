@@ -0,0 +1,256 @@
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, terminal};
+
+use super::{Palette, RunReport};
+use crate::automaton::AutomatonImpl;
+
+/// Options of the live terminal viewer [`watch`].
+#[derive(Debug, Clone)]
+pub struct TerminalView {
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The rotation of the palette (the colors, not the states).
+    pub palette_rotate: u8,
+    /// The number of updates between two drawn grids.
+    pub skip: u32,
+    /// The time between two drawn grids while playing.
+    pub delay: Duration,
+}
+
+impl Default for TerminalView {
+    fn default() -> Self {
+        TerminalView {
+            palette: Palette::default(),
+            palette_rotate: 0,
+            skip: 1,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// What a key press does in the viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Play or pause the simulation.
+    Toggle,
+    /// Pause and advance by a single frame.
+    Step,
+    Quit,
+}
+
+impl Action {
+    fn from_key(key: KeyEvent) -> Option<Action> {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::Quit)
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') => Some(Action::Toggle),
+            KeyCode::Char('n') | KeyCode::Char('s') | KeyCode::Right => Some(Action::Step),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Restores the terminal when the viewer stops, even on errors.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws the top left corner of a grid of side `size`, at most `rows` cells
+/// high and `cols` cells wide, with unicode half blocks: every character
+/// shows two cells of a column, the upper one in the foreground color and
+/// the lower one in the background color (24-bit ANSI colors). `palette`
+/// holds the RGB triplets of the states, as returned by
+/// [`Palette::to_rgb`]. The lines end with `\r\n`, so that they can be
+/// written to a terminal in raw mode.
+///
+/// ```
+/// use rust_ca::output::{render_half_blocks, Palette};
+///
+/// let palette = Palette::grayscale().to_rgb(2, 0);
+/// let frame = render_half_blocks(&[1, 0, 0, 1], 2, &palette, (2, 2));
+/// assert_eq!(frame.lines().count(), 1);
+/// assert_eq!(frame.matches('▀').count(), 2);
+/// assert!(frame.starts_with("\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀"));
+/// ```
+pub fn render_half_blocks(
+    grid: &[u8],
+    size: usize,
+    palette: &[u8],
+    (rows, cols): (usize, usize),
+) -> String {
+    let (rows, cols) = (rows.min(size), cols.min(size));
+    let color = |state: u8| &palette[3 * state as usize..3 * state as usize + 3];
+    let mut frame = String::new();
+    for line in 0..rows.div_ceil(2) {
+        let (mut fg, mut bg) = (None, None);
+        for j in 0..cols {
+            let top = grid[2 * line * size + j];
+            if fg != Some(top) {
+                let c = color(top);
+                let _ = write!(frame, "\x1b[38;2;{};{};{}m", c[0], c[1], c[2]);
+                fg = Some(top);
+            }
+            // An odd number of rows leaves the last lower halves empty.
+            let bottom = (2 * line + 1 < rows).then(|| grid[(2 * line + 1) * size + j]);
+            if bg != Some(bottom) {
+                match bottom {
+                    Some(state) => {
+                        let c = color(state);
+                        let _ = write!(frame, "\x1b[48;2;{};{};{}m", c[0], c[1], c[2]);
+                    }
+                    None => frame.push_str("\x1b[49m"),
+                }
+                bg = Some(bottom);
+            }
+            frame.push('▀');
+        }
+        frame.push_str("\x1b[0m\x1b[K\r\n");
+    }
+    frame
+}
+
+/// Runs the CA live in the terminal, drawing its grid with
+/// [`render_half_blocks`] every `view.skip` updates and as much of the grid
+/// as fits in the terminal. The space bar plays or pauses the simulation,
+/// `n` (or the right arrow) advances it by a frame while paused, and `q` (or
+/// escape) quits. The viewer starts playing and runs until it is quit; the
+/// report counts the steps run and the frames drawn.
+///
+/// Returns an error if the standard output isn't a terminal.
+pub fn watch<T: AutomatonImpl>(autom: &mut T, view: &TerminalView) -> io::Result<RunReport> {
+    let mut stdout = io::stdout();
+    if !stdout.is_terminal() {
+        return Err(io::Error::other("the viewer needs a terminal"));
+    }
+    let (size, states) = (autom.size(), autom.states());
+    let palette = view.palette.to_rgb(states as usize, view.palette_rotate);
+    let mut report = RunReport::default();
+    let start = Instant::now();
+    let mut grid = vec![];
+    let mut playing = true;
+    let _guard = TerminalGuard::enter()?;
+    'frames: loop {
+        let (cols, lines) = terminal::size()?;
+        let rows = 2 * lines.saturating_sub(1) as usize;
+        autom.grid_into(&mut grid);
+        queue!(stdout, cursor::MoveTo(0, 0))?;
+        stdout.write_all(
+            render_half_blocks(&grid, size, &palette, (rows, cols as usize)).as_bytes(),
+        )?;
+        let status = format!(
+            "step {} | {} | space: play/pause, n: step, q: quit",
+            report.steps,
+            if playing { "playing" } else { "paused" }
+        );
+        let status: String = status.chars().take(cols as usize).collect();
+        queue!(stdout, cursor::MoveTo(0, lines.saturating_sub(1)))?;
+        write!(stdout, "{}\x1b[K", status)?;
+        stdout.flush()?;
+        report.frames += 1;
+
+        let deadline = Instant::now() + view.delay;
+        let advance = loop {
+            let event = if playing {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if !event::poll(timeout)? {
+                    break true;
+                }
+                event::read()?
+            } else {
+                event::read()?
+            };
+            match event {
+                Event::Key(key) => match Action::from_key(key) {
+                    Some(Action::Quit) => break 'frames,
+                    Some(Action::Toggle) => {
+                        playing = !playing;
+                        break playing;
+                    }
+                    Some(Action::Step) => {
+                        playing = false;
+                        break true;
+                    }
+                    None => {}
+                },
+                Event::Resize(..) => {
+                    queue!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                    break false;
+                }
+                _ => {}
+            }
+        };
+        if advance {
+            for _ in 0..view.skip {
+                autom.update();
+            }
+            report.steps += view.skip as u64;
+        }
+    }
+    report.cell_updates = report.steps * (size * size) as u64;
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_half_blocks, Action};
+    use crate::output::Palette;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn half_blocks_pair_the_rows() {
+        let palette = Palette::grayscale().to_rgb(2, 0);
+        // Three rows: the lower halves of the second line are empty.
+        let grid = [1, 1, 1, 0, 0, 0, 1, 0, 1];
+        let frame = render_half_blocks(&grid, 3, &palette, (3, 3));
+        let lines: Vec<&str> = frame.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        // The colors are only written when they change.
+        assert_eq!(lines[0].matches("\x1b[38;2;").count(), 1);
+        assert_eq!(lines[1].matches("\x1b[38;2;").count(), 3);
+        assert!(lines[1].contains("\x1b[49m▀"));
+        // The frame is cropped to the viewport.
+        let frame = render_half_blocks(&grid, 3, &palette, (2, 1));
+        assert_eq!(frame.matches('▀').count(), 1);
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(
+            Action::from_key(key(KeyCode::Char(' '))),
+            Some(Action::Toggle)
+        );
+        assert_eq!(Action::from_key(key(KeyCode::Right)), Some(Action::Step));
+        assert_eq!(
+            Action::from_key(key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+        assert_eq!(Action::from_key(key(KeyCode::Char('x'))), None);
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(Action::from_key(ctrl_c), Some(Action::Quit));
+    }
+}
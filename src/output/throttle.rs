@@ -0,0 +1,147 @@
+use super::{Frame, FrameMeta, OutputSink};
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The settings of a [`ThrottledSink`], shared between the sink and the code
+/// controlling it. Cloning the control gives another handle on the same
+/// settings, so they can be changed from another thread while the simulation
+/// runs. A setting of 0 disables the corresponding limit.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleControl(Arc<[AtomicU32; 3]>);
+
+impl ThrottleControl {
+    const RENDER_EVERY: usize = 0;
+    const MAX_FPS: usize = 1;
+    const MAX_STEPS_PER_SEC: usize = 2;
+
+    /// Makes a control with no limits.
+    pub fn new() -> ThrottleControl {
+        ThrottleControl::default()
+    }
+
+    /// Only renders one frame out of `n`.
+    pub fn set_render_every(&self, n: u32) {
+        self.0[Self::RENDER_EVERY].store(n, Ordering::Relaxed)
+    }
+
+    /// Drops the frames that come less than `1 / fps` seconds after the
+    /// last rendered frame. The simulation isn't slowed down.
+    pub fn set_max_fps(&self, fps: u32) {
+        self.0[Self::MAX_FPS].store(fps, Ordering::Relaxed)
+    }
+
+    /// Slows the simulation down to at most `steps` steps per second.
+    pub fn set_max_steps_per_sec(&self, steps: u32) {
+        self.0[Self::MAX_STEPS_PER_SEC].store(steps, Ordering::Relaxed)
+    }
+
+    fn get(&self, setting: usize) -> u32 {
+        self.0[setting].load(Ordering::Relaxed)
+    }
+}
+
+/// Wraps a sink to decouple the speed of the simulation from the rendering
+/// of live outputs, e.g. raw frames piped to a video player. Frames can be
+/// skipped (every `n` frames, or when they come faster than a target frame
+/// rate) and the simulation can be capped to a number of steps per second.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, RawSink, ThrottleControl, ThrottledSink};
+/// use rust_ca::rule::Rule;
+///
+/// let mut automaton = Automaton::new(2, 16, Rule::gol());
+/// automaton.random_init();
+/// let control = ThrottleControl::new();
+/// control.set_render_every(4);
+/// let mut sink = ThrottledSink::new(RawSink(vec![]), control.clone());
+/// output::write_to_sink(&mut automaton, 20, 1, &mut FilterChain::new(), &mut sink)?;
+/// assert_eq!(sink.into_inner().0.len(), 5 * 16 * 16);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ThrottledSink<S: OutputSink> {
+    inner: S,
+    control: ThrottleControl,
+    last_render: Option<Instant>,
+    start: Option<(Instant, u64)>,
+}
+
+impl<S: OutputSink> ThrottledSink<S> {
+    /// Wraps a sink, throttled with the settings of `control`.
+    pub fn new(inner: S, control: ThrottleControl) -> ThrottledSink<S> {
+        ThrottledSink {
+            inner,
+            control,
+            last_render: None,
+            start: None,
+        }
+    }
+
+    /// Returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: OutputSink> OutputSink for ThrottledSink<S> {
+    fn write_frame(&mut self, frame: &Frame, meta: &FrameMeta) -> Result<(), io::Error> {
+        let now = Instant::now();
+        let max_steps = self.control.get(ThrottleControl::MAX_STEPS_PER_SEC);
+        let (start, first_step) = *self.start.get_or_insert((now, meta.step));
+        if max_steps > 0 {
+            // Sleep until the steps since the first frame took long enough.
+            let target =
+                Duration::from_secs_f64((meta.step - first_step) as f64 / max_steps as f64);
+            if let Some(wait) = target.checked_sub(now - start) {
+                std::thread::sleep(wait);
+            }
+        }
+        let every = self.control.get(ThrottleControl::RENDER_EVERY).max(1);
+        if !meta.index.is_multiple_of(every) {
+            return Ok(());
+        }
+        let max_fps = self.control.get(ThrottleControl::MAX_FPS);
+        let now = Instant::now();
+        if let (Some(last), true) = (self.last_render, max_fps > 0) {
+            if now - last < Duration::from_secs_f64(1. / max_fps as f64) {
+                return Ok(());
+            }
+        }
+        self.last_render = Some(now);
+        self.inner.write_frame(frame, meta)
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ThrottleControl, ThrottledSink};
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::output::{write_to_sink, FilterChain, RawSink};
+    use crate::rule::Rule;
+    use std::time::Instant;
+
+    #[test]
+    fn throttles_frames_and_steps() {
+        let mut a = Automaton::new(2, 8, Rule::gol());
+        let control = ThrottleControl::new();
+        control.set_max_fps(1);
+        let mut sink = ThrottledSink::new(RawSink(vec![]), control.clone());
+        write_to_sink(&mut a, 10, 1, &mut FilterChain::new(), &mut sink).unwrap();
+        // The other frames all come less than a second after the first one.
+        assert_eq!(sink.into_inner().0.len(), 64);
+
+        control.set_max_fps(0);
+        control.set_max_steps_per_sec(200);
+        let start = Instant::now();
+        let mut sink = ThrottledSink::new(RawSink(vec![]), control);
+        write_to_sink(&mut a, 20, 1, &mut FilterChain::new(), &mut sink).unwrap();
+        assert!(start.elapsed().as_secs_f64() >= 19. / 200.);
+        assert_eq!(sink.into_inner().0.len(), 20 * 64);
+    }
+}
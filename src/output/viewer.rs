@@ -0,0 +1,180 @@
+use std::io;
+use std::time::Instant;
+
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+use super::{Palette, RunReport};
+use crate::automaton::AutomatonImpl;
+
+/// The largest number of updates between two drawn grids in the window.
+const MAX_STEPS_PER_FRAME: u32 = 1 << 12;
+
+/// Options of the window viewer [`view_in_window`].
+#[derive(Debug, Clone)]
+pub struct WindowView {
+    /// The colors of the states.
+    pub palette: Palette,
+    /// The rotation of the palette (the colors, not the states).
+    pub palette_rotate: u8,
+    /// The side of a cell in pixels when the window opens.
+    pub scale: usize,
+    /// The number of updates between two drawn grids when the window opens.
+    pub skip: u32,
+    /// The largest number of grids drawn per second.
+    pub fps: usize,
+}
+
+impl Default for WindowView {
+    fn default() -> Self {
+        WindowView {
+            palette: Palette::default(),
+            palette_rotate: 0,
+            scale: 4,
+            skip: 1,
+            fps: 30,
+        }
+    }
+}
+
+/// What a key press does in the viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Play or pause the simulation.
+    Toggle,
+    /// Pause and advance by a single frame.
+    Step,
+    /// Double the number of updates per frame.
+    Faster,
+    /// Halve the number of updates per frame.
+    Slower,
+    /// Fill the grid with random states.
+    Randomize,
+    Quit,
+}
+
+impl Action {
+    fn from_key(key: Key) -> Option<Action> {
+        match key {
+            Key::Space | Key::P => Some(Action::Toggle),
+            Key::N | Key::S | Key::Right => Some(Action::Step),
+            Key::Up | Key::Equal | Key::NumPadPlus => Some(Action::Faster),
+            Key::Down | Key::Minus | Key::NumPadMinus => Some(Action::Slower),
+            Key::R => Some(Action::Randomize),
+            Key::Q | Key::Escape => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a grid to the `0RGB` pixels of a window, `colors` holding the
+/// pixel of every state.
+fn grid_to_pixels(grid: &[u8], colors: &[u32], pixels: &mut Vec<u32>) {
+    pixels.clear();
+    pixels.extend(grid.iter().map(|&state| colors[state as usize]));
+}
+
+/// Runs the CA live in a window, drawing its whole grid every `view.skip`
+/// updates at most `view.fps` times per second. The space bar plays or
+/// pauses the simulation, `n` (or the right arrow) advances it by a frame
+/// while paused, the up and down arrows (or `+` and `-`) double or halve the
+/// number of updates per frame, `r` fills the grid with random states and
+/// `q` (or escape) quits. The viewer starts playing and runs until it is
+/// quit or its window is closed; the report counts the steps run and the
+/// frames drawn.
+///
+/// Returns an error if the window can't be opened.
+pub fn view_in_window<T: AutomatonImpl>(autom: &mut T, view: &WindowView) -> io::Result<RunReport> {
+    let (size, states) = (autom.size(), autom.states());
+    let colors: Vec<u32> = view
+        .palette
+        .to_rgb(states as usize, view.palette_rotate)
+        .chunks(3)
+        .map(|c| u32::from_be_bytes([0, c[0], c[1], c[2]]))
+        .collect();
+    let side = size * view.scale.max(1);
+    let options = WindowOptions {
+        resize: true,
+        scale_mode: ScaleMode::AspectRatioStretch,
+        ..WindowOptions::default()
+    };
+    let mut window = Window::new("rust_ca", side, side, options).map_err(io::Error::other)?;
+    window.set_target_fps(view.fps.max(1));
+
+    let mut report = RunReport::default();
+    let start = Instant::now();
+    let (mut grid, mut pixels) = (vec![], vec![]);
+    let mut skip = view.skip.clamp(1, MAX_STEPS_PER_FRAME);
+    let mut playing = true;
+    let mut title = String::new();
+    'frames: while window.is_open() {
+        let mut advance = playing;
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match Action::from_key(key) {
+                Some(Action::Quit) => break 'frames,
+                Some(Action::Toggle) => {
+                    playing = !playing;
+                    advance = playing;
+                }
+                Some(Action::Step) => {
+                    playing = false;
+                    advance = true;
+                }
+                Some(Action::Faster) => skip = (skip * 2).min(MAX_STEPS_PER_FRAME),
+                Some(Action::Slower) => skip = (skip / 2).max(1),
+                Some(Action::Randomize) => autom.random_init(),
+                None => {}
+            }
+        }
+        if advance {
+            for _ in 0..skip {
+                autom.update();
+            }
+            report.steps += skip as u64;
+        }
+
+        let status = format!(
+            "rust_ca | step {} | {} | {} steps per frame",
+            report.steps,
+            if playing { "playing" } else { "paused" },
+            skip
+        );
+        if status != title {
+            window.set_title(&status);
+            title = status;
+        }
+        autom.grid_into(&mut grid);
+        grid_to_pixels(&grid, &colors, &mut pixels);
+        window
+            .update_with_buffer(&pixels, size, size)
+            .map_err(io::Error::other)?;
+        report.frames += 1;
+    }
+    report.cell_updates = report.steps * (size * size) as u64;
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grid_to_pixels, Action};
+    use minifb::Key;
+
+    #[test]
+    fn grids_are_converted_to_pixels() {
+        let colors = [0x000000, 0xff8000];
+        let mut pixels = vec![7; 9];
+        grid_to_pixels(&[1, 0, 0, 1], &colors, &mut pixels);
+        assert_eq!(pixels, [0xff8000, 0, 0, 0xff8000]);
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        assert_eq!(Action::from_key(Key::Space), Some(Action::Toggle));
+        assert_eq!(Action::from_key(Key::Right), Some(Action::Step));
+        assert_eq!(Action::from_key(Key::Up), Some(Action::Faster));
+        assert_eq!(Action::from_key(Key::Minus), Some(Action::Slower));
+        assert_eq!(Action::from_key(Key::R), Some(Action::Randomize));
+        assert_eq!(Action::from_key(Key::Escape), Some(Action::Quit));
+        assert_eq!(Action::from_key(Key::X), None);
+    }
+}
@@ -0,0 +1,969 @@
+//! Patterns of cells, read from pattern files or strings, or built in code,
+//! to initialize the grids of the automata.
+//!
+//! ```
+//! use rust_ca::automaton::{Automaton, AutomatonImpl};
+//! use rust_ca::pattern::{Pattern, Rotation};
+//! use rust_ca::rule::Rule;
+//!
+//! let blinker = Pattern::new(2, 0, vec![vec![1, 1, 1]])?;
+//! let glider: Pattern = "x = 3, y = 3\nbo$2bo$3o!".parse()?;
+//! let mut automaton = Automaton::new(2, 32, Rule::gol());
+//! automaton.place_pattern(&blinker, 4, 4, Rotation::Rot90, false)?;
+//! automaton.place_pattern(&glider, 20, 20, Rotation::Rot0, false)?;
+//! assert_eq!(automaton.state_counts()[1], 8);
+//! # Ok::<(), rust_ca::pattern::PatternError>(())
+//! ```
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// A starting pattern: a rectangle of cells in some states, with a
+/// background state for the rest of the grid. Patterns are built with
+/// [`Pattern::new`], parsed from strings, or read from pattern files with
+/// [`Pattern::from_file`], and placed in a CA with
+/// [`AutomatonImpl::place_pattern`](crate::automaton::AutomatonImpl::place_pattern).
+/// Patterns can be turned and mirrored before they are placed, e.g. to send
+/// spaceships in different directions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// The total number of states in the pattern.
+    pub(crate) states: u8,
+    /// The pattern background state (for inserting in a larger CA).
+    pub(crate) background: u8,
+    /// The pattern itself (2D grid).
+    pub(crate) rows: Vec<Vec<u8>>,
+}
+
+/// Error type for an error that happend during pattern parsing.
+#[derive(Debug)]
+pub enum PatternError {
+    /// A io error during pattern parsing.
+    PatternFileError(io::Error),
+    /// A file format error during pattern parsing.
+    PatternFormatError,
+    /// A region of the grid to save as a pattern doesn't fit in the grid.
+    PatternBoundsError,
+    /// A cell or the background of a pattern is in a state outside of the
+    /// states of the pattern or of the CA.
+    PatternStateError,
+    /// The CA can't place patterns on its grid.
+    PatternUnsupported,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternError::PatternFormatError => write!(f, "incorrect pattern format in file"),
+            PatternError::PatternBoundsError => write!(f, "the pattern region is outside the grid"),
+            PatternError::PatternStateError => write!(f, "a state of the pattern is out of range"),
+            PatternError::PatternUnsupported => write!(f, "the CA can't place patterns"),
+            // The wrapped error contains additional information and is available
+            // via the source() method.
+            PatternError::PatternFileError(..) => {
+                write!(f, "io error with the pattern file")
+            }
+        }
+    }
+}
+
+impl error::Error for PatternError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            PatternError::PatternFormatError
+            | PatternError::PatternBoundsError
+            | PatternError::PatternStateError
+            | PatternError::PatternUnsupported => None,
+            // The cause is the underlying implementation error type. Is implicitly
+            // cast to the trait object `&error::Error`. This works because the
+            // underlying type already implements the `Error` trait.
+            PatternError::PatternFileError(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for PatternError {
+    fn from(err: io::Error) -> PatternError {
+        PatternError::PatternFileError(err)
+    }
+}
+
+/// The longest lines written in RLE files, as recommended by Golly.
+const RLE_LINE_LENGTH: usize = 70;
+
+/// A clockwise rotation of a pattern placed in a CA, parsed from `rot0`,
+/// `rot90`, `rot180` or `rot270`.
+///
+/// ```
+/// use rust_ca::pattern::Rotation;
+///
+/// assert_eq!("rot90".parse(), Ok(Rotation::Rot90));
+/// assert!("rot45".parse::<Rotation>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// No rotation.
+    #[default]
+    Rot0,
+    /// A quarter turn.
+    Rot90,
+    /// A half turn.
+    Rot180,
+    /// Three quarter turns.
+    Rot270,
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rot0" => Ok(Rotation::Rot0),
+            "rot90" => Ok(Rotation::Rot90),
+            "rot180" => Ok(Rotation::Rot180),
+            "rot270" => Ok(Rotation::Rot270),
+            _ => Err(format!(
+                "unknown rotation {:?}, expected rot0, rot90, rot180 or rot270",
+                s
+            )),
+        }
+    }
+}
+
+impl Pattern {
+    /// Makes a pattern from its rows of cells, in `states` states, placed on
+    /// a background of state `background`. The short rows are padded with
+    /// the background. An error is returned if a cell or the background isn't
+    /// one of the states.
+    ///
+    /// ```
+    /// use rust_ca::pattern::Pattern;
+    ///
+    /// let pattern = Pattern::new(3, 0, vec![vec![2, 1], vec![1]])?;
+    /// assert_eq!(pattern.rows(), [[2, 1], [1, 0]]);
+    /// assert!(Pattern::new(2, 0, vec![vec![2]]).is_err());
+    /// assert!(Pattern::new(2, 2, vec![vec![1]]).is_err());
+    /// # Ok::<(), rust_ca::pattern::PatternError>(())
+    /// ```
+    pub fn new(states: u8, background: u8, rows: Vec<Vec<u8>>) -> Result<Pattern, PatternError> {
+        let pattern = Pattern {
+            states,
+            background,
+            rows,
+        };
+        pattern.validate()?;
+        Ok(pattern.with_rows(pattern.padded()))
+    }
+
+    /// Makes a broken wave front for the excitable media of
+    /// [`Rule::greenberg_hastings`](crate::rule::Rule::greenberg_hastings)
+    /// with `states` states: a row of `length` excited cells (state 1)
+    /// followed by one row of every refractory state, on a resting
+    /// background. The wave travels up, and its free ends curl into a pair of
+    /// spirals that keep exciting the grid.
+    ///
+    /// ```
+    /// use rust_ca::automaton::{Automaton, AutomatonImpl, BoundaryCondition};
+    /// use rust_ca::pattern::{Pattern, Rotation};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let wave = Pattern::broken_wave(6, 20)?;
+    /// assert_eq!(wave.rows().len(), 5);
+    /// let mut automaton = Automaton::new(6, 64, Rule::greenberg_hastings(6, 1)?);
+    /// automaton.set_boundary(BoundaryCondition::Fixed(0));
+    /// automaton.place_pattern(&wave, 22, 30, Rotation::Rot0, false)?;
+    /// for _ in 0..300 {
+    ///     automaton.update();
+    /// }
+    /// // A plane wave would have left the grid: the spirals are still turning.
+    /// assert!(automaton.grid().contains(&1));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn broken_wave(states: u8, length: usize) -> Result<Pattern, PatternError> {
+        let rows = (1..states.max(2))
+            .map(|state| vec![state; length])
+            .collect();
+        Pattern::new(states, 0, rows)
+    }
+
+    /// Reads a pattern file in any of the formats read by
+    /// [`AutomatonImpl::init_from_pattern`](crate::automaton::AutomatonImpl::init_from_pattern).
+    pub fn from_file(pattern_fname: &str) -> Result<Pattern, PatternError> {
+        parse_pattern(pattern_fname)
+    }
+
+    /// Returns an error if a cell or the background isn't one of the states
+    /// of the pattern.
+    fn validate(&self) -> Result<(), PatternError> {
+        let mut cells = self.rows.iter().flatten();
+        if self.background >= self.states || cells.any(|&c| c >= self.states) {
+            return Err(PatternError::PatternStateError);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of states of the pattern.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Returns the background state of the pattern.
+    pub fn background(&self) -> u8 {
+        self.background
+    }
+
+    /// Returns the number of columns of the longest row of the pattern.
+    fn cols(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Returns the rows of the pattern.
+    pub fn rows(&self) -> &[Vec<u8>] {
+        &self.rows
+    }
+
+    /// Returns the rows of the pattern, the short ones padded with the
+    /// background.
+    fn padded(&self) -> Vec<Vec<u8>> {
+        let cols = self.cols();
+        let mut rows = self.rows.clone();
+        for row in rows.iter_mut() {
+            row.resize(cols, self.background);
+        }
+        rows
+    }
+
+    /// Returns a copy of the pattern with other rows.
+    fn with_rows(&self, rows: Vec<Vec<u8>>) -> Pattern {
+        Pattern {
+            rows,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the pattern rotated clockwise. The short rows are padded with
+    /// the background.
+    ///
+    /// ```
+    /// use rust_ca::pattern::{Pattern, Rotation};
+    ///
+    /// let glider = Pattern::from_file("patterns/glider.rle")?;
+    /// let turned = glider.rotate(Rotation::Rot90);
+    /// assert_eq!(turned.rows(), [[1, 0, 0], [1, 0, 1], [1, 1, 0]]);
+    /// assert_eq!(turned.rotate(Rotation::Rot270), glider);
+    /// # Ok::<(), rust_ca::pattern::PatternError>(())
+    /// ```
+    pub fn rotate(&self, rotation: Rotation) -> Pattern {
+        let rows = self.padded();
+        let column = |j: usize| rows.iter().map(move |row| row[j]);
+        match rotation {
+            Rotation::Rot0 => self.with_rows(rows.clone()),
+            Rotation::Rot90 => self.with_rows(
+                (0..self.cols())
+                    .map(|j| column(j).rev().collect())
+                    .collect(),
+            ),
+            Rotation::Rot180 => self.mirror_horizontally().mirror_vertically(),
+            Rotation::Rot270 => self.with_rows(
+                (0..self.cols())
+                    .rev()
+                    .map(|j| column(j).collect())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the pattern mirrored left to right. The short rows are padded
+    /// with the background.
+    ///
+    /// ```
+    /// use rust_ca::pattern::Pattern;
+    ///
+    /// let glider = Pattern::from_file("patterns/glider.rle")?;
+    /// let mirrored = glider.mirror_horizontally();
+    /// assert_eq!(mirrored.rows(), [[0, 1, 0], [1, 0, 0], [1, 1, 1]]);
+    /// assert_eq!(mirrored.mirror_horizontally(), glider);
+    /// # Ok::<(), rust_ca::pattern::PatternError>(())
+    /// ```
+    pub fn mirror_horizontally(&self) -> Pattern {
+        let mut rows = self.padded();
+        for row in rows.iter_mut() {
+            row.reverse();
+        }
+        self.with_rows(rows)
+    }
+
+    /// Returns the pattern mirrored top to bottom. The short rows are padded
+    /// with the background.
+    ///
+    /// ```
+    /// use rust_ca::pattern::Pattern;
+    ///
+    /// let glider = Pattern::from_file("patterns/glider.rle")?;
+    /// let mirrored = glider.mirror_vertically();
+    /// assert_eq!(mirrored.rows(), [[1, 1, 1], [0, 0, 1], [0, 1, 0]]);
+    /// # Ok::<(), rust_ca::pattern::PatternError>(())
+    /// ```
+    pub fn mirror_vertically(&self) -> Pattern {
+        let mut rows = self.padded();
+        rows.reverse();
+        self.with_rows(rows)
+    }
+
+    /// Returns the cells of the pattern mirrored left to right if `flip` is
+    /// set, rotated, and placed with its top left corner at column `x` and
+    /// row `y` of a square grid of side `size`, as `(row, column, state)`
+    /// triples. The pattern must fit in the grid and in its `states` states,
+    /// otherwise an error is returned.
+    pub(crate) fn placed(
+        &self,
+        (x, y): (usize, usize),
+        (rotation, flip): (Rotation, bool),
+        size: usize,
+        states: u8,
+    ) -> Result<Vec<(usize, usize, u8)>, PatternError> {
+        if self.states > states || self.background >= states {
+            return Err(PatternError::PatternStateError);
+        }
+        let spec = if flip {
+            self.mirror_horizontally().rotate(rotation)
+        } else {
+            self.rotate(rotation)
+        };
+        if !Rect::new(y, x, spec.rows.len(), spec.cols()).fits(size) {
+            return Err(PatternError::PatternBoundsError);
+        }
+        let cells = spec.rows.iter().enumerate().flat_map(|(i, row)| {
+            let cells = row.iter().enumerate();
+            cells.map(move |(j, &state)| (y + i, x + j, state))
+        });
+        Ok(cells.collect())
+    }
+
+    /// Returns the column and row of the top left corner of the pattern when
+    /// it's centered in a square grid of side `size`.
+    pub(crate) fn centered(&self, size: usize) -> (usize, usize) {
+        (
+            (size / 2).saturating_sub(self.cols() / 2),
+            (size / 2).saturating_sub(self.rows.len() / 2),
+        )
+    }
+
+    /// Parses a pattern in the run length encoded format of Golly and the
+    /// LifeWiki, e.g. `x = 3, y = 3\nbo$2bo$3o!` for a glider. The comment
+    /// lines start with `#`, and the rule of the header is ignored. The
+    /// states are `b` (0) and `o` (1), or `.` (0), `A` to `X` (1 to 24) and
+    /// the letters prefixed by `p` to `y` for the next states. The background
+    /// is state 0.
+    pub fn from_rle(rle: &str) -> Result<Pattern, PatternError> {
+        let mut lines = rle.lines().map(str::trim).filter(|l| !l.starts_with('#'));
+        let header = lines.next().ok_or(PatternError::PatternFormatError)?;
+        let dimension = |name: &str| -> Result<usize, PatternError> {
+            header
+                .split(',')
+                .filter_map(|item| item.split_once('='))
+                .find(|(key, _)| key.trim() == name)
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .ok_or(PatternError::PatternFormatError)
+        };
+        let (cols, rows) = (dimension("x")?, dimension("y")?);
+        let mut pattern: Vec<Vec<u8>> = vec![vec![]];
+        let (mut count, mut prefix) = (None, None);
+        'data: for c in lines.flat_map(str::chars) {
+            let state = match c {
+                '0'..='9' => {
+                    let digit = c as usize - '0' as usize;
+                    count = Some(count.unwrap_or(0) * 10 + digit);
+                    continue;
+                }
+                'p'..='y' if prefix.is_none() => {
+                    prefix = Some(c as u8 - b'p' + 1);
+                    continue;
+                }
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => {
+                    let state =
+                        prefix.take().unwrap_or(0) as usize * 24 + (c as u8 - b'A') as usize;
+                    u8::try_from(state + 1).map_err(|_| PatternError::PatternFormatError)?
+                }
+                '$' => {
+                    for _ in 0..count.take().unwrap_or(1) {
+                        pattern.push(vec![]);
+                    }
+                    continue;
+                }
+                '!' => break 'data,
+                c if c.is_whitespace() => continue,
+                _ => return Err(PatternError::PatternFormatError),
+            };
+            if prefix.is_some() {
+                return Err(PatternError::PatternFormatError);
+            }
+            let row = pattern.last_mut().unwrap();
+            row.extend(std::iter::repeat_n(state, count.take().unwrap_or(1)));
+        }
+        let cols = pattern.iter().map(Vec::len).max().unwrap_or(0).max(cols);
+        pattern.resize(rows.max(pattern.len()), vec![]);
+        for row in pattern.iter_mut() {
+            row.resize(cols, 0);
+        }
+        let max_state = pattern.iter().flatten().copied().max().unwrap_or(0);
+        Ok(Pattern {
+            states: max_state.max(1) + 1,
+            background: 0,
+            rows: pattern,
+        })
+    }
+
+    /// Writes the pattern in the run length encoded format (see
+    /// [`Pattern::from_rle`]). The background isn't recorded: the cells
+    /// outside the pattern are in state 0 when it's read back.
+    pub fn to_rle(&self) -> String {
+        let cols = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut tokens = vec![];
+        let mut empty_rows = 0;
+        for row in &self.rows {
+            // The trailing dead cells of the rows are implicit.
+            let len = row.iter().rposition(|&c| c != 0).map_or(0, |k| k + 1);
+            if len == 0 {
+                empty_rows += 1;
+                continue;
+            }
+            if !tokens.is_empty() || empty_rows > 0 {
+                let rows_ended = empty_rows + usize::from(!tokens.is_empty());
+                tokens.push(run(rows_ended, "$".to_string()));
+            }
+            empty_rows = 0;
+            let mut k = 0;
+            while k < len {
+                let length = row[k..len].iter().take_while(|&&c| c == row[k]).count();
+                tokens.push(run(length, rle_state(row[k], self.states)));
+                k += length;
+            }
+        }
+        tokens.push("!".to_string());
+        let mut out = format!("x = {}, y = {}\n", cols, self.rows.len());
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length + token.len() > RLE_LINE_LENGTH {
+                out.push('\n');
+                line_length = 0;
+            }
+            line_length += token.len();
+            out += &token;
+        }
+        out.push('\n');
+        out
+    }
+
+    /// Parses a pattern in the format of the pattern files of the crate: the
+    /// number of states `N=` and the background `BG=`, followed by the rows
+    /// of digits of the pattern between two `#` lines. Every cell must be in
+    /// one of the `N` states.
+    pub fn from_pat(contents: &str) -> Result<Pattern, PatternError> {
+        let mut background: u8 = 0;
+        let mut states: u8 = 0;
+        let mut begin_pattern = false;
+        let mut pattern: Vec<Vec<u8>> = vec![];
+        for line in contents.lines() {
+            if line.starts_with('#') {
+                begin_pattern = !begin_pattern;
+            } else if begin_pattern {
+                let cell = |c: char| {
+                    let digit = (c as u32).checked_sub('0' as u32);
+                    digit.and_then(|d| u8::try_from(d).ok())
+                };
+                let row = line.chars().map(cell).collect::<Option<Vec<u8>>>();
+                pattern.push(row.ok_or(PatternError::PatternFormatError)?);
+            } else if line.contains('=') {
+                let content: Vec<&str> = line.split('=').take(2).collect();
+                match content[0] {
+                    "N" => {
+                        states = content[1]
+                            .parse()
+                            .map_err(|_| PatternError::PatternFormatError)?
+                    }
+                    "BG" => {
+                        background = content[1]
+                            .parse()
+                            .map_err(|_| PatternError::PatternFormatError)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let pattern = Pattern {
+            states,
+            background,
+            rows: pattern,
+        };
+        pattern.validate()?;
+        Ok(pattern)
+    }
+
+    /// Parses a pattern in the plaintext format of the LifeWiki, with one
+    /// line of `.` (state 0) and `O` (state 1) cells per row, e.g.
+    /// `.O.\n..O\nOOO` for a glider. `*` is also read as state 1. The
+    /// comment lines start with `!`. Short rows are padded with state 0,
+    /// which is the background.
+    pub fn from_cells(contents: &str) -> Result<Pattern, PatternError> {
+        let mut pattern = contents
+            .lines()
+            .map(str::trim_end)
+            .filter(|l| !l.starts_with('!'))
+            .map(|l| {
+                l.chars()
+                    .map(|c| match c {
+                        '.' => Ok(0),
+                        'O' | '*' => Ok(1),
+                        _ => Err(PatternError::PatternFormatError),
+                    })
+                    .collect::<Result<Vec<u8>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let cols = pattern.iter().map(Vec::len).max().unwrap_or(0);
+        for row in pattern.iter_mut() {
+            row.resize(cols, 0);
+        }
+        Ok(Pattern {
+            states: 2,
+            background: 0,
+            rows: pattern,
+        })
+    }
+
+    /// Writes the pattern in the format of the pattern files of the crate
+    /// (see [`Pattern::from_pat`]).
+    pub fn to_pat(&self) -> String {
+        let mut out = format!("N={}\nBG={}\n#\n", self.states, self.background);
+        for row in &self.rows {
+            out.extend(row.iter().map(|&c| (b'0' + c) as char));
+            out.push('\n');
+        }
+        out.push_str("#\n");
+        out
+    }
+}
+
+/// Returns the RLE token of a run of `length` tags.
+fn run(length: usize, tag: String) -> String {
+    if length > 1 {
+        format!("{}{}", length, tag)
+    } else {
+        tag
+    }
+}
+
+/// Returns the RLE tag of a state, `b` and `o` for patterns with 2 states.
+fn rle_state(state: u8, states: u8) -> String {
+    match (state, states) {
+        (0, 0..=2) => "b".to_string(),
+        (1, 0..=2) => "o".to_string(),
+        (0, _) => ".".to_string(),
+        _ => {
+            let (prefix, letter) = ((state - 1) / 24, (state - 1) % 24);
+            let mut tag = String::new();
+            if prefix > 0 {
+                tag.push((b'p' + prefix - 1) as char);
+            }
+            tag.push((b'A' + letter) as char);
+            tag
+        }
+    }
+}
+
+/// The formats of the pattern files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternFormat {
+    /// The format of the pattern files of the crate.
+    Pat,
+    /// The run length encoded format of Golly (see [`Pattern::from_rle`]).
+    Rle,
+    /// The plaintext format of the LifeWiki (see
+    /// [`Pattern::from_cells`]).
+    Cells,
+}
+
+impl PatternFormat {
+    /// Returns the format of a pattern file from its extension, if it's
+    /// known.
+    fn from_extension(path: &str) -> Option<PatternFormat> {
+        let path = path.to_lowercase();
+        if path.ends_with(".rle") {
+            Some(PatternFormat::Rle)
+        } else if path.ends_with(".cells") {
+            Some(PatternFormat::Cells)
+        } else if path.ends_with(".pat") {
+            Some(PatternFormat::Pat)
+        } else {
+            None
+        }
+    }
+
+    /// Guesses the format of a pattern from its contents: the plaintext
+    /// patterns start with a `!` comment or only have `.` and `O` cells, and
+    /// the RLE patterns start with a `x = ` header after their comments.
+    fn detect(contents: &str) -> PatternFormat {
+        let mut lines = contents.lines().map(str::trim).filter(|l| !l.is_empty());
+        let is_rle_header = |l: &str| l.starts_with('x') && l[1..].trim_start().starts_with('=');
+        if contents.trim_start().starts_with('!')
+            || lines
+                .clone()
+                .all(|l| l.chars().all(|c| c == '.' || c == 'O'))
+        {
+            PatternFormat::Cells
+        } else if lines
+            .find(|l| !l.starts_with('#'))
+            .is_some_and(is_rle_header)
+        {
+            PatternFormat::Rle
+        } else {
+            PatternFormat::Pat
+        }
+    }
+}
+
+impl PatternFormat {
+    /// Parses a pattern in the format.
+    fn parse(self, contents: &str) -> Result<Pattern, PatternError> {
+        match self {
+            PatternFormat::Pat => Pattern::from_pat(contents),
+            PatternFormat::Rle => Pattern::from_rle(contents),
+            PatternFormat::Cells => Pattern::from_cells(contents),
+        }
+    }
+}
+
+/// Parses a pattern in any of the formats of the pattern files, detected
+/// from its contents.
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PatternFormat::detect(s).parse(s)
+    }
+}
+
+/// Parses a pattern file. The format is given by the extension of the file
+/// (`.rle` or `.cells`, see [`Pattern::from_rle`] and
+/// [`Pattern::from_cells`]), or detected from its contents. This returns
+/// a Pattern or an error if the pattern is incorrect.
+pub(crate) fn parse_pattern(pattern_fname: &str) -> Result<Pattern, PatternError> {
+    let contents = fs::read_to_string(pattern_fname)?;
+    PatternFormat::from_extension(pattern_fname)
+        .unwrap_or_else(|| PatternFormat::detect(&contents))
+        .parse(&contents)
+}
+
+/// A rectangle of cells of a grid.
+///
+/// ```
+/// use rust_ca::pattern::Rect;
+///
+/// let rect = Rect::new(2, 3, 4, 5);
+/// assert!(rect.fits(8));
+/// assert!(!rect.fits(7));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// The row of the top left cell of the rectangle.
+    pub top: usize,
+    /// The column of the top left cell of the rectangle.
+    pub left: usize,
+    /// The number of rows of the rectangle.
+    pub rows: usize,
+    /// The number of columns of the rectangle.
+    pub cols: usize,
+}
+
+impl Rect {
+    /// Makes the rectangle of `rows` by `cols` cells whose top left cell is
+    /// `(top, left)`.
+    pub fn new(top: usize, left: usize, rows: usize, cols: usize) -> Rect {
+        Rect {
+            top,
+            left,
+            rows,
+            cols,
+        }
+    }
+
+    /// Returns whether the rectangle is inside a square grid of side `size`.
+    pub fn fits(&self, size: usize) -> bool {
+        self.top + self.rows <= size && self.left + self.cols <= size
+    }
+}
+
+/// Writes the cells of a rectangle of a square grid of side `size` to a
+/// pattern file, the whole grid if no rectangle is given. The file is run
+/// length encoded if its extension is `.rle`.
+pub(crate) fn write_pattern(
+    path: &str,
+    grid: &[u8],
+    size: usize,
+    states: u8,
+    bounding_box: Option<Rect>,
+) -> Result<(), PatternError> {
+    let rect = bounding_box.unwrap_or_else(|| Rect::new(0, 0, size, size));
+    if !rect.fits(size) {
+        return Err(PatternError::PatternBoundsError);
+    }
+    let rows: Vec<&[u8]> = (rect.top..rect.top + rect.rows)
+        .map(|i| &grid[i * size + rect.left..i * size + rect.left + rect.cols])
+        .collect();
+    let counts = crate::stats::state_counts(&rows.concat(), states);
+    let spec = Pattern {
+        states,
+        background: (0..states).max_by_key(|&s| counts[s as usize]).unwrap_or(0),
+        rows: rows.iter().map(|row| row.to_vec()).collect(),
+    };
+    let mut f = crate::output::AtomicFile::create(path)?;
+    if PatternFormat::from_extension(path) == Some(PatternFormat::Rle) {
+        f.write_all(spec.to_rle().as_bytes())?;
+    } else {
+        f.write_all(spec.to_pat().as_bytes())?;
+    }
+    f.commit()?;
+    Ok(())
+}
+
+/// The dimensions and states of a pattern (see [`pattern_shape`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternShape {
+    /// The number of rows of the pattern.
+    pub rows: usize,
+    /// The number of columns of the longest row of the pattern.
+    pub cols: usize,
+    /// The total number of states in the pattern.
+    pub states: u8,
+    /// The pattern background state.
+    pub background: u8,
+}
+
+/// Reads the shape of the pattern of a pattern file, to check that it fits
+/// in a CA before initializing it with
+/// [`AutomatonImpl::init_from_pattern`](crate::automaton::AutomatonImpl::init_from_pattern).
+///
+/// ```
+/// use rust_ca::pattern::pattern_shape;
+///
+/// let shape = pattern_shape("patterns/exploding.pat")?;
+/// assert!(shape.rows > 0 && shape.cols > 0);
+/// # Ok::<(), rust_ca::pattern::PatternError>(())
+/// ```
+pub fn pattern_shape(pattern_fname: &str) -> Result<PatternShape, PatternError> {
+    let spec = parse_pattern(pattern_fname)?;
+    Ok(PatternShape {
+        rows: spec.rows.len(),
+        cols: spec.cols(),
+        states: spec.states,
+        background: spec.background,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pattern, Pattern, PatternFormat, Rotation};
+    use crate::automaton::{Automaton, AutomatonImpl, PatternError, Rect, TiledAutomaton};
+    use crate::rule::Rule;
+
+    #[test]
+    fn rle_patterns_are_decoded() {
+        let glider = "#N Glider\n#C A comment.\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        let spec = Pattern::from_rle(glider).unwrap();
+        assert_eq!(spec.rows, vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 1, 1]]);
+        assert_eq!((spec.states, spec.background), (2, 0));
+        assert_eq!(spec.to_rle(), "x = 3, y = 3\nbo$2bo$3o!\n");
+
+        // Empty rows, multi-state letters and runs split across lines.
+        let spec = Pattern::from_rle("x = 4, y = 4\n2.A$\n2$3pB\nC!").unwrap();
+        assert_eq!(spec.states, 27);
+        assert_eq!(spec.rows[0], vec![0, 0, 1, 0]);
+        assert_eq!(spec.rows[2], vec![0; 4]);
+        assert_eq!(spec.rows[3], vec![26, 26, 26, 3]);
+        let decoded = Pattern::from_rle(&spec.to_rle()).unwrap();
+        assert_eq!(decoded.rows, spec.rows);
+
+        for bad in ["bo$2bo!", "x = 3, y = 1\n2bz!", "x = 1, y = 1\npo!"] {
+            assert!(Pattern::from_rle(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn plaintext_patterns_are_decoded() {
+        let glider = "!Name: Glider\n!\n.O\n..O\nOOO\n";
+        let spec = Pattern::from_cells(glider).unwrap();
+        assert_eq!(spec.rows, vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 1, 1]]);
+        assert_eq!((spec.states, spec.background), (2, 0));
+        assert!(Pattern::from_cells(".O\nxO").is_err());
+    }
+
+    #[test]
+    fn pattern_formats_are_detected() {
+        let detect = PatternFormat::detect;
+        assert_eq!(detect("!Name: Blinker\nOOO"), PatternFormat::Cells);
+        assert_eq!(detect("..O\n.O.\n"), PatternFormat::Cells);
+        assert_eq!(detect("#N Blinker\nx = 3, y = 1\n3o!"), PatternFormat::Rle);
+        assert_eq!(detect("N=2\nBG=0\n#\n111\n#\n"), PatternFormat::Pat);
+        assert_eq!(
+            PatternFormat::from_extension("a.CELLS"),
+            Some(PatternFormat::Cells)
+        );
+
+        // Files without a known extension are parsed in the detected format.
+        std::fs::write("test_detected_pattern", "x = 3, y = 1\n3o!\n").unwrap();
+        let spec = parse_pattern("test_detected_pattern").unwrap();
+        assert_eq!(spec.rows, vec![vec![1, 1, 1]]);
+        std::fs::remove_file("test_detected_pattern").unwrap();
+    }
+
+    #[test]
+    fn patterns_are_parsed_from_strings_and_validated() {
+        let pat: Pattern = "N=3\nBG=1\n#\n012\n2\n#\n".parse().unwrap();
+        assert_eq!((pat.states(), pat.background()), (3, 1));
+        assert_eq!(pat.rows(), [vec![0, 1, 2], vec![2]]);
+        assert_eq!(pat.to_pat().parse::<Pattern>().unwrap(), pat);
+        let rle: Pattern = "x = 2, y = 1\n2o!".parse().unwrap();
+        let cells: Pattern = "OO".parse().unwrap();
+        assert_eq!(rle, cells);
+        for bad in [
+            "N=2\nBG=0\n#\n012\n#\n",
+            "N=2\nBG=2\n#\n01\n#\n",
+            "N=2\n#\n0 1\n#",
+        ] {
+            assert!(bad.parse::<Pattern>().is_err(), "{:?}", bad);
+        }
+        let pattern = Pattern::new(3, 0, vec![vec![2]]).unwrap();
+        let mut automaton = Automaton::new(2, 8, Rule::gol());
+        assert!(matches!(
+            automaton.place_pattern(&pattern, 0, 0, Rotation::Rot0, false),
+            Err(PatternError::PatternStateError)
+        ));
+    }
+
+    #[test]
+    fn long_rle_lines_are_wrapped() {
+        let row: Vec<u8> = (0..200).map(|k| (k % 2) as u8).collect();
+        let spec = Pattern {
+            states: 2,
+            background: 0,
+            rows: vec![row.clone(), vec![0; 200], row],
+        };
+        let rle = spec.to_rle();
+        assert!(rle.lines().all(|l| l.len() <= 70));
+        assert!(rle.contains("2$"));
+        assert_eq!(Pattern::from_rle(&rle).unwrap().rows, spec.rows);
+    }
+
+    #[test]
+    fn saved_rle_patterns_are_read_back() {
+        let mut a = Automaton::new(3, 12, Rule::random(1, 3));
+        a.random_init();
+        a.save_pattern("test_saved.rle", Some(Rect::new(0, 0, 12, 12)))
+            .unwrap();
+        let mut b = Automaton::new(3, 12, Rule::random(1, 3));
+        b.init_from_pattern("test_saved.rle").unwrap();
+        assert_eq!(a.grid(), b.grid());
+        std::fs::remove_file("test_saved.rle").unwrap();
+    }
+
+    #[test]
+    fn patterns_are_rotated_and_placed() {
+        // An L tromino with a ragged row, padded with the background.
+        let spec = Pattern {
+            states: 3,
+            background: 0,
+            rows: vec![vec![1], vec![2, 1]],
+        };
+        let grid = |rotation, flip| {
+            let mut cells = spec.placed((1, 1), (rotation, flip), 4, 3).unwrap();
+            cells.sort_unstable();
+            cells
+                .iter()
+                .map(|&(i, j, state)| (i - 1, j - 1, state))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            grid(Rotation::Rot0, false),
+            vec![(0, 0, 1), (0, 1, 0), (1, 0, 2), (1, 1, 1)]
+        );
+        assert_eq!(
+            grid(Rotation::Rot90, false),
+            vec![(0, 0, 2), (0, 1, 1), (1, 0, 1), (1, 1, 0)]
+        );
+        assert_eq!(
+            grid(Rotation::Rot180, false),
+            vec![(0, 0, 1), (0, 1, 2), (1, 0, 0), (1, 1, 1)]
+        );
+        assert_eq!(
+            grid(Rotation::Rot270, false),
+            vec![(0, 0, 0), (0, 1, 1), (1, 0, 1), (1, 1, 2)]
+        );
+        assert_eq!(
+            grid(Rotation::Rot0, true),
+            vec![(0, 0, 0), (0, 1, 1), (1, 0, 1), (1, 1, 2)]
+        );
+        assert!(matches!(
+            spec.placed((3, 0), (Rotation::Rot0, false), 4, 3),
+            Err(PatternError::PatternBoundsError)
+        ));
+    }
+
+    #[test]
+    fn pattern_transforms_compose() {
+        let spec = Pattern {
+            states: 2,
+            background: 0,
+            rows: vec![vec![1, 1, 0, 1], vec![0, 1, 1, 0]],
+        };
+        let quarter = |s: &Pattern| s.rotate(Rotation::Rot90);
+        assert_eq!(quarter(&quarter(&spec)), spec.rotate(Rotation::Rot180));
+        assert_eq!(
+            quarter(&quarter(&quarter(&spec))),
+            spec.rotate(Rotation::Rot270)
+        );
+        assert_eq!(quarter(&spec.rotate(Rotation::Rot270)), spec);
+        assert_eq!(spec.mirror_vertically().mirror_vertically(), spec);
+        // Mirroring along one axis then the other is a half turn, and
+        // mirroring along a diagonal is a mirror and a quarter turn.
+        assert_eq!(
+            spec.mirror_horizontally().mirror_vertically(),
+            spec.rotate(Rotation::Rot180)
+        );
+        let transposed: Vec<Vec<u8>> = (0..4)
+            .map(|j| spec.rows.iter().map(|row| row[j]).collect())
+            .collect();
+        assert_eq!(
+            spec.mirror_horizontally().rotate(Rotation::Rot270).rows,
+            transposed
+        );
+    }
+
+    #[test]
+    fn placed_patterns_match_across_backends() {
+        let glider = Pattern::from_file("patterns/glider.rle").unwrap();
+        let mut a = Automaton::new(2, 512, Rule::gol());
+        let mut b = TiledAutomaton::new(2, 512, Rule::gol());
+        for (x, y, rotation, flip) in [
+            (10, 20, Rotation::Rot0, false),
+            (254, 100, Rotation::Rot90, false),
+            (128, 509, Rotation::Rot270, true),
+        ] {
+            a.place_pattern(&glider, x, y, rotation, flip).unwrap();
+            b.place_pattern(&glider, x, y, rotation, flip).unwrap();
+        }
+        assert!(a
+            .place_pattern(&glider, 510, 0, Rotation::Rot0, false)
+            .is_err());
+        assert_eq!(a.state_counts(), vec![512 * 512 - 15, 15]);
+        assert_eq!(a.grid(), b.grid());
+        a.update();
+        b.update();
+        assert_eq!(a.grid(), b.grid());
+    }
+}
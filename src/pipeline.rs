@@ -0,0 +1,377 @@
+//! This module chains simulations: the final grid of each stage, optionally
+//! coarse-grained and with its states mapped, is the initial grid of the next
+//! stage, which may use another rule. This makes multi-phase experiments
+//! possible, such as growing structures with one rule and probing them with
+//! another.
+//!
+//! ```
+//! use rust_ca::pipeline::{Pipeline, Stage};
+//! use rust_ca::rule::Rule;
+//!
+//! let mut pipeline = Pipeline::new(64);
+//! pipeline.set_seed(3);
+//! let mut first = Stage::new(Rule::random(1, 3), 20);
+//! // Blocks of 2x2 cells become single cells, and state 2 becomes state 1.
+//! first.block = 2;
+//! first.state_map = Some(vec![0, 1, 1]);
+//! pipeline.add_stage(first);
+//! pipeline.add_stage(Stage::new(Rule::gol(), 50));
+//! let last = pipeline.run()?;
+//! assert_eq!(last.rows().len(), 32);
+//! # Ok::<(), rust_ca::pipeline::PipelineError>(())
+//! ```
+//!
+//! Pipelines are also read from text files (see [`Pipeline::from_file`]).
+
+use std::error;
+use std::fmt;
+use std::io;
+#[cfg(feature = "rule-io")]
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::analysis::{blocks_per_side, coarse_grain, BlockMapping};
+use crate::automaton::{Automaton, AutomatonImpl, Rotation};
+use crate::pattern::{Pattern, PatternError};
+use crate::rule::{Rule, RuleError};
+
+/// Error type for an error that happened while reading or running a
+/// pipeline.
+#[derive(Debug)]
+pub enum PipelineError {
+    /// An io error, while reading the pipeline file or in the function
+    /// running a stage.
+    Io(io::Error),
+    /// An error while reading the rule file of a stage.
+    Rule(RuleError),
+    /// The grid handed to a stage has states outside of the states of its
+    /// rule.
+    Pattern(PatternError),
+    /// A syntax error in the pipeline file.
+    Config {
+        /// The line of the error, starting at 1.
+        line: usize,
+        /// What is wrong with the line.
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Io(e) => write!(f, "io error in the pipeline: {}", e),
+            PipelineError::Rule(e) => write!(f, "{}", e),
+            PipelineError::Pattern(_) => write!(
+                f,
+                "a stage gets a grid with more states than its rule (see the state map)"
+            ),
+            PipelineError::Config { line, message } => {
+                write!(f, "incorrect pipeline file at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PipelineError::Io(e) => Some(e),
+            PipelineError::Rule(e) => Some(e),
+            PipelineError::Pattern(e) => Some(e),
+            PipelineError::Config { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for PipelineError {
+    fn from(err: io::Error) -> PipelineError {
+        PipelineError::Io(err)
+    }
+}
+
+impl From<RuleError> for PipelineError {
+    fn from(err: RuleError) -> PipelineError {
+        PipelineError::Rule(err)
+    }
+}
+
+impl From<PatternError> for PipelineError {
+    fn from(err: PatternError) -> PipelineError {
+        PipelineError::Pattern(err)
+    }
+}
+
+/// A stage of a [`Pipeline`]: a rule simulated for a number of steps, and
+/// the transformation of its final grid into the initial grid of the next
+/// stage.
+#[derive(Debug, Clone)]
+pub struct Stage {
+    /// The rule of the stage, which also sets its number of states.
+    pub rule: Rule,
+    /// The number of steps of the stage.
+    pub steps: u32,
+    /// The side of the blocks of cells the final grid is coarse-grained in,
+    /// each block becoming the most common state of its cells (see
+    /// [`BlockMapping::Majority`]). A block of 1 keeps the grid as is.
+    pub block: usize,
+    /// The new state of each state of the coarse-grained final grid. The
+    /// states without an entry are kept.
+    pub state_map: Option<Vec<u8>>,
+}
+
+impl Stage {
+    /// Makes a stage simulating `rule` for `steps` steps and handing its
+    /// final grid as is to the next stage.
+    pub fn new(rule: Rule, steps: u32) -> Stage {
+        Stage {
+            rule,
+            steps,
+            block: 1,
+            state_map: None,
+        }
+    }
+
+    /// Coarse-grains and maps the states of the final grid of the stage, of
+    /// side `size`, and returns it with its new side.
+    fn transform(&self, grid: &[u8], size: usize) -> (Vec<u8>, usize) {
+        let mut grid = if self.block > 1 {
+            coarse_grain(grid, size, self.block, &BlockMapping::Majority)
+        } else {
+            grid.to_vec()
+        };
+        if let Some(map) = &self.state_map {
+            for c in grid.iter_mut() {
+                *c = map.get(*c as usize).copied().unwrap_or(*c);
+            }
+        }
+        (grid, blocks_per_side(size, self.block.max(1)))
+    }
+}
+
+/// A chain of simulations, each stage starting from the transformed final
+/// grid of the previous one. The first stage starts from a random grid of
+/// side `size`, and the grids shrink with the coarse-graining of the stages.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    size: usize,
+    seed: Option<u64>,
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Makes an empty pipeline whose first stage starts from a random grid of
+    /// side `size`.
+    pub fn new(size: usize) -> Pipeline {
+        Pipeline {
+            size,
+            seed: None,
+            stages: vec![],
+        }
+    }
+
+    /// Reads a pipeline from a text file. Each line is a directive, and
+    /// empty lines and lines starting with `#` are ignored:
+    ///
+    /// ```text
+    /// size 128
+    /// seed 7
+    /// stage growth.rule 200
+    /// block 4
+    /// map 0 1 1
+    /// stage gol 100
+    /// ```
+    ///
+    /// `size N` sets the side of the initial grid (128 by default), and
+    /// `seed S` seeds it. `stage RULE STEPS` adds a stage simulating a rule
+    /// file, or the game of life for `gol`, for a number of steps. The rule
+    /// files are relative to the directory of the pipeline file. `block B`
+    /// and `map S0 S1 ...` set the coarse-graining and the state map of the
+    /// last stage (see [`Stage`]).
+    #[cfg(feature = "rule-io")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Pipeline, PipelineError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut pipeline = Pipeline::new(128);
+        for (line, directive) in contents.lines().map(str::trim).enumerate() {
+            if directive.is_empty() || directive.starts_with('#') {
+                continue;
+            }
+            let error = |message| PipelineError::Config {
+                line: line + 1,
+                message,
+            };
+            let words: Vec<&str> = directive.split_whitespace().collect();
+            match (words[0], &words[1..]) {
+                ("size", [size]) => {
+                    pipeline.size = size.parse().map_err(|_| error("invalid grid size"))?
+                }
+                ("seed", [seed]) => {
+                    pipeline.set_seed(seed.parse().map_err(|_| error("invalid seed"))?)
+                }
+                ("stage", [rule, steps]) => {
+                    let rule = match *rule {
+                        "gol" => Rule::gol(),
+                        file => Rule::from_file(&dir.join(file))?,
+                    };
+                    let steps = steps
+                        .parse()
+                        .map_err(|_| error("invalid number of steps"))?;
+                    pipeline.add_stage(Stage::new(rule, steps));
+                }
+                ("block", [block]) => {
+                    let stage = pipeline.stages.last_mut();
+                    let stage = stage.ok_or_else(|| error("no stage to coarse-grain"))?;
+                    stage.block = block
+                        .parse()
+                        .ok()
+                        .filter(|&b| b > 0)
+                        .ok_or_else(|| error("invalid block size"))?
+                }
+                ("map", states) => {
+                    let stage = pipeline.stages.last_mut();
+                    let stage = stage.ok_or_else(|| error("no stage to map"))?;
+                    let map: Result<Vec<u8>, _> = states.iter().map(|s| s.parse()).collect();
+                    stage.state_map = Some(map.map_err(|_| error("invalid state"))?);
+                }
+                _ => return Err(error("expected size, seed, stage, block or map")),
+            }
+        }
+        Ok(pipeline)
+    }
+
+    /// Seeds the random initial grid of the first stage. Pipelines are
+    /// seeded from the system entropy by default.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Appends a stage to the pipeline.
+    pub fn add_stage(&mut self, stage: Stage) {
+        self.stages.push(stage);
+    }
+
+    /// Returns the stages of the pipeline.
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Runs all the stages, and returns the transformed final grid of the
+    /// last one as a pattern whose background is state 0.
+    pub fn run(&self) -> Result<Pattern, PipelineError> {
+        self.run_with(|stage, autom| {
+            for _ in 0..stage.steps {
+                autom.update();
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs all the stages like [`Pipeline::run`], but lets `run_stage`
+    /// simulate each stage from its initial grid, to render or record it
+    /// along the way. `run_stage` must advance the automaton by the steps
+    /// of the stage.
+    ///
+    /// ```
+    /// use rust_ca::automaton::AutomatonImpl;
+    /// use rust_ca::pipeline::{Pipeline, Stage};
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let mut pipeline = Pipeline::new(32);
+    /// pipeline.add_stage(Stage::new(Rule::gol(), 10));
+    /// let mut grids = vec![];
+    /// pipeline.run_with(|stage, autom| {
+    ///     grids.extend(autom.skipped_iter(stage.steps, 1, 1));
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(grids.len(), 10);
+    /// # Ok::<(), rust_ca::pipeline::PipelineError>(())
+    /// ```
+    pub fn run_with<F>(&self, mut run_stage: F) -> Result<Pattern, PipelineError>
+    where
+        F: FnMut(&Stage, &mut Automaton) -> Result<(), io::Error>,
+    {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let (mut grid, mut size, mut states) = (None, self.size, 1);
+        for stage in &self.stages {
+            states = stage.rule.states;
+            let mut autom = Automaton::new(states, size, stage.rule.clone());
+            match grid.take() {
+                None => autom.random_init_seeded(seed),
+                Some(initial) => {
+                    let initial = grid_pattern(initial, size, 1)?;
+                    autom.place_pattern(&initial, 0, 0, Rotation::Rot0, false)?
+                }
+            }
+            run_stage(stage, &mut autom)?;
+            let (next, next_size) = stage.transform(&autom.grid(), size);
+            grid = Some(next);
+            size = next_size;
+        }
+        let grid = grid.unwrap_or_else(|| vec![0; size * size]);
+        Ok(grid_pattern(grid, size, states)?)
+    }
+}
+
+/// Makes a pattern of a grid of side `size`, with at least `states` states.
+fn grid_pattern(grid: Vec<u8>, size: usize, states: u8) -> Result<Pattern, PatternError> {
+    let used = grid.iter().max().map_or(1, |&s| s.saturating_add(1));
+    let rows = grid.chunks(size.max(1)).map(<[u8]>::to_vec).collect();
+    Pattern::new(states.max(used), 0, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pipeline, PipelineError, Stage};
+    use crate::automaton::AutomatonImpl;
+    use crate::rule::Rule;
+
+    #[test]
+    fn stages_start_from_the_transformed_grid() {
+        let mut pipeline = Pipeline::new(8);
+        pipeline.set_seed(1);
+        let mut copy = Stage::new(Rule::random(1, 3), 0);
+        copy.block = 2;
+        copy.state_map = Some(vec![0, 1, 0]);
+        pipeline.add_stage(copy);
+        let mut sizes = vec![];
+        let last = pipeline
+            .run_with(|stage, autom| {
+                sizes.push((autom.size(), stage.rule.states));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(sizes, [(8, 3)]);
+        assert_eq!(last.rows().len(), 4);
+        assert!(last.rows().iter().flatten().all(|&c| c < 2));
+
+        // A stage can't take states beyond the ones of its rule.
+        let mut pipeline = Pipeline::new(8);
+        let to_two = Rule::new(1, 3, vec![2; 3usize.pow(9)]);
+        pipeline.add_stage(Stage::new(to_two, 1));
+        pipeline.add_stage(Stage::new(Rule::gol(), 1));
+        assert!(matches!(pipeline.run(), Err(PipelineError::Pattern(_))));
+    }
+
+    #[cfg(feature = "rule-io")]
+    #[test]
+    fn pipelines_are_read_from_files() {
+        let dir = std::env::temp_dir().join("rust_ca_pipeline");
+        std::fs::create_dir_all(&dir).unwrap();
+        Rule::random(1, 3).to_file(dir.join("first.rule")).unwrap();
+        let path = dir.join("pipeline.txt");
+        let config = "# Two stages\nsize 32\nseed 4\nstage first.rule 5\nblock 2\nmap 0 1 1\n\
+                      \nstage gol 5\n";
+        std::fs::write(&path, config).unwrap();
+        let pipeline = Pipeline::from_file(&path).unwrap();
+        assert_eq!(pipeline.stages().len(), 2);
+        assert_eq!(pipeline.stages()[0].state_map, Some(vec![0, 1, 1]));
+        assert_eq!(pipeline.run().unwrap().rows().len(), 16);
+
+        std::fs::write(&path, "size 32\nblock 2\n").unwrap();
+        let err = Pipeline::from_file(&path).unwrap_err();
+        assert!(matches!(err, PipelineError::Config { line: 2, .. }));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -28,34 +28,183 @@
 //! let rule = Rule::random_dirichlet(1, 2, Some(0.3));
 //! ```
 //!
+//! ## Constrained sampling
+//! Some transitions can be fixed before sampling the rest of the rule with a
+//! [`RuleBuilder`]:
+//! ```
+//! use rust_ca::rule::{RuleBuilder, SamplingMode};
+//!
+//! // The empty neighborhood stays empty.
+//! let rule = RuleBuilder::new(1, 2).pin(&[0; 9], 0).sample(SamplingMode::Dirichlet);
+//! assert_eq!(rule[0], 0);
+//! ```
+//!
+//! ## Writing transitions by hand
+//! The [`rule!`](crate::rule!) macro compiles neighborhood patterns with
+//! wildcards (`_`) and counts of wildcard cells in a given state (`#s = n`,
+//! `#s <= n`, `#s >= n`) to rule table entries. This is the game of life:
+//! ```
+//! use rust_ca::rule;
+//! use rust_ca::rule::Rule;
+//!
+//! let gol = rule! {
+//!     [_ _ _ / _ 0 _ / _ _ _] #1 = 3 => 1;
+//!     [_ _ _ / _ 1 _ / _ _ _] #1 >= 2, #1 <= 3 => 1
+//! }
+//! .build(0);
+//! assert_eq!(gol.table(), Rule::gol().table());
+//! ```
+//!
+//! ## Totalistic rules
+//! Rules only depending on sums of states are stored more compactly as a
+//! [`TotalisticRule`]. A
+//! [`TotalisticAutomaton`](crate::automaton::TotalisticAutomaton) runs them
+//! from their sums without expanding them to full tables, which makes
+//! multi-state rules with large horizons tractable. Life-like rules can be
+//! written as rule strings:
+//! ```
+//! use rust_ca::automaton::{AutomatonImpl, TotalisticAutomaton};
+//! use rust_ca::rule::TotalisticRule;
+//!
+//! let highlife: TotalisticRule = "B36/S23".parse()?;
+//! let mut automaton = TotalisticAutomaton::with_rule(64, highlife);
+//! automaton.update();
+//! # Ok::<(), &'static str>(())
+//! ```
+//!
+//! ## 1D rules
+//! Rules of one-dimensional CA are stored as a [`Rule1D`], and elementary
+//! rules are made from their Wolfram code:
+//! ```
+//! use rust_ca::rule::Rule1D;
+//!
+//! let rule30 = Rule1D::elementary(30);
+//! assert_eq!(rule30.apply(&[0, 0, 1, 0, 0]), vec![0, 1, 1, 1, 0]);
+//! ```
+//!
+//! ## 3D rules
+//! Rules of three-dimensional CA are outer totalistic, stored as a
+//! [`Rule3D`]. 3D Life-like rules are written like the 2D ones:
+//! ```
+//! use rust_ca::rule::Rule3D;
+//!
+//! let rule: Rule3D = "B5/S4,5".parse()?;
+//! # Ok::<(), &'static str>(())
+//! ```
+//!
+//! ## Neighborhoods
+//! Rules use the Moore neighborhood (the square around the cell) by default.
+//! Rules on the von Neumann neighborhood or on hexagonal grids have one table
+//! digit per cell of their [`Neighborhood`]:
+//! ```
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
+//! use rust_ca::rule::{Neighborhood, Rule, SamplingMode};
+//!
+//! let mut rng = StdRng::seed_from_u64(1);
+//! let rule = Rule::random_with_neighborhood(1, 2, Neighborhood::Hex, SamplingMode::Uniform, &mut rng);
+//! assert_eq!(rule.table().len(), 1 << 7);
+//! ```
+//!
 //! ## Built-in rules
 //! You can also use a built-in rule like Game of Life:
 //! ```
 //! # use rust_ca::rule::Rule;
 //! let gol_rule = Rule::gol();
 //! ```
+//! Other Life-like rules are made from their B/S notation:
+//! ```
+//! # use rust_ca::rule::Rule;
+//! let highlife = Rule::from_bs_notation("B36/S23")?;
+//! # Ok::<(), rust_ca::rule::RuleError>(())
+//! ```
 extern crate rand_distr;
+mod builder;
+mod catalog;
+mod cubic;
+mod cyclic;
+mod dsl;
+mod elementary;
+mod excitable;
+mod explain;
+mod format;
+mod hash;
+mod lambda;
+mod majority;
+#[cfg(feature = "rule-io")]
+mod mapped;
+mod neighborhood;
+mod totalistic;
 mod utils;
+#[cfg(feature = "rule-io")]
+mod watch;
+
+pub use builder::RuleBuilder;
+pub use catalog::RuleCatalog;
+pub use cubic::Rule3D;
+pub use cyclic::CyclicRule;
+pub use dsl::DslError;
+pub use elementary::Rule1D;
+pub use explain::{Explanation, Totalism};
+pub use lambda::LambdaFamily;
+pub use majority::TieBreak;
+#[cfg(feature = "rule-io")]
+pub use mapped::MappedRule;
+pub use neighborhood::Neighborhood;
+pub use totalistic::TotalisticRule;
+#[cfg(feature = "rule-io")]
+pub use watch::RuleWatcher;
 
-use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
+use std::error;
+use std::fmt;
+#[cfg(feature = "rule-io")]
 use std::fs::File;
 use std::hash::{Hash, Hasher};
+use std::io;
+#[cfg(feature = "rule-io")]
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::{Index, IndexMut};
+#[cfg(feature = "rule-io")]
 use std::path::Path;
 use std::str::FromStr;
 
-use flate2::read::{GzDecoder, ZlibDecoder};
-use flate2::write::GzEncoder;
+#[cfg(feature = "rule-io")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+#[cfg(feature = "rule-io")]
+use base64::Engine;
+#[cfg(feature = "rule-io")]
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+#[cfg(feature = "rule-io")]
+use flate2::write::{DeflateEncoder, GzEncoder};
+#[cfg(feature = "rule-io")]
 use flate2::Compression;
 use rand::Rng;
 use rand_distr::{Dirichlet, Distribution};
 
+#[cfg(feature = "rule-io")]
+use crate::output::AtomicFile;
+
 const ALPHA: f64 = 0.2;
+/// Largest number of configurations enumerated by the exact reversibility
+/// check.
+const EXACT_REVERSIBILITY_LIMIT: u64 = 1 << 20;
+/// Number of random configurations tested by the probabilistic reversibility
+/// check.
+const REVERSIBILITY_TRIALS: usize = 64;
+/// The largest tables built by the rule families defined by their parameters
+/// (64 MiB).
+const MAX_TABLE_SIZE: u64 = 1 << 26;
+#[cfg(feature = "rule-io")]
 const GZIP_H: [u8; 9] = [0x1f, 0x8b, 0x08, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0];
+#[cfg(feature = "rule-io")]
+const ZSTD_H: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Compression level of zstd compressed rule files.
+#[cfg(feature = "rule-io")]
+const ZSTD_LEVEL: i32 = 3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// The sampling mode for the random rule generation.
 pub enum SamplingMode {
     /// Uniformly sample transitions in the rule table.
@@ -77,7 +226,132 @@ impl FromStr for SamplingMode {
     }
 }
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The compression used when writing rule files. The compression is detected
+/// automatically when reading them.
+pub enum FileCompression {
+    /// Gzip compression (the default).
+    #[default]
+    Gzip,
+    /// Zstd compression, much faster for very large rule tables.
+    Zstd,
+}
+
+// Implement the FromStr trait for CLI options parsing.
+impl FromStr for FileCompression {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(FileCompression::Gzip),
+            "zstd" => Ok(FileCompression::Zstd),
+            _ => Err("no match"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The result of a reversibility check (see [`Rule::is_reversible_on_torus`]).
+pub enum Reversibility {
+    /// The rule is reversible (exact check).
+    Reversible,
+    /// Two configurations with the same image were found, the rule is not
+    /// reversible.
+    NotReversible,
+    /// No configurations with the same image were found among the tested
+    /// ones (probabilistic check).
+    ProbablyReversible,
+}
+
+/// Error type for an error that happened while reading a rule file.
+#[derive(Debug)]
+pub enum RuleError {
+    /// An io error while reading the rule file.
+    RuleFileError(io::Error),
+    /// A file format error in the rule file.
+    RuleFormatError(&'static str),
+    /// The checksum of the rule table doesn't match the one recorded in the
+    /// file, the file is corrupted.
+    RuleChecksumError {
+        /// The checksum recorded in the file.
+        expected: u32,
+        /// The checksum of the table read.
+        found: u32,
+    },
+    /// The id of the rule doesn't match the expected id (see
+    /// [`Rule::verify_against`]).
+    RuleIdError {
+        /// The expected id.
+        expected: u64,
+        /// The id of the rule.
+        found: u64,
+    },
+    /// The horizon or number of states of the rule doesn't match the
+    /// expected ones (see [`Rule::verify_shape`]).
+    RuleShapeError {
+        /// The expected horizon and number of states.
+        expected: (i8, u8),
+        /// The horizon and number of states of the rule.
+        found: (i8, u8),
+    },
+    /// The neighborhood of the rule doesn't match the expected one (see
+    /// [`Rule::verify_shape`]).
+    RuleNeighborhoodError {
+        /// The expected neighborhood.
+        expected: Neighborhood,
+        /// The neighborhood of the rule.
+        found: Neighborhood,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleError::RuleFileError(..) => write!(f, "io error with the rule file"),
+            RuleError::RuleFormatError(msg) => write!(f, "incorrect rule file format: {}", msg),
+            RuleError::RuleChecksumError { expected, found } => write!(
+                f,
+                "corrupted rule file: checksum {:08x} doesn't match the recorded {:08x}",
+                found, expected
+            ),
+            RuleError::RuleIdError { expected, found } => {
+                write!(
+                    f,
+                    "rule id {} doesn't match the expected {}",
+                    found, expected
+                )
+            }
+            RuleError::RuleShapeError { expected, found } => write!(
+                f,
+                "rule of horizon {} with {} states doesn't match the expected horizon {} with \
+                 {} states",
+                found.0, found.1, expected.0, expected.1
+            ),
+            RuleError::RuleNeighborhoodError { expected, found } => write!(
+                f,
+                "rule on the {} neighborhood doesn't match the expected {} neighborhood",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for RuleError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            RuleError::RuleFileError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RuleError {
+    fn from(err: io::Error) -> RuleError {
+        RuleError::RuleFileError(err)
+    }
+}
+
+#[derive(Debug, Clone)]
 /// The rule object. Represents a cellular automaton rule.
 pub struct Rule {
     /// The size of the neighborhood.
@@ -85,16 +359,48 @@ pub struct Rule {
     /// The number of cell states the rule expects
     pub states: u8,
     table: Vec<u8>,
+    neighborhood: Neighborhood,
+}
+
+/// Moore rules hash like they did before rules had a neighborhood, so their
+/// ids don't change.
+impl Hash for Rule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.horizon.hash(state);
+        self.states.hash(state);
+        self.table.hash(state);
+        if self.neighborhood != Neighborhood::Moore {
+            self.neighborhood.hash(state);
+        }
+    }
 }
 
 impl Rule {
+    /// The version of the definition of [`Rule::canonical_id`]. Version 1
+    /// ids were the ids of the canonical forms (see [`Rule::canonical`]),
+    /// without relabeling the states, and version 2 ids also identify the
+    /// rules equivalent up to a relabeling of the states.
+    pub const CANONICAL_ID_VERSION: u32 = 2;
+
     /// Construct a new rule from a given number of states, horizon and rule
     /// table
     pub fn new(horizon: i8, states: u8, table: Vec<u8>) -> Rule {
+        Rule::with_neighborhood(horizon, states, Neighborhood::Moore, table)
+    }
+
+    /// Construct a new rule on the given neighborhood. The table has one
+    /// entry for every configuration of the cells of the neighborhood.
+    pub fn with_neighborhood(
+        horizon: i8,
+        states: u8,
+        neighborhood: Neighborhood,
+        table: Vec<u8>,
+    ) -> Rule {
         let r = Rule {
             horizon,
             states,
             table,
+            neighborhood,
         };
         if r.check() {
             r
@@ -116,22 +422,92 @@ impl Rule {
         &mut self.table
     }
 
+    /// Returns the neighborhood of the rule.
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
     /// Returns the expected rule size for a given (horizon, states) pair. Used
     /// for checking the rule is well formed.
     fn rule_size(horizon: i8, states: u8) -> u64 {
-        (states as u64).pow((2 * horizon + 1).pow(2).try_into().unwrap())
+        Rule::table_size(horizon, states, Neighborhood::Moore)
+    }
+
+    /// Returns the expected rule size for a given (horizon, states) pair on a
+    /// neighborhood.
+    fn table_size(horizon: i8, states: u8, neighborhood: Neighborhood) -> u64 {
+        (states as u64).pow(neighborhood.n_cells(horizon).try_into().unwrap())
+    }
+
+    /// Returns the size of the table of the rules with the given horizon and
+    /// number of states on a neighborhood, or an error if the horizon is
+    /// negative, there are no states or the table would be larger than 64
+    /// MiB. Use to check shapes read from files or given by users.
+    ///
+    /// ```
+    /// use rust_ca::rule::{Neighborhood, Rule};
+    ///
+    /// assert_eq!(Rule::checked_table_size(1, 2, Neighborhood::Moore)?, 512);
+    /// assert!(Rule::checked_table_size(3, 2, Neighborhood::Moore).is_err());
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    pub fn checked_table_size(
+        horizon: i8,
+        states: u8,
+        neighborhood: Neighborhood,
+    ) -> Result<u64, RuleError> {
+        if horizon < 0 || states == 0 {
+            return Err(RuleError::RuleFormatError(
+                "the horizon can't be negative and there must be states",
+            ));
+        }
+        (states as u64)
+            .checked_pow(neighborhood.n_cells(horizon) as u32)
+            .filter(|&size| size <= MAX_TABLE_SIZE)
+            .ok_or(RuleError::RuleFormatError(
+                "the rule table would be larger than 64 MiB",
+            ))
     }
 
     /// Create a random rule with uniformly sampled transitions.
     pub fn random(horizon: i8, states: u8) -> Rule {
-        let mut rng = rand::thread_rng();
-        let big_bound: u64 = Rule::rule_size(horizon, states);
-        let table: Vec<u8> = (0..big_bound).map(|_| rng.gen_range(0..states)).collect();
-        Rule {
-            horizon,
-            states,
-            table,
-        }
+        Rule::random_with_rng(horizon, states, &mut rand::thread_rng())
+    }
+
+    /// Create a random rule with uniformly sampled transitions, drawn from
+    /// the given random number generator. A seeded generator always gives the
+    /// same rule.
+    ///
+    /// ```
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let a = Rule::random_with_rng(1, 2, &mut StdRng::seed_from_u64(7));
+    /// let b = Rule::random_with_rng(1, 2, &mut StdRng::seed_from_u64(7));
+    /// assert_eq!(a.table(), b.table());
+    /// ```
+    pub fn random_with_rng<R: Rng + ?Sized>(horizon: i8, states: u8, rng: &mut R) -> Rule {
+        let table = uniform_table(Rule::rule_size(horizon, states), states, rng);
+        Rule::new(horizon, states, table)
+    }
+
+    /// Create a random rule on the given neighborhood, with transitions
+    /// sampled uniformly or with a Dirichlet distribution (see
+    /// [`Rule::random_dirichlet`]) from the given random number generator.
+    pub fn random_with_neighborhood<R: Rng + ?Sized>(
+        horizon: i8,
+        states: u8,
+        neighborhood: Neighborhood,
+        sampling: SamplingMode,
+        rng: &mut R,
+    ) -> Rule {
+        let size = Rule::table_size(horizon, states, neighborhood);
+        let table = match sampling {
+            SamplingMode::Uniform => uniform_table(size, states, rng),
+            SamplingMode::Dirichlet => dirichlet_table(size, states, None, rng),
+        };
+        Rule::with_neighborhood(horizon, states, neighborhood, table)
     }
 
     /// Create a random rule with transitions sampled according to a Dirichlet
@@ -140,80 +516,61 @@ impl Rule {
     /// For more information see this [note about CA rule
     /// sampling](https://hugocisneros.com/notes/cellular_automata/#dirichlet-based-sampling).
     pub fn random_dirichlet(horizon: i8, states: u8, alpha: Option<f64>) -> Rule {
-        let alpha = match alpha {
-            Some(v) => v,
-            None => ALPHA,
-        };
-        let dirichlet = Dirichlet::new_with_size(alpha, states.into()).unwrap();
-        let lambdas: Vec<f64> = dirichlet
-            .sample(&mut rand::thread_rng())
-            .iter()
-            .scan(0., |acc, &x| {
-                *acc += x;
-                Some(*acc)
-            })
-            .collect();
-        let big_bound: u64 = Rule::rule_size(horizon, states);
-        let table: Vec<u8> = (0..big_bound)
-            .map(|_| rand_state(&lambdas, states))
-            .collect();
-        Rule {
-            horizon,
-            states,
-            table,
-        }
+        Rule::random_dirichlet_with_rng(horizon, states, alpha, &mut rand::thread_rng())
+    }
+
+    /// Create a random rule like [`Rule::random_dirichlet`], drawn from the
+    /// given random number generator.
+    pub fn random_dirichlet_with_rng<R: Rng + ?Sized>(
+        horizon: i8,
+        states: u8,
+        alpha: Option<f64>,
+        rng: &mut R,
+    ) -> Rule {
+        let table = dirichlet_table(Rule::rule_size(horizon, states), states, alpha, rng);
+        Rule::new(horizon, states, table)
     }
 
-    /// Read a rule from specified filename.
+    /// Read a rule from specified filename. The checksum of the table is
+    /// verified when the file contains one, and a
+    /// [`RuleError::RuleChecksumError`] is returned if the file is corrupted.
     /// ```
     /// use rust_ca::rule::Rule;
     ///
     /// # let rule = Rule::random(1, 2);
     /// # rule.to_file("test_path.rule")?;
     /// let rule_from_file = Rule::from_file("test_path.rule")?;
-    /// # Ok::<(), std::io::Error>(())
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
     /// ```
-    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<Rule, std::io::Error> {
+    #[cfg(feature = "rule-io")]
+    pub fn from_file<P: AsRef<Path> + Copy>(path: P) -> Result<Rule, RuleError> {
         let mut f = File::open(path)?;
         let mut header_test = [0; 9];
 
         f.read_exact(&mut header_test)?;
         f.seek(SeekFrom::Start(0))?;
 
-        let mut table = Vec::new();
-        if !header_test.iter().zip(GZIP_H.iter()).all(|(a, b)| a == b) {
-            let mut decoder = ZlibDecoder::new(f);
-            decoder.read_to_end(&mut table)?;
+        let payload = if header_test.starts_with(&mapped::MAGIC) {
+            let mut payload = Vec::new();
+            f.read_to_end(&mut payload)?;
+            let (horizon, states, table) = mapped::parse(&payload)?;
+            mapped::verify(&payload, table)?;
+            return Ok(Rule::new(horizon, states, table.to_vec()));
+        } else if header_test.starts_with(&ZSTD_H) {
+            format::read_payload(zstd::stream::read::Decoder::new(f)?)?
+        } else if !header_test.iter().zip(GZIP_H.iter()).all(|(a, b)| a == b) {
+            format::read_payload(ZlibDecoder::new(f))?
         } else {
-            let mut decoder = GzDecoder::new(f);
-            decoder.read_to_end(&mut table)?;
+            format::read_payload(GzDecoder::new(f))?
         };
-        let zero = '0';
-        for i in &mut table {
-            *i -= zero as u8;
-        }
-
-        // Infer the number of states and horizon from the table size
-        let (states, horizon) = (2..30)
-            .find_map(|i| {
-                let d = (table.len() as f64).ln() / (i as f64).ln();
-                if (d - d.floor()).abs() < f64::EPSILON
-                    && (d.sqrt() - d.sqrt().floor()).abs() < f64::EPSILON
-                {
-                    Some((i, ((d.sqrt() - 1.) / 2.) as i8))
-                } else {
-                    None
-                }
-            })
-            .unwrap();
-        Ok(Rule::new(horizon, states, table))
+        format::decode(&payload)
     }
 
     /// Write a compressed representation of the rule to a specified filename.
     /// The resulting file contains the zlib compressed bytes of the rule.
-    ///
-    ///
-    ///
+    /// Totalistic and outer totalistic rules (see [`Rule::is_totalistic`] and
+    /// [`Rule::is_outer_totalistic`]) are stored as their much smaller sum
+    /// tables.
     /// ```
     /// use rust_ca::rule::Rule;
     ///
@@ -221,16 +578,72 @@ impl Rule {
     /// rule.to_file("test_path.rule")?;
     /// # Ok::<(), std::io::Error>(())
     /// ```
+    #[cfg(feature = "rule-io")]
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
-        let f = File::create(path)?;
-        let mut encoder = GzEncoder::new(f, Compression::default());
-        let zero = '0';
-        let mut out_vec = Vec::new();
-        for i in &self.table {
-            out_vec.push(i + zero as u8);
+        self.to_file_with_compression(path, FileCompression::Gzip)
+    }
+
+    /// Write a compressed representation of the rule to a specified filename
+    /// with the given compression (see [`Rule::to_file`]).
+    ///
+    /// ```
+    /// use rust_ca::rule::{FileCompression, Rule};
+    ///
+    /// let rule = Rule::random(1, 3);
+    /// rule.to_file_with_compression("test_zstd.rule", FileCompression::Zstd)?;
+    /// assert_eq!(Rule::from_file("test_zstd.rule")?.table(), rule.table());
+    /// # std::fs::remove_file("test_zstd.rule")?;
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    #[cfg(feature = "rule-io")]
+    pub fn to_file_with_compression<P: AsRef<Path>>(
+        &self,
+        path: P,
+        compression: FileCompression,
+    ) -> Result<(), std::io::Error> {
+        let f = AtomicFile::create(path)?;
+        match compression {
+            FileCompression::Gzip => {
+                let mut encoder = GzEncoder::new(f, Compression::default());
+                encoder.write_all(&format::encode(self))?;
+                encoder.finish()?.commit()
+            }
+            FileCompression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(f, ZSTD_LEVEL)?;
+                encoder.write_all(&format::encode(self))?;
+                encoder.finish()?.commit()
+            }
         }
-        encoder.write_all(&out_vec)?;
-        encoder.try_finish()
+    }
+
+    /// Returns a compact URL-safe base64 string representing the rule, to
+    /// share small rules as text. The string contains the deflate compressed
+    /// payload of rule files.
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let encoded = Rule::gol().to_base64();
+    /// assert!(encoded.len() < 100);
+    /// assert_eq!(Rule::from_base64(&encoded)?.table(), Rule::gol().table());
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    #[cfg(feature = "rule-io")]
+    pub fn to_base64(&self) -> String {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&format::encode(self))
+            .expect("Error compressing the rule");
+        URL_SAFE_NO_PAD.encode(encoder.finish().expect("Error compressing the rule"))
+    }
+
+    /// Reads a rule from a string written by [`Rule::to_base64`].
+    #[cfg(feature = "rule-io")]
+    pub fn from_base64(encoded: &str) -> Result<Rule, RuleError> {
+        let compressed = URL_SAFE_NO_PAD
+            .decode(encoded.trim())
+            .map_err(|_| RuleError::RuleFormatError("invalid base64 string"))?;
+        let payload = format::read_payload(DeflateDecoder::new(&compressed[..]))?;
+        format::decode(&payload)
     }
 
     /// Perform some checks on the rule to ensure its correctness.
@@ -244,7 +657,31 @@ impl Rule {
     /// assert!(!rule.check());
     /// ```
     pub fn check(&self) -> bool {
-        self.table.len() as u64 == Rule::rule_size(self.horizon, self.states)
+        self.table.len() as u64 == Rule::table_size(self.horizon, self.states, self.neighborhood)
+    }
+
+    /// Returns whether the rule is totalistic, i.e. whether the next state
+    /// only depends on the sum of the states of the cells in the neighborhood.
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// assert!(!Rule::gol().is_totalistic());
+    /// assert!(Rule::new(1, 2, vec![0; 512]).is_totalistic());
+    /// ```
+    pub fn is_totalistic(&self) -> bool {
+        format::compact_table(self, false).is_some()
+    }
+
+    /// Returns whether the rule is outer totalistic, i.e. whether the next
+    /// state only depends on the state of the central cell and on the sum of
+    /// the states of the other cells in the neighborhood.
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// assert!(Rule::gol().is_outer_totalistic());
+    /// ```
+    pub fn is_outer_totalistic(&self) -> bool {
+        format::compact_table(self, true).is_some()
     }
 
     /// Returns the game of life rule.
@@ -270,6 +707,38 @@ impl Rule {
         Rule::new(1, 2, utils::GOL.to_vec())
     }
 
+    /// Makes a Life-like rule (2 states, horizon 1, outer totalistic) from a
+    /// rule string in B/S notation such as `B3/S23` for the game of life or
+    /// `B36/S23` for HighLife (see [`TotalisticRule`]).
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let life = Rule::from_bs_notation("B3/S23")?;
+    /// assert_eq!(life.table(), Rule::gol().table());
+    /// assert!(Rule::from_bs_notation("B3/X23").is_err());
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    pub fn from_bs_notation(s: &str) -> Result<Rule, RuleError> {
+        s.parse::<TotalisticRule>()
+            .map(|rule| rule.to_rule())
+            .map_err(RuleError::RuleFormatError)
+    }
+
+    /// Returns the B/S notation of the rule if it is Life-like (see
+    /// [`Rule::from_bs_notation`]).
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// assert_eq!(Rule::gol().to_bs_notation().as_deref(), Some("B3/S23"));
+    /// assert_eq!(Rule::random(1, 3).to_bs_notation(), None);
+    /// ```
+    pub fn to_bs_notation(&self) -> Option<String> {
+        if self.horizon != 1 || self.states != 2 {
+            return None;
+        }
+        TotalisticRule::from_rule(self, true).map(|rule| rule.to_string())
+    }
+
     /// This function symmetrizes a rule, making all positions which are the
     /// same up to a symmetry transformation have the same next state.
     /// ```
@@ -281,6 +750,20 @@ impl Rule {
     /// assert_eq!(rule[236], rule[299]);
     /// ```
     pub fn symmetrize(&mut self) {
+        if self.neighborhood != Neighborhood::Moore {
+            let images = self.symmetric_positions();
+            let mut book_keep = vec![false; self.table.len()];
+            for position in 0..self.table.len() {
+                if book_keep[position] {
+                    continue;
+                }
+                for image in images(position) {
+                    book_keep[image] = true;
+                    self.table[image] = self.table[position];
+                }
+            }
+            return;
+        }
         let states = self.states;
         let side = (self.horizon * 2 + 1) as usize;
         let table = self.table_mut();
@@ -329,21 +812,425 @@ impl Rule {
         }
     }
 
-    /// Returns the id of the rule, a `u64` number uniquely (up to hash
-    /// collisions) identifying the rule.
-    ///
+    /// Returns the canonical form of the rule: the smallest of the tables of
+    /// the rules obtained by rotating and reflecting the neighborhoods. Rules
+    /// with the same canonical form have the same dynamics, up to a rotation
+    /// or a reflection of the grid. The states aren't relabeled.
     /// ```
     /// use rust_ca::rule::Rule;
     ///
     /// let rule = Rule::random(1, 2);
+    /// assert_eq!(rule.canonical().canonical_id(), rule.canonical_id());
+    /// // Symmetric rules are invariant under rotations and reflections.
+    /// assert_eq!(Rule::gol().canonical().table(), Rule::gol().table());
+    /// ```
+    pub fn canonical(&self) -> Rule {
+        if self.neighborhood != Neighborhood::Moore {
+            let images = self.symmetric_positions();
+            let n_symmetries = self.neighborhood.symmetries().len();
+            let mut tables = vec![Vec::with_capacity(self.table.len()); n_symmetries];
+            for position in 0..self.table.len() {
+                for (table, image) in tables.iter_mut().zip(images(position)) {
+                    table.push(self.table[image]);
+                }
+            }
+            let table = tables.into_iter().min().expect("there are symmetries");
+            return Rule::with_neighborhood(self.horizon, self.states, self.neighborhood, table);
+        }
+        let (states, side) = (self.states, (self.horizon * 2 + 1) as usize);
+        (0..8u8)
+            .map(|t| {
+                let table = (0..self.table.len() as u64)
+                    .map(|mut p| {
+                        if t & 4 != 0 {
+                            p = transpose_position(p, states, side);
+                        }
+                        if t & 2 != 0 {
+                            p = reverse_rows_position(p, states, side);
+                        }
+                        if t & 1 != 0 {
+                            p = reverse_cols_position(p, states, side);
+                        }
+                        self.table[p as usize]
+                    })
+                    .collect();
+                Rule::new(self.horizon, states, table)
+            })
+            .min_by(|a, b| a.table.cmp(&b.table))
+            .expect("there are 8 symmetries")
+    }
+
+    /// Returns a function mapping a position of the table to its images by
+    /// the symmetries of the neighborhood.
+    fn symmetric_positions(&self) -> impl Fn(usize) -> Vec<usize> {
+        let offsets = self.neighborhood.offsets(self.horizon);
+        // The cell of the position moved to each cell by each symmetry.
+        let permutations: Vec<Vec<usize>> = self
+            .neighborhood
+            .symmetries()
+            .iter()
+            .map(|symmetry| {
+                offsets
+                    .iter()
+                    .map(|&o| offsets.iter().position(|&p| p == symmetry(o)).unwrap())
+                    .collect()
+            })
+            .collect();
+        let states = self.states as usize;
+        move |mut position| {
+            let digits: Vec<usize> = (0..offsets.len())
+                .map(|_| {
+                    let d = position % states;
+                    position /= states;
+                    d
+                })
+                .collect();
+            permutations
+                .iter()
+                .map(|p| p.iter().rev().fold(0, |acc, &k| acc * states + digits[k]))
+                .collect()
+        }
+    }
+
+    /// Returns the rule whose states are relabeled by `permutation`: state
+    /// `s` becomes state `permutation[s]`. The relabeled rule has the same
+    /// dynamics as the rule, on grids relabeled the same way. Panics if
+    /// `permutation` isn't a permutation of the states.
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // The game of life with dead cells in state 1 and live cells in state 0.
+    /// let inverted = Rule::gol().relabeled(&[1, 0]);
+    /// // A crowded live cell dies.
+    /// assert_eq!(inverted.explain("000/000/000")?.next, 1);
+    /// // A live cell with two live neighbors survives.
+    /// assert_eq!(inverted.explain("111/000/111")?.next, 0);
+    /// assert_eq!(inverted.relabeled(&[1, 0]).table(), Rule::gol().table());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn relabeled(&self, permutation: &[u8]) -> Rule {
+        let states = self.states as usize;
+        let mut inverse = vec![states; states];
+        for (s, &image) in permutation.iter().enumerate() {
+            inverse[image as usize] = s;
+        }
+        assert!(
+            permutation.len() == states && inverse.iter().all(|&s| s < states),
+            "The relabeling must be a permutation of the states"
+        );
+        let cells = self.neighborhood.offsets(self.horizon).len();
+        let table = (0..self.table.len())
+            .map(|position| {
+                let (mut rest, mut source, mut power) = (position, 0, 1);
+                for _ in 0..cells {
+                    source += power * inverse[rest % states];
+                    rest /= states;
+                    power *= states;
+                }
+                permutation[self.table[source] as usize]
+            })
+            .collect();
+        Rule::with_neighborhood(self.horizon, self.states, self.neighborhood, table)
+    }
+
+    /// Returns the canonical id of the rule, shared by all the rules
+    /// equivalent to it up to a rotation or a reflection of the grid (see
+    /// [`Rule::canonical`]) and a relabeling of the states (see
+    /// [`Rule::relabeled`]). It is as stable as [`Rule::id`], and its
+    /// definition is versioned by [`Rule::CANONICAL_ID_VERSION`].
+    ///
+    /// The states are first ranked by a signature that the symmetries and
+    /// the relabelings leave unchanged, and only the relabelings following
+    /// that ranking are tried: the id is the one of the smallest of their
+    /// canonical forms. Most rules have a single such relabeling, but all the
+    /// orderings of the states sharing a signature are tried, so rules with
+    /// many states that can't be told apart (e.g. rules invariant under
+    /// relabelings) are slow.
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
     ///
-    /// println!("{}", rule.id());
+    /// let rule = Rule::random(1, 3);
+    /// assert_eq!(rule.relabeled(&[2, 0, 1]).canonical_id(), rule.canonical_id());
+    /// assert_eq!(rule.canonical().canonical_id(), rule.canonical_id());
+    /// ```
+    pub fn canonical_id(&self) -> u64 {
+        let mut orderings: Vec<Vec<u8>> = vec![vec![]];
+        for class in self.state_classes() {
+            let arrangements = permutations(&class);
+            orderings = orderings
+                .iter()
+                .flat_map(|ordering| {
+                    arrangements.iter().map(move |arrangement| {
+                        let mut ordering = ordering.clone();
+                        ordering.extend(arrangement);
+                        ordering
+                    })
+                })
+                .collect();
+        }
+        orderings
+            .into_iter()
+            .map(|ordering| {
+                // The state ranked k becomes state k.
+                let mut permutation = vec![0; ordering.len()];
+                for (rank, &state) in ordering.iter().enumerate() {
+                    permutation[state as usize] = rank as u8;
+                }
+                self.relabeled(&permutation).canonical()
+            })
+            .min_by(|a, b| a.table.cmp(&b.table))
+            .expect("there is at least one ordering")
+            .id()
+    }
+
+    /// Returns the states grouped by their signature, in the order of the
+    /// signatures. The signature of a state counts the transitions leading
+    /// to it, and those leading to it from neighborhoods with `k` cells in
+    /// it for each `k`, so that a symmetry of the grid keeps it and a
+    /// relabeling carries it over to the relabeled state.
+    fn state_classes(&self) -> Vec<Vec<u8>> {
+        let states = self.states as usize;
+        let cells = self.neighborhood.offsets(self.horizon).len();
+        let mut signatures = vec![vec![0usize; cells + 2]; states];
+        let mut counts = vec![0; states];
+        for (position, &next) in self.table.iter().enumerate() {
+            counts.iter_mut().for_each(|c| *c = 0);
+            let mut rest = position;
+            for _ in 0..cells {
+                counts[rest % states] += 1;
+                rest /= states;
+            }
+            let signature = &mut signatures[next as usize];
+            signature[0] += 1;
+            signature[1 + counts[next as usize]] += 1;
+        }
+        let mut classes: BTreeMap<Vec<usize>, Vec<u8>> = BTreeMap::new();
+        for (state, signature) in signatures.into_iter().enumerate() {
+            classes.entry(signature).or_default().push(state as u8);
+        }
+        classes.into_values().collect()
+    }
+
+    /// Applies the rule once to a periodic grid of size `size`, returning the
+    /// next grid.
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // A blinker oscillates with period 2 in the Game of Life.
+    /// let mut grid = vec![0; 25];
+    /// grid[11..14].copy_from_slice(&[1, 1, 1]);
+    /// let next = Rule::gol().apply_on_torus(&grid, 5);
+    /// assert_eq!((next[7], next[12], next[17]), (1, 1, 1));
+    /// assert_eq!(Rule::gol().apply_on_torus(&next, 5), grid);
+    /// ```
+    pub fn apply_on_torus(&self, grid: &[u8], size: usize) -> Vec<u8> {
+        let mut next = Vec::with_capacity(size * size);
+        for i in 0..size {
+            for j in 0..size {
+                next.push(self.table[self.position_on_torus(grid, size, (i, j))]);
+            }
+        }
+        next
+    }
+
+    /// Returns the rule table position of the neighborhood of cell `(i, j)`
+    /// of a periodic grid of size `size`.
+    pub(crate) fn position_on_torus(
+        &self,
+        grid: &[u8],
+        size: usize,
+        (i, j): (usize, usize),
+    ) -> usize {
+        let h = self.horizon as isize;
+        let s = size as isize;
+        let (i, j) = (i as isize, j as isize);
+        let states = self.states as usize;
+        let mut ind = 0;
+        let mut power = 1;
+        for a in -h..=h {
+            for b in (-h..=h).filter(|&b| self.neighborhood.contains(a, b, h)) {
+                let idx = (i + a).rem_euclid(s) * s + (j + b).rem_euclid(s);
+                ind += power * grid[idx as usize] as usize;
+                power *= states;
+            }
+        }
+        ind
+    }
+
+    /// Checks whether the rule is reversible on a periodic grid of size
+    /// `size`, i.e. whether no two configurations of the grid have the same
+    /// image. For tiny grids all configurations are enumerated and the check
+    /// is exact. For larger grids, random configurations are compared with
+    /// all the configurations that differ from them by a single cell, and the
+    /// rule is only reported as probably reversible if no collision is found.
+    /// ```
+    /// use rust_ca::rule::{Reversibility, Rule};
+    ///
+    /// assert_eq!(Rule::gol().is_reversible_on_torus(4), Reversibility::NotReversible);
+    ///
+    /// // The identity rule only copies the central cell.
+    /// let identity = Rule::new(1, 2, (0..512).map(|i| ((i >> 4) & 1) as u8).collect());
+    /// assert_eq!(identity.is_reversible_on_torus(4), Reversibility::Reversible);
+    /// assert_eq!(identity.is_reversible_on_torus(16), Reversibility::ProbablyReversible);
+    /// ```
+    pub fn is_reversible_on_torus(&self, size: usize) -> Reversibility {
+        let states = self.states as u64;
+        let n_cells = (size * size) as u32;
+        let n_configs = states.checked_pow(n_cells);
+        let decode = |mut config: u64| -> Vec<u8> {
+            (0..n_cells)
+                .map(|_| {
+                    let c = (config % states) as u8;
+                    config /= states;
+                    c
+                })
+                .collect()
+        };
+        let encode = |grid: &[u8]| grid.iter().rev().fold(0, |acc, &c| acc * states + c as u64);
+
+        match n_configs {
+            Some(n) if n <= EXACT_REVERSIBILITY_LIMIT => {
+                let mut seen = vec![false; n as usize];
+                for config in 0..n {
+                    let image = encode(&self.apply_on_torus(&decode(config), size)) as usize;
+                    if seen[image] {
+                        return Reversibility::NotReversible;
+                    }
+                    seen[image] = true;
+                }
+                Reversibility::Reversible
+            }
+            _ => {
+                let mut rng = rand::thread_rng();
+                for _ in 0..REVERSIBILITY_TRIALS {
+                    let mut grid: Vec<u8> = (0..n_cells)
+                        .map(|_| rng.gen_range(0..self.states))
+                        .collect();
+                    let image = self.apply_on_torus(&grid, size);
+                    for idx in 0..grid.len() {
+                        let original = grid[idx];
+                        for state in (0..self.states).filter(|&s| s != original) {
+                            grid[idx] = state;
+                            if self.apply_on_torus(&grid, size) == image {
+                                return Reversibility::NotReversible;
+                            }
+                        }
+                        grid[idx] = original;
+                    }
+                }
+                Reversibility::ProbablyReversible
+            }
+        }
+    }
+
+    /// Returns the id of the rule, a `u64` number uniquely (up to hash
+    /// collisions) identifying the rule. The id is a hash of the horizon, the
+    /// number of states, the table and, for the rules on other neighborhoods
+    /// than the Moore neighborhood, the neighborhood. Its algorithm is
+    /// pinned, so the id of a rule is the same on every platform and Rust
+    /// version, and can be recorded in file names and catalogs. It is the
+    /// hash the ids were computed with by the standard library before, so the
+    /// ids recorded by earlier versions are unchanged.
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// assert_eq!(Rule::gol().id(), 5664888421622951056);
     /// ```
     pub fn id(&self) -> u64 {
-        let mut s = DefaultHasher::new();
+        let mut s = hash::StableHasher::new();
         self.hash(&mut s);
         s.finish()
     }
+
+    /// Checks that the id of the rule matches an expected id, e.g. the id
+    /// recorded in a catalog for the file the rule was read from.
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let rule = Rule::random(1, 2);
+    /// rule.to_file("test_verify.rule")?;
+    /// let from_file = Rule::from_file("test_verify.rule")?;
+    /// assert!(from_file.verify_against(rule.id()).is_ok());
+    /// assert!(from_file.verify_against(rule.id() + 1).is_err());
+    /// # std::fs::remove_file("test_verify.rule")?;
+    /// # Ok::<(), rust_ca::rule::RuleError>(())
+    /// ```
+    pub fn verify_against(&self, id: u64) -> Result<(), RuleError> {
+        let found = self.id();
+        if found == id {
+            Ok(())
+        } else {
+            Err(RuleError::RuleIdError {
+                expected: id,
+                found,
+            })
+        }
+    }
+
+    /// Checks that the rule has the given horizon and number of states,
+    /// e.g. before replacing the rule of a running automaton.
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// assert!(Rule::gol().verify_shape(1, 2).is_ok());
+    /// assert!(Rule::gol().verify_shape(1, 3).is_err());
+    /// ```
+    pub fn verify_shape(&self, horizon: i8, states: u8) -> Result<(), RuleError> {
+        if (self.horizon, self.states) == (horizon, states) {
+            Ok(())
+        } else {
+            Err(RuleError::RuleShapeError {
+                expected: (horizon, states),
+                found: (self.horizon, self.states),
+            })
+        }
+    }
+
+    /// Checks that the rule has the same shape and neighborhood as another
+    /// one, so that it can replace it in a running automaton. Returns a
+    /// [`RuleError::RuleShapeError`] or a
+    /// [`RuleError::RuleNeighborhoodError`] otherwise.
+    ///
+    /// ```
+    /// use rust_ca::rule::{Neighborhood, Rule};
+    ///
+    /// let hex = Rule::with_neighborhood(1, 2, Neighborhood::Hex, vec![0; 128]);
+    /// assert!(Rule::gol().verify_compatible(&Rule::random(1, 2)).is_ok());
+    /// assert!(hex.verify_compatible(&Rule::gol()).is_err());
+    /// ```
+    pub fn verify_compatible(&self, other: &Rule) -> Result<(), RuleError> {
+        self.verify_shape(other.horizon, other.states)?;
+        if self.neighborhood == other.neighborhood {
+            Ok(())
+        } else {
+            Err(RuleError::RuleNeighborhoodError {
+                expected: other.neighborhood,
+                found: self.neighborhood,
+            })
+        }
+    }
+}
+
+/// Returns all the orderings of `items`.
+fn permutations(items: &[u8]) -> Vec<Vec<u8>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut orderings = vec![];
+    for (k, &first) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(k);
+        for mut ordering in permutations(&rest) {
+            ordering.insert(0, first);
+            orderings.push(ordering);
+        }
+    }
+    orderings
 }
 
 /// A position is a unsigned integer (`u64`) which represents a single
@@ -407,9 +1294,39 @@ impl IndexMut<usize> for Rule {
     }
 }
 
-fn rand_state(lambdas: &[f64], states: u8) -> u8 {
+/// Samples a rule table of `size` transitions uniformly.
+fn uniform_table<R: Rng + ?Sized>(size: u64, states: u8, rng: &mut R) -> Vec<u8> {
+    (0..size).map(|_| rng.gen_range(0..states)).collect()
+}
+
+/// Samples a rule table of `size` transitions according to a Dirichlet
+/// distribution with parameter `alpha` (see [`Rule::random_dirichlet`]).
+fn dirichlet_table<R: Rng + ?Sized>(
+    size: u64,
+    states: u8,
+    alpha: Option<f64>,
+    rng: &mut R,
+) -> Vec<u8> {
+    let alpha = match alpha {
+        Some(v) => v,
+        None => ALPHA,
+    };
+    let dirichlet = Dirichlet::new_with_size(alpha, states.into()).unwrap();
+    let lambdas: Vec<f64> = dirichlet
+        .sample(rng)
+        .iter()
+        .scan(0., |acc, &x| {
+            *acc += x;
+            Some(*acc)
+        })
+        .collect();
+    (0..size)
+        .map(|_| rand_state(&lambdas, states, rng))
+        .collect()
+}
+
+fn rand_state<R: Rng + ?Sized>(lambdas: &[f64], states: u8, rng: &mut R) -> u8 {
     assert_eq!(lambdas.len(), states.into());
-    let mut rng = rand::thread_rng();
     let val: f64 = rng.gen_range(0.0..1.0);
     lambdas
         .iter()
@@ -424,7 +1341,16 @@ mod tests {
     use crate::rule::reverse_cols_position;
     use crate::rule::reverse_rows_position;
 
-    use super::{transpose_position, Rule};
+    use super::{transpose_position, Neighborhood, Reversibility, Rule, SamplingMode};
+
+    #[test]
+    fn bs_notation_roundtrip() {
+        for notation in ["B3/S23", "B36/S23", "B/S", "B2/S", "B012345678/S012345678"] {
+            let rule = Rule::from_bs_notation(notation).unwrap();
+            assert_eq!(rule.to_bs_notation().as_deref(), Some(notation));
+        }
+        assert!(Rule::from_bs_notation("GOL").is_err());
+    }
 
     #[test]
     fn should_check_correct_rule_size() {
@@ -432,6 +1358,7 @@ mod tests {
             states: 2,
             horizon: 1,
             table: vec![1; 512],
+            neighborhood: Neighborhood::Moore,
         };
         assert!(rule.check());
         rule.table.push(0);
@@ -441,6 +1368,7 @@ mod tests {
             states: 3,
             horizon: 1,
             table: vec![1; 19683],
+            neighborhood: Neighborhood::Moore,
         };
         assert!(rule.check());
         rule.table.push(0);
@@ -448,9 +1376,41 @@ mod tests {
     }
 
     #[test]
-    fn encode_decode() -> Result<(), std::io::Error> {
+    #[cfg(feature = "rule-io")]
+    fn base64_roundtrip() {
+        use super::{Compression, DeflateEncoder, RuleError, MAX_TABLE_SIZE, URL_SAFE_NO_PAD};
+        use base64::Engine;
+        use std::io::Write;
+
         let rule = Rule::random(1, 3);
-        let table_before = rule.table().clone();
+        let encoded = rule.to_base64();
+        assert_eq!(Rule::from_base64(&encoded).unwrap().table(), rule.table());
+        assert!(Rule::from_base64("not base64!").is_err());
+        assert!(Rule::from_base64(&encoded[..encoded.len() / 2]).is_err());
+        // Headers and payloads too large for any rule are rejected.
+        let deflate = |payload: &[u8]| {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(payload).unwrap();
+            URL_SAFE_NO_PAD.encode(encoder.finish().unwrap())
+        };
+        let huge = deflate(b"#encoding=full horizon=100 states=2\n0");
+        assert!(matches!(
+            Rule::from_base64(&huge),
+            Err(RuleError::RuleFormatError(_))
+        ));
+        let bomb = deflate(&vec![b'0'; (MAX_TABLE_SIZE + 2048) as usize]);
+        assert!(bomb.len() < 1 << 20);
+        assert!(matches!(
+            Rule::from_base64(&bomb),
+            Err(RuleError::RuleFormatError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "rule-io")]
+    fn encode_decode() -> Result<(), super::RuleError> {
+        let rule = Rule::random(1, 3);
+        let table_before = rule.table().to_vec();
         rule.to_file("test_encode_decode.rule")?;
 
         let rule_after = Rule::from_file("test_encode_decode.rule")?;
@@ -527,4 +1487,98 @@ mod tests {
             .zip(table_before.iter())
             .all(|(a, b)| a == b));
     }
+
+    #[test]
+    fn hexagonal_rules_have_twelve_symmetries() {
+        let mut rng = rand::thread_rng();
+        let mut rule = Rule::random_with_neighborhood(
+            1,
+            2,
+            Neighborhood::Hex,
+            SamplingMode::Uniform,
+            &mut rng,
+        );
+        assert_eq!(rule.symmetric_positions()(1).len(), 12);
+        let canonical = rule.canonical();
+        assert_eq!(canonical.canonical().table(), canonical.table());
+        rule.symmetrize();
+        assert_eq!(rule.canonical().table(), rule.table());
+        // The 6 configurations with a single live neighbor are rotations of
+        // each other.
+        let singles: Vec<u8> = (0..7).filter(|&k| k != 3).map(|k| rule[1 << k]).collect();
+        assert!(singles.iter().all(|&s| s == singles[0]));
+    }
+
+    #[test]
+    fn rotated_rules_share_a_canonical_form() {
+        let rule = Rule::random(1, 3);
+        let transposed = Rule::new(
+            1,
+            3,
+            (0..19683)
+                .map(|p| rule[transpose_position(p, 3, 3) as usize])
+                .collect(),
+        );
+        assert_eq!(rule.canonical_id(), transposed.canonical_id());
+        assert_ne!(rule.canonical_id(), Rule::random(1, 3).canonical_id());
+
+        // The transposed rule acts on transposed grids.
+        let grid: Vec<u8> = (0..64).map(|i| ((i * 7) % 5 % 3) as u8).collect();
+        let transpose = |g: &[u8]| -> Vec<u8> { (0..64).map(|i| g[(i % 8) * 8 + i / 8]).collect() };
+        assert_eq!(
+            transpose(&rule.apply_on_torus(&grid, 8)),
+            transposed.apply_on_torus(&transpose(&grid), 8)
+        );
+    }
+
+    #[test]
+    fn relabeled_rules_share_a_canonical_id() {
+        let rule = Rule::random(1, 3);
+        let relabeled = rule.relabeled(&[1, 2, 0]);
+        let grid: Vec<u8> = (0..64).map(|i| ((i * 7) % 5 % 3) as u8).collect();
+        let relabel = |g: &[u8]| -> Vec<u8> { g.iter().map(|&c| [1, 2, 0][c as usize]).collect() };
+        assert_eq!(
+            relabel(&rule.apply_on_torus(&grid, 8)),
+            relabeled.apply_on_torus(&relabel(&grid), 8)
+        );
+        assert_eq!(relabeled.canonical().canonical_id(), rule.canonical_id());
+        assert_ne!(relabeled.id(), rule.id());
+        // The von Neumann rules are relabeled on their own neighborhood.
+        let excitable = Rule::greenberg_hastings(4, 1).unwrap();
+        let relabeled = excitable.relabeled(&[0, 3, 1, 2]);
+        assert_eq!(relabeled.neighborhood(), Neighborhood::VonNeumann);
+        assert_eq!(relabeled.explain("030/000/000").unwrap().next, 3);
+        assert_eq!(relabeled.canonical_id(), excitable.canonical_id());
+        // Rules with more states only try the relabelings of their ranking.
+        let rule = Rule::random(1, 4);
+        assert_eq!(rule.state_classes().len(), 4);
+        assert_eq!(
+            rule.relabeled(&[3, 1, 0, 2]).canonical_id(),
+            rule.canonical_id()
+        );
+        // The states of the shift rule can't be told apart.
+        let shift = Rule::new(1, 3, (0..19683).map(|i| ((i / 27) % 3) as u8).collect());
+        assert_eq!(shift.state_classes(), vec![vec![0, 1, 2]]);
+        assert_eq!(shift.relabeled(&[2, 0, 1]).table(), shift.table());
+    }
+
+    #[test]
+    fn shift_rule_is_reversible() {
+        // Every cell takes the state of its west neighbor.
+        let shift = Rule::new(1, 3, (0..19683).map(|i| ((i / 27) % 3) as u8).collect());
+        assert_eq!(shift.is_reversible_on_torus(3), Reversibility::Reversible);
+    }
+
+    #[test]
+    fn constant_rule_is_not_reversible() {
+        let constant = Rule::new(1, 2, vec![0; 512]);
+        assert_eq!(
+            constant.is_reversible_on_torus(2),
+            Reversibility::NotReversible
+        );
+        assert_eq!(
+            constant.is_reversible_on_torus(32),
+            Reversibility::NotReversible
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use super::dsl::{parse_transitions, DslError};
+use super::{Rule, SamplingMode};
+
+/// A builder for random rules with some transitions fixed in advance. The
+/// pinned transitions are kept while the rest of the rule table is randomly
+/// sampled, so that rule searches respect prior constraints.
+///
+/// ```
+/// use rust_ca::rule::{RuleBuilder, SamplingMode};
+///
+/// // The empty neighborhood stays empty and the full neighborhood dies.
+/// let rule = RuleBuilder::new(1, 2)
+///     .pin(&[0; 9], 0)
+///     .pin(&[1; 9], 0)
+///     .sample(SamplingMode::Uniform);
+/// assert_eq!(rule[0], 0);
+/// assert_eq!(rule[511], 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RuleBuilder {
+    horizon: i8,
+    states: u8,
+    pins: BTreeMap<usize, u8>,
+}
+
+impl RuleBuilder {
+    /// Makes a builder for rules with a given horizon and number of states,
+    /// without any pinned transition.
+    pub fn new(horizon: i8, states: u8) -> RuleBuilder {
+        RuleBuilder {
+            horizon,
+            states,
+            pins: BTreeMap::new(),
+        }
+    }
+
+    /// Makes a builder for rules with `states` states from transitions written
+    /// in the transition language (see the [`rule!`](crate::rule!) macro). The
+    /// horizon is inferred from the size of the neighborhoods.
+    /// ```
+    /// use rust_ca::rule::RuleBuilder;
+    ///
+    /// let builder = RuleBuilder::from_dsl(2, "[_ _ _ / _ 1 _ / _ _ _] #1 >= 4 => 0")?;
+    /// let rule = builder.build(1);
+    /// assert_eq!(rule[511], 0);
+    /// assert_eq!(rule[0], 1);
+    /// # Ok::<(), rust_ca::rule::DslError>(())
+    /// ```
+    pub fn from_dsl(states: u8, src: &str) -> Result<RuleBuilder, DslError> {
+        let transitions = parse_transitions(src, states)?;
+        let side = transitions.first().map_or(3, |t| t.side());
+        let mut builder = RuleBuilder::new((side / 2) as i8, states);
+        for t in transitions {
+            for position in t.positions(states) {
+                builder = builder.pin_position(position, t.state);
+            }
+        }
+        Ok(builder)
+    }
+
+    /// Pins the transition of a neighborhood to a given next state. The
+    /// neighborhood is given as the `(2 * horizon + 1)^2` cells of the square
+    /// around the central cell, in row-major order.
+    pub fn pin(self, neighborhood: &[u8], state: u8) -> RuleBuilder {
+        let side = (2 * self.horizon + 1) as usize;
+        assert_eq!(
+            neighborhood.len(),
+            side * side,
+            "The neighborhood must have {} cells",
+            side * side
+        );
+        assert!(
+            neighborhood.iter().all(|&c| c < self.states),
+            "Incorrect neighborhood for {} states",
+            self.states
+        );
+        let states = self.states as usize;
+        let position = neighborhood
+            .iter()
+            .rev()
+            .fold(0, |acc, &c| acc * states + c as usize);
+        self.pin_position(position, state)
+    }
+
+    /// Pins the transition at a given position of the rule table (see
+    /// [`Rule::gol`] for the encoding of positions) to a given next state.
+    pub fn pin_position(mut self, position: usize, state: u8) -> RuleBuilder {
+        assert!(
+            state < self.states,
+            "Incorrect state {} for {} states",
+            state,
+            self.states
+        );
+        assert!(
+            (position as u64) < Rule::rule_size(self.horizon, self.states),
+            "Position {} out of the rule table",
+            position
+        );
+        self.pins.insert(position, state);
+        self
+    }
+
+    /// Builds a rule where all the transitions that aren't pinned lead to the
+    /// `default` state.
+    pub fn build(&self, default: u8) -> Rule {
+        assert!(
+            default < self.states,
+            "Incorrect state {} for {} states",
+            default,
+            self.states
+        );
+        let mut rule = Rule::new(
+            self.horizon,
+            self.states,
+            vec![default; Rule::rule_size(self.horizon, self.states) as usize],
+        );
+        for (&position, &state) in &self.pins {
+            rule[position] = state;
+        }
+        rule
+    }
+
+    /// Samples a random rule with the given sampling mode and applies the
+    /// pinned transitions.
+    pub fn sample(&self, mode: SamplingMode) -> Rule {
+        let mut rule = match mode {
+            SamplingMode::Uniform => Rule::random(self.horizon, self.states),
+            SamplingMode::Dirichlet => Rule::random_dirichlet(self.horizon, self.states, None),
+        };
+        for (&position, &state) in &self.pins {
+            rule[position] = state;
+        }
+        rule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleBuilder;
+    use crate::rule::SamplingMode;
+
+    #[test]
+    fn pins_use_the_table_encoding() {
+        // Only the top-left cell alive.
+        let rule = RuleBuilder::new(1, 3)
+            .pin(&[1, 0, 0, 0, 0, 0, 0, 0, 0], 2)
+            .pin(&[0, 0, 0, 0, 0, 0, 0, 0, 2], 1)
+            .sample(SamplingMode::Dirichlet);
+        assert_eq!(rule[1], 2);
+        assert_eq!(rule[2 * 3usize.pow(8)], 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pin_rejects_wrong_neighborhoods() {
+        RuleBuilder::new(1, 2).pin(&[0; 4], 0);
+    }
+}
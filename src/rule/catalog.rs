@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{Rule, RuleError};
+use crate::output::AtomicFile;
+
+/// A record of the rules already evaluated, to skip the rules equivalent to
+/// them (up to a rotation, a reflection or a relabeling of the states, see
+/// [`Rule::canonical_id`]) in large sampling campaigns. Each rule is mapped to
+/// its canonical id, and each canonical id to the first rule recorded with
+/// it, its representative.
+///
+/// Catalogs are saved as text files with one `rule_id canonical_id` line per
+/// rule, so that several campaigns can share and extend them. A `#version`
+/// line gives the [`Rule::CANONICAL_ID_VERSION`] of the lines following it.
+/// Files without one are from version 1, whose canonical ids are still
+/// looked up, as the ids of the canonical forms of the rules.
+///
+/// ```
+/// use rust_ca::rule::{Rule, RuleCatalog};
+///
+/// let mut catalog = RuleCatalog::new();
+/// let rule = Rule::random(1, 2);
+/// assert_eq!(catalog.record(&rule), None);
+/// // The rule is now its own representative.
+/// assert_eq!(catalog.lookup(&rule), Some(rule.id()));
+/// catalog.to_file("test_catalog.txt")?;
+/// let catalog = RuleCatalog::from_file("test_catalog.txt")?;
+/// assert_eq!(catalog.len(), 1);
+/// # std::fs::remove_file("test_catalog.txt")?;
+/// # Ok::<(), rust_ca::rule::RuleError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RuleCatalog {
+    /// The rule ids and canonical ids, in the order they were recorded.
+    rules: Vec<(u64, u64)>,
+    /// The canonical ids, and the id of their representative.
+    representatives: HashMap<u64, u64>,
+    /// The rule ids and version 1 canonical ids read from older catalogs.
+    legacy_rules: Vec<(u64, u64)>,
+    /// The version 1 canonical ids, and the id of their representative.
+    legacy_representatives: HashMap<u64, u64>,
+}
+
+impl RuleCatalog {
+    /// Makes an empty catalog.
+    pub fn new() -> RuleCatalog {
+        RuleCatalog::default()
+    }
+
+    /// Reads a catalog from a file. A missing file is an empty catalog.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<RuleCatalog, RuleError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut catalog = RuleCatalog::new();
+        let mut version = 1;
+        for line in contents.lines().map(str::trim) {
+            if let Some(v) = line.strip_prefix("#version") {
+                version = v
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&v| v == 1 || v == Rule::CANONICAL_ID_VERSION)
+                    .ok_or(RuleError::RuleFormatError("unsupported catalog version"))?;
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let ids: Vec<u64> = line
+                .split_whitespace()
+                .map(|id| id.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| RuleError::RuleFormatError("invalid rule id in the catalog"))?;
+            match (&ids[..], version) {
+                (&[id, canonical], 1) => catalog.insert_legacy(id, canonical),
+                (&[id, canonical], _) => {
+                    catalog.insert(id, canonical);
+                }
+                _ => {
+                    return Err(RuleError::RuleFormatError(
+                        "catalog lines must have a rule id and a canonical id",
+                    ))
+                }
+            };
+        }
+        Ok(catalog)
+    }
+
+    /// Writes the catalog to a file.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut f = AtomicFile::create(path)?;
+        writeln!(f, "# rule_id canonical_id")?;
+        if !self.legacy_rules.is_empty() {
+            writeln!(f, "#version 1")?;
+            for (id, canonical) in &self.legacy_rules {
+                writeln!(f, "{} {}", id, canonical)?;
+            }
+        }
+        writeln!(f, "#version {}", Rule::CANONICAL_ID_VERSION)?;
+        for (id, canonical) in &self.rules {
+            writeln!(f, "{} {}", id, canonical)?;
+        }
+        f.commit()
+    }
+
+    /// Returns the number of rules recorded.
+    pub fn len(&self) -> usize {
+        self.rules.len() + self.legacy_rules.len()
+    }
+
+    /// Returns whether no rule was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the id of the representative of the rules equivalent to
+    /// `rule`, if one was recorded. The rules of version 1 catalogs are only
+    /// found for the rules equivalent to them up to a symmetry of the grid.
+    pub fn lookup(&self, rule: &Rule) -> Option<u64> {
+        self.lookup_canonical(rule, rule.canonical_id())
+    }
+
+    /// Looks a rule up like [`RuleCatalog::lookup`], with its already
+    /// computed canonical id.
+    pub(crate) fn lookup_canonical(&self, rule: &Rule, canonical: u64) -> Option<u64> {
+        self.representatives
+            .get(&canonical)
+            .or_else(|| {
+                if self.legacy_representatives.is_empty() {
+                    return None;
+                }
+                self.legacy_representatives.get(&rule.canonical().id())
+            })
+            .copied()
+    }
+
+    /// Records a rule, and returns the id of the representative of the
+    /// equivalent rules recorded before it, if any.
+    pub fn record(&mut self, rule: &Rule) -> Option<u64> {
+        let previous = self.lookup(rule);
+        let canonical = rule.canonical_id();
+        if let Some(representative) = previous {
+            // Keeps the representative of a version 1 catalog.
+            self.representatives
+                .entry(canonical)
+                .or_insert(representative);
+        }
+        self.insert(rule.id(), canonical);
+        previous
+    }
+
+    /// Records a rule id and its canonical id, and returns the previous
+    /// representative of the canonical id.
+    fn insert(&mut self, id: u64, canonical: u64) -> Option<u64> {
+        let previous = self.representatives.get(&canonical).copied();
+        if !self.rules.contains(&(id, canonical)) {
+            self.rules.push((id, canonical));
+            self.representatives.entry(canonical).or_insert(id);
+        }
+        previous
+    }
+
+    /// Records a rule id and its version 1 canonical id.
+    fn insert_legacy(&mut self, id: u64, canonical: u64) {
+        if !self.legacy_rules.contains(&(id, canonical)) {
+            self.legacy_rules.push((id, canonical));
+            self.legacy_representatives.entry(canonical).or_insert(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleCatalog;
+    use crate::rule::Rule;
+
+    #[test]
+    fn equivalent_rules_map_to_their_representative() {
+        // Every cell takes the state of its west neighbor, or of its east
+        // neighbor: the rules are mirror images.
+        let west = Rule::new(1, 2, (0..512).map(|i| ((i / 8) % 2) as u8).collect());
+        let east = Rule::new(1, 2, (0..512).map(|i| ((i / 32) % 2) as u8).collect());
+        let mut catalog = RuleCatalog::new();
+        assert_eq!(catalog.record(&west), None);
+        assert_eq!(catalog.lookup(&east), Some(west.id()));
+        assert_eq!(catalog.record(&east), Some(west.id()));
+        assert_eq!(catalog.record(&east), Some(west.id()));
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(catalog.lookup(&Rule::gol()), None);
+
+        let path = std::env::temp_dir().join("rust_ca_catalog.txt");
+        catalog.to_file(&path).unwrap();
+        let read = RuleCatalog::from_file(&path).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read.lookup(&east), Some(west.id()));
+        // The rules of version 1 catalogs are still found.
+        std::fs::write(&path, format!("{} {}\n", west.id(), west.canonical().id())).unwrap();
+        let legacy = RuleCatalog::from_file(&path).unwrap();
+        assert_eq!(legacy.lookup(&east), Some(west.id()));
+        let mut upgraded = legacy.clone();
+        assert_eq!(upgraded.record(&Rule::gol()), None);
+        upgraded.to_file(&path).unwrap();
+        let read = RuleCatalog::from_file(&path).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read.lookup(&east), Some(west.id()));
+        assert_eq!(read.lookup(&Rule::gol()), Some(Rule::gol().id()));
+        std::fs::write(&path, "#version 9\n").unwrap();
+        assert!(RuleCatalog::from_file(&path).is_err());
+        std::fs::write(&path, "1 2 3\n").unwrap();
+        assert!(RuleCatalog::from_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(RuleCatalog::from_file(&path).unwrap().is_empty());
+    }
+}
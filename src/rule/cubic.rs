@@ -0,0 +1,203 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::format::compact_size;
+use super::{dirichlet_table, uniform_table};
+
+/// An outer totalistic rule of a three-dimensional CA: the next state of a
+/// cell depends on its state and on the sum of the states of the other cells
+/// of its 3D Moore neighborhood, the cube of side `2 horizon + 1` centered on
+/// it.
+///
+/// Full tables would have `states^27` entries already for horizon 1, so 3D
+/// rules are always stored as sum tables, indexed like the ones of outer
+/// [`TotalisticRule`](super::TotalisticRule)s. 3D Life-like rules are written
+/// `B<birth counts>/S<survival counts>`, the counts separated by commas when
+/// some of them have two digits:
+///
+/// ```
+/// use rust_ca::rule::Rule3D;
+///
+/// let rule: Rule3D = "B5/S4,5".parse()?;
+/// assert_eq!(rule.next_state(0, 5), 1);
+/// assert_eq!(rule.next_state(1, 6), 0);
+/// assert_eq!(rule.to_string(), "B5/S45");
+/// # Ok::<(), &'static str>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rule3D {
+    /// The number of cells on each side of the central cell in the
+    /// neighborhood.
+    pub horizon: i8,
+    /// The number of cell states the rule expects.
+    pub states: u8,
+    table: Vec<u8>,
+}
+
+/// The number of cells in the 3D neighborhood of a given horizon.
+fn n_cells(horizon: i8) -> usize {
+    (2 * horizon as usize + 1).pow(3)
+}
+
+impl Rule3D {
+    /// Construct a new rule from its number of states, horizon and sum
+    /// table.
+    pub fn new(horizon: i8, states: u8, table: Vec<u8>) -> Rule3D {
+        if table.len() != Rule3D::rule_size(horizon, states) || table.iter().any(|&s| s >= states) {
+            panic!(
+                "Incorrect 3D rule for neighborhood size {} and number of states {}",
+                horizon, states
+            )
+        }
+        Rule3D {
+            horizon,
+            states,
+            table,
+        }
+    }
+
+    /// Returns the size of the sum table for a (horizon, states) pair.
+    fn rule_size(horizon: i8, states: u8) -> usize {
+        compact_size(states as usize, n_cells(horizon), true)
+    }
+
+    /// Makes the 2 states rule of horizon 1 where a dead cell becomes alive
+    /// when its number of live neighbors is in `birth`, and a live cell stays
+    /// alive when it is in `survival`. Counts above 26 are ignored.
+    pub fn life_like(birth: &[usize], survival: &[usize]) -> Rule3D {
+        let mut table = vec![0; 54];
+        for (center, counts) in [birth, survival].iter().enumerate() {
+            for &n in counts.iter().filter(|&&n| n <= 26) {
+                table[27 * center + n] = 1;
+            }
+        }
+        Rule3D::new(1, 2, table)
+    }
+
+    /// Returns a reference to the sum table.
+    pub fn table(&self) -> &[u8] {
+        &self.table
+    }
+
+    /// Create a random rule with uniformly sampled transitions.
+    pub fn random(horizon: i8, states: u8) -> Rule3D {
+        Rule3D::random_with_rng(horizon, states, &mut rand::thread_rng())
+    }
+
+    /// Create a random rule with uniformly sampled transitions, drawn from
+    /// the given random number generator.
+    pub fn random_with_rng<R: Rng + ?Sized>(horizon: i8, states: u8, rng: &mut R) -> Rule3D {
+        let size = Rule3D::rule_size(horizon, states) as u64;
+        Rule3D::new(horizon, states, uniform_table(size, states, rng))
+    }
+
+    /// Create a random rule with transitions sampled according to a Dirichlet
+    /// distribution with parameter `alpha`, like
+    /// [`Rule::random_dirichlet`](super::Rule::random_dirichlet).
+    pub fn random_dirichlet_with_rng<R: Rng + ?Sized>(
+        horizon: i8,
+        states: u8,
+        alpha: Option<f64>,
+        rng: &mut R,
+    ) -> Rule3D {
+        let size = Rule3D::rule_size(horizon, states) as u64;
+        Rule3D::new(horizon, states, dirichlet_table(size, states, alpha, rng))
+    }
+
+    /// Returns the next state of a cell in state `center` whose other
+    /// neighborhood cells sum to `sum`.
+    #[inline]
+    pub fn next_state(&self, center: u8, sum: usize) -> u8 {
+        let n_sums = (n_cells(self.horizon) - 1) * (self.states as usize - 1) + 1;
+        self.table[center as usize * n_sums + sum]
+    }
+}
+
+/// Parses a 3D Life-like rule string `B<birth counts>/S<survival counts>`
+/// (see [`Rule3D::life_like`]). Counts are single digits, or separated by
+/// commas.
+impl FromStr for Rule3D {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let counts = |part: &str, prefix: char| -> Result<Vec<usize>, Self::Err> {
+            let part = part
+                .strip_prefix(prefix)
+                .or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))
+                .ok_or("expected a B<counts>/S<counts> rule string")?;
+            let count = |c: &str| match c.parse() {
+                Ok(n) if n <= 26 => Ok(n),
+                _ => Err("invalid neighbor count"),
+            };
+            if part.contains(',') {
+                part.split(',').map(count).collect()
+            } else {
+                (0..part.len()).map(|i| count(&part[i..i + 1])).collect()
+            }
+        };
+        let (birth, survival) = s
+            .trim()
+            .split_once('/')
+            .ok_or("expected a B<counts>/S<counts> rule string")?;
+        Ok(Rule3D::life_like(
+            &counts(birth, 'B')?,
+            &counts(survival, 'S')?,
+        ))
+    }
+}
+
+/// Writes Life-like rules as `B<birth counts>/S<survival counts>`, and other
+/// rules as their shape and sum table.
+impl fmt::Display for Rule3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.horizon == 1 && self.states == 2 {
+            let counts = |center: usize| -> Vec<String> {
+                (0..27)
+                    .filter(|&n| self.table[27 * center + n] == 1)
+                    .map(|n| n.to_string())
+                    .collect()
+            };
+            let (birth, survival) = (counts(0), counts(1));
+            let sep = if birth.iter().chain(&survival).any(|c| c.len() > 1) {
+                ","
+            } else {
+                ""
+            };
+            write!(f, "B{}/S{}", birth.join(sep), survival.join(sep))
+        } else {
+            write!(
+                f,
+                "3D outer-totalistic horizon={} states={} table=",
+                self.horizon, self.states
+            )?;
+            self.table.iter().try_for_each(|s| write!(f, "{}", s))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule3D;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn rule_strings_round_trip() {
+        for s in &["B4/S", "B5,6,7/S13,26", "B/S0123"] {
+            let rule: Rule3D = s.parse().unwrap();
+            assert_eq!(rule.to_string(), *s);
+        }
+        assert_eq!(
+            "B4".parse::<Rule3D>(),
+            Err("expected a B<counts>/S<counts> rule string")
+        );
+        assert_eq!("B5,27/S".parse::<Rule3D>(), Err("invalid neighbor count"));
+
+        let mut rng = StdRng::seed_from_u64(2);
+        let rule = Rule3D::random_dirichlet_with_rng(1, 3, None, &mut rng);
+        assert_eq!(rule.table().len(), 3 * (26 * 2 + 1));
+        assert!(rule.table().iter().all(|&s| s < 3));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+use super::{Neighborhood, Rule, RuleError, MAX_TABLE_SIZE};
+
+/// A cyclic cellular automaton rule: the states are arranged in a cycle,
+/// and a cell in state `s` advances to the next state `s + 1` (wrapping to 0
+/// after the last state) when at least `threshold` of its neighbors are in
+/// that next state. From random grids, the states eat each other like in a
+/// game of rock-paper-scissors, and the grid organizes into spirals.
+///
+/// The rule is defined by its parameters rather than by a table, so that it
+/// can be run on ranges whose tables wouldn't fit in memory by the
+/// [`CyclicAutomaton`](crate::automaton::CyclicAutomaton) backend. The tables
+/// of the small rules are built by [`CyclicRule::to_rule`] or
+/// [`Rule::cyclic`].
+///
+/// ```
+/// use rust_ca::rule::CyclicRule;
+///
+/// // The classic "313" rule: range 1, threshold 3, 3 states.
+/// let rule = CyclicRule::new(3, 3, 1);
+/// assert_eq!(rule.next_state(2, 3), 0);
+/// assert_eq!(rule.next_state(2, 2), 2);
+/// assert_eq!(rule.to_rule()?.table().len(), 3usize.pow(9));
+/// # Ok::<(), rust_ca::rule::RuleError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CyclicRule {
+    /// The number of states of the cycle.
+    pub states: u8,
+    /// The number of neighbors in the next state needed to advance.
+    pub threshold: usize,
+    /// The range of the neighborhood.
+    pub range: i8,
+    /// The neighborhood, central cell excluded, of the given range.
+    pub neighborhood: Neighborhood,
+}
+
+impl CyclicRule {
+    /// Makes a cyclic rule on the Moore neighborhood.
+    pub fn new(states: u8, threshold: usize, range: i8) -> CyclicRule {
+        CyclicRule::with_neighborhood(states, threshold, range, Neighborhood::Moore)
+    }
+
+    /// Makes a cyclic rule on the given neighborhood.
+    pub fn with_neighborhood(
+        states: u8,
+        threshold: usize,
+        range: i8,
+        neighborhood: Neighborhood,
+    ) -> CyclicRule {
+        CyclicRule {
+            states,
+            threshold,
+            range,
+            neighborhood,
+        }
+    }
+
+    /// Returns the state following `state` in the cycle.
+    #[inline]
+    pub fn successor(&self, state: u8) -> u8 {
+        if state + 1 >= self.states {
+            0
+        } else {
+            state + 1
+        }
+    }
+
+    /// Returns the next state of a cell in state `center` with `successors`
+    /// neighbors in the state following it.
+    #[inline]
+    pub fn next_state(&self, center: u8, successors: usize) -> u8 {
+        if successors >= self.threshold {
+            self.successor(center)
+        } else {
+            center
+        }
+    }
+
+    /// Builds the table of the rule. The table has `states^n` entries for a
+    /// neighborhood of `n` cells, so this fails for the rules whose table
+    /// would be larger than 64 MiB.
+    pub fn to_rule(&self) -> Result<Rule, RuleError> {
+        let offsets = self.neighborhood.offsets(self.range);
+        let size = (self.states as u64)
+            .checked_pow(offsets.len() as u32)
+            .filter(|&size| size <= MAX_TABLE_SIZE);
+        let size = match size {
+            Some(size) if self.range >= 1 => size,
+            _ => {
+                return Err(RuleError::RuleFormatError(
+                    "cyclic rule tables need a range of at least 1 and at most 64 MiB",
+                ))
+            }
+        };
+        let states = self.states as usize;
+        let center = offsets.iter().position(|&o| o == (0, 0)).unwrap_or(0);
+        let mut cells = vec![0; offsets.len()];
+        let table = (0..size as usize)
+            .map(|position| {
+                let mut rest = position;
+                for cell in cells.iter_mut() {
+                    *cell = (rest % states) as u8;
+                    rest /= states;
+                }
+                let successor = self.successor(cells[center]);
+                let successors = cells.iter().filter(|&&c| c == successor).count()
+                    - (cells[center] == successor) as usize;
+                self.next_state(cells[center], successors)
+            })
+            .collect();
+        Ok(Rule::with_neighborhood(
+            self.range,
+            self.states,
+            self.neighborhood,
+            table,
+        ))
+    }
+}
+
+/// Prints `cyclic R/T/S/M`, the range, threshold, number of states and
+/// neighborhood in the usual notation of the cyclic rules, `M` being `M`
+/// (Moore), `N` (von Neumann) or `H` (hexagonal).
+impl fmt::Display for CyclicRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let neighborhood = match self.neighborhood {
+            Neighborhood::Moore => 'M',
+            Neighborhood::VonNeumann => 'N',
+            Neighborhood::Hex => 'H',
+        };
+        write!(
+            f,
+            "cyclic {}/{}/{}/{}",
+            self.range, self.threshold, self.states, neighborhood
+        )
+    }
+}
+
+impl Rule {
+    /// Makes the table of the cyclic rule with `states` states on the Moore
+    /// neighborhood of horizon `range`, where a cell advances to the next
+    /// state of the cycle when at least `threshold` of its neighbors are in
+    /// that state (see [`CyclicRule`]). This fails when the table would be
+    /// larger than 64 MiB, in which case the rule can be run by a
+    /// [`CyclicAutomaton`](crate::automaton::CyclicAutomaton).
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let rule = Rule::cyclic(3, 3, 1)?;
+    /// // A cell in state 0 with three neighbors in state 1 advances.
+    /// assert_eq!(rule.explain("110/100/000")?.next, 1);
+    /// assert!(Rule::cyclic(14, 1, 1).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn cyclic(states: u8, threshold: usize, range: i8) -> Result<Rule, RuleError> {
+        CyclicRule::new(states, threshold, range).to_rule()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CyclicRule;
+    use crate::rule::{Neighborhood, Rule};
+
+    #[test]
+    fn tables_match_the_threshold() {
+        let rule = Rule::cyclic(4, 2, 1).unwrap();
+        let next = |neighborhood: &str| rule.explain(neighborhood).unwrap().next;
+        assert_eq!(next("100/000/001"), 1);
+        assert_eq!(next("100/000/000"), 0);
+        assert_eq!(next("200/010/002"), 2);
+        // The last state is followed by the first one.
+        assert_eq!(next("000/030/000"), 0);
+        assert_eq!(next("111/131/111"), 3);
+
+        let rule = CyclicRule::with_neighborhood(3, 1, 1, Neighborhood::VonNeumann);
+        assert_eq!(rule.to_string(), "cyclic 1/1/3/N");
+        let table = rule.to_rule().unwrap();
+        // The corners are outside of the neighborhood.
+        assert_eq!(table.explain("100/000/000").unwrap().next, 0);
+        assert_eq!(table.explain("010/000/000").unwrap().next, 1);
+        assert!(CyclicRule::new(3, 1, 0).to_rule().is_err());
+    }
+}
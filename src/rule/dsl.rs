@@ -0,0 +1,440 @@
+//! A small language to write rule transitions by hand.
+//!
+//! Transitions are separated by `;`. Each transition is a neighborhood between
+//! brackets with rows separated by `/`, optional count constraints, and the
+//! next state after `=>`:
+//!
+//! ```text
+//! [0 1 0 / 1 _ 1 / 0 1 0] => 1;
+//! [_ _ _ / _ 0 _ / _ _ _] #1 = 3 => 1;
+//! [_ _ _ / _ 1 _ / _ _ _] #1 >= 2, #1 <= 3 => 1
+//! ```
+//!
+//! A `_` matches any state. A constraint `#s op n` (with `op` one of `=`,
+//! `<=` and `>=`) restricts the number of wildcard cells in state `s`. When
+//! several transitions match the same neighborhood, the last one wins.
+//!
+//! The cells of a neighborhood are single digits, so the rows can be written
+//! without spaces (`[010/1_1/010]`): a rule of the Moore neighborhood with
+//! more than 10 states would have over `11^9` transitions. The wildcards are
+//! expanded to all the positions they match, and the neighborhoods whose rule
+//! tables would have more than `2^26` transitions are rejected (see
+//! [`DslError::TableTooLarge`]).
+
+use super::MAX_TABLE_SIZE;
+use std::error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Compiles hand-written transitions to a [`RuleBuilder`](crate::rule::RuleBuilder)
+/// with the corresponding pinned transitions. The number of states defaults to
+/// 2 and can be given with a `states = n;` header. Panics if the transitions
+/// are invalid (see [`RuleBuilder::from_dsl`](crate::rule::RuleBuilder::from_dsl)
+/// for a fallible version).
+///
+/// ```
+/// use rust_ca::rule;
+///
+/// let rule = rule! {
+///     states = 3;
+///     [0 1 0 / 1 _ 1 / 0 1 0] => 2;
+///     [_ _ _ / _ 2 _ / _ _ _] #2 >= 1 => 1
+/// }
+/// .build(0);
+/// // The cross pattern with a central cell in state 2.
+/// assert_eq!(rule[3 + 27 + 2 * 81 + 243 + 2187], 2);
+/// // A cell in state 2 with two neighbors in state 2.
+/// assert_eq!(rule[2 + 2 * 9 + 2 * 81], 1);
+/// assert_eq!(rule[0], 0);
+/// ```
+#[macro_export]
+macro_rules! rule {
+    (states = $states:literal; $($t:tt)*) => {
+        $crate::rule::RuleBuilder::from_dsl($states, stringify!($($t)*))
+            .unwrap_or_else(|e| panic!("{}", e))
+    };
+    ($($t:tt)*) => {
+        $crate::rule::RuleBuilder::from_dsl(2, stringify!($($t)*))
+            .unwrap_or_else(|e| panic!("{}", e))
+    };
+}
+
+/// Error type for an error that happened while parsing rule transitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DslError {
+    /// A syntax error in a transition.
+    Syntax {
+        /// The index of the transition with the error.
+        transition: usize,
+        /// A description of the error.
+        message: String,
+    },
+    /// A state larger than the number of states of the rule.
+    InvalidState {
+        /// The index of the transition with the error.
+        transition: usize,
+        /// The invalid state.
+        state: u8,
+    },
+    /// A neighborhood that isn't a square of odd side, or that doesn't have
+    /// the same size as the previous ones.
+    InvalidNeighborhood {
+        /// The index of the transition with the error.
+        transition: usize,
+    },
+    /// A neighborhood too large for the rule table to be built.
+    TableTooLarge {
+        /// The index of the transition with the error.
+        transition: usize,
+    },
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DslError::Syntax {
+                transition,
+                message,
+            } => write!(f, "syntax error in transition {}: {}", transition, message),
+            DslError::InvalidState { transition, state } => {
+                write!(f, "invalid state {} in transition {}", state, transition)
+            }
+            DslError::InvalidNeighborhood { transition } => {
+                write!(f, "invalid neighborhood size in transition {}", transition)
+            }
+            DslError::TableTooLarge { transition } => write!(
+                f,
+                "the neighborhood of transition {} gives a rule table too large",
+                transition
+            ),
+        }
+    }
+}
+
+impl error::Error for DslError {}
+
+/// A comparison in a count constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    AtMost,
+    AtLeast,
+}
+
+/// A single parsed transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Transition {
+    /// The cells of the neighborhood in row-major order, `None` for wildcards.
+    pub(super) cells: Vec<Option<u8>>,
+    /// The constraints on the number of wildcard cells in a given state.
+    counts: Vec<(u8, Comparison, usize)>,
+    /// The next state.
+    pub(super) state: u8,
+}
+
+impl Transition {
+    /// Returns the side of the square neighborhood.
+    pub(super) fn side(&self) -> usize {
+        (self.cells.len() as f64).sqrt() as usize
+    }
+
+    /// Returns the rule table positions matched by the transition, enumerating
+    /// all the states of the wildcard cells that satisfy the count
+    /// constraints.
+    pub(super) fn positions(&self, states: u8) -> Vec<usize> {
+        let k = states as usize;
+        let wildcards: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| self.cells[i].is_none())
+            .collect();
+        let base: usize = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c.unwrap_or(0) as usize * k.pow(i as u32))
+            .sum();
+        let mut positions = vec![];
+        let mut counts = vec![0; k];
+        for assignment in 0..k.pow(wildcards.len() as u32) {
+            counts.iter_mut().for_each(|c| *c = 0);
+            let mut position = base;
+            let mut rest = assignment;
+            for &i in &wildcards {
+                let s = rest % k;
+                rest /= k;
+                counts[s] += 1;
+                position += s * k.pow(i as u32);
+            }
+            let valid = self.counts.iter().all(|&(s, cmp, n)| {
+                let c = counts[s as usize];
+                match cmp {
+                    Comparison::Equal => c == n,
+                    Comparison::AtMost => c <= n,
+                    Comparison::AtLeast => c >= n,
+                }
+            });
+            if valid {
+                positions.push(position);
+            }
+        }
+        positions
+    }
+}
+
+/// A cursor over the characters of a transition, skipping whitespace.
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    transition: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+        self.chars.peek().copied()
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, DslError> {
+        Err(DslError::Syntax {
+            transition: self.transition,
+            message: message.to_string(),
+        })
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), DslError> {
+        for e in expected.chars() {
+            if self.peek() != Some(e) {
+                return self.error(&format!("expected '{}'", expected));
+            }
+            self.chars.next();
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<usize, DslError> {
+        let mut digits = String::new();
+        if self.peek().is_none() {
+            return self.error("expected a number");
+        }
+        while let Some(c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*c);
+            self.chars.next();
+        }
+        digits.parse().or_else(|_| self.error("expected a number"))
+    }
+
+    fn state(&mut self, states: u8) -> Result<u8, DslError> {
+        let n = self.number()?;
+        if n < states as usize {
+            Ok(n as u8)
+        } else {
+            Err(DslError::InvalidState {
+                transition: self.transition,
+                state: n.min(u8::MAX as usize) as u8,
+            })
+        }
+    }
+}
+
+/// Parses the cells of a square neighborhood with rows separated by `/`, up
+/// to the `end` character (consumed) or to the end of the input.
+fn parse_cells(
+    cursor: &mut Cursor,
+    states: u8,
+    end: Option<char>,
+) -> Result<Vec<Option<u8>>, DslError> {
+    let transition = cursor.transition;
+    let mut rows: Vec<usize> = vec![0];
+    let mut cells = vec![];
+    loop {
+        match cursor.peek() {
+            c if c == end => {
+                cursor.chars.next();
+                break;
+            }
+            Some('/') => {
+                cursor.chars.next();
+                rows.push(0);
+            }
+            Some('_') => {
+                cursor.chars.next();
+                cells.push(None);
+                *rows.last_mut().unwrap() += 1;
+            }
+            Some(c) if c.is_ascii_digit() => {
+                // Cells may be written without spaces, one digit per cell.
+                cursor.chars.next();
+                let s = c as u8 - b'0';
+                if s >= states {
+                    return Err(DslError::InvalidState {
+                        transition,
+                        state: s,
+                    });
+                }
+                cells.push(Some(s));
+                *rows.last_mut().unwrap() += 1;
+            }
+            _ if end.is_some() => return cursor.error("expected a cell, '/' or ']'"),
+            _ => return cursor.error("expected a cell or '/'"),
+        }
+    }
+    let side = rows.len();
+    if side.is_multiple_of(2) || rows.iter().any(|&r| r != side) {
+        return Err(DslError::InvalidNeighborhood { transition });
+    }
+    Ok(cells)
+}
+
+/// Parses a single transition.
+fn parse_transition(src: &str, transition: usize, states: u8) -> Result<Transition, DslError> {
+    let mut cursor = Cursor {
+        chars: src.chars().peekable(),
+        transition,
+    };
+    cursor.expect("[")?;
+    let cells = parse_cells(&mut cursor, states, Some(']'))?;
+    // Bounds the expansion of the wildcards by the size of the table.
+    if (states as u64)
+        .checked_pow(cells.len() as u32)
+        .is_none_or(|size| size > MAX_TABLE_SIZE)
+    {
+        return Err(DslError::TableTooLarge { transition });
+    }
+    let mut counts = vec![];
+    while cursor.peek() == Some('#') {
+        cursor.chars.next();
+        let s = cursor.state(states)?;
+        let cmp = match cursor.peek() {
+            Some('<') => {
+                cursor.expect("<=")?;
+                Comparison::AtMost
+            }
+            Some('>') => {
+                cursor.expect(">=")?;
+                Comparison::AtLeast
+            }
+            _ => {
+                cursor.expect("=")?;
+                Comparison::Equal
+            }
+        };
+        counts.push((s, cmp, cursor.number()?));
+        if cursor.peek() == Some(',') {
+            cursor.chars.next();
+        }
+    }
+    cursor.expect("=>")?;
+    let state = cursor.state(states)?;
+    if cursor.peek().is_some() {
+        return cursor.error("unexpected characters after the next state");
+    }
+    Ok(Transition {
+        cells,
+        counts,
+        state,
+    })
+}
+
+/// Parses a list of transitions separated by `;` for a rule with `states`
+/// states. All the neighborhoods must have the same size.
+pub(super) fn parse_transitions(src: &str, states: u8) -> Result<Vec<Transition>, DslError> {
+    let transitions: Vec<Transition> = src
+        .split(';')
+        .filter(|t| !t.trim().is_empty())
+        .enumerate()
+        .map(|(i, t)| parse_transition(t, i, states))
+        .collect::<Result<_, _>>()?;
+    if let Some(i) = transitions
+        .iter()
+        .position(|t| t.cells.len() != transitions[0].cells.len())
+    {
+        return Err(DslError::InvalidNeighborhood { transition: i });
+    }
+    Ok(transitions)
+}
+
+/// Parses a single neighborhood without wildcards, like `010/111/000` or
+/// `[0 1 0 / 1 1 1 / 0 0 0]`, returning its cells in row-major order.
+pub(super) fn parse_neighborhood(src: &str, states: u8) -> Result<Vec<u8>, DslError> {
+    let mut cursor = Cursor {
+        chars: src.chars().peekable(),
+        transition: 0,
+    };
+    let end = if cursor.peek() == Some('[') {
+        cursor.chars.next();
+        Some(']')
+    } else {
+        None
+    };
+    let cells = parse_cells(&mut cursor, states, end)?;
+    if cursor.peek().is_some() {
+        return cursor.error("unexpected characters after the neighborhood");
+    }
+    cells.into_iter().collect::<Option<_>>().map_or_else(
+        || cursor.error("wildcards can't be used in a neighborhood"),
+        Ok,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_neighborhood, parse_transitions, DslError};
+
+    #[test]
+    fn wildcards_and_counts_expand_to_positions() {
+        let t = parse_transitions("[_ _ _ / _ 0 _ / _ _ _] #1 = 3 => 1", 2).unwrap();
+        // 8 choose 3 neighborhoods with 3 live neighbors.
+        assert_eq!(t[0].positions(2).len(), 56);
+        let t = parse_transitions("[0 1 0 / 1 _ 1 / 0 1 0] #1 <= 0 => 1", 2).unwrap();
+        assert_eq!(t[0].positions(2), vec![2 + 8 + 32 + 128]);
+    }
+
+    #[test]
+    fn compact_rows_are_accepted() {
+        let t = parse_transitions("[010/1_1/010] => 1; [000/000/000] => 0", 2).unwrap();
+        assert_eq!(t.len(), 2);
+        assert_eq!(t[0].positions(2).len(), 2);
+    }
+
+    #[test]
+    fn neighborhoods_are_parsed_with_or_without_brackets() {
+        let cells = vec![0, 1, 0, 1, 1, 1, 0, 0, 0];
+        assert_eq!(parse_neighborhood("010/111/000", 2), Ok(cells.clone()));
+        assert_eq!(
+            parse_neighborhood(" [0 1 0 / 1 1 1 / 0 0 0] ", 2),
+            Ok(cells)
+        );
+        assert!(matches!(
+            parse_neighborhood("[010/111/000", 2),
+            Err(DslError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        assert_eq!(
+            parse_transitions("[0 1 / 1 0] => 1", 2),
+            Err(DslError::InvalidNeighborhood { transition: 0 })
+        );
+        assert_eq!(
+            parse_transitions("[0] => 1; [0 0 0 / 0 2 0 / 0 0 0] => 1", 2),
+            Err(DslError::InvalidState {
+                transition: 1,
+                state: 2
+            })
+        );
+        assert!(matches!(
+            parse_transitions("[0] -> 1", 2),
+            Err(DslError::Syntax { transition: 0, .. })
+        ));
+        // 3^25 transitions, which aren't enumerated.
+        let row = "_ _ _ _ _ /";
+        let src = format!("[{}{}{}{} _ _ _ _ _] => 1", row, row, row, row);
+        assert_eq!(
+            parse_transitions(&format!("[_] => 0; {}", src), 3),
+            Err(DslError::TableTooLarge { transition: 1 })
+        );
+        assert!(parse_transitions(&src, 2).is_ok());
+    }
+}
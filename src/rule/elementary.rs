@@ -0,0 +1,134 @@
+use rand::Rng;
+
+use super::{dirichlet_table, uniform_table};
+
+/// A rule of a one-dimensional CA, mapping the `2 horizon + 1` cells of a
+/// neighborhood in a row to the next state of its central cell.
+///
+/// The table is indexed like the Wolfram codes: the neighborhood is read as a
+/// number in base `states`, the leftmost cell being the most significant
+/// digit. The elementary rules (2 states, horizon 1) are made from their
+/// Wolfram code with [`Rule1D::elementary`].
+///
+/// ```
+/// use rust_ca::rule::Rule1D;
+///
+/// // Rule 90 is the XOR of the two neighbors.
+/// let rule90 = Rule1D::elementary(90);
+/// assert_eq!(rule90.table(), &[0, 1, 0, 1, 1, 0, 1, 0]);
+/// assert_eq!(rule90.next_state(&[1, 1, 0]), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rule1D {
+    /// The number of cells on each side of the central cell in the
+    /// neighborhood.
+    pub horizon: i8,
+    /// The number of cell states the rule expects.
+    pub states: u8,
+    table: Vec<u8>,
+}
+
+impl Rule1D {
+    /// Construct a new rule from its number of states, horizon and table.
+    pub fn new(horizon: i8, states: u8, table: Vec<u8>) -> Rule1D {
+        if table.len() as u64 != Rule1D::rule_size(horizon, states)
+            || table.iter().any(|&s| s >= states)
+        {
+            panic!(
+                "Incorrect 1D rule for neighborhood size {} and number of states {}",
+                horizon, states
+            )
+        }
+        Rule1D {
+            horizon,
+            states,
+            table,
+        }
+    }
+
+    /// Makes the elementary rule (2 states, horizon 1) with the given Wolfram
+    /// code.
+    pub fn elementary(code: u8) -> Rule1D {
+        Rule1D::new(1, 2, (0..8).map(|i| (code >> i) & 1).collect())
+    }
+
+    /// Returns a reference to the rule table.
+    pub fn table(&self) -> &[u8] {
+        &self.table
+    }
+
+    /// Returns the number of neighborhoods for a (horizon, states) pair.
+    fn rule_size(horizon: i8, states: u8) -> u64 {
+        (states as u64).pow(2 * horizon as u32 + 1)
+    }
+
+    /// Create a random rule with uniformly sampled transitions.
+    pub fn random(horizon: i8, states: u8) -> Rule1D {
+        Rule1D::random_with_rng(horizon, states, &mut rand::thread_rng())
+    }
+
+    /// Create a random rule with uniformly sampled transitions, drawn from
+    /// the given random number generator.
+    pub fn random_with_rng<R: Rng + ?Sized>(horizon: i8, states: u8, rng: &mut R) -> Rule1D {
+        let table = uniform_table(Rule1D::rule_size(horizon, states), states, rng);
+        Rule1D::new(horizon, states, table)
+    }
+
+    /// Create a random rule with transitions sampled according to a Dirichlet
+    /// distribution with parameter `alpha`, like
+    /// [`Rule::random_dirichlet`](super::Rule::random_dirichlet).
+    pub fn random_dirichlet_with_rng<R: Rng + ?Sized>(
+        horizon: i8,
+        states: u8,
+        alpha: Option<f64>,
+        rng: &mut R,
+    ) -> Rule1D {
+        let table = dirichlet_table(Rule1D::rule_size(horizon, states), states, alpha, rng);
+        Rule1D::new(horizon, states, table)
+    }
+
+    /// Returns the next state of the central cell of a neighborhood of
+    /// `2 horizon + 1` cells, given from left to right.
+    #[inline]
+    pub fn next_state(&self, neighborhood: &[u8]) -> u8 {
+        let index = neighborhood
+            .iter()
+            .fold(0, |acc, &c| acc * self.states as usize + c as usize);
+        self.table[index]
+    }
+
+    /// Applies the rule once on a row whose ends wrap around.
+    pub fn apply(&self, row: &[u8]) -> Vec<u8> {
+        let (h, n) = (self.horizon as isize, row.len() as isize);
+        (0..n)
+            .map(|i| {
+                let neighborhood: Vec<u8> = (i - h..=i + h)
+                    .map(|j| row[j.rem_euclid(n) as usize])
+                    .collect();
+                self.next_state(&neighborhood)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule1D;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn elementary_rules_follow_the_wolfram_codes() {
+        // Rule 110 from a single cell, the pattern growing to the left.
+        let rule110 = Rule1D::elementary(110);
+        let row = rule110.apply(&[0, 0, 0, 0, 1]);
+        assert_eq!(row, vec![0, 0, 0, 1, 1]);
+        assert_eq!(rule110.apply(&row), vec![0, 0, 1, 1, 1]);
+        assert_eq!(Rule1D::elementary(0).apply(&row), vec![0; 5]);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let rule = Rule1D::random_dirichlet_with_rng(2, 3, None, &mut rng);
+        assert_eq!(rule.table().len(), 243);
+        assert!(rule.table().iter().all(|&s| s < 3));
+    }
+}
@@ -0,0 +1,85 @@
+use super::{Neighborhood, Rule, RuleError, MAX_TABLE_SIZE};
+
+impl Rule {
+    /// Makes the Greenberg–Hastings rule of excitable media with `states`
+    /// states on the von Neumann neighborhood of horizon 1. State 0 is the
+    /// resting state, state 1 the excited state and the others the
+    /// refractory states: a resting cell gets excited when at least
+    /// `threshold` of its 4 neighbors are excited, and the other cells
+    /// advance to the next state, the last refractory state going back to
+    /// rest. Waves of excitation travel across the resting cells, and the
+    /// broken wave fronts curl into spirals (see [`Pattern::broken_wave`]).
+    /// The refractory states are best rendered with
+    /// [`Palette::excitable`](crate::output::Palette::excitable).
+    ///
+    /// The table has `states^5` entries, so this fails for the rules with
+    /// less than 3 or more than 36 states.
+    ///
+    /// [`Pattern::broken_wave`]: crate::pattern::Pattern::broken_wave
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// let rule = Rule::greenberg_hastings(5, 1)?;
+    /// assert_eq!(rule.explain("010/000/000")?.next, 1);
+    /// // The corners aren't neighbors.
+    /// assert_eq!(rule.explain("100/000/000")?.next, 0);
+    /// assert_eq!(rule.explain("010/020/000")?.next, 3);
+    /// assert_eq!(rule.explain("000/040/000")?.next, 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn greenberg_hastings(states: u8, threshold: usize) -> Result<Rule, RuleError> {
+        let offsets = Neighborhood::VonNeumann.offsets(1);
+        let size = (states as u64)
+            .checked_pow(offsets.len() as u32)
+            .filter(|&size| states >= 3 && size <= MAX_TABLE_SIZE)
+            .ok_or(RuleError::RuleFormatError(
+                "Greenberg-Hastings rule tables need 3 to 36 states",
+            ))?;
+        let states = states as usize;
+        let center = offsets.iter().position(|&o| o == (0, 0)).unwrap_or(0);
+        let mut cells = vec![0; offsets.len()];
+        let table = (0..size as usize)
+            .map(|position| {
+                let mut rest = position;
+                for cell in cells.iter_mut() {
+                    *cell = rest % states;
+                    rest /= states;
+                }
+                let next = match cells[center] {
+                    0 => {
+                        let excited = cells.iter().filter(|&&c| c == 1).count();
+                        (excited >= threshold) as usize
+                    }
+                    state => (state + 1) % states,
+                };
+                next as u8
+            })
+            .collect();
+        Ok(Rule::with_neighborhood(
+            1,
+            states as u8,
+            Neighborhood::VonNeumann,
+            table,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rule::Rule;
+
+    #[test]
+    fn resting_cells_need_enough_excited_neighbors() {
+        let rule = Rule::greenberg_hastings(4, 2).unwrap();
+        let next = |neighborhood: &str| rule.explain(neighborhood).unwrap().next;
+        assert_eq!(next("010/000/000"), 0);
+        assert_eq!(next("010/100/000"), 1);
+        // Only the neighbors in the excited state count.
+        assert_eq!(next("020/200/000"), 0);
+        assert_eq!(next("010/011/010"), 2);
+        assert_eq!(next("000/030/000"), 0);
+        assert!(Rule::greenberg_hastings(2, 1).is_err());
+        assert!(Rule::greenberg_hastings(37, 1).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use super::dsl::{parse_neighborhood, DslError};
+use super::Rule;
+
+/// How a totalistic-like rule reads a neighborhood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Totalism {
+    /// The next state only depends on the sum of the states of the
+    /// neighborhood, central cell included.
+    Totalistic {
+        /// The sum of the states of the neighborhood.
+        sum: usize,
+    },
+    /// The next state depends on the central cell and on the sum of the
+    /// states of the other cells.
+    OuterTotalistic {
+        /// The state of the central cell.
+        center: u8,
+        /// The sum of the states of the other cells.
+        sum: usize,
+    },
+}
+
+/// The transition of a rule for a single neighborhood, returned by
+/// [`Rule::explain`]. Its [`Display`](fmt::Display) implementation prints a
+/// short report for teaching and debugging.
+///
+/// ```
+/// use rust_ca::rule::{Rule, Totalism};
+///
+/// let explanation = Rule::gol().explain("010/111/000")?;
+/// assert_eq!(explanation.next, 1);
+/// assert_eq!(explanation.counts, vec![5, 3]);
+/// assert_eq!(
+///     explanation.totalism,
+///     Some(Totalism::OuterTotalistic { center: 1, sum: 3 })
+/// );
+/// # Ok::<(), rust_ca::rule::DslError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The cells of the square neighborhood, in row-major order.
+    pub cells: Vec<u8>,
+    /// The position of the neighborhood in the rule table.
+    pub position: usize,
+    /// The next state of the central cell.
+    pub next: u8,
+    /// The number of neighbors (central cell excluded) in each state.
+    pub counts: Vec<usize>,
+    /// The sums the next state depends on, if the rule is totalistic or
+    /// outer totalistic.
+    pub totalism: Option<Totalism>,
+}
+
+impl Rule {
+    /// Explains the transition of the rule for a neighborhood written like
+    /// the neighborhoods of the [transition language](crate::rule!), e.g.
+    /// `010/111/000` or `[0 1 0 / 1 1 1 / 0 0 0]`, without wildcards. The
+    /// neighborhood is the square around the central cell; on the von
+    /// Neumann and hexagonal neighborhoods, the cells outside of the
+    /// neighborhood of the rule are ignored.
+    pub fn explain(&self, neighborhood: &str) -> Result<Explanation, DslError> {
+        let cells = parse_neighborhood(neighborhood, self.states)?;
+        let h = self.horizon as isize;
+        let side = (2 * h + 1) as usize;
+        if cells.len() != side * side {
+            return Err(DslError::InvalidNeighborhood { transition: 0 });
+        }
+        let states = self.states as usize;
+        let (mut position, mut power) = (0, 1);
+        let mut counts = vec![0; states];
+        for (a, b) in self.neighborhood().offsets(self.horizon) {
+            let cell = cells[(a + h) as usize * side + (b + h) as usize] as usize;
+            position += power * cell;
+            power *= states;
+            if (a, b) != (0, 0) {
+                counts[cell] += 1;
+            }
+        }
+        let center = cells[cells.len() / 2];
+        let sum: usize = counts.iter().enumerate().map(|(s, n)| s * n).sum();
+        let totalism = if self.is_totalistic() {
+            Some(Totalism::Totalistic {
+                sum: sum + center as usize,
+            })
+        } else if self.is_outer_totalistic() {
+            Some(Totalism::OuterTotalistic { center, sum })
+        } else {
+            None
+        };
+        Ok(Explanation {
+            cells,
+            position,
+            next: self[position],
+            counts,
+            totalism,
+        })
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = (self.cells.len() as f64).sqrt() as usize;
+        for row in self.cells.chunks(side) {
+            let row: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        writeln!(
+            f,
+            "table position {}: next state {}",
+            self.position, self.next
+        )?;
+        let counts: Vec<String> = (0..self.counts.len())
+            .rev()
+            .filter(|&s| self.counts[s] > 0)
+            .map(|s| format!("{} in state {}", self.counts[s], s))
+            .collect();
+        writeln!(
+            f,
+            "central cell in state {}, neighbors: {}",
+            self.cells[self.cells.len() / 2],
+            counts.join(", ")
+        )?;
+        match self.totalism {
+            Some(Totalism::Totalistic { sum }) => {
+                writeln!(
+                    f,
+                    "totalistic: a neighborhood sum of {} gives {}",
+                    sum, self.next
+                )
+            }
+            Some(Totalism::OuterTotalistic { center, sum }) => writeln!(
+                f,
+                "outer totalistic: a cell in state {} with a neighbor sum of {} gives {}",
+                center, sum, self.next
+            ),
+            None => writeln!(f, "not totalistic: the whole neighborhood matters"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Totalism;
+    use crate::rule::{DslError, Neighborhood, Rule};
+
+    #[test]
+    fn neighborhoods_are_explained() {
+        // A dead cell with two live neighbors stays dead.
+        let e = Rule::gol().explain("[1 0 0 / 0 0 0 / 0 0 1]").unwrap();
+        assert_eq!((e.position, e.next), (1 + 256, 0));
+        assert_eq!(
+            e.totalism,
+            Some(Totalism::OuterTotalistic { center: 0, sum: 2 })
+        );
+        assert!(e.to_string().contains("neighbor sum of 2 gives 0"));
+
+        let rule = Rule::new(1, 3, vec![2; 3usize.pow(9)]);
+        let e = rule.explain("000/020/001").unwrap();
+        assert_eq!(e.counts, vec![7, 1, 0]);
+        assert_eq!(e.totalism, Some(Totalism::Totalistic { sum: 3 }));
+
+        // The corners are outside of the von Neumann neighborhood.
+        let rule = Rule::with_neighborhood(
+            1,
+            2,
+            Neighborhood::VonNeumann,
+            (0..32).map(|i| (i % 2) as u8).collect(),
+        );
+        let e = rule.explain("111/010/000").unwrap();
+        assert_eq!((e.position, e.counts.clone()), (1 + 4, vec![3, 1]));
+        assert_eq!(e.totalism, None);
+    }
+
+    #[test]
+    fn invalid_neighborhoods_are_rejected() {
+        let gol = Rule::gol();
+        assert_eq!(
+            gol.explain(&["00000"; 5].join("/")),
+            Err(DslError::InvalidNeighborhood { transition: 0 })
+        );
+        assert_eq!(
+            gol.explain("010/121/000"),
+            Err(DslError::InvalidState {
+                transition: 0,
+                state: 2
+            })
+        );
+        assert!(gol.explain("010/1_1/000").is_err());
+        assert!(gol.explain("010/111/000] => 1").is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! The payload of rule files (before compression).
+//!
+//! The payload is the rule table written as ASCII digits, one per position,
+//! after a header line `#key=value key=value ...` describing how the table is
+//! stored. The `crc32` key records the CRC-32 checksum of the table digits,
+//! verified when reading. Legacy payloads without header contain the full
+//! table, and the horizon and number of states are inferred from its length.
+//!
+//! Totalistic rules (the next state only depends on the sum of the states in
+//! the neighborhood) and outer totalistic rules (the next state depends on the
+//! central cell and on the sum of the states of the other cells) are stored
+//! as their compact sum tables, with the `encoding` key set to `totalistic` or
+//! `outer-totalistic`.
+//!
+//! Rules on other neighborhoods than the Moore one record it with the
+//! `neighborhood` key (`von-neumann` or `hex`), and their table has one digit
+//! per cell of the neighborhood.
+
+#[cfg(feature = "rule-io")]
+use std::collections::HashMap;
+#[cfg(feature = "rule-io")]
+use std::convert::TryFrom;
+#[cfg(feature = "rule-io")]
+use std::io::Read;
+
+use super::Rule;
+#[cfg(feature = "rule-io")]
+use super::{Neighborhood, RuleError, MAX_TABLE_SIZE};
+
+/// The number of cells in the neighborhood of a rule with a given horizon.
+pub(super) fn n_cells(horizon: i8) -> usize {
+    let side = (2 * horizon + 1) as usize;
+    side * side
+}
+
+/// Returns the central cell state and the sum of the states of the other
+/// cells of a position.
+fn center_and_sum(mut position: usize, states: usize, n_cells: usize) -> (usize, usize) {
+    let (mut center, mut sum) = (0, 0);
+    for i in 0..n_cells {
+        let s = position % states;
+        position /= states;
+        if i == n_cells / 2 {
+            center = s;
+        } else {
+            sum += s;
+        }
+    }
+    (center, sum)
+}
+
+/// Returns the index of a position in the compact table of a totalistic
+/// (`outer` false) or outer totalistic (`outer` true) rule.
+pub(super) fn compact_index(position: usize, states: usize, n_cells: usize, outer: bool) -> usize {
+    let (center, sum) = center_and_sum(position, states, n_cells);
+    if outer {
+        center * ((n_cells - 1) * (states - 1) + 1) + sum
+    } else {
+        center + sum
+    }
+}
+
+/// Returns the size of the compact table of a totalistic (`outer` false) or
+/// outer totalistic (`outer` true) rule.
+pub(super) fn compact_size(states: usize, n_cells: usize, outer: bool) -> usize {
+    if outer {
+        states * ((n_cells - 1) * (states - 1) + 1)
+    } else {
+        n_cells * (states - 1) + 1
+    }
+}
+
+/// Returns the compact table of the rule if it is totalistic (`outer` false)
+/// or outer totalistic (`outer` true).
+pub(super) fn compact_table(rule: &Rule, outer: bool) -> Option<Vec<u8>> {
+    let states = rule.states as usize;
+    let n_cells = rule.neighborhood.n_cells(rule.horizon);
+    let mut compact: Vec<Option<u8>> = vec![None; compact_size(states, n_cells, outer)];
+    for (position, &next) in rule.table.iter().enumerate() {
+        let entry = &mut compact[compact_index(position, states, n_cells, outer)];
+        match *entry {
+            Some(s) if s != next => return None,
+            _ => *entry = Some(next),
+        }
+    }
+    Some(compact.iter().map(|s| s.unwrap_or(0)).collect())
+}
+
+#[cfg(feature = "rule-io")]
+fn to_digits(table: &[u8]) -> Vec<u8> {
+    table.iter().map(|&s| s + b'0').collect()
+}
+
+/// Serializes a rule table, using the compact representation of totalistic
+/// and outer totalistic rules when possible.
+#[cfg(feature = "rule-io")]
+pub(super) fn encode(rule: &Rule) -> Vec<u8> {
+    let (encoding, digits) = [(false, "totalistic"), (true, "outer-totalistic")]
+        .iter()
+        .find_map(|&(outer, encoding)| {
+            compact_table(rule, outer).map(|compact| (encoding, to_digits(&compact)))
+        })
+        .unwrap_or_else(|| ("full", to_digits(&rule.table)));
+    let neighborhood = match rule.neighborhood {
+        Neighborhood::Moore => String::new(),
+        neighborhood => format!(" neighborhood={}", neighborhood),
+    };
+    let mut payload = format!(
+        "#encoding={} horizon={} states={}{} crc32={:08x}\n",
+        encoding,
+        rule.horizon,
+        rule.states,
+        neighborhood,
+        crc32fast::hash(&digits)
+    )
+    .into_bytes();
+    payload.extend(digits);
+    payload
+}
+
+/// The largest header line of the payloads read.
+#[cfg(feature = "rule-io")]
+const MAX_HEADER_SIZE: u64 = 1 << 10;
+
+/// Reads a payload from a decompressing reader. The payload is read up to
+/// the size of the largest table [`decode`] accepts and its header, so that
+/// small compressed inputs can't inflate to huge payloads.
+#[cfg(feature = "rule-io")]
+pub(super) fn read_payload<R: Read>(reader: R) -> Result<Vec<u8>, RuleError> {
+    let limit = MAX_TABLE_SIZE + MAX_HEADER_SIZE;
+    let mut payload = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut payload)?;
+    if payload.len() as u64 > limit {
+        return Err(RuleError::RuleFormatError(
+            "the payload is larger than the largest rule table",
+        ));
+    }
+    Ok(payload)
+}
+
+/// Infers the number of states and horizon of a rule from its table size.
+#[cfg(feature = "rule-io")]
+fn infer_shape(len: usize) -> Option<(u8, i8)> {
+    (2..30).find_map(|i| {
+        let d = (len as f64).ln() / (i as f64).ln();
+        if (d - d.floor()).abs() < f64::EPSILON
+            && (d.sqrt() - d.sqrt().floor()).abs() < f64::EPSILON
+        {
+            Some((i, ((d.sqrt() - 1.) / 2.) as i8))
+        } else {
+            None
+        }
+    })
+}
+
+/// Deserializes a rule table written by [`encode`] (or a legacy payload
+/// containing only the full table).
+#[cfg(feature = "rule-io")]
+pub(super) fn decode(payload: &[u8]) -> Result<Rule, RuleError> {
+    let (header, digits) = if payload.first() == Some(&b'#') {
+        let end = payload
+            .iter()
+            .position(|&c| c == b'\n')
+            .ok_or(RuleError::RuleFormatError("unterminated header"))?;
+        let header = std::str::from_utf8(&payload[1..end])
+            .map_err(|_| RuleError::RuleFormatError("invalid header"))?;
+        let fields: HashMap<&str, &str> = header
+            .split_whitespace()
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        (fields, &payload[end + 1..])
+    } else {
+        (HashMap::new(), payload)
+    };
+    if let Some(crc) = header.get("crc32") {
+        let expected = u32::from_str_radix(crc, 16)
+            .map_err(|_| RuleError::RuleFormatError("invalid checksum"))?;
+        let found = crc32fast::hash(digits);
+        if found != expected {
+            return Err(RuleError::RuleChecksumError { expected, found });
+        }
+    }
+    let table: Vec<u8> = digits.iter().map(|&c| c.wrapping_sub(b'0')).collect();
+
+    let field = |key: &str| -> Result<u8, RuleError> {
+        header
+            .get(key)
+            .and_then(|v| v.parse().ok())
+            .ok_or(RuleError::RuleFormatError(
+                "missing or invalid header field",
+            ))
+    };
+    let outer = match header.get("encoding").copied().unwrap_or("full") {
+        "full" => None,
+        "totalistic" => Some(false),
+        "outer-totalistic" => Some(true),
+        _ => return Err(RuleError::RuleFormatError("unknown rule encoding")),
+    };
+    let (horizon, states) = match (header.get("horizon"), header.get("states")) {
+        (None, None) => {
+            let (states, horizon) = infer_shape(table.len())
+                .ok_or(RuleError::RuleFormatError("invalid rule table size"))?;
+            (horizon, states)
+        }
+        _ => {
+            let horizon = i8::try_from(field("horizon")?)
+                .map_err(|_| RuleError::RuleFormatError("invalid horizon"))?;
+            (horizon, field("states")?)
+        }
+    };
+    let neighborhood = match header.get("neighborhood") {
+        Some(name) => name
+            .parse()
+            .map_err(|_| RuleError::RuleFormatError("unknown neighborhood"))?,
+        None => Neighborhood::Moore,
+    };
+    // The header is checked before the compact tables are expanded.
+    let size = Rule::checked_table_size(horizon, states, neighborhood)?;
+    if table.iter().any(|&s| s >= states) {
+        return Err(RuleError::RuleFormatError("invalid state in rule table"));
+    }
+    let table = match outer {
+        None => table,
+        Some(outer) => {
+            let n_cells = neighborhood.n_cells(horizon);
+            if table.len() != compact_size(states as usize, n_cells, outer) {
+                return Err(RuleError::RuleFormatError(
+                    "invalid compact rule table size",
+                ));
+            }
+            (0..size as usize)
+                .map(|position| table[compact_index(position, states as usize, n_cells, outer)])
+                .collect()
+        }
+    };
+    if table.len() as u64 != size {
+        return Err(RuleError::RuleFormatError("invalid rule table size"));
+    }
+    Ok(Rule::with_neighborhood(
+        horizon,
+        states,
+        neighborhood,
+        table,
+    ))
+}
+
+#[cfg(all(test, feature = "rule-io"))]
+mod tests {
+    use super::{decode, encode};
+    use crate::rule::{Neighborhood, Rule, RuleError, SamplingMode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn gol_is_stored_compactly() {
+        let payload = encode(&Rule::gol());
+        assert!(payload.starts_with(b"#encoding=outer-totalistic"));
+        assert!(payload.len() < 100);
+        assert_eq!(decode(&payload).unwrap().table(), Rule::gol().table());
+    }
+
+    #[test]
+    fn totalistic_rules_roundtrip() {
+        // The next state is the sum of the states modulo 5.
+        let table = (0..5usize.pow(9))
+            .map(|p| ((0..9).map(|i| p / 5usize.pow(i) % 5).sum::<usize>() % 5) as u8)
+            .collect();
+        let rule = Rule::new(1, 5, table);
+        let payload = encode(&rule);
+        assert!(payload.starts_with(b"#encoding=totalistic"));
+        assert_eq!(decode(&payload).unwrap().table(), rule.table());
+    }
+
+    #[test]
+    fn neighborhoods_are_recorded() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for &nb in &[Neighborhood::VonNeumann, Neighborhood::Hex] {
+            let rule = Rule::random_with_neighborhood(1, 3, nb, SamplingMode::Uniform, &mut rng);
+            let decoded = decode(&encode(&rule)).unwrap();
+            assert_eq!(decoded.neighborhood(), nb);
+            assert_eq!(decoded.id(), rule.id());
+            // The cells with exactly two live cells in their neighborhood live.
+            let table = (0..1u32 << nb.n_cells(1))
+                .map(|p| (p.count_ones() == 2) as u8)
+                .collect();
+            let sums = Rule::with_neighborhood(1, 2, nb, table);
+            let payload = encode(&sums);
+            assert!(payload.starts_with(b"#encoding=totalistic"));
+            assert_eq!(decode(&payload).unwrap().table(), sums.table());
+        }
+        assert!(decode(b"#encoding=full horizon=1 states=2 neighborhood=tri\n00").is_err());
+    }
+
+    #[test]
+    fn legacy_payloads_are_decoded() {
+        let rule = Rule::random(1, 3);
+        let legacy: Vec<u8> = rule.table().iter().map(|s| s + b'0').collect();
+        assert_eq!(decode(&legacy).unwrap().table(), rule.table());
+        assert!(decode(b"#encoding=totalistic horizon=1 states=2\n0101").is_err());
+    }
+
+    #[test]
+    fn oversized_headers_are_rejected() {
+        for header in [
+            "#encoding=full horizon=10 states=2",
+            "#encoding=outer-totalistic horizon=10 states=2",
+            "#encoding=totalistic horizon=200 states=2",
+            "#encoding=full horizon=1 states=0",
+        ] {
+            let payload = format!("{}\n0101", header);
+            assert!(matches!(
+                decode(payload.as_bytes()),
+                Err(RuleError::RuleFormatError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn corrupted_payloads_are_detected() {
+        let rule = Rule::random(1, 2);
+        let mut payload = encode(&rule);
+        assert!(payload.starts_with(b"#encoding=full"));
+        let last = payload.len() - 1;
+        payload[last] = if payload[last] == b'0' { b'1' } else { b'0' };
+        assert!(matches!(
+            decode(&payload),
+            Err(RuleError::RuleChecksumError { .. })
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::convert::TryInto;
+use std::hash::Hasher;
+
+/// SipHash-1-3 with null keys, the algorithm of the
+/// [`DefaultHasher`](std::collections::hash_map::DefaultHasher) the rule ids
+/// were first computed with. The standard library doesn't guarantee that its
+/// hasher never changes, so it is pinned here to keep the ids of the rules,
+/// found in file names and catalogs, stable across Rust versions.
+#[derive(Debug, Clone)]
+pub(super) struct StableHasher {
+    v: [u64; 4],
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+impl StableHasher {
+    pub(super) fn new() -> StableHasher {
+        StableHasher {
+            v: [
+                0x736f6d6570736575,
+                0x646f72616e646f6d,
+                0x6c7967656e657261,
+                0x7465646279746573,
+            ],
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        let [v0, v1, v2, v3] = &mut self.v;
+        *v0 = v0.wrapping_add(*v1);
+        *v1 = v1.rotate_left(13) ^ *v0;
+        *v0 = v0.rotate_left(32);
+        *v2 = v2.wrapping_add(*v3);
+        *v3 = v3.rotate_left(16) ^ *v2;
+        *v0 = v0.wrapping_add(*v3);
+        *v3 = v3.rotate_left(21) ^ *v0;
+        *v2 = v2.wrapping_add(*v1);
+        *v1 = v1.rotate_left(17) ^ *v2;
+        *v2 = v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, m: u64) {
+        self.v[3] ^= m;
+        self.round();
+        self.v[0] ^= m;
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len();
+        while self.ntail > 0 && !bytes.is_empty() {
+            self.tail |= (bytes[0] as u64) << (8 * self.ntail);
+            self.ntail = (self.ntail + 1) % 8;
+            bytes = &bytes[1..];
+            if self.ntail == 0 {
+                let m = std::mem::take(&mut self.tail);
+                self.compress(m);
+            }
+        }
+        if self.ntail > 0 {
+            return;
+        }
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.compress(u64::from_le_bytes(word.try_into().expect("8 bytes")));
+        }
+        for (k, &byte) in words.remainder().iter().enumerate() {
+            self.tail |= (byte as u64) << (8 * k);
+        }
+        self.ntail = words.remainder().len();
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = self.clone();
+        let b = ((self.length as u64 & 0xff) << 56) | self.tail;
+        state.compress(b);
+        state.v[2] ^= 0xff;
+        for _ in 0..3 {
+            state.round();
+        }
+        state.v.iter().fold(0, |acc, v| acc ^ v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StableHasher;
+    use crate::rule::{Neighborhood, Rule};
+    use std::hash::Hasher;
+
+    #[test]
+    fn ids_are_pinned() {
+        // The ids the rules had with the hasher of the standard library.
+        assert_eq!(Rule::gol().id(), 5664888421622951056);
+        let rule = Rule::greenberg_hastings(3, 1).unwrap();
+        assert_eq!(rule.neighborhood(), Neighborhood::VonNeumann);
+        assert_eq!(rule.id(), 16552103865383427742);
+        // The bytes can be written in any pieces.
+        let bytes: Vec<u8> = (0..50).collect();
+        let mut whole = StableHasher::new();
+        whole.write(&bytes);
+        let mut pieces = StableHasher::new();
+        for piece in [&bytes[..3], &bytes[3..4], &bytes[4..21], &bytes[21..]] {
+            pieces.write(piece);
+        }
+        assert_eq!(whole.finish(), pieces.finish());
+    }
+}
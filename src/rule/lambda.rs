@@ -0,0 +1,136 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use super::Rule;
+
+/// A family of rules indexed by Langton's lambda parameter, the fraction of
+/// the transitions of a rule table leading to a state other than the
+/// quiescent state 0 (see [`Rule::lambda`]).
+///
+/// The family is drawn once from a seed with the "table walk-through" of
+/// Langton: the transitions are visited in a random order, each with a
+/// random non-quiescent state, and the rule of parameter lambda sets the
+/// first `lambda` fraction of them to their state, the others going to 0.
+/// The rules of a family differ only by the transitions added or removed
+/// between their parameters, so that a sweep of lambda shows the transition
+/// from frozen to chaotic dynamics of a single rule rather than of
+/// unrelated samples. The transition of the all-quiescent neighborhood is
+/// always 0, so that an empty grid stays empty.
+///
+/// ```
+/// use rust_ca::rule::LambdaFamily;
+///
+/// let family = LambdaFamily::new(1, 4, 7);
+/// let (low, high) = (family.rule(0.2), family.rule(0.6));
+/// assert!((low.lambda() - 0.2).abs() < 0.01);
+/// // The transitions of the lower rule are kept by the higher one.
+/// assert!(low
+///     .table()
+///     .iter()
+///     .zip(high.table())
+///     .all(|(&a, &b)| a == 0 || a == b));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LambdaFamily {
+    horizon: i8,
+    states: u8,
+    order: Vec<usize>,
+    targets: Vec<u8>,
+}
+
+impl LambdaFamily {
+    /// Draws the family of the rules with the given horizon and number of
+    /// states (at least 2) from the seed.
+    pub fn new(horizon: i8, states: u8, seed: u64) -> LambdaFamily {
+        LambdaFamily::with_rng(horizon, states, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Draws the family from the given random number generator.
+    pub fn with_rng<R: Rng + ?Sized>(horizon: i8, states: u8, rng: &mut R) -> LambdaFamily {
+        assert!(states >= 2, "A lambda family needs at least 2 states");
+        let size = Rule::rule_size(horizon, states) as usize;
+        let mut order: Vec<usize> = (1..size).collect();
+        order.shuffle(rng);
+        let targets = order.iter().map(|_| rng.gen_range(1..states)).collect();
+        LambdaFamily {
+            horizon,
+            states,
+            order,
+            targets,
+        }
+    }
+
+    /// Returns the largest parameter of the family, reached when all the
+    /// transitions but the one of the all-quiescent neighborhood are
+    /// non-quiescent.
+    pub fn max_lambda(&self) -> f64 {
+        self.order.len() as f64 / (self.order.len() + 1) as f64
+    }
+
+    /// Returns the rule of the family with the parameter closest to
+    /// `lambda`, which is clamped to `0..=max_lambda()`.
+    pub fn rule(&self, lambda: f64) -> Rule {
+        let size = self.order.len() + 1;
+        let active = ((lambda.max(0.) * size as f64).round() as usize).min(self.order.len());
+        let mut table = vec![0; size];
+        for (&position, &state) in self.order.iter().zip(&self.targets).take(active) {
+            table[position] = state;
+        }
+        Rule::new(self.horizon, self.states, table)
+    }
+
+    /// Returns the rules of `points` parameters evenly spaced from 0 to
+    /// [`LambdaFamily::max_lambda`], with their parameters.
+    pub fn sweep(&self, points: usize) -> Vec<(f64, Rule)> {
+        (0..points)
+            .map(|i| {
+                let lambda = self.max_lambda() * i as f64 / (points.max(2) - 1) as f64;
+                let rule = self.rule(lambda);
+                (rule.lambda(), rule)
+            })
+            .collect()
+    }
+}
+
+impl Rule {
+    /// Returns Langton's lambda parameter of the rule: the fraction of its
+    /// transitions leading to a state other than the quiescent state 0.
+    /// Rules of low lambda freeze, and rules of high lambda are chaotic; the
+    /// complex dynamics lie in between (see [`LambdaFamily`]).
+    ///
+    /// ```
+    /// use rust_ca::rule::Rule;
+    ///
+    /// // In the game of life, the 56 dead cells with 3 live neighbors are
+    /// // born and the 28 + 56 live cells with 2 or 3 live neighbors survive.
+    /// assert_eq!(Rule::gol().lambda(), 140. / 512.);
+    /// ```
+    pub fn lambda(&self) -> f64 {
+        let active = self.table().iter().filter(|&&s| s != 0).count();
+        active as f64 / self.table().len().max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LambdaFamily;
+
+    #[test]
+    fn sweeps_span_the_parameters() {
+        let family = LambdaFamily::new(1, 2, 3);
+        let sweep = family.sweep(5);
+        assert_eq!(sweep.len(), 5);
+        assert_eq!(sweep[0].0, 0.);
+        assert_eq!(sweep[4].0, family.max_lambda());
+        assert_eq!(sweep[4].0, 511. / 512.);
+        assert!(sweep.windows(2).all(|w| w[0].0 < w[1].0));
+        // The all-quiescent neighborhood stays quiescent.
+        assert!(sweep.iter().all(|(_, rule)| rule.table()[0] == 0));
+        // The same seed gives the same family.
+        let again = LambdaFamily::new(1, 2, 3).rule(0.5);
+        assert_eq!(family.rule(0.5).table(), again.table());
+        assert_ne!(LambdaFamily::new(1, 2, 4).rule(0.5).table(), again.table());
+        assert_eq!(family.rule(2.).lambda(), family.max_lambda());
+    }
+}
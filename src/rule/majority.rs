@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::{Neighborhood, Rule, RuleError, MAX_TABLE_SIZE};
+
+/// How a majority rule chooses between the states shared by the most cells
+/// of a neighborhood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    /// One of the tied states, drawn once per neighborhood configuration
+    /// when the table is built.
+    Random,
+    /// The state of the central cell if it is tied, the lowest tied state
+    /// otherwise.
+    Keep,
+    /// The lowest tied state.
+    Lowest,
+}
+
+impl fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TieBreak::Random => "random",
+            TieBreak::Keep => "keep",
+            TieBreak::Lowest => "lowest",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for TieBreak {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(TieBreak::Random),
+            "keep" => Ok(TieBreak::Keep),
+            "lowest" => Ok(TieBreak::Lowest),
+            _ => Err("the tie-breaking is random, keep or lowest"),
+        }
+    }
+}
+
+impl Rule {
+    /// Makes the majority vote rule with `states` states on the Moore
+    /// neighborhood of horizon 1: every cell takes the state shared by the
+    /// most cells of its neighborhood, itself included, and `tie_break`
+    /// chooses between the states shared by as many cells. The random
+    /// tie-breaking draws from the thread random number generator (see
+    /// [`Rule::majority_with_rng`]).
+    ///
+    /// The table has `states^9` entries, so this fails for the rules with
+    /// more than 7 states, whose tables would be larger than 64 MiB.
+    ///
+    /// ```
+    /// use rust_ca::rule::{Rule, TieBreak};
+    ///
+    /// let rule = Rule::majority(3, TieBreak::Keep)?;
+    /// assert_eq!(rule.explain("112/202/210")?.next, 2);
+    /// // Three cells in state 0, 1 and 2: the central cell keeps its state.
+    /// assert_eq!(rule.explain("001/122/201")?.next, 2);
+    /// let rule = Rule::majority(3, TieBreak::Lowest)?;
+    /// assert_eq!(rule.explain("001/122/201")?.next, 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn majority(states: u8, tie_break: TieBreak) -> Result<Rule, RuleError> {
+        Rule::majority_with_rng(states, tie_break, &mut rand::thread_rng())
+    }
+
+    /// Makes the majority vote rule of [`Rule::majority`], the random
+    /// tie-breaking drawing from the given random number generator. A
+    /// seeded generator always gives the same rule.
+    pub fn majority_with_rng<R: Rng + ?Sized>(
+        states: u8,
+        tie_break: TieBreak,
+        rng: &mut R,
+    ) -> Result<Rule, RuleError> {
+        let offsets = Neighborhood::Moore.offsets(1);
+        let size = (states as u64)
+            .checked_pow(offsets.len() as u32)
+            .filter(|&size| states >= 1 && size <= MAX_TABLE_SIZE)
+            .ok_or(RuleError::RuleFormatError(
+                "majority rule tables need 1 to 7 states",
+            ))?;
+        let states = states as usize;
+        let center = offsets.iter().position(|&o| o == (0, 0)).unwrap_or(0);
+        let mut cells = vec![0; offsets.len()];
+        let mut counts = vec![0; states];
+        let mut tied = Vec::with_capacity(states);
+        let table = (0..size as usize)
+            .map(|position| {
+                let mut rest = position;
+                counts.fill(0);
+                for cell in cells.iter_mut() {
+                    *cell = rest % states;
+                    counts[*cell] += 1;
+                    rest /= states;
+                }
+                let most = counts.iter().copied().max().unwrap_or(0);
+                tied.clear();
+                tied.extend((0..states).filter(|&s| counts[s] == most));
+                let next = match tie_break {
+                    _ if tied.len() == 1 => tied[0],
+                    TieBreak::Random => tied[rng.gen_range(0..tied.len())],
+                    TieBreak::Keep if counts[cells[center]] == most => cells[center],
+                    TieBreak::Keep | TieBreak::Lowest => tied[0],
+                };
+                next as u8
+            })
+            .collect();
+        Ok(Rule::new(1, states as u8, table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TieBreak;
+    use crate::rule::Rule;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn the_majority_wins_and_ties_are_broken() {
+        // With 2 states there are no ties among 9 cells: the rule is the
+        // totalistic vote.
+        let rule = Rule::majority(2, TieBreak::Lowest).unwrap();
+        assert!(rule.is_totalistic());
+        assert_eq!(rule.explain("110/110/100").unwrap().next, 1);
+        assert_eq!(rule.explain("110/100/100").unwrap().next, 0);
+
+        let next = |tie_break, neighborhood| {
+            let rule = Rule::majority(4, tie_break).unwrap();
+            rule.explain(neighborhood).unwrap().next
+        };
+        // States 1 and 3 are tied, and the central cell is in neither.
+        assert_eq!(next(TieBreak::Keep, "113/303/120"), 1);
+        assert_eq!(next(TieBreak::Lowest, "113/303/120"), 1);
+        // The central cell is in state 3.
+        assert_eq!(next(TieBreak::Keep, "113/033/120"), 3);
+        assert_eq!(next(TieBreak::Lowest, "113/033/120"), 1);
+        assert!(Rule::majority(8, TieBreak::Keep).is_err());
+        assert!(Rule::majority(0, TieBreak::Keep).is_err());
+    }
+
+    #[test]
+    fn random_ties_pick_a_tied_state() {
+        let a = Rule::majority_with_rng(3, TieBreak::Random, &mut StdRng::seed_from_u64(2));
+        let b = Rule::majority_with_rng(3, TieBreak::Random, &mut StdRng::seed_from_u64(2));
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a.table(), b.table());
+        // States 1 and 2 are tied.
+        let next = a.explain("112/122/120").unwrap().next;
+        assert!(next == 1 || next == 2);
+        assert_ne!(
+            a.table(),
+            Rule::majority(3, TieBreak::Lowest).unwrap().table()
+        );
+        assert_eq!("random".parse(), Ok(TieBreak::Random));
+        assert_eq!(TieBreak::Keep.to_string(), "keep");
+        assert!("first".parse::<TieBreak>().is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Uncompressed rule files that can be memory-mapped.
+//!
+//! The file starts with a 16 bytes header (the magic bytes ending with the
+//! version of the format, the horizon, the number of states, two reserved
+//! zero bytes and the little-endian CRC-32 checksum of the table), followed
+//! by the raw rule table, one byte per position. The first version 1 files
+//! were written without checksum, with zero bytes in its place, which aren't
+//! verified.
+//!
+//! The backends don't run on the mapped table: [`MappedRule::to_rule`]
+//! copies it to memory before simulating. Mapping is useful to inspect or
+//! copy parts of a large table without decompressing it, not to simulate
+//! rules that don't fit in memory.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Index;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use super::{Neighborhood, Rule, RuleError};
+use crate::output::AtomicFile;
+
+/// The magic bytes at the start of uncompressed rule files, followed by the
+/// version of the format.
+pub(super) const MAGIC: [u8; 7] = *b"CARULE\0";
+/// The version of the format of the files written, with a checksum.
+const VERSION: u8 = 2;
+/// The size of the header of uncompressed rule files.
+const HEADER_SIZE: usize = 16;
+
+/// Parses the header of an uncompressed rule file, returning the horizon, the
+/// number of states and the rule table. The table isn't read.
+fn parse_header(bytes: &[u8]) -> Result<(i8, u8, &[u8]), RuleError> {
+    if bytes.len() < HEADER_SIZE || bytes[..MAGIC.len()] != MAGIC {
+        return Err(RuleError::RuleFormatError("not an uncompressed rule file"));
+    }
+    if !(1..=VERSION).contains(&bytes[MAGIC.len()]) {
+        return Err(RuleError::RuleFormatError(
+            "unsupported uncompressed rule file version",
+        ));
+    }
+    let (horizon, states) = (bytes[8] as i8, bytes[9]);
+    let table = &bytes[HEADER_SIZE..];
+    let size = Rule::checked_table_size(horizon, states, Neighborhood::Moore)?;
+    if states < 2 || table.len() as u64 != size {
+        return Err(RuleError::RuleFormatError("invalid rule table size"));
+    }
+    Ok((horizon, states, table))
+}
+
+/// Parses an uncompressed rule file, returning the horizon, the number of
+/// states and the rule table, whose entries are checked to be states.
+pub(super) fn parse(bytes: &[u8]) -> Result<(i8, u8, &[u8]), RuleError> {
+    let (horizon, states, table) = parse_header(bytes)?;
+    if table.iter().any(|&s| s >= states) {
+        return Err(RuleError::RuleFormatError("invalid state in rule table"));
+    }
+    Ok((horizon, states, table))
+}
+
+/// Checks the table of an uncompressed rule file against the checksum in its
+/// header. Version 1 files without checksum always pass.
+pub(super) fn verify(bytes: &[u8], table: &[u8]) -> Result<(), RuleError> {
+    let expected = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+    if bytes[MAGIC.len()] < 2 && expected == 0 {
+        return Ok(());
+    }
+    let found = crc32fast::hash(table);
+    if found == expected {
+        Ok(())
+    } else {
+        Err(RuleError::RuleChecksumError { expected, found })
+    }
+}
+
+/// A rule table backed by a memory-mapped uncompressed rule file. Opening the
+/// file doesn't read the table: pages are loaded by the OS when the
+/// transitions are accessed, so single transitions of very large rules can
+/// be read without decompressing them up front. Simulating the rule needs a
+/// copy of the table in memory (see [`MappedRule::to_rule`]).
+///
+/// ```
+/// use rust_ca::rule::{MappedRule, Rule};
+///
+/// let rule = Rule::random(1, 3);
+/// MappedRule::write(&rule, "test_mapped.rule")?;
+/// let mapped = MappedRule::open("test_mapped.rule")?;
+/// assert_eq!(mapped[42], rule[42]);
+/// assert_eq!(mapped.table(), rule.table());
+/// mapped.verify()?;
+/// // Uncompressed rule files can also be read as regular rules.
+/// assert_eq!(Rule::from_file("test_mapped.rule")?.table(), rule.table());
+/// # std::fs::remove_file("test_mapped.rule")?;
+/// # Ok::<(), rust_ca::rule::RuleError>(())
+/// ```
+pub struct MappedRule {
+    horizon: i8,
+    states: u8,
+    mmap: Mmap,
+}
+
+impl MappedRule {
+    /// Writes a rule to an uncompressed rule file that can be opened with
+    /// [`MappedRule::open`]. Only Moore rules (see [`Neighborhood`]) can be
+    /// stored in these files; the tables of the other neighborhoods are small
+    /// enough for the regular rule files.
+    pub fn write<P: AsRef<Path>>(rule: &Rule, path: P) -> Result<(), io::Error> {
+        if rule.neighborhood() != Neighborhood::Moore {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "uncompressed rule files only store Moore rules",
+            ));
+        }
+        let mut f = AtomicFile::create(path)?;
+        let mut header = [0; HEADER_SIZE];
+        header[..MAGIC.len()].copy_from_slice(&MAGIC);
+        header[MAGIC.len()] = VERSION;
+        header[8] = rule.horizon as u8;
+        header[9] = rule.states;
+        header[12..].copy_from_slice(&crc32fast::hash(rule.table()).to_le_bytes());
+        f.write_all(&header)?;
+        f.write_all(rule.table())?;
+        f.commit()
+    }
+
+    /// Memory-maps an uncompressed rule file. Only the header is read and
+    /// checked; use [`MappedRule::verify`] to check the whole table.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedRule, RuleError> {
+        let f = File::open(path)?;
+        // Safety: the map is read-only. As with any memory-mapped file, the
+        // file must not be modified by another process while it is mapped.
+        let mmap = unsafe { Mmap::map(&f)? };
+        let (horizon, states, _) = parse_header(&mmap)?;
+        Ok(MappedRule {
+            horizon,
+            states,
+            mmap,
+        })
+    }
+
+    /// Reads the whole table and checks its entries and the checksum
+    /// recorded in the file.
+    pub fn verify(&self) -> Result<(), RuleError> {
+        let (_, _, table) = parse(&self.mmap)?;
+        verify(&self.mmap, table)
+    }
+
+    /// Returns the size of the neighborhood.
+    pub fn horizon(&self) -> i8 {
+        self.horizon
+    }
+
+    /// Returns the number of cell states the rule expects.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Returns the rule table.
+    pub fn table(&self) -> &[u8] {
+        &self.mmap[HEADER_SIZE..]
+    }
+
+    /// Copies the table to a regular in-memory rule. The entries of the
+    /// table aren't checked unless [`MappedRule::verify`] was called.
+    pub fn to_rule(&self) -> Rule {
+        Rule::new(self.horizon, self.states, self.table().to_vec())
+    }
+}
+
+impl Index<usize> for MappedRule {
+    type Output = u8;
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.mmap[HEADER_SIZE + idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, verify, MAGIC};
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([2, 1, 2, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(vec![0; 511]);
+        assert!(parse(&bytes).is_err());
+        bytes.push(0);
+        assert!(parse(&bytes).is_ok());
+        // The header can't describe tables too large for any rule.
+        bytes[8] = 100;
+        assert!(parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn tables_are_checked() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([1, 1, 2, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(vec![1; 512]);
+        // Version 1 files may have no checksum.
+        let (_, _, table) = parse(&bytes).unwrap();
+        assert!(verify(&bytes, table).is_ok());
+        bytes[12] = 1;
+        assert!(verify(&bytes, &bytes[16..]).is_err());
+        bytes[12] = 0;
+        bytes[7] = 2;
+        let (_, _, table) = parse(&bytes).unwrap();
+        assert!(verify(&bytes, table).is_err());
+        bytes[7] = 3;
+        assert!(parse(&bytes).is_err());
+        bytes[7] = 1;
+        bytes[100] = 2;
+        assert!(parse(&bytes).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// A transformation of the neighborhood offsets.
+type Symmetry = fn((isize, isize)) -> (isize, isize);
+
+/// The rotations and reflections of a square.
+const SQUARE_SYMMETRIES: [Symmetry; 8] = [
+    |(a, b)| (a, b),
+    |(a, b)| (b, -a),
+    |(a, b)| (-a, -b),
+    |(a, b)| (-b, a),
+    |(a, b)| (b, a),
+    |(a, b)| (-a, b),
+    |(a, b)| (a, -b),
+    |(a, b)| (-b, -a),
+];
+
+/// The rotations and reflections of a hexagon, in axial coordinates.
+const HEX_SYMMETRIES: [Symmetry; 12] = [
+    |(a, b)| (a, b),
+    |(a, b)| (-b, a + b),
+    |(a, b)| (-a - b, a),
+    |(a, b)| (-a, -b),
+    |(a, b)| (b, -a - b),
+    |(a, b)| (a + b, -a),
+    |(a, b)| (b, a),
+    |(a, b)| (a + b, -b),
+    |(a, b)| (a, -a - b),
+    |(a, b)| (-b, -a),
+    |(a, b)| (-a - b, b),
+    |(a, b)| (-a, a + b),
+];
+
+/// The cells whose states determine the next state of the central cell of a
+/// rule, as offsets `(a, b)` from the central cell, `a` along the rows and
+/// `b` along the columns.
+///
+/// The rule tables are indexed like the ones of Moore rules (see
+/// [`Rule`](super::Rule)), with one digit per cell of the neighborhood in the
+/// order of [`Neighborhood::offsets`].
+///
+/// Hexagonal grids are stored in square grids with axial coordinates: the
+/// neighbors of the cell `(i, j)` at horizon 1 are `(i - 1, j)`,
+/// `(i - 1, j + 1)`, `(i, j - 1)`, `(i, j + 1)`, `(i + 1, j - 1)` and
+/// `(i + 1, j)`. They are drawn with each row shifted half a cell to the
+/// right of the row above (see [`HexLayout`](crate::output::HexLayout)).
+///
+/// ```
+/// use rust_ca::rule::Neighborhood;
+///
+/// assert_eq!(Neighborhood::Moore.n_cells(1), 9);
+/// assert_eq!(Neighborhood::VonNeumann.n_cells(2), 13);
+/// assert_eq!(Neighborhood::Hex.n_cells(1), 7);
+/// assert!(!Neighborhood::Hex.contains(-1, -1, 1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Neighborhood {
+    /// The square of side `2 horizon + 1` centered on the cell.
+    #[default]
+    Moore,
+    /// The cells at a Manhattan distance of at most `horizon`.
+    VonNeumann,
+    /// The cells at a hexagonal distance of at most `horizon`.
+    Hex,
+}
+
+impl Neighborhood {
+    /// Returns whether the cell at offset `(a, b)` is in the neighborhood of
+    /// the given horizon. The offsets are at most `horizon` in absolute
+    /// value.
+    #[inline]
+    pub fn contains(self, a: isize, b: isize, horizon: isize) -> bool {
+        match self {
+            Neighborhood::Moore => true,
+            Neighborhood::VonNeumann => a.abs() + b.abs() <= horizon,
+            Neighborhood::Hex => (a + b).abs() <= horizon,
+        }
+    }
+
+    /// Returns the column offsets `b` of the cells of the neighborhood on the
+    /// row at offset `a`. They are contiguous in every neighborhood, so the
+    /// update kernels read each row of a neighborhood as a single slice.
+    ///
+    /// ```
+    /// use rust_ca::rule::Neighborhood;
+    ///
+    /// assert_eq!(Neighborhood::Moore.row_span(1, 2), -2..=2);
+    /// assert_eq!(Neighborhood::VonNeumann.row_span(-1, 2), -1..=1);
+    /// assert_eq!(Neighborhood::Hex.row_span(1, 1), -1..=0);
+    /// ```
+    #[inline]
+    pub fn row_span(self, a: isize, horizon: isize) -> RangeInclusive<isize> {
+        let h = horizon;
+        match self {
+            Neighborhood::Moore => -h..=h,
+            Neighborhood::VonNeumann => -(h - a.abs())..=h - a.abs(),
+            Neighborhood::Hex => (-h).max(-h - a)..=h.min(h - a),
+        }
+    }
+
+    /// Returns the offsets of the cells of the neighborhood, row by row.
+    pub fn offsets(self, horizon: i8) -> Vec<(isize, isize)> {
+        let h = horizon as isize;
+        (-h..=h)
+            .flat_map(|a| self.row_span(a, h).map(move |b| (a, b)))
+            .collect()
+    }
+
+    /// Returns the number of cells in the neighborhood of the given horizon.
+    pub fn n_cells(self, horizon: i8) -> usize {
+        self.offsets(horizon).len()
+    }
+
+    /// Returns the rotations and reflections mapping the neighborhood onto
+    /// itself.
+    pub(super) fn symmetries(self) -> &'static [Symmetry] {
+        match self {
+            Neighborhood::Moore | Neighborhood::VonNeumann => &SQUARE_SYMMETRIES,
+            Neighborhood::Hex => &HEX_SYMMETRIES,
+        }
+    }
+}
+
+// Implement the FromStr trait for CLI options parsing.
+impl FromStr for Neighborhood {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moore" => Ok(Neighborhood::Moore),
+            "von-neumann" => Ok(Neighborhood::VonNeumann),
+            "hex" => Ok(Neighborhood::Hex),
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// Writes the names parsed by the [`FromStr`] implementation.
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Neighborhood::Moore => "moore",
+            Neighborhood::VonNeumann => "von-neumann",
+            Neighborhood::Hex => "hex",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Neighborhood;
+
+    #[test]
+    fn symmetries_map_the_neighborhoods_onto_themselves() {
+        for &nb in &[
+            Neighborhood::Moore,
+            Neighborhood::VonNeumann,
+            Neighborhood::Hex,
+        ] {
+            assert_eq!(nb.to_string().parse(), Ok(nb));
+            for horizon in 1..4 {
+                let offsets = nb.offsets(horizon);
+                // The central cell is in the middle of the table digits.
+                assert_eq!(offsets[offsets.len() / 2], (0, 0));
+                for symmetry in nb.symmetries() {
+                    let mut image: Vec<_> = offsets.iter().map(|&o| symmetry(o)).collect();
+                    image.sort_unstable();
+                    assert_eq!(image, offsets);
+                }
+            }
+        }
+        assert_eq!(Neighborhood::Hex.n_cells(2), 19);
+    }
+
+    #[test]
+    fn row_spans_hold_the_cells_of_the_rows() {
+        for &nb in &[
+            Neighborhood::Moore,
+            Neighborhood::VonNeumann,
+            Neighborhood::Hex,
+        ] {
+            for h in 1..4 {
+                for a in -h..=h {
+                    let cells: Vec<_> = (-h..=h).filter(|&b| nb.contains(a, b, h)).collect();
+                    assert_eq!(nb.row_span(a, h).collect::<Vec<_>>(), cells);
+                }
+            }
+        }
+    }
+}
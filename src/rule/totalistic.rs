@@ -0,0 +1,279 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::format::{compact_index, compact_size, compact_table, n_cells};
+use super::{Neighborhood, Rule};
+
+/// A totalistic or outer totalistic rule, stored as a table indexed by sums
+/// of states instead of full neighborhood configurations.
+///
+/// The next state of a totalistic rule only depends on the sum of the states
+/// of the neighborhood (central cell included), so its table has
+/// `n (states - 1) + 1` entries for a neighborhood of `n` cells. The next
+/// state of an outer totalistic rule depends on the state of the central
+/// cell and on the sum of the states of the other cells, for
+/// `states ((n - 1) (states - 1) + 1)` entries. Unlike the
+/// `states^n` entries of a [`Rule`], these grow polynomially with the horizon
+/// and the number of states. A
+/// [`TotalisticAutomaton`](crate::automaton::TotalisticAutomaton) runs the
+/// rules from these tables, while [`TotalisticRule::to_rule`] expands them
+/// to full tables for the other automata.
+///
+/// ```
+/// use rust_ca::rule::{Rule, TotalisticRule};
+///
+/// let life: TotalisticRule = "B3/S23".parse()?;
+/// assert_eq!(life.table().len(), 18);
+/// assert_eq!(life.to_rule().table(), Rule::gol().table());
+/// # Ok::<(), &'static str>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TotalisticRule {
+    /// The size of the neighborhood.
+    pub horizon: i8,
+    /// The number of cell states the rule expects.
+    pub states: u8,
+    outer: bool,
+    table: Vec<u8>,
+}
+
+impl TotalisticRule {
+    /// Construct a new totalistic (`outer` false) or outer totalistic
+    /// (`outer` true) rule from its sum table. The entries of outer totalistic
+    /// tables are grouped by central cell state.
+    pub fn new(horizon: i8, states: u8, outer: bool, table: Vec<u8>) -> TotalisticRule {
+        if table.len() != compact_size(states as usize, n_cells(horizon), outer) {
+            panic!(
+                "Incorrect totalistic rule for neighborhood size {} and number of states {}",
+                horizon, states
+            )
+        }
+        TotalisticRule {
+            horizon,
+            states,
+            outer,
+            table,
+        }
+    }
+
+    /// Create a random rule with uniformly sampled transitions.
+    pub fn random(horizon: i8, states: u8, outer: bool) -> TotalisticRule {
+        TotalisticRule::random_with_rng(horizon, states, outer, &mut rand::thread_rng())
+    }
+
+    /// Create a random rule with uniformly sampled transitions, drawn from
+    /// the given random number generator.
+    pub fn random_with_rng<R: Rng + ?Sized>(
+        horizon: i8,
+        states: u8,
+        outer: bool,
+        rng: &mut R,
+    ) -> TotalisticRule {
+        let table = (0..compact_size(states as usize, n_cells(horizon), outer))
+            .map(|_| rng.gen_range(0..states))
+            .collect();
+        TotalisticRule::new(horizon, states, outer, table)
+    }
+
+    /// Makes the 2 states outer totalistic rule of horizon 1 where a dead
+    /// cell becomes alive when its number of live neighbors is in `birth`,
+    /// and a live cell stays alive when it is in `survival`. Counts above 8
+    /// are ignored.
+    pub fn life_like(birth: &[usize], survival: &[usize]) -> TotalisticRule {
+        let mut table = vec![0; 18];
+        for (center, counts) in [birth, survival].iter().enumerate() {
+            for &n in counts.iter().filter(|&&n| n <= 8) {
+                table[9 * center + n] = 1;
+            }
+        }
+        TotalisticRule::new(1, 2, true, table)
+    }
+
+    /// Returns the totalistic (`outer` false) or outer totalistic (`outer`
+    /// true) version of a rule, or `None` if the rule isn't of this kind.
+    pub fn from_rule(rule: &Rule, outer: bool) -> Option<TotalisticRule> {
+        if rule.neighborhood() != Neighborhood::Moore {
+            return None;
+        }
+        compact_table(rule, outer)
+            .map(|table| TotalisticRule::new(rule.horizon, rule.states, outer, table))
+    }
+
+    /// Returns a reference to the sum table.
+    pub fn table(&self) -> &[u8] {
+        &self.table
+    }
+
+    /// Returns whether the rule is outer totalistic.
+    pub fn is_outer(&self) -> bool {
+        self.outer
+    }
+
+    /// Returns the next state of a cell in state `center` whose other
+    /// neighborhood cells sum to `sum`.
+    #[inline]
+    pub fn next_state(&self, center: u8, sum: usize) -> u8 {
+        if self.outer {
+            let n_sums = (n_cells(self.horizon) - 1) * (self.states as usize - 1) + 1;
+            self.table[center as usize * n_sums + sum]
+        } else {
+            self.table[center as usize + sum]
+        }
+    }
+
+    /// Expands the rule to a full rule table. The full table has
+    /// `states^n` entries for a neighborhood of `n` cells, so this is only
+    /// possible for small horizons and numbers of states: larger rules are
+    /// run by a [`TotalisticAutomaton`](crate::automaton::TotalisticAutomaton).
+    pub fn to_rule(&self) -> Rule {
+        let (states, n_cells) = (self.states as usize, n_cells(self.horizon));
+        let table = (0..Rule::rule_size(self.horizon, self.states) as usize)
+            .map(|position| self.table[compact_index(position, states, n_cells, self.outer)])
+            .collect();
+        Rule::new(self.horizon, self.states, table)
+    }
+
+    /// Applies the rule once to a periodic grid of side `size`, returning the
+    /// next grid. The neighborhood sums are computed with sliding windows, so
+    /// the cost doesn't depend on the horizon.
+    pub fn apply_on_torus(&self, grid: &[u8], size: usize) -> Vec<u8> {
+        let w = 2 * self.horizon as usize + 1;
+        // Sums of the `w` cells centered on every cell of the rows, then of
+        // the `w` row sums centered on every cell of the columns.
+        let window_sums = |get: &dyn Fn(usize) -> usize| -> Vec<usize> {
+            let mut sum: usize = (0..w).map(|k| get((k + size * w - w / 2) % size)).sum();
+            let mut sums = Vec::with_capacity(size);
+            for j in 0..size {
+                sums.push(sum);
+                sum = sum + get((j + w / 2 + 1) % size) - get((j + size * w - w / 2) % size);
+            }
+            sums
+        };
+        let mut rows = vec![0; size * size];
+        for i in 0..size {
+            let row = &grid[i * size..(i + 1) * size];
+            rows[i * size..(i + 1) * size].copy_from_slice(&window_sums(&|j| row[j] as usize));
+        }
+        let mut next = vec![0; size * size];
+        for j in 0..size {
+            let column = window_sums(&|i| rows[i * size + j]);
+            for (i, sum) in column.into_iter().enumerate() {
+                let center = grid[i * size + j];
+                next[i * size + j] = self.next_state(center, sum - center as usize);
+            }
+        }
+        next
+    }
+}
+
+/// Parses a Life-like rule string `B<birth counts>/S<survival counts>`, like
+/// `B3/S23` for the game of life (see [`TotalisticRule::life_like`]).
+impl FromStr for TotalisticRule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let counts = |part: &str, prefix: char| -> Result<Vec<usize>, Self::Err> {
+            part.strip_prefix(prefix)
+                .or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))
+                .ok_or("expected a B<counts>/S<counts> rule string")?
+                .chars()
+                .map(|c| match c.to_digit(10) {
+                    Some(n) if n <= 8 => Ok(n as usize),
+                    _ => Err("invalid neighbor count"),
+                })
+                .collect()
+        };
+        let (birth, survival) = s
+            .trim()
+            .split_once('/')
+            .ok_or("expected a B<counts>/S<counts> rule string")?;
+        Ok(TotalisticRule::life_like(
+            &counts(birth, 'B')?,
+            &counts(survival, 'S')?,
+        ))
+    }
+}
+
+/// Writes Life-like rules as `B<birth counts>/S<survival counts>`, and other
+/// rules as their kind, shape and sum table.
+impl fmt::Display for TotalisticRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.outer && self.horizon == 1 && self.states == 2 {
+            let counts = |center: usize| -> String {
+                (0..9)
+                    .filter(|&n| self.table[9 * center + n] == 1)
+                    .map(|n| n.to_string())
+                    .collect()
+            };
+            write!(f, "B{}/S{}", counts(0), counts(1))
+        } else {
+            write!(
+                f,
+                "{} horizon={} states={} table=",
+                if self.outer {
+                    "outer-totalistic"
+                } else {
+                    "totalistic"
+                },
+                self.horizon,
+                self.states
+            )?;
+            self.table.iter().try_for_each(|s| write!(f, "{}", s))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TotalisticRule;
+    use crate::rule::Rule;
+    use rand::Rng;
+
+    #[test]
+    fn expanded_rules_are_recovered() {
+        for &outer in &[false, true] {
+            let rule = TotalisticRule::random(1, 3, outer);
+            let full = rule.to_rule();
+            assert!(full.is_outer_totalistic());
+            assert_eq!(TotalisticRule::from_rule(&full, outer), Some(rule));
+        }
+        assert_eq!(TotalisticRule::from_rule(&Rule::gol(), false), None);
+    }
+
+    #[test]
+    fn torus_updates_match_the_full_rule() {
+        let mut rng = rand::thread_rng();
+        for &(outer, size) in &[(false, 7), (true, 16), (true, 2)] {
+            let rule = TotalisticRule::random(1, 3, outer);
+            let grid: Vec<u8> = (0..size * size).map(|_| rng.gen_range(0..3)).collect();
+            assert_eq!(
+                rule.apply_on_torus(&grid, size),
+                rule.to_rule().apply_on_torus(&grid, size)
+            );
+        }
+    }
+
+    #[test]
+    fn large_horizons_stay_tractable() {
+        // The full table of this rule would have 4^121 entries.
+        let rule = TotalisticRule::random(5, 4, true);
+        assert_eq!(rule.table().len(), 4 * (120 * 3 + 1));
+        let grid = vec![1; 32 * 32];
+        let next = rule.apply_on_torus(&grid, 32);
+        assert!(next.iter().all(|&s| s == rule.next_state(1, 120)));
+    }
+
+    #[test]
+    fn life_like_rule_strings_roundtrip() {
+        let highlife: TotalisticRule = "B36/S23".parse().unwrap();
+        assert_eq!(highlife.to_string(), "B36/S23");
+        assert_eq!(
+            "b3/s23".parse::<TotalisticRule>().unwrap().to_string(),
+            "B3/S23"
+        );
+        assert!("B9/S23".parse::<TotalisticRule>().is_err());
+        assert!("S23".parse::<TotalisticRule>().is_err());
+    }
+}
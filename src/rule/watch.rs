@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{Rule, RuleError};
+
+/// Watches a rule file for changes, to swap the rule of a running automaton
+/// (see [`AutomatonImpl::set_rule`](crate::automaton::AutomatonImpl::set_rule))
+/// while the rule is being tweaked. The file is polled: its modification time
+/// and size are compared with the ones of the last poll.
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::rule::{Rule, RuleWatcher};
+///
+/// Rule::gol().to_file("test_watched.rule")?;
+/// let mut watcher = RuleWatcher::new("test_watched.rule");
+/// let mut automaton = Automaton::new(2, 32, Rule::gol());
+/// for _ in 0..10 {
+///     if let Some(rule) = watcher.poll() {
+///         automaton.set_rule(rule?)?;
+///     }
+///     automaton.update();
+/// }
+/// # std::fs::remove_file("test_watched.rule")?;
+/// # Ok::<(), rust_ca::rule::RuleError>(())
+/// ```
+pub struct RuleWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl RuleWatcher {
+    /// Starts watching a rule file. The current version of the file isn't
+    /// reported as a change.
+    pub fn new<P: AsRef<Path>>(path: P) -> RuleWatcher {
+        let path = path.as_ref().to_path_buf();
+        let stamp = file_stamp(&path);
+        RuleWatcher { path, stamp }
+    }
+
+    /// Returns the rule in the file if the file changed since the last poll,
+    /// or an error if the changed file couldn't be read. Nothing is reported
+    /// while the file doesn't exist, e.g. while an editor replaces it.
+    pub fn poll(&mut self) -> Option<Result<Rule, RuleError>> {
+        let stamp = file_stamp(&self.path);
+        if stamp.is_none() || stamp == self.stamp {
+            return None;
+        }
+        self.stamp = stamp;
+        Some(Rule::from_file(self.path.as_path()))
+    }
+}
+
+/// The modification time and size of a file, if it exists.
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleWatcher;
+    use crate::automaton::{Automaton, AutomatonImpl};
+    use crate::rule::{Rule, RuleError};
+
+    #[test]
+    fn changed_rules_are_swapped_in() {
+        let path = std::env::temp_dir().join("rust_ca_watched.rule");
+        Rule::gol().to_file(&path).unwrap();
+        let mut watcher = RuleWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        // A rule of a different size is also a different file size.
+        let mut a = Automaton::new(2, 16, Rule::gol());
+        a.random_init();
+        Rule::new(1, 2, vec![0; 512]).to_file(&path).unwrap();
+        a.set_rule(watcher.poll().unwrap().unwrap()).unwrap();
+        a.update();
+        assert!(a.grid().iter().all(|&c| c == 0));
+        assert!(watcher.poll().is_none());
+
+        Rule::random(1, 3).to_file(&path).unwrap();
+        let rule = watcher.poll().unwrap().unwrap();
+        assert!(matches!(
+            a.set_rule(rule),
+            Err(RuleError::RuleShapeError {
+                expected: (1, 2),
+                found: (1, 3)
+            })
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_none());
+    }
+}
@@ -0,0 +1,464 @@
+//! This module samples random rules, runs short simulations of them and ranks
+//! them with fitness functions, to find the rules worth a closer look in
+//! large rule spaces.
+//!
+//! ```
+//! use rust_ca::search::{Activity, EntropyBand, Search};
+//!
+//! let mut search = Search::new(1, 2, 32, 50);
+//! search.set_seed(7);
+//! search.add_fitness(1., EntropyBand { low: 0.2, high: 0.8 });
+//! search.add_fitness(1., Activity { min: 0.01 });
+//! let best = search.run(20, 3);
+//! assert_eq!(best.len(), 3);
+//! assert!(best[0].score >= best[2].score);
+//! ```
+
+#[cfg(feature = "rule-io")]
+use std::io::{self, Write};
+#[cfg(feature = "rule-io")]
+use std::path::{Path, PathBuf};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::analysis::{self, FingerprintConfig};
+use crate::automaton::{Automaton, AutomatonImpl};
+use crate::rule::{LambdaFamily, Rule, RuleCatalog, SamplingMode};
+use crate::stats;
+
+/// The frames of the simulation of a sampled rule, one per step.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    /// The size of the grids.
+    pub size: usize,
+    /// The number of states of the rule.
+    pub states: u8,
+    /// The grids of every step, starting with the random initial grid.
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl Trajectory {
+    /// Returns the second half of the frames, after the transient of most
+    /// rules.
+    fn late_frames(&self) -> &[Vec<u8>] {
+        &self.frames[self.frames.len() / 2..]
+    }
+}
+
+/// A fitness function scoring the trajectory of a rule, from 0 for
+/// uninteresting rules to 1. Closures taking a [`Trajectory`] are fitness
+/// functions.
+pub trait Fitness {
+    /// Scores a trajectory.
+    fn score(&self, trajectory: &Trajectory) -> f64;
+}
+
+impl<F: Fn(&Trajectory) -> f64> Fitness for F {
+    fn score(&self, trajectory: &Trajectory) -> f64 {
+        self(trajectory)
+    }
+}
+
+/// Scores the rules keeping the entropy of the state distribution (see
+/// [`stats::entropy`]) between two bounds, away from the frozen and the
+/// random ones. The score is the fraction of the late frames (the second
+/// half) inside the band.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntropyBand {
+    /// The lowest entropy in the band.
+    pub low: f64,
+    /// The highest entropy in the band.
+    pub high: f64,
+}
+
+impl Fitness for EntropyBand {
+    fn score(&self, trajectory: &Trajectory) -> f64 {
+        let frames = trajectory.late_frames();
+        let inside = frames
+            .iter()
+            .map(|f| stats::entropy(f, trajectory.states))
+            .filter(|e| (self.low..=self.high).contains(e))
+            .count();
+        inside as f64 / frames.len().max(1) as f64
+    }
+}
+
+/// Scores the rules whose activity doesn't die out. The score is the
+/// fraction of the late steps (the second half) changing at least a fraction
+/// `min` of the cells (see [`stats::activity`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Activity {
+    /// The smallest fraction of changed cells of an active step.
+    pub min: f64,
+}
+
+impl Fitness for Activity {
+    fn score(&self, trajectory: &Trajectory) -> f64 {
+        let frames = trajectory.late_frames();
+        let active = frames
+            .windows(2)
+            .filter(|w| stats::activity(&w[0], &w[1]) >= self.min)
+            .count();
+        active as f64 / frames.len().saturating_sub(1).max(1) as f64
+    }
+}
+
+/// Scores the rules producing structured grids, neither uniform nor random.
+/// The last grid is compressed with deflate, and its compressed size is
+/// placed between the ones of a uniform grid (0) and a random grid (1). This
+/// position `r` is scored `4 r (1 - r)`, which peaks halfway between the two.
+#[cfg(feature = "rule-io")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Compressibility;
+
+#[cfg(feature = "rule-io")]
+impl Compressibility {
+    fn compressed_len(grid: &[u8]) -> usize {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(grid)
+            .and_then(|_| encoder.finish())
+            .map_or(grid.len(), |c| c.len())
+    }
+}
+
+#[cfg(feature = "rule-io")]
+impl Fitness for Compressibility {
+    fn score(&self, trajectory: &Trajectory) -> f64 {
+        let last = match trajectory.frames.last() {
+            Some(last) => last,
+            None => return 0.,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let random: Vec<u8> = (0..last.len())
+            .map(|_| rng.gen_range(0..trajectory.states.max(1)))
+            .collect();
+        let uniform = Self::compressed_len(&vec![0; last.len()]) as f64;
+        let random = Self::compressed_len(&random) as f64;
+        let r = (Self::compressed_len(last) as f64 - uniform) / (random - uniform).max(1.);
+        let r = r.clamp(0., 1.);
+        4. * r * (1. - r)
+    }
+}
+
+/// A rule found by a [`Search`], with its score.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// The rule.
+    pub rule: Rule,
+    /// The weighted mean of the scores of the fitness functions.
+    pub score: f64,
+    /// The seed of the random initial grid of the scored simulation.
+    pub seed: u64,
+}
+
+/// A search for the rules maximizing a weighted mean of fitness functions.
+/// Each sample draws a random rule, simulates it from a random grid and
+/// scores the trajectory.
+pub struct Search {
+    horizon: i8,
+    states: u8,
+    size: usize,
+    steps: u32,
+    sampling: SamplingMode,
+    seed: Option<u64>,
+    catalog: Option<RuleCatalog>,
+    fitness: Vec<(f64, Box<dyn Fitness>)>,
+}
+
+impl Search {
+    /// Makes a search among the rules with the given horizon and number of
+    /// states, simulated on grids of side `size` for `steps` steps. The rules
+    /// are sampled with [`SamplingMode::Dirichlet`] by default.
+    pub fn new(horizon: i8, states: u8, size: usize, steps: u32) -> Search {
+        Search {
+            horizon,
+            states,
+            size,
+            steps,
+            sampling: SamplingMode::Dirichlet,
+            seed: None,
+            catalog: None,
+            fitness: vec![],
+        }
+    }
+
+    /// Sets the distribution the rules are sampled from.
+    pub fn set_sampling(&mut self, sampling: SamplingMode) {
+        self.sampling = sampling;
+    }
+
+    /// Seeds the search, making the sampled rules and their scores
+    /// reproducible. Searches are seeded from the system entropy by default.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Leaves the rules equivalent to a rule of the catalog out of the
+    /// results (see [`RuleCatalog::lookup`]), to skip the rules found by
+    /// previous searches.
+    pub fn set_catalog(&mut self, catalog: RuleCatalog) {
+        self.catalog = Some(catalog);
+    }
+
+    /// Returns the catalog of the search, to record the rules found.
+    pub fn catalog_mut(&mut self) -> Option<&mut RuleCatalog> {
+        self.catalog.as_mut()
+    }
+
+    /// Adds a fitness function to the score, with the given weight.
+    pub fn add_fitness<F: Fitness + 'static>(&mut self, weight: f64, fitness: F) {
+        self.fitness.push((weight, Box::new(fitness)));
+    }
+
+    /// Simulates a rule from the random grid of the given seed and scores
+    /// it.
+    pub fn evaluate(&self, rule: &Rule, seed: u64) -> f64 {
+        let mut autom = Automaton::new(self.states, self.size, rule.clone());
+        autom.random_init_seeded(seed);
+        let trajectory = Trajectory {
+            size: self.size,
+            states: self.states,
+            frames: autom.skipped_iter(self.steps, 1, 1).collect(),
+        };
+        let total: f64 = self.fitness.iter().map(|(w, _)| w).sum();
+        if total <= 0. {
+            return 0.;
+        }
+        self.fitness
+            .iter()
+            .map(|(w, f)| w * f.score(&trajectory))
+            .sum::<f64>()
+            / total
+    }
+
+    /// Evaluates `samples` random rules and returns the `top` best ones,
+    /// best first. The rules equivalent to a better one (see
+    /// [`Rule::canonical_id`]) or to a rule of the catalog of the search are
+    /// left out.
+    pub fn run(&self, samples: u64, top: usize) -> Vec<Candidate> {
+        let base = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut candidates: Vec<Candidate> = Vec::with_capacity(top + 1);
+        let mut classes: Vec<u64> = Vec::with_capacity(top + 1);
+        for i in 0..samples {
+            let seed = base.wrapping_add(i);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let rule = match self.sampling {
+                SamplingMode::Dirichlet => {
+                    Rule::random_dirichlet_with_rng(self.horizon, self.states, None, &mut rng)
+                }
+                SamplingMode::Uniform => Rule::random_with_rng(self.horizon, self.states, &mut rng),
+            };
+            let score = self.evaluate(&rule, seed);
+            let at = candidates.partition_point(|c| c.score >= score);
+            if at < top {
+                let class = rule.canonical_id();
+                if let Some(catalog) = &self.catalog {
+                    if catalog.lookup_canonical(&rule, class).is_some() {
+                        continue;
+                    }
+                }
+                match classes.iter().position(|&c| c == class) {
+                    Some(k) if k < at => continue,
+                    Some(k) => {
+                        candidates.remove(k);
+                        classes.remove(k);
+                    }
+                    None => {}
+                }
+                candidates.insert(at, Candidate { rule, score, seed });
+                classes.insert(at, class);
+                candidates.truncate(top);
+                classes.truncate(top);
+            }
+        }
+        candidates
+    }
+
+    /// Groups the candidates by their fingerprints in at most `k` clusters
+    /// and returns the candidate closest to the center of each cluster (see
+    /// [`analysis::cluster_exemplars`]), best first. The fingerprints
+    /// are simulated with the grid size and steps of the search, and drawn
+    /// with the clustering from its seed, so that a seeded search always
+    /// picks the same exemplars.
+    pub fn exemplars(&self, candidates: &[Candidate], k: usize) -> Vec<Candidate> {
+        if k == 0 {
+            return vec![];
+        }
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let config = FingerprintConfig {
+            size: self.size,
+            steps: self.steps,
+            ..FingerprintConfig::default()
+        };
+        let rules: Vec<Rule> = candidates.iter().map(|c| c.rule.clone()).collect();
+        let mut picked = analysis::cluster_exemplars_with_rng(
+            &rules,
+            k,
+            &config,
+            &mut StdRng::seed_from_u64(seed),
+        );
+        picked.sort_unstable();
+        picked.into_iter().map(|i| candidates[i].clone()).collect()
+    }
+
+    /// Evaluates `points` rules of a single [`LambdaFamily`] drawn from the
+    /// seed of the search, with parameters evenly spaced from 0 to the
+    /// largest one, and returns them by increasing parameter. The rules are
+    /// all simulated from the same random grid, so that the scores follow
+    /// the transition from frozen to chaotic dynamics along the sweep.
+    pub fn sweep(&self, points: usize) -> Vec<Candidate> {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        LambdaFamily::new(self.horizon, self.states, seed)
+            .sweep(points)
+            .into_iter()
+            .map(|(_, rule)| {
+                let score = self.evaluate(&rule, seed);
+                Candidate { rule, score, seed }
+            })
+            .collect()
+    }
+}
+
+/// Writes the rules of the candidates to `dir` as `RANK_ID.rule` files (see
+/// [`Rule::to_file`]), creating the directory if needed, and returns the
+/// paths of the files.
+#[cfg(feature = "rule-io")]
+pub fn write_rules<P: AsRef<Path>>(
+    dir: P,
+    candidates: &[Candidate],
+) -> Result<Vec<PathBuf>, io::Error> {
+    std::fs::create_dir_all(&dir)?;
+    candidates
+        .iter()
+        .enumerate()
+        .map(|(rank, c)| {
+            let path = dir
+                .as_ref()
+                .join(format!("{}_{}.rule", rank + 1, c.rule.id()));
+            c.rule.to_file(&path).map(|_| path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Activity, EntropyBand, Fitness, Search, Trajectory};
+    use crate::rule::{Rule, RuleCatalog, SamplingMode};
+
+    #[test]
+    fn fitness_functions_tell_frozen_rules_apart() {
+        let frozen = Trajectory {
+            size: 64,
+            states: 2,
+            frames: vec![vec![0; 64 * 64]; 4],
+        };
+        let blinking = Trajectory {
+            size: 2,
+            states: 2,
+            frames: vec![
+                vec![0, 1, 1, 0],
+                vec![1, 0, 0, 1],
+                vec![0, 1, 1, 0],
+                vec![1, 0, 0, 1],
+            ],
+        };
+        let band = EntropyBand { low: 0.5, high: 1. };
+        let activity = Activity { min: 0.5 };
+        assert_eq!(band.score(&frozen), 0.);
+        assert_eq!(band.score(&blinking), 1.);
+        assert_eq!(activity.score(&frozen), 0.);
+        assert_eq!(activity.score(&blinking), 1.);
+        #[cfg(feature = "rule-io")]
+        {
+            use super::Compressibility;
+            assert_eq!(Compressibility.score(&frozen), 0.);
+        }
+    }
+
+    #[test]
+    fn seeded_searches_are_reproducible() {
+        let mut search = Search::new(1, 2, 16, 20);
+        search.set_seed(3);
+        search.add_fitness(1., Activity { min: 0.01 });
+        search.add_fitness(0.5, |t: &Trajectory| t.frames.len() as f64 / 20.);
+        let (a, b) = (search.run(10, 4), search.run(10, 4));
+        assert_eq!(a.len(), 4);
+        for (a, b) in a.iter().zip(&b) {
+            assert_eq!(a.rule.table(), b.rule.table());
+            assert_eq!(a.score, b.score);
+            assert_eq!(search.evaluate(&a.rule, a.seed), a.score);
+        }
+        assert!(a.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(Search::new(1, 2, 16, 20).evaluate(&Rule::gol(), 0), 0.);
+    }
+
+    #[test]
+    fn equivalent_rules_are_kept_once() {
+        // All the rules of the elementary neighborhood of horizon 0 with 2
+        // states are the identity, its negation and the two constant rules,
+        // and the constant rules are relabelings of each other.
+        let mut search = Search::new(0, 2, 8, 4);
+        search.set_sampling(SamplingMode::Uniform);
+        search.set_seed(1);
+        let found = search.run(50, 10);
+        assert_eq!(found.len(), 3);
+        let ids: Vec<u64> = found.iter().map(|c| c.rule.canonical_id()).collect();
+        assert!(ids
+            .iter()
+            .all(|id| ids.iter().filter(|&i| i == id).count() == 1));
+    }
+
+    #[test]
+    fn exemplars_are_seeded_and_ranked() {
+        let mut search = Search::new(1, 2, 16, 20);
+        search.set_seed(9);
+        search.add_fitness(1., Activity { min: 0.01 });
+        let best = search.run(12, 6);
+        let exemplars = search.exemplars(&best, 3);
+        assert!(!exemplars.is_empty() && exemplars.len() <= 3);
+        assert!(exemplars.windows(2).all(|w| w[0].score >= w[1].score));
+        let again = search.exemplars(&best, 3);
+        assert!(exemplars
+            .iter()
+            .zip(&again)
+            .all(|(a, b)| a.rule.table() == b.rule.table()));
+        assert!(search.exemplars(&best, 0).is_empty());
+        assert!(search.exemplars(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn catalogued_rules_are_left_out() {
+        let mut search = Search::new(1, 2, 16, 20);
+        search.set_seed(4);
+        search.add_fitness(1., Activity { min: 0.01 });
+        let best = search.run(10, 3);
+        let mut catalog = RuleCatalog::new();
+        catalog.record(&best[0].rule);
+        search.set_catalog(catalog);
+        let found = search.run(10, 3);
+        assert!(found
+            .iter()
+            .all(|c| c.rule.canonical_id() != best[0].rule.canonical_id()));
+        assert_eq!(found[0].rule.table(), best[1].rule.table());
+        search.catalog_mut().unwrap().record(&found[0].rule);
+        assert_eq!(search.catalog_mut().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sweeps_go_from_frozen_to_active_rules() {
+        let mut search = Search::new(1, 3, 24, 30);
+        search.set_seed(5);
+        search.add_fitness(1., Activity { min: 0.05 });
+        let sweep = search.sweep(6);
+        assert_eq!(sweep.len(), 6);
+        assert!(sweep
+            .windows(2)
+            .all(|w| w[0].rule.lambda() < w[1].rule.lambda()));
+        assert_eq!(sweep[0].score, 0.);
+        assert_eq!(sweep[5].score, 1.);
+        assert_eq!(search.sweep(6)[3].rule.table(), sweep[3].rule.table());
+    }
+}
@@ -0,0 +1,507 @@
+//! This module contains functions computing statistics of CA grids.
+//!
+//! ## State statistics
+//! ```
+//! use rust_ca::stats;
+//!
+//! let previous = vec![0, 0, 1, 1];
+//! let grid = vec![0, 1, 1, 1];
+//! assert_eq!(stats::densities(&grid, 2), vec![0.25, 0.75]);
+//! assert_eq!(stats::activity(&previous, &grid), 0.25);
+//! assert_eq!(stats::entropy(&vec![0, 1, 0, 1], 2), 1.);
+//! ```
+//!
+//! ## Spatial structure
+//! The spatial structure of a grid is characterized by its two-point
+//! correlation function and its structure factor (the power spectrum of the
+//! grid). Both are radially averaged, and the correlation length summarizes the
+//! typical size of the structures in the grid.
+//! ```
+//! use rust_ca::stats;
+//!
+//! // Vertical stripes of width 4.
+//! let grid: Vec<u8> = (0..32 * 32).map(|x| ((x % 32) / 4 % 2) as u8).collect();
+//! let correlation = stats::two_point_correlation(&grid, 32);
+//! assert!((correlation[0] - 1.).abs() < 1e-9);
+//!
+//! let structure_factor = stats::structure_factor(&grid, 32);
+//! // The dominant wavenumber corresponds to the period of the stripes.
+//! let peak = (1..structure_factor.len())
+//!     .max_by(|&a, &b| structure_factor[a].total_cmp(&structure_factor[b]))
+//!     .unwrap();
+//! assert_eq!(peak, 32 / 8);
+//! ```
+//!
+//! ## Spatial heterogeneity
+//! The tiled automaton reports the density and the activity of each of its
+//! tiles (see [`TiledAutomaton::tile_stats`]), whose variances measure how
+//! unevenly the grid evolves.
+//! ```
+//! use rust_ca::automaton::{AutomatonImpl, TiledAutomaton, TILE_SIZE};
+//! use rust_ca::rule::Rule;
+//!
+//! let mut automaton = TiledAutomaton::new(2, 2 * TILE_SIZE, Rule::gol());
+//! // Only the first tile is alive.
+//! automaton.set(1, 2, 1);
+//! automaton.set(2, 2, 1);
+//! automaton.set(3, 2, 1);
+//! automaton.update();
+//! let tiles = automaton.tile_stats();
+//! assert_eq!(tiles.densities[0], 3. / (TILE_SIZE * TILE_SIZE) as f64);
+//! assert_eq!(tiles.densities[1..], [0.; 3]);
+//! assert!(tiles.density_variance() > 0.);
+//! ```
+//!
+//! [`TiledAutomaton::tile_stats`]: crate::automaton::TiledAutomaton::tile_stats
+use std::io::{self, Write};
+use std::path::Path;
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::automaton::AutomatonImpl;
+use crate::output::{AtomicFile, Frame, FrameFilter, FrameMeta};
+
+/// The state statistics of a single frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameStats {
+    /// The time step of the frame.
+    pub step: u64,
+    /// The normalized Shannon entropy of the state distribution (see
+    /// [`entropy`]).
+    pub entropy: f64,
+    /// The fraction of cells in each state.
+    pub densities: Vec<f64>,
+    /// The fraction of cells that changed since the previous frame (0 for the
+    /// first frame).
+    pub activity: f64,
+}
+
+impl FrameStats {
+    /// Computes the statistics of a grid, given the grid of the previous
+    /// frame if there is one.
+    pub fn new(step: u64, grid: &[u8], previous: Option<&[u8]>, states: u8) -> FrameStats {
+        let counts = state_counts(grid, states);
+        FrameStats {
+            step,
+            entropy: normalized_entropy(&counts, states),
+            densities: counts
+                .iter()
+                .map(|&c| c as f64 / grid.len() as f64)
+                .collect(),
+            activity: previous.map_or(0., |p| activity(p, grid)),
+        }
+    }
+}
+
+/// An iterator adapter computing the [`FrameStats`] of a sequence of grids
+/// (see [`AutomatonImpl::iter_stats`]).
+pub struct StatsIter<I> {
+    grids: I,
+    states: u8,
+    skip: u32,
+    index: u64,
+    previous: Option<Vec<u8>>,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> StatsIter<I> {
+    /// Wraps an iterator over the grids of every `skip`-th step of a run.
+    pub fn new(grids: I, states: u8, skip: u32) -> StatsIter<I> {
+        StatsIter {
+            grids,
+            states,
+            skip,
+            index: 0,
+            previous: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Iterator for StatsIter<I> {
+    type Item = FrameStats;
+
+    fn next(&mut self) -> Option<FrameStats> {
+        let grid = self.grids.next()?;
+        let step = self.index * self.skip as u64;
+        let stats = FrameStats::new(step, &grid, self.previous.as_deref(), self.states);
+        self.index += 1;
+        self.previous = Some(grid);
+        Some(stats)
+    }
+}
+
+/// Writes the [`FrameStats`] of frames as CSV rows with the step, the
+/// entropy, the activity and the density of each state.
+///
+/// The writer is also a [`FrameFilter`] leaving the frames unchanged, to
+/// record the statistics of a run while it is rendered. It must then be
+/// placed before the filters that change the cells. The errors are reported
+/// by [`StatsCsv::into_inner`].
+///
+/// ```
+/// use rust_ca::automaton::{Automaton, AutomatonImpl};
+/// use rust_ca::output::{self, FilterChain, NullSink, Upscale};
+/// use rust_ca::rule::Rule;
+/// use rust_ca::stats::StatsCsv;
+///
+/// let mut automaton = Automaton::new(2, 32, Rule::gol());
+/// automaton.random_init();
+/// let mut csv = StatsCsv::new(vec![], 2)?;
+/// let mut filter = FilterChain::new();
+/// filter.push(&mut csv).push(Upscale(2));
+/// output::write_to_sink(&mut automaton, 10, 1, &mut filter, &mut NullSink)?;
+/// drop(filter);
+/// let csv = String::from_utf8(csv.into_inner()?).unwrap();
+/// assert_eq!(csv.lines().next(), Some("step,entropy,activity,density_0,density_1"));
+/// assert_eq!(csv.lines().count(), 11);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct StatsCsv<W: Write> {
+    writer: W,
+    states: u8,
+    previous: Option<Vec<u8>>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> StatsCsv<W> {
+    /// Makes a writer for grids with `states` states and writes the header.
+    pub fn new(mut writer: W, states: u8) -> Result<StatsCsv<W>, io::Error> {
+        write!(writer, "step,entropy,activity")?;
+        for s in 0..states {
+            write!(writer, ",density_{}", s)?;
+        }
+        writeln!(writer)?;
+        Ok(StatsCsv {
+            writer,
+            states,
+            previous: None,
+            error: None,
+        })
+    }
+
+    /// Writes the statistics of a frame.
+    pub fn write(&mut self, stats: &FrameStats) -> Result<(), io::Error> {
+        write!(
+            self.writer,
+            "{},{},{}",
+            stats.step, stats.entropy, stats.activity
+        )?;
+        for d in &stats.densities {
+            write!(self.writer, ",{}", d)?;
+        }
+        writeln!(self.writer)
+    }
+
+    /// Flushes the writer and returns it, or the first error that happened
+    /// while recording frames.
+    pub fn into_inner(mut self) -> Result<W, io::Error> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> FrameFilter for StatsCsv<W> {
+    fn apply(&mut self, frame: &mut Frame, meta: &FrameMeta) {
+        if self.error.is_some() {
+            return;
+        }
+        let stats = FrameStats::new(
+            meta.step,
+            &frame.cells,
+            self.previous.as_deref(),
+            self.states,
+        );
+        if let Err(err) = self.write(&stats) {
+            self.error = Some(err);
+        }
+        self.previous = Some(frame.cells.clone());
+    }
+}
+
+/// The density and the activity of the tiles of a grid, in row-major order of
+/// the tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileStats {
+    /// The number of tiles on each side of the grid.
+    pub tiles: usize,
+    /// The fraction of non-zero cells of each tile.
+    pub densities: Vec<f64>,
+    /// The fraction of cells of each tile changed by the last update.
+    pub activities: Vec<f64>,
+}
+
+impl TileStats {
+    /// Returns the variance of the densities of the tiles.
+    pub fn density_variance(&self) -> f64 {
+        variance(&self.densities)
+    }
+
+    /// Returns the variance of the activities of the tiles.
+    pub fn activity_variance(&self) -> f64 {
+        variance(&self.activities)
+    }
+}
+
+fn variance(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64
+}
+
+/// The spatial structure statistics of a single frame.
+#[derive(Debug, Clone)]
+pub struct FrameStructure {
+    /// The time step of the frame.
+    pub step: u32,
+    /// The correlation length of the grid (see [`correlation_length`]).
+    pub correlation_length: f64,
+    /// The radially averaged structure factor of the grid (see
+    /// [`structure_factor`]).
+    pub structure_factor: Vec<f64>,
+}
+
+/// Counts the number of cells in each state.
+pub fn state_counts(grid: &[u8], states: u8) -> Vec<usize> {
+    let mut counts = vec![0; states as usize];
+    for &c in grid {
+        counts[c as usize] += 1;
+    }
+    counts
+}
+
+/// Returns the fraction of cells in each state.
+pub fn densities(grid: &[u8], states: u8) -> Vec<f64> {
+    state_counts(grid, states)
+        .iter()
+        .map(|&c| c as f64 / grid.len() as f64)
+        .collect()
+}
+
+/// Returns the Shannon entropy of the state distribution of the grid,
+/// normalized by the maximum entropy `ln(states)` so that it lies in `[0, 1]`.
+pub fn entropy(grid: &[u8], states: u8) -> f64 {
+    normalized_entropy(&state_counts(grid, states), states)
+}
+
+/// Returns the fraction of cells that changed between two grids.
+pub fn activity(previous: &[u8], grid: &[u8]) -> f64 {
+    let changed = previous
+        .iter()
+        .zip(grid.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    changed as f64 / grid.len() as f64
+}
+
+/// Shannon entropy of a histogram of states, normalized by `ln(states)`.
+pub(crate) fn normalized_entropy(counts: &[usize], states: u8) -> f64 {
+    let total: usize = counts.iter().sum();
+    if total == 0 || states < 2 {
+        return 0.;
+    }
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / total as f64;
+            -p * p.ln()
+        })
+        .sum();
+    entropy / (states as f64).ln()
+}
+
+/// Computes the 2D Fourier transform of the centered grid (the mean state is
+/// subtracted from every cell).
+fn centered_fft(grid: &[u8], size: usize) -> Vec<Complex<f64>> {
+    let mean = grid.iter().map(|&x| x as f64).sum::<f64>() / grid.len() as f64;
+    let mut data: Vec<Complex<f64>> = grid
+        .iter()
+        .map(|&x| Complex::new(x as f64 - mean, 0.))
+        .collect();
+    fft_2d(&mut data, size, false);
+    data
+}
+
+/// In place 2D FFT of a `size`x`size` array in row-major order.
+fn fft_2d(data: &mut [Complex<f64>], size: usize, inverse: bool) {
+    let mut planner = FftPlanner::new();
+    let fft = if inverse {
+        planner.plan_fft_inverse(size)
+    } else {
+        planner.plan_fft_forward(size)
+    };
+    // Rows
+    fft.process(data);
+    // Columns
+    let mut column = vec![Complex::new(0., 0.); size];
+    for j in 0..size {
+        for i in 0..size {
+            column[i] = data[i * size + j];
+        }
+        fft.process(&mut column);
+        for i in 0..size {
+            data[i * size + j] = column[i];
+        }
+    }
+}
+
+/// Averages a `size`x`size` periodic array over rings of integer radius
+/// around the origin, up to radius `size / 2`.
+fn radial_average(values: &[f64], size: usize) -> Vec<f64> {
+    let n_bins = size / 2 + 1;
+    let mut sums = vec![0.; n_bins];
+    let mut counts = vec![0usize; n_bins];
+    for i in 0..size {
+        for j in 0..size {
+            let di = i.min(size - i) as f64;
+            let dj = j.min(size - j) as f64;
+            let r = (di * di + dj * dj).sqrt().round() as usize;
+            if r < n_bins {
+                sums[r] += values[i * size + j];
+                counts[r] += 1;
+            }
+        }
+    }
+    sums.iter()
+        .zip(counts.iter())
+        .map(|(&s, &c)| if c > 0 { s / c as f64 } else { 0. })
+        .collect()
+}
+
+/// Computes the radially averaged structure factor of the grid, the power
+/// spectrum `|F(k)|^2 / N` of the centered grid averaged over rings of integer
+/// wavenumber `|k|`. The returned vector is indexed by the wavenumber, from 0
+/// to `size / 2`.
+pub fn structure_factor(grid: &[u8], size: usize) -> Vec<f64> {
+    let n = (size * size) as f64;
+    let power: Vec<f64> = centered_fft(grid, size)
+        .iter()
+        .map(|c| c.norm_sqr() / n)
+        .collect();
+    radial_average(&power, size)
+}
+
+/// Computes the radially averaged two-point correlation function of the grid
+/// states, normalized so that the correlation at distance 0 is 1 (a uniform
+/// grid has a correlation of 0 everywhere). The returned vector is indexed by
+/// the distance, from 0 to `size / 2`. The grid is considered periodic.
+pub fn two_point_correlation(grid: &[u8], size: usize) -> Vec<f64> {
+    let mut spectrum: Vec<Complex<f64>> = centered_fft(grid, size)
+        .iter()
+        .map(|c| Complex::new(c.norm_sqr(), 0.))
+        .collect();
+    fft_2d(&mut spectrum, size, true);
+    let variance = spectrum[0].re;
+    if variance.abs() < f64::EPSILON {
+        return vec![0.; size / 2 + 1];
+    }
+    let correlation: Vec<f64> = spectrum.iter().map(|c| c.re / variance).collect();
+    radial_average(&correlation, size)
+}
+
+/// Estimates the correlation length from a two-point correlation function
+/// (see [`two_point_correlation`]), as the distance at which the correlation
+/// first drops below `1/e`. The distance is linearly interpolated between
+/// integer distances. If the correlation never drops below `1/e`, the largest
+/// distance is returned.
+pub fn correlation_length(correlation: &[f64]) -> f64 {
+    let threshold = (-1f64).exp();
+    correlation
+        .windows(2)
+        .enumerate()
+        .find(|(_, w)| w[1] < threshold)
+        .map_or(correlation.len().saturating_sub(1) as f64, |(r, w)| {
+            r as f64 + (w[0] - threshold) / (w[0] - w[1])
+        })
+}
+
+/// Simulates the automaton for `steps` steps and computes the spatial
+/// structure statistics of every `skip`-th frame.
+pub fn structure_stats<T: AutomatonImpl>(
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+) -> Vec<FrameStructure> {
+    let size = autom.size();
+    autom
+        .skipped_iter(steps, skip, 1)
+        .enumerate()
+        .map(|(c, grid)| FrameStructure {
+            step: c as u32 * skip,
+            correlation_length: correlation_length(&two_point_correlation(&grid, size)),
+            structure_factor: structure_factor(&grid, size),
+        })
+        .collect()
+}
+
+/// Simulates the automaton for `steps` steps and writes the spatial structure
+/// statistics of every `skip`-th frame to a CSV file. Each row contains the
+/// step, the correlation length and the radially averaged structure factor.
+pub fn write_structure_csv<P: AsRef<Path>, T: AutomatonImpl>(
+    path: P,
+    autom: &mut T,
+    steps: u32,
+    skip: u32,
+) -> Result<(), io::Error> {
+    let mut f = AtomicFile::create(path)?;
+    let n_bins = autom.size() / 2 + 1;
+    write!(f, "step,correlation_length")?;
+    for k in 0..n_bins {
+        write!(f, ",s_{}", k)?;
+    }
+    writeln!(f)?;
+    for frame in structure_stats(autom, steps, skip) {
+        write!(f, "{},{}", frame.step, frame.correlation_length)?;
+        for s in frame.structure_factor {
+            write!(f, ",{}", s)?;
+        }
+        writeln!(f)?;
+    }
+    f.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{correlation_length, structure_factor, two_point_correlation};
+    use super::{FrameStats, StatsIter};
+
+    #[test]
+    fn uniform_grid_has_no_structure() {
+        let grid = vec![1; 64];
+        assert!(structure_factor(&grid, 8).iter().all(|&s| s.abs() < 1e-9));
+        assert!(two_point_correlation(&grid, 8).iter().all(|&c| c == 0.));
+    }
+
+    #[test]
+    fn checkerboard_has_short_correlation_length() {
+        let grid: Vec<u8> = (0..64).map(|x| ((x / 8 + x % 8) % 2) as u8).collect();
+        let correlation = two_point_correlation(&grid, 8);
+        assert!((correlation[0] - 1.).abs() < 1e-9);
+        assert!(correlation_length(&correlation) < 1.);
+    }
+
+    #[test]
+    fn correlation_length_interpolates() {
+        let threshold = (-1f64).exp();
+        let correlation = [1., 2. * threshold, 0.];
+        assert!((correlation_length(&correlation) - 1.5).abs() < 1e-9);
+        assert_eq!(correlation_length(&[1., 0.9, 0.8]), 2.);
+    }
+
+    #[test]
+    fn stats_follow_the_frames() {
+        let grids = vec![vec![0, 0, 1, 1], vec![0, 1, 1, 1], vec![0, 1, 1, 1]];
+        let stats: Vec<FrameStats> = StatsIter::new(grids.into_iter(), 3, 5).collect();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].activity, 0.);
+        assert_eq!((stats[1].step, stats[1].activity), (5, 0.25));
+        assert_eq!(stats[1].densities, vec![0.25, 0.75, 0.]);
+        assert_eq!(stats[2].activity, 0.);
+        assert_eq!(stats[2].entropy, stats[1].entropy);
+    }
+}
@@ -0,0 +1,146 @@
+//! Replays seeded simulations on every backend and thread count, checking that
+//! they all go through bit-identical states.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_ca::automaton::{
+    Automaton, AutomatonImpl, BoundaryCondition, RecursiveAutomaton, TiledAutomaton,
+};
+use rust_ca::rule::{Neighborhood, Rule};
+
+/// The side of the grids, two tiles wide so that the tiled backend exchanges
+/// halos between tiles.
+const SIZE: usize = 512;
+
+/// The steps after which the states are compared.
+const CHECKPOINTS: [u32; 4] = [0, 1, 4, 8];
+
+/// The parameters of a replayed simulation.
+#[derive(Clone)]
+struct Run {
+    rule: Rule,
+    seed: u64,
+    noise: f64,
+    boundary: BoundaryCondition,
+}
+
+/// Returns a random rule on a neighborhood, drawn from a seeded generator so
+/// that failures can be reproduced.
+fn seeded_rule(horizon: i8, states: u8, neighborhood: Neighborhood, seed: u64) -> Rule {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let cells = neighborhood.offsets(horizon).len();
+    let table = (0..(states as usize).pow(cells as u32))
+        .map(|_| rng.gen_range(0..states))
+        .collect();
+    Rule::with_neighborhood(horizon, states, neighborhood, table)
+}
+
+/// Hashes the grid and the state counts of an automaton.
+fn state_hash<A: AutomatonImpl>(automaton: &A) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    automaton.grid().hash(&mut hasher);
+    automaton.state_counts().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Runs a simulation on a backend, returning the hashes of its states at the
+/// checkpoints.
+fn replay<A: AutomatonImpl>(run: &Run, threads: usize) -> Vec<u64> {
+    let mut automaton = A::from_rule(run.rule.clone(), SIZE);
+    automaton.set_threads(threads);
+    automaton.set_boundary(run.boundary);
+    automaton.random_init_seeded(run.seed);
+    automaton.set_noise(run.noise);
+    let mut step = 0;
+    CHECKPOINTS
+        .iter()
+        .map(|&checkpoint| {
+            while step < checkpoint {
+                automaton.update();
+                step += 1;
+            }
+            state_hash(&automaton)
+        })
+        .collect()
+}
+
+/// Checks that every backend and thread count replays the run identically.
+fn assert_deterministic(run: Run) {
+    let expected = replay::<Automaton>(&run, 1);
+    assert_eq!(expected, replay::<Automaton>(&run, 1), "replay differs");
+    let replays = [
+        ("automaton, 3 threads", replay::<Automaton>(&run, 3)),
+        ("automaton, 8 threads", replay::<Automaton>(&run, 8)),
+        ("tiled, 1 thread", replay::<TiledAutomaton>(&run, 1)),
+        ("tiled, 4 threads", replay::<TiledAutomaton>(&run, 4)),
+        ("recursive, 1 thread", replay::<RecursiveAutomaton>(&run, 1)),
+        (
+            "recursive, 2 threads",
+            replay::<RecursiveAutomaton>(&run, 2),
+        ),
+    ];
+    for (backend, hashes) in replays {
+        assert_eq!(hashes, expected, "{} diverges", backend);
+    }
+}
+
+#[test]
+fn binary_rules_replay_identically() {
+    assert_deterministic(Run {
+        rule: Rule::gol(),
+        seed: 1,
+        noise: 0.,
+        boundary: BoundaryCondition::Periodic,
+    });
+}
+
+#[test]
+fn multistate_rules_replay_identically() {
+    assert_deterministic(Run {
+        rule: seeded_rule(1, 3, Neighborhood::Moore, 2),
+        seed: 2,
+        noise: 0.,
+        boundary: BoundaryCondition::Periodic,
+    });
+}
+
+#[test]
+fn dynamic_kernels_replay_identically() {
+    assert_deterministic(Run {
+        rule: seeded_rule(2, 3, Neighborhood::VonNeumann, 3),
+        seed: 3,
+        noise: 0.,
+        boundary: BoundaryCondition::Periodic,
+    });
+    assert_deterministic(Run {
+        rule: seeded_rule(1, 4, Neighborhood::Hex, 4),
+        seed: 4,
+        noise: 0.,
+        boundary: BoundaryCondition::Periodic,
+    });
+}
+
+#[test]
+fn noisy_runs_replay_identically() {
+    assert_deterministic(Run {
+        rule: seeded_rule(1, 3, Neighborhood::Moore, 5),
+        seed: 5,
+        noise: 0.01,
+        boundary: BoundaryCondition::Periodic,
+    });
+}
+
+#[test]
+fn bounded_runs_replay_identically() {
+    for boundary in [BoundaryCondition::Fixed(1), BoundaryCondition::Reflective] {
+        assert_deterministic(Run {
+            rule: seeded_rule(1, 2, Neighborhood::Moore, 6),
+            seed: 6,
+            noise: 0.,
+            boundary,
+        });
+    }
+}